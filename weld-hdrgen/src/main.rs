@@ -96,8 +96,13 @@ impl CppHeaderGenerator {
         }
 
         let result = match *ty {
+            // Decimals are passed as their scaled `i64` values.
+            Scalar(ScalarKind::Decimal(_, _)) => Ok("i64".to_string()),
+            // Dates, timestamps and intervals are passed as their stored values.
+            Scalar(kind) if kind.is_temporal() => Ok(format!("{}", kind.storage_kind())),
             Scalar(ref kind) => Ok(format!("{}", kind)),
             Vector(ref elem) => Ok(format!("vec<{}>", self.generate_type(elem)?)),
+            Optional(ref value) => Ok(format!("optional<{}>", self.generate_type(value)?)),
             Struct(ref elems) => self.generate_struct_definition(elems),
             // Other types (Builders, Functions, etc.) cannot be passed into Weld.
             _ => weld_err!("Invalid C++ type {:?}", ty),
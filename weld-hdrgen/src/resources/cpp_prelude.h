@@ -30,3 +30,10 @@ vec<T> new_vec(i64 size) {
   t.size = size;
   return t;
 }
+
+// Defines a value that may be null, with the layout of Weld's optional[T].
+template<typename T>
+struct optional {
+  bool valid;
+  T value;
+};
@@ -106,10 +106,23 @@ pub enum Type {
     Vector(Box<Type>),
     /// A dictionary mapping keys to values.
     Dict(Box<Type>, Box<Type>),
+    /// A set of distinct keys.
+    ///
+    /// Sets are lowered to a dictionary with zero-size `{}` values before code generation.
+    Set(Box<Type>),
+    /// A sketch that summarizes values of the given type in a bounded amount of space.
+    ///
+    /// Sketches are lowered to vectors and dictionaries before code generation.
+    Sketch(SketchKind, Box<Type>),
     /// A mutable builder to construct results.
     Builder(BuilderKind, Annotations),
     /// An ordered struct or tuple.
     Struct(Vec<Type>),
+    /// A value of the given type that may be null.
+    ///
+    /// Optional values are lowered to a `{bool, T}` struct before code generation, where the
+    /// `bool` indicates whether the value is non-null.
+    Optional(Box<Type>),
     /// A function with a list of arguments and return type.
     Function(Vec<Type>, Box<Type>),
     /// An alias for a type.
@@ -127,13 +140,19 @@ impl Type {
             Unknown | Scalar(_) | Simd(_) => vec![],
             Alias(_, ref ty) => vec![ty.as_ref()],
             Vector(ref elem) => vec![elem.as_ref()],
+            Optional(ref value) => vec![value.as_ref()],
             Dict(ref key, ref value) => vec![key.as_ref(), value.as_ref()],
+            Set(ref key) => vec![key.as_ref()],
+            Sketch(_, ref item) => vec![item.as_ref()],
             Builder(ref kind, _) => match kind {
                 Appender(ref elem) => vec![elem.as_ref()],
                 Merger(ref elem, _) => vec![elem.as_ref()],
                 DictMerger(ref key, ref value, _) => vec![key.as_ref(), value.as_ref()],
                 GroupMerger(ref key, ref value) => vec![key.as_ref(), value.as_ref()],
                 VecMerger(ref elem, _) => vec![elem.as_ref()],
+                TopK(ref elem, _) => vec![elem.as_ref()],
+                SetMerger(ref key) => vec![key.as_ref()],
+                SketchMerger(_, ref item) => vec![item.as_ref()],
             },
             Struct(ref elems) => elems.iter().collect(),
             Function(ref params, ref res) => {
@@ -156,13 +175,19 @@ impl Type {
             Unknown | Scalar(_) | Simd(_) => vec![],
             Alias(_, ref mut ty) => vec![ty.as_mut()],
             Vector(ref mut elem) => vec![elem.as_mut()],
+            Optional(ref mut value) => vec![value.as_mut()],
             Dict(ref mut key, ref mut value) => vec![key.as_mut(), value.as_mut()],
+            Set(ref mut key) => vec![key.as_mut()],
+            Sketch(_, ref mut item) => vec![item.as_mut()],
             Builder(ref mut kind, _) => match kind {
                 Appender(ref mut elem) => vec![elem.as_mut()],
                 Merger(ref mut elem, _) => vec![elem.as_mut()],
                 DictMerger(ref mut key, ref mut value, _) => vec![key.as_mut(), value.as_mut()],
                 GroupMerger(ref mut key, ref mut value) => vec![key.as_mut(), value.as_mut()],
                 VecMerger(ref mut elem, _) => vec![elem.as_mut()],
+                TopK(ref mut elem, _) => vec![elem.as_mut()],
+                SetMerger(ref mut key) => vec![key.as_mut()],
+                SketchMerger(_, ref mut item) => vec![item.as_mut()],
             },
             Struct(ref mut elems) => elems.iter_mut().collect(),
             Function(ref mut params, ref mut res) => {
@@ -182,16 +207,21 @@ impl Type {
         Type::Vector(Box::new(Type::Scalar(ScalarKind::I8)))
     }
 
+    /// Returns the type of an optional value of this type.
+    pub fn optional_type(&self) -> Type {
+        Type::Optional(Box::new(self.clone()))
+    }
+
     /// Returns whether this `Type` is a SIMD value.
     ///
     /// A value is a SIMD value if its a `Simd` type or it is a `Struct` where each member is a
     /// `Simd` type. We additionally consider each of the builders to be SIMD values, since they
-    /// can operate over SIMD values as inputs.
+    /// can operate over SIMD values as inputs. The empty struct is not a SIMD value.
     pub fn is_simd(&self) -> bool {
         use self::Type::*;
         match *self {
             Simd(_) | Builder(_, _) => true,
-            Struct(ref fields) => fields.iter().all(|f| f.is_simd()),
+            Struct(ref fields) => !fields.is_empty() && fields.iter().all(|f| f.is_simd()),
             _ => false,
         }
     }
@@ -204,6 +234,15 @@ impl Type {
         }
     }
 
+    /// Returns whether this `Type` is optional.
+    pub fn is_optional(&self) -> bool {
+        use self::Type::Optional;
+        match *self {
+            Optional(_) => true,
+            _ => false,
+        }
+    }
+
     /// Returns whether this `Type` contains a builder.
     pub fn contains_builder(&self) -> bool {
         use self::Type::Builder;
@@ -213,12 +252,12 @@ impl Type {
         }
     }
 
-    /// Returns whether this `Type` is a builder.
+    /// Returns whether this `Type` is a builder or a non-empty struct of builders.
     pub fn is_builder(&self) -> bool {
         use self::Type::{Builder, Struct};
         match *self {
             Builder(_, _) => true,
-            Struct(ref tys) => tys.iter().all(|t| t.is_builder()),
+            Struct(ref tys) => !tys.is_empty() && tys.iter().all(|t| t.is_builder()),
             _ => false,
         }
     }
@@ -232,8 +271,9 @@ impl Type {
             Simd(_) => true,
             Struct(ref tys) => tys.iter().all(|t| t.is_hashable()),
             Vector(ref elem) => elem.is_hashable(),
+            Optional(ref value) => value.is_hashable(),
             Builder(_, _) => false,
-            Dict(_, _) => false,
+            Dict(_, _) | Set(_) | Sketch(_, _) => false,
             Function(_, _) | Alias(_, _) | Unknown => false,
         }
     }
@@ -301,7 +341,10 @@ impl fmt::Display for Type {
             Scalar(ref kind) => format!("{}", kind),
             Simd(ref kind) => format!("simd[{}]", kind),
             Vector(ref elem) => format!("vec[{}]", elem),
+            Optional(ref value) => format!("optional[{}]", value),
             Dict(ref key, ref value) => format!("dict[{},{}]", key, value),
+            Set(ref key) => format!("set[{}]", key),
+            Sketch(kind, ref item) => format!("{}[{}]", kind, item),
             Struct(ref elems) => util::join("{", ",", "}", elems.iter().map(|e| e.to_string())),
             Function(ref params, ref return_type) => {
                 let mut res = util::join("|", ",", "|(", params.iter().map(|e| e.to_string()));
@@ -331,8 +374,23 @@ pub enum ScalarKind {
     U64,
     F32,
     F64,
+    /// A fixed-point decimal with a precision and a scale.
+    ///
+    /// Decimals are stored as an `i64` holding the value multiplied by `10^scale`. The precision
+    /// is the total number of decimal digits, and is at most `MAX_DECIMAL_PRECISION`.
+    Decimal(u8, u8),
+    /// A calendar date, stored as an `i32` holding the number of days since 1970-01-01.
+    Date,
+    /// A point in time, stored as an `i64` holding the number of microseconds since
+    /// 1970-01-01 00:00:00 UTC.
+    Timestamp,
+    /// A fixed duration, stored as an `i64` holding a number of microseconds.
+    Interval,
 }
 
+/// The maximum precision of a decimal, which is the number of digits that always fit in an `i64`.
+pub const MAX_DECIMAL_PRECISION: u8 = 18;
+
 impl ScalarKind {
     /// Returns whether this scalar is a floating-point type.
     ///
@@ -372,9 +430,25 @@ impl ScalarKind {
         }
     }
 
+    /// Returns whether this scalar is a decimal.
+    pub fn is_decimal(self) -> bool {
+        match self {
+            Decimal(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether this scalar is a date, timestamp or interval.
+    pub fn is_temporal(self) -> bool {
+        match self {
+            Date | Timestamp | Interval => true,
+            _ => false,
+        }
+    }
+
     /// Returns whether this scalar is signed.
     pub fn is_signed(self) -> bool {
-        self.is_signed_integer() || self.is_float()
+        self.is_signed_integer() || self.is_float() || self.is_decimal() || self.is_temporal()
     }
 
     /// Returns whether this scalar is an integer.
@@ -395,8 +469,30 @@ impl ScalarKind {
             Bool => 1,
             I8 | U8 => 8,
             I16 | U16 => 16,
-            I32 | U32 | F32 => 32,
-            I64 | U64 | F64 => 64,
+            I32 | U32 | F32 | Date => 32,
+            I64 | U64 | F64 | Decimal(_, _) | Timestamp | Interval => 64,
+        }
+    }
+
+    /// Returns the primitive scalar kind that values of this scalar are stored as.
+    ///
+    /// Decimals, dates, timestamps and intervals are stored as signed integers. Other scalars are
+    /// stored as themselves.
+    pub fn storage_kind(self) -> ScalarKind {
+        match self {
+            Date => I32,
+            Decimal(_, _) | Timestamp | Interval => I64,
+            _ => self,
+        }
+    }
+
+    /// Returns the factor by which the stored value of this scalar is scaled.
+    ///
+    /// This is `10^scale` for decimals and one for other scalars.
+    pub fn scale_factor(self) -> i64 {
+        match self {
+            Decimal(_, scale) => 10i64.pow(u32::from(scale)),
+            _ => 1,
         }
     }
 
@@ -414,6 +510,7 @@ impl ScalarKind {
 impl fmt::Display for ScalarKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let text = match *self {
+            Decimal(precision, scale) => return write!(f, "decimal[{},{}]", precision, scale),
             Bool => "bool",
             I8 => "i8",
             I16 => "i16",
@@ -425,6 +522,9 @@ impl fmt::Display for ScalarKind {
             U64 => "u64",
             F32 => "f32",
             F64 => "f64",
+            Date => "date",
+            Timestamp => "timestamp",
+            Interval => "interval",
         };
         f.write_str(text)
     }
@@ -451,6 +551,19 @@ pub enum BuilderKind {
     ///
     /// Elements are updated by index using an associative binary operator.
     VecMerger(Box<Type>, BinOpKind),
+    /// A builder that keeps the `k` largest items merged into it.
+    ///
+    /// Items are kept in a bounded heap and are ordered using the default comparator. The result
+    /// is a vector of at most `k` items sorted in descending order.
+    TopK(Box<Type>, i64),
+    /// A builder that creates a set.
+    ///
+    /// Duplicate keys are merged into a single key.
+    SetMerger(Box<Type>),
+    /// A builder that summarizes items in a sketch.
+    ///
+    /// Partial sketches of the same kind and item type can also be merged into the builder.
+    SketchMerger(SketchKind, Box<Type>),
 }
 
 impl BuilderKind {
@@ -464,6 +577,9 @@ impl BuilderKind {
             DictMerger(ref key, ref value, _) => Struct(vec![*key.clone(), *value.clone()]),
             GroupMerger(ref key, ref value) => Struct(vec![*key.clone(), *value.clone()]),
             VecMerger(ref elem, _) => Struct(vec![Scalar(I64), *elem.clone()]),
+            TopK(ref elem, _) => *elem.clone(),
+            SetMerger(ref key) => *key.clone(),
+            SketchMerger(_, ref item) => *item.clone(),
         }
     }
 
@@ -477,6 +593,9 @@ impl BuilderKind {
             DictMerger(ref key, ref value, _) => Dict(key.clone(), value.clone()),
             GroupMerger(ref key, ref value) => Dict(key.clone(), Box::new(Vector(value.clone()))),
             VecMerger(ref elem, _) => Vector(elem.clone()),
+            TopK(ref elem, _) => Vector(elem.clone()),
+            SetMerger(ref key) => Set(key.clone()),
+            SketchMerger(kind, ref item) => Sketch(kind, item.clone()),
         }
     }
 }
@@ -490,6 +609,41 @@ impl fmt::Display for BuilderKind {
             GroupMerger(ref key, ref value) => format!("groupmerger[{},{}]", key, value),
             VecMerger(ref elem, op) => format!("vecmerger[{},{}]", elem, op),
             Merger(ref elem, op) => format!("merger[{},{}]", elem, op),
+            TopK(ref elem, k) => format!("topk[{},{}]", elem, k),
+            SetMerger(ref key) => format!("setmerger[{}]", key),
+            SketchMerger(kind, ref item) => format!("{}[{}]", kind.merger_name(), item),
+        };
+        f.write_str(text)
+    }
+}
+
+/// Kinds of sketches, which summarize items in a bounded amount of space.
+///
+/// Sketches can be combined associatively, so partial sketches computed in parallel or in
+/// different runs can be merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SketchKind {
+    /// A HyperLogLog sketch, which estimates the number of distinct items.
+    HyperLogLog,
+    /// A sketch that estimates quantiles of floating point items with a bounded relative error.
+    Quantiles,
+}
+
+impl SketchKind {
+    /// Returns the name of the builder that creates sketches of this kind.
+    pub fn merger_name(self) -> &'static str {
+        match self {
+            SketchKind::HyperLogLog => "hllmerger",
+            SketchKind::Quantiles => "quantilemerger",
+        }
+    }
+}
+
+impl fmt::Display for SketchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match *self {
+            SketchKind::HyperLogLog => "hll",
+            SketchKind::Quantiles => "quantiles",
         };
         f.write_str(text)
     }
@@ -639,12 +793,21 @@ pub enum ExprKind {
     },
     /// Applies a unary operator to the child expressions.
     UnaryOp { kind: UnaryOpKind, value: Box<Expr> },
+    /// Applies a string operator to the child expressions.
+    ///
+    /// Strings are vectors of `i8`. The number and types of the arguments depend on `kind`.
+    StringOp { kind: StringOpKind, args: Vec<Expr> },
+    /// Applies a calendar function to a date or timestamp.
+    ///
+    /// Calendar functions only exist in the type-checked AST, and are lowered to integer
+    /// arithmetic before optimization.
+    DateOp { kind: DateOpKind, value: Box<Expr> },
     /// Cast a scalar or SIMD child expression to another type.
     Cast {
         kind: ScalarKind,
         child_expr: Box<Expr>,
     },
-    /// Convert a dictionary into a vector of key/value pairs.
+    /// Convert a dictionary into a vector of key/value pairs, or a set into a vector of keys.
     ToVec { child_expr: Box<Expr> },
     /// Construct a struct from a list of child expressions.
     MakeStruct { elems: Vec<Expr> },
@@ -656,7 +819,7 @@ pub enum ExprKind {
     Zip { vectors: Vec<Expr> },
     /// Access a struct field at the given index.
     GetField { expr: Box<Expr>, index: u32 },
-    /// Get the length of a vector as an `i64`.
+    /// Get the length of a vector, dictionary or set as an `i64`.
     Length { data: Box<Expr> },
     /// Lookup a value in a collection.
     ///
@@ -668,8 +831,10 @@ pub enum ExprKind {
     ///
     /// Returns a `{bool, V}`, where the `bool` indicates whether the value was in the dictionary.
     OptLookup { data: Box<Expr>, index: Box<Expr> },
-    /// Check whether a key exists in a dictionary.
+    /// Check whether a key exists in a dictionary or set.
     KeyExists { data: Box<Expr>, key: Box<Expr> },
+    /// Hashes a value into a `u32` with the hash function used for dictionary keys.
+    Hash(Box<Expr>),
     /// Slices a vector, creating a view into it.
     ///
     /// This does not allocate new data.
@@ -678,6 +843,49 @@ pub enum ExprKind {
         index: Box<Expr>,
         size: Box<Expr>,
     },
+    /// A null value of an optional type.
+    Null,
+    /// Wraps a non-null value into an optional value.
+    MakeOptional(Box<Expr>),
+    /// Checks whether an optional value is null.
+    IsNull(Box<Expr>),
+    /// Returns the value of an optional if it is not null and `default` otherwise.
+    ///
+    /// `default` is only evaluated if the value is null.
+    Coalesce {
+        value: Box<Expr>,
+        default: Box<Expr>,
+    },
+    /// Estimates the number of distinct items summarized by a HyperLogLog sketch as an `i64`.
+    ///
+    /// Sketch operators only exist in the type-checked AST, and are lowered before optimization.
+    DistinctCount(Box<Expr>),
+    /// Estimates a quantile of the items summarized by a quantiles sketch.
+    ///
+    /// `quantile` is an `f64` between zero and one.
+    Quantile {
+        sketch: Box<Expr>,
+        quantile: Box<Expr>,
+    },
+    /// Combines each element of a vector with all elements before it using `op`.
+    ///
+    /// Window operators only exist in the type-checked AST, and are lowered before optimization.
+    Scan { data: Box<Expr>, op: BinOpKind },
+    /// Combines each element of a vector with the elements before it using `op`, in a frame
+    /// of `size` elements that ends at the element.
+    Window {
+        data: Box<Expr>,
+        size: Box<Expr>,
+        op: BinOpKind,
+    },
+    /// Returns the elements of a vector `offset` positions before or after each element, or
+    /// `default` where that position is outside of the vector.
+    Shift {
+        kind: ShiftKind,
+        data: Box<Expr>,
+        offset: Box<Expr>,
+        default: Box<Expr>,
+    },
     /// Sorts a vector.
     ///
     /// The sort operator takes a vector comprised of any non-builder, non-SIMD, or non-dictionary type
@@ -769,6 +977,8 @@ impl ExprKind {
             Broadcast(_) => "Broadcast",
             BinOp { .. } => "BinOp",
             UnaryOp { .. } => "UnaryOp",
+            StringOp { .. } => "StringOp",
+            DateOp { .. } => "DateOp",
             Cast { .. } => "Cast",
             ToVec { .. } => "ToVec",
             MakeStruct { .. } => "MakeStruct",
@@ -779,7 +989,17 @@ impl ExprKind {
             Lookup { .. } => "Lookup",
             OptLookup { .. } => "OptLookup",
             KeyExists { .. } => "KeyExists",
+            Hash(_) => "Hash",
             Slice { .. } => "Slice",
+            Null => "Null",
+            MakeOptional(_) => "MakeOptional",
+            IsNull(_) => "IsNull",
+            Coalesce { .. } => "Coalesce",
+            DistinctCount(_) => "DistinctCount",
+            Quantile { .. } => "Quantile",
+            Scan { .. } => "Scan",
+            Window { .. } => "Window",
+            Shift { .. } => "Shift",
             Sort { .. } => "Sort",
             Let { .. } => "Let",
             If { .. } => "If",
@@ -878,6 +1098,8 @@ pub enum BinOpKind {
     BitwiseAnd,
     BitwiseOr,
     Xor,
+    ShiftLeft,
+    ShiftRight,
     Max,
     Min,
     Pow,
@@ -913,6 +1135,8 @@ impl fmt::Display for BinOpKind {
             BitwiseAnd => "&",
             BitwiseOr => "|",
             Xor => "^",
+            ShiftLeft => "<<",
+            ShiftRight => ">>",
             Max => "max",
             Min => "min",
             Pow => "pow",
@@ -937,6 +1161,34 @@ pub enum UnaryOpKind {
     Cosh,
     Tanh,
     Erf,
+    Cbrt,
+    Floor,
+    Ceil,
+    /// Rounds half-way values away from zero.
+    Round,
+    Abs,
+    /// Counts the set bits of an integer.
+    Popcount,
+    /// Counts the leading zero bits of an integer.
+    Clz,
+    /// Counts the trailing zero bits of an integer.
+    Ctz,
+}
+
+impl UnaryOpKind {
+    /// Returns whether the operator only applies to integers.
+    pub fn is_integer_op(self) -> bool {
+        use self::UnaryOpKind::*;
+        match self {
+            Popcount | Clz | Ctz => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the operator applies to both integers and floating point values.
+    pub fn is_numeric_op(self) -> bool {
+        self == UnaryOpKind::Abs
+    }
 }
 
 impl fmt::Display for UnaryOpKind {
@@ -946,6 +1198,159 @@ impl fmt::Display for UnaryOpKind {
     }
 }
 
+/// String operators in the Weld IR.
+///
+/// Strings are vectors of `i8` holding ASCII characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StringOpKind {
+    /// Concatenates two strings.
+    Concat,
+    /// Returns the substring of a string at a start index with a length.
+    ///
+    /// The substring is clamped to the bounds of the string.
+    Substr,
+    /// Returns the index of the first occurrence of a string in another string, or -1.
+    Find,
+    /// Returns whether a string starts with another string.
+    StartsWith,
+    /// Returns whether a string ends with another string.
+    EndsWith,
+    /// Converts the letters of a string to upper case.
+    ToUpper,
+    /// Converts the letters of a string to lower case.
+    ToLower,
+    /// Parses an `i64` from a string, raising a `ParseError` if the string is not a number.
+    ParseI64,
+    /// Parses an `f64` from a string, raising a `ParseError` if the string is not a number.
+    ParseF64,
+    /// Formats a number as a string.
+    ToString,
+}
+
+impl StringOpKind {
+    /// Returns the number of arguments the operator takes.
+    pub fn num_args(self) -> usize {
+        use self::StringOpKind::*;
+        match self {
+            Substr => 3,
+            Concat | Find | StartsWith | EndsWith => 2,
+            ToUpper | ToLower | ParseI64 | ParseF64 | ToString => 1,
+        }
+    }
+}
+
+impl fmt::Display for StringOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::StringOpKind::*;
+        let text = match *self {
+            Concat => "strconcat",
+            Substr => "substr",
+            Find => "strfind",
+            StartsWith => "startswith",
+            EndsWith => "endswith",
+            ToUpper => "toupper",
+            ToLower => "tolower",
+            ParseI64 => "strtoi64",
+            ParseF64 => "strtof64",
+            ToString => "tostring",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Calendar functions in the Weld IR.
+///
+/// Calendar fields are computed in UTC using the proleptic Gregorian calendar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DateOpKind {
+    /// Returns the year of a date or timestamp.
+    Year,
+    /// Returns the month of a date or timestamp, from 1 to 12.
+    Month,
+    /// Returns the day of the month of a date or timestamp, from 1 to 31.
+    Day,
+    /// Returns the hour of a timestamp, from 0 to 23.
+    Hour,
+    /// Truncates a date or timestamp to the start of the enclosing unit.
+    Trunc(DateUnit),
+}
+
+impl fmt::Display for DateOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::DateOpKind::*;
+        let text = match *self {
+            Year => "year",
+            Month => "month",
+            Day => "day",
+            Hour => "hour",
+            Trunc(_) => "date_trunc",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Directions in which `Shift` moves the elements of a vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShiftKind {
+    /// Returns the element `offset` positions before each element.
+    Lag,
+    /// Returns the element `offset` positions after each element.
+    Lead,
+}
+
+impl fmt::Display for ShiftKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match *self {
+            ShiftKind::Lag => "lag",
+            ShiftKind::Lead => "lead",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Units that dates and timestamps can be truncated to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DateUnit {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl DateUnit {
+    /// Returns the unit with the given name, if one exists.
+    pub fn from_name(name: &str) -> Option<DateUnit> {
+        use self::DateUnit::*;
+        match name {
+            "year" => Some(Year),
+            "month" => Some(Month),
+            "day" => Some(Day),
+            "hour" => Some(Hour),
+            "minute" => Some(Minute),
+            "second" => Some(Second),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the unit is at least a day long, so dates can be truncated to it.
+    pub fn is_calendar_unit(self) -> bool {
+        use self::DateUnit::*;
+        match self {
+            Year | Month | Day => true,
+            Hour | Minute | Second => false,
+        }
+    }
+}
+
+impl fmt::Display for DateUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = format!("{:?}", self);
+        f.write_str(text.to_lowercase().as_ref())
+    }
+}
+
 /// A Parameter in a Lambda.
 ///
 /// A parameter is a typed `Symbol`.
@@ -972,6 +1377,8 @@ impl Expr {
                 ..
             } => vec![left.as_ref(), right.as_ref()],
             UnaryOp { ref value, .. } => vec![value.as_ref()],
+            StringOp { ref args, .. } => args.iter().collect(),
+            DateOp { ref value, .. } => vec![value.as_ref()],
             Cast { ref child_expr, .. } => vec![child_expr.as_ref()],
             ToVec { ref child_expr } => vec![child_expr.as_ref()],
             Let {
@@ -994,15 +1401,37 @@ impl Expr {
                 ref index,
             } => vec![data.as_ref(), index.as_ref()],
             KeyExists { ref data, ref key } => vec![data.as_ref(), key.as_ref()],
+            Hash(ref value) => vec![value.as_ref()],
             Slice {
                 ref data,
                 ref index,
                 ref size,
             } => vec![data.as_ref(), index.as_ref(), size.as_ref()],
+            Scan { ref data, .. } => vec![data.as_ref()],
+            Window {
+                ref data, ref size, ..
+            } => vec![data.as_ref(), size.as_ref()],
+            Shift {
+                ref data,
+                ref offset,
+                ref default,
+                ..
+            } => vec![data.as_ref(), offset.as_ref(), default.as_ref()],
             Sort {
                 ref data,
                 ref cmpfunc,
             } => vec![data.as_ref(), cmpfunc.as_ref()],
+            MakeOptional(ref value) => vec![value.as_ref()],
+            IsNull(ref value) => vec![value.as_ref()],
+            Coalesce {
+                ref value,
+                ref default,
+            } => vec![value.as_ref(), default.as_ref()],
+            DistinctCount(ref sketch) => vec![sketch.as_ref()],
+            Quantile {
+                ref sketch,
+                ref quantile,
+            } => vec![sketch.as_ref(), quantile.as_ref()],
             Merge {
                 ref builder,
                 ref value,
@@ -1067,7 +1496,7 @@ impl Expr {
             Assert(ref t) => vec![t.as_ref()],
             Broadcast(ref t) => vec![t.as_ref()],
             // Explicitly list types instead of doing _ => ... to remember to add new types.
-            Literal(_) | Ident(_) => vec![],
+            Literal(_) | Ident(_) | Null => vec![],
         }
         .into_iter()
     }
@@ -1082,6 +1511,8 @@ impl Expr {
                 ..
             } => vec![left.as_mut(), right.as_mut()],
             UnaryOp { ref mut value, .. } => vec![value.as_mut()],
+            StringOp { ref mut args, .. } => args.iter_mut().collect(),
+            DateOp { ref mut value, .. } => vec![value.as_mut()],
             Cast {
                 ref mut child_expr, ..
             } => vec![child_expr.as_mut()],
@@ -1109,15 +1540,39 @@ impl Expr {
                 ref mut data,
                 ref mut key,
             } => vec![data.as_mut(), key.as_mut()],
+            Hash(ref mut value) => vec![value.as_mut()],
             Slice {
                 ref mut data,
                 ref mut index,
                 ref mut size,
             } => vec![data.as_mut(), index.as_mut(), size.as_mut()],
+            Scan { ref mut data, .. } => vec![data.as_mut()],
+            Window {
+                ref mut data,
+                ref mut size,
+                ..
+            } => vec![data.as_mut(), size.as_mut()],
+            Shift {
+                ref mut data,
+                ref mut offset,
+                ref mut default,
+                ..
+            } => vec![data.as_mut(), offset.as_mut(), default.as_mut()],
             Sort {
                 ref mut data,
                 ref mut cmpfunc,
             } => vec![data.as_mut(), cmpfunc.as_mut()],
+            MakeOptional(ref mut value) => vec![value.as_mut()],
+            IsNull(ref mut value) => vec![value.as_mut()],
+            Coalesce {
+                ref mut value,
+                ref mut default,
+            } => vec![value.as_mut(), default.as_mut()],
+            DistinctCount(ref mut sketch) => vec![sketch.as_mut()],
+            Quantile {
+                ref mut sketch,
+                ref mut quantile,
+            } => vec![sketch.as_mut(), quantile.as_mut()],
             Merge {
                 ref mut builder,
                 ref mut value,
@@ -1183,7 +1638,7 @@ impl Expr {
             Assert(ref mut t) => vec![t.as_mut()],
             Broadcast(ref mut t) => vec![t.as_mut()],
             // Explicitly list types instead of doing _ => ... to remember to add new types.
-            Literal(_) | Ident(_) => vec![],
+            Literal(_) | Ident(_) | Null => vec![],
         }
         .into_iter()
     }
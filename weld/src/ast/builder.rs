@@ -35,6 +35,10 @@ pub trait NewExpr {
     fn new_bin_op(kind: BinOpKind, left: Expr, right: Expr) -> WeldResult<Expr>;
     /// Creates a new unary operator expression.
     fn new_unary_op(kind: UnaryOpKind, value: Expr) -> WeldResult<Expr>;
+    /// Creates a new string operator expression.
+    fn new_string_op(kind: StringOpKind, args: Vec<Expr>) -> WeldResult<Expr>;
+    /// Creates a new calendar function expression.
+    fn new_date_op(kind: DateOpKind, value: Expr) -> WeldResult<Expr>;
     /// Creates a new cast operator expression.
     fn new_cast(kind: ScalarKind, expr: Expr) -> WeldResult<Expr>;
     /// Creates a new negation operator expression.
@@ -64,14 +68,26 @@ pub trait NewExpr {
     fn new_opt_lookup(data: Expr, index: Expr) -> WeldResult<Expr>;
     /// Creates a new dictionary key exists expression.
     fn new_key_exists(data: Expr, key: Expr) -> WeldResult<Expr>;
+    /// Creates a new hash expression.
+    fn new_hash(value: Expr) -> WeldResult<Expr>;
     /// Creates a new vector slicing expression.
     fn new_slice(data: Expr, index: Expr, size: Expr) -> WeldResult<Expr>;
+    /// Creates a new null expression of the given optional type.
+    fn new_null(ty: Type) -> WeldResult<Expr>;
+    /// Creates a new non-null optional expression.
+    fn new_make_optional(value: Expr) -> WeldResult<Expr>;
+    /// Creates a new null check expression.
+    fn new_is_null(value: Expr) -> WeldResult<Expr>;
+    /// Creates a new coalesce expression.
+    fn new_coalesce(value: Expr, default: Expr) -> WeldResult<Expr>;
     /// Creates a new vector sort expression.
     fn new_sort(data: Expr, cmpfunc: Expr) -> WeldResult<Expr>;
     /// Creates a new let expression.
     fn new_let(name: Symbol, value: Expr, body: Expr) -> WeldResult<Expr>;
     /// Creates a new if expression.
     fn new_if(cond: Expr, on_true: Expr, on_false: Expr) -> WeldResult<Expr>;
+    /// Creates a new sequential iteration expression.
+    fn new_iterate(initial: Expr, update_func: Expr) -> WeldResult<Expr>;
     /// Creates a new select expression.
     fn new_select(cond: Expr, on_true: Expr, on_false: Expr) -> WeldResult<Expr>;
     /// Creates a new lambda expression.
@@ -150,6 +166,17 @@ impl NewExpr for Expr {
         })
     }
 
+    fn new_string_op(kind: StringOpKind, args: Vec<Expr>) -> WeldResult<Expr> {
+        Self::new(StringOp { kind, args })
+    }
+
+    fn new_date_op(kind: DateOpKind, value: Expr) -> WeldResult<Expr> {
+        Self::new(DateOp {
+            kind,
+            value: Box::new(value),
+        })
+    }
+
     fn new_cast(kind: ScalarKind, expr: Expr) -> WeldResult<Expr> {
         Self::new(Cast {
             kind,
@@ -221,6 +248,10 @@ impl NewExpr for Expr {
         })
     }
 
+    fn new_hash(value: Expr) -> WeldResult<Expr> {
+        Self::new(Hash(Box::new(value)))
+    }
+
     fn new_slice(data: Expr, index: Expr, size: Expr) -> WeldResult<Expr> {
         Self::new(Slice {
             data: Box::new(data),
@@ -229,6 +260,25 @@ impl NewExpr for Expr {
         })
     }
 
+    fn new_null(ty: Type) -> WeldResult<Expr> {
+        Self::new_with_type(Null, ty)
+    }
+
+    fn new_make_optional(value: Expr) -> WeldResult<Expr> {
+        Self::new(MakeOptional(Box::new(value)))
+    }
+
+    fn new_is_null(value: Expr) -> WeldResult<Expr> {
+        Self::new(IsNull(Box::new(value)))
+    }
+
+    fn new_coalesce(value: Expr, default: Expr) -> WeldResult<Expr> {
+        Self::new(Coalesce {
+            value: Box::new(value),
+            default: Box::new(default),
+        })
+    }
+
     fn new_sort(data: Expr, cmpfunc: Expr) -> WeldResult<Expr> {
         Self::new(Sort {
             data: Box::new(data),
@@ -252,6 +302,13 @@ impl NewExpr for Expr {
         })
     }
 
+    fn new_iterate(initial: Expr, update_func: Expr) -> WeldResult<Expr> {
+        Self::new(Iterate {
+            initial: Box::new(initial),
+            update_func: Box::new(update_func),
+        })
+    }
+
     fn new_select(cond: Expr, on_true: Expr, on_false: Expr) -> WeldResult<Expr> {
         Self::new(Select {
             cond: Box::new(cond),
@@ -45,7 +45,23 @@ impl CompareIgnoringSymbols for Expr {
                         kind: ref kind2, ..
                     },
                 ) if kind1 == kind2 => Ok(true),
+                (
+                    &DateOp {
+                        kind: ref kind1, ..
+                    },
+                    &DateOp {
+                        kind: ref kind2, ..
+                    },
+                ) if kind1 == kind2 => Ok(true),
                 (&UnaryOp { .. }, &UnaryOp { .. }) => Ok(true),
+                (
+                    &StringOp {
+                        kind: ref kind1, ..
+                    },
+                    &StringOp {
+                        kind: ref kind2, ..
+                    },
+                ) if kind1 == kind2 => Ok(true),
                 (
                     &Cast {
                         kind: ref kind1, ..
@@ -97,8 +113,25 @@ impl CompareIgnoringSymbols for Expr {
                 (&Lookup { .. }, &Lookup { .. }) => Ok(true),
                 (&OptLookup { .. }, &OptLookup { .. }) => Ok(true),
                 (&KeyExists { .. }, &KeyExists { .. }) => Ok(true),
+                (&Hash(_), &Hash(_)) => Ok(true),
                 (&Slice { .. }, &Slice { .. }) => Ok(true),
+                (&Scan { op: op1, .. }, &Scan { op: op2, .. }) if op1 == op2 => Ok(true),
+                (&Window { op: op1, .. }, &Window { op: op2, .. }) if op1 == op2 => Ok(true),
+                (
+                    &Shift {
+                        kind: ref kind1, ..
+                    },
+                    &Shift {
+                        kind: ref kind2, ..
+                    },
+                ) if kind1 == kind2 => Ok(true),
                 (&Sort { .. }, &Sort { .. }) => Ok(true),
+                (&Null, &Null) => Ok(true),
+                (&MakeOptional(_), &MakeOptional(_)) => Ok(true),
+                (&IsNull(_), &IsNull(_)) => Ok(true),
+                (&Coalesce { .. }, &Coalesce { .. }) => Ok(true),
+                (&DistinctCount(_), &DistinctCount(_)) => Ok(true),
+                (&Quantile { .. }, &Quantile { .. }) => Ok(true),
                 (&Merge { .. }, &Merge { .. }) => Ok(true),
                 (&Res { .. }, &Res { .. }) => Ok(true),
                 (
@@ -205,10 +205,9 @@ pub fn lookup_expr(data: Expr, index: Expr) -> WeldResult<Expr> {
 
 pub fn keyexists_expr(data: Expr, key: Expr) -> WeldResult<Expr> {
     let err = compile_err!("Internal error: Mismatched types in keyexists_expr");
-    let kt = if let Dict(ref kt, _) = data.ty {
-        *kt.clone()
-    } else {
-        return err;
+    let kt = match data.ty {
+        Dict(ref kt, _) | Set(ref kt) => *kt.clone(),
+        _ => return err,
     };
 
     if key.ty != kt {
@@ -549,11 +548,17 @@ pub fn merge_expr(builder: Expr, value: Expr) -> WeldResult<Expr> {
                     return err;
                 }
             }
-            Merger(ref elem_ty, _) => {
+            Merger(ref elem_ty, _) | TopK(ref elem_ty, _) | SetMerger(ref elem_ty) => {
                 if elem_ty.as_ref() != &value.ty {
                     return err;
                 }
             }
+            SketchMerger(kind, ref elem_ty) => {
+                // Partial sketches of the same kind can also be merged.
+                if elem_ty.as_ref() != &value.ty && Sketch(kind, elem_ty.clone()) != value.ty {
+                    return err;
+                }
+            }
             DictMerger(ref elem_ty1, ref elem_ty2, _) => {
                 if let Struct(ref v_ty) = value.ty {
                     if v_ty.len() < 2 {
@@ -622,7 +627,9 @@ pub fn result_expr(builder: Expr) -> WeldResult<Expr> {
             Merger(ref elem_ty, _) => *elem_ty.clone(),
             DictMerger(ref kt, ref vt, _) => Dict(kt.clone(), vt.clone()),
             GroupMerger(ref kt, ref vt) => Dict(kt.clone(), Box::new(Vector(vt.clone()))),
-            VecMerger(ref elem_ty, _) => Vector(elem_ty.clone()),
+            VecMerger(ref elem_ty, _) | TopK(ref elem_ty, _) => Vector(elem_ty.clone()),
+            SetMerger(ref kt) => Set(kt.clone()),
+            SketchMerger(kind, ref elem_ty) => Sketch(kind, elem_ty.clone()),
         }
     } else {
         return err;
@@ -97,9 +97,21 @@ impl ExprHash {
             UnaryOp { ref kind, .. } => {
                 kind.hash(&mut self.hasher);
             }
+            StringOp { ref kind, .. } => {
+                kind.hash(&mut self.hasher);
+            }
+            DateOp { ref kind, .. } => {
+                kind.hash(&mut self.hasher);
+            }
             Cast { ref kind, .. } => {
                 kind.hash(&mut self.hasher);
             }
+            Scan { ref op, .. } | Window { ref op, .. } => {
+                op.hash(&mut self.hasher);
+            }
+            Shift { ref kind, .. } => {
+                kind.hash(&mut self.hasher);
+            }
             GetField { ref index, .. } => {
                 index.hash(&mut self.hasher);
             }
@@ -170,8 +182,15 @@ impl ExprHash {
             | Lookup { .. }
             | OptLookup { .. }
             | KeyExists { .. }
+            | Hash(_)
             | Slice { .. }
             | Sort { .. }
+            | Null
+            | MakeOptional(_)
+            | IsNull(_)
+            | Coalesce { .. }
+            | DistinctCount(_)
+            | Quantile { .. }
             | If { .. }
             | Iterate { .. }
             | Select { .. }
@@ -9,9 +9,14 @@ pub use self::ast::*;
 pub use self::builder::NewExpr;
 pub use self::cmp::CompareIgnoringSymbols;
 pub use self::hash::HashIgnoringSymbols;
+pub use self::optional::LowerOptionals;
 pub use self::pretty_print::{PrettyPrint, PrettyPrintConfig};
+pub use self::set::LowerSets;
+pub use self::sketch::LowerSketches;
+pub use self::temporal::LowerTemporals;
 pub use self::type_inference::InferTypes;
 pub use self::uniquify::Uniquify;
+pub use self::window::LowerWindows;
 
 pub mod constructors;
 pub mod prelude;
@@ -20,6 +25,11 @@ mod ast;
 mod builder;
 mod cmp;
 mod hash;
+mod optional;
 mod pretty_print;
+mod set;
+mod sketch;
+mod temporal;
 mod type_inference;
 mod uniquify;
+mod window;
@@ -0,0 +1,296 @@
+//! Lowers optional values to structs.
+//!
+//! An `optional[T]` is represented as a `{bool, T}` struct, where the `bool` indicates whether the
+//! value is non-null. This is the same layout as the result of `optlookup`. The value of a null
+//! optional is always zero, so optional values can be compared and hashed like other structs.
+//!
+//! Optional values only exist in the type-checked AST: this pass runs directly after type
+//! inference, so the optimizer and code generators only see the lowered structs.
+
+use crate::ast::BinOpKind::*;
+use crate::ast::ExprKind::*;
+use crate::ast::LiteralKind::*;
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::*;
+use crate::ast::*;
+use crate::error::*;
+use crate::util::SymbolGenerator;
+
+/// Index of the field that holds whether an optional value is non-null.
+const VALID_INDEX: u32 = 0;
+/// Index of the field that holds the value of an optional value.
+const VALUE_INDEX: u32 = 1;
+
+/// A trait for lowering optional values to structs.
+pub trait LowerOptionals {
+    /// Replaces optional types and expressions over optional values with structs, in place.
+    ///
+    /// The expression must be type checked.
+    fn lower_optionals(&mut self) -> WeldResult<()>;
+}
+
+impl LowerOptionals for Expr {
+    fn lower_optionals(&mut self) -> WeldResult<()> {
+        let mut sym_gen = SymbolGenerator::from_expression(self);
+        lower_expr(self, &mut sym_gen)
+    }
+}
+
+/// Replaces an optional type with its struct representation.
+fn lower_type(ty: &mut Type) {
+    for ty in ty.children_mut() {
+        lower_type(ty);
+    }
+
+    let lowered = if let Optional(ref value) = *ty {
+        Some(Struct(vec![Scalar(Bool), value.as_ref().clone()]))
+    } else {
+        None
+    };
+
+    if let Some(lowered) = lowered {
+        *ty = lowered;
+    }
+}
+
+/// Returns a literal of the given scalar kind.
+pub(super) fn scalar_literal(kind: ScalarKind, value: i64) -> WeldResult<Expr> {
+    let literal = match kind {
+        Bool => BoolLiteral(value != 0),
+        I8 => I8Literal(value as i8),
+        I16 => I16Literal(value as i16),
+        I32 => I32Literal(value as i32),
+        I64 => I64Literal(value),
+        U8 => U8Literal(value as u8),
+        U16 => U16Literal(value as u16),
+        U32 => U32Literal(value as u32),
+        U64 => U64Literal(value as u64),
+        F32 => F32Literal((value as f32).to_bits()),
+        F64 => F64Literal((value as f64).to_bits()),
+        Decimal(_, _) | Timestamp | Interval => {
+            return Expr::new_cast(kind, Expr::new_literal(I64Literal(value))?)
+        }
+        Date => return Expr::new_cast(kind, Expr::new_literal(I32Literal(value as i32))?),
+    };
+    Expr::new_literal(literal)
+}
+
+/// Returns the value held by null optionals of type `ty`.
+fn zero_value(ty: &Type) -> WeldResult<Expr> {
+    match *ty {
+        Scalar(kind) => scalar_literal(kind, 0),
+        Vector(ref elem) => Expr::new_make_vector_typed(vec![], elem.as_ref().clone()),
+        Struct(ref fields) if !fields.is_empty() => {
+            let fields = fields.iter().map(zero_value).collect::<WeldResult<_>>()?;
+            Expr::new_make_struct(fields)
+        }
+        _ => compile_err!("Null values of type {} are not supported", ty),
+    }
+}
+
+/// Returns the value type of a lowered optional type.
+fn value_type(ty: &Type) -> WeldResult<&Type> {
+    match *ty {
+        Struct(ref fields) if fields.len() == 2 => Ok(&fields[VALUE_INDEX as usize]),
+        _ => compile_err!("Expected lowered optional type, got {}", ty),
+    }
+}
+
+/// Lowers a binary operator with optional operands.
+///
+/// The operator is applied to the values of the operands, and the result is valid if every
+/// optional operand is valid. Integer and decimal division by the value of a null divisor is
+/// avoided.
+fn lower_bin_op(
+    kind: BinOpKind,
+    left: Expr,
+    left_optional: bool,
+    right: Expr,
+    right_optional: bool,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Expr> {
+    let mut bindings = vec![];
+    let mut values = vec![];
+    let mut valids = vec![];
+    for (operand, optional) in vec![(left, left_optional), (right, right_optional)] {
+        let name = sym_gen.new_symbol("operand");
+        let ident = Expr::new_ident(name.clone(), operand.ty.clone())?;
+        if optional {
+            valids.push(Expr::new_get_field(ident.clone(), VALID_INDEX)?);
+            values.push(Expr::new_get_field(ident, VALUE_INDEX)?);
+        } else {
+            values.push(ident);
+        }
+        bindings.push((name, operand));
+    }
+
+    let right_value = values.pop().unwrap();
+    let left_value = values.pop().unwrap();
+    let right_value = match right_value.ty {
+        Scalar(scalar)
+            if right_optional
+                && (scalar.is_integer() || scalar.is_decimal())
+                && (kind == Divide || kind == Modulo) =>
+        {
+            let right_valid = valids.last().unwrap().clone();
+            Expr::new_select(right_valid, right_value, scalar_literal(scalar, 1)?)?
+        }
+        _ => right_value,
+    };
+
+    let valid = match (valids.pop(), valids.pop()) {
+        (Some(right_valid), Some(left_valid)) => {
+            Expr::new_bin_op(LogicalAnd, left_valid, right_valid)?
+        }
+        (Some(valid), None) => valid,
+        _ => Expr::new_literal(BoolLiteral(true))?,
+    };
+
+    let value = Expr::new_bin_op(kind, left_value, right_value)?;
+    let zero = zero_value(&value.ty)?;
+    let value = Expr::new_select(valid.clone(), value, zero)?;
+    let mut result = Expr::new_make_struct(vec![valid, value])?;
+
+    for (name, operand) in bindings.into_iter().rev() {
+        result = Expr::new_let(name, operand, result)?;
+    }
+    Ok(result)
+}
+
+/// Lowers optional values in an expression and its subexpressions.
+fn lower_expr(expr: &mut Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<()> {
+    // Binary operators are rewritten if they have optional operands, which we can only tell
+    // before lowering the operands.
+    let optional_operands = match expr.kind {
+        BinOp {
+            ref left,
+            ref right,
+            ..
+        } if expr.ty.is_optional() => Some((left.ty.is_optional(), right.ty.is_optional())),
+        _ => None,
+    };
+
+    for child in expr.children_mut() {
+        lower_expr(child, sym_gen)?;
+    }
+
+    match expr.kind {
+        Lambda { ref mut params, .. } => {
+            for param in params.iter_mut() {
+                lower_type(&mut param.ty);
+            }
+        }
+        CUDF {
+            ref mut return_ty, ..
+        } => {
+            lower_type(return_ty);
+        }
+        Deserialize {
+            ref mut value_ty, ..
+        } => {
+            lower_type(value_ty);
+        }
+        _ => (),
+    }
+    lower_type(&mut expr.ty);
+
+    let lowered = match expr.kind {
+        Null => {
+            let zero = zero_value(value_type(&expr.ty)?)?;
+            Some(Expr::new_make_struct(vec![
+                Expr::new_literal(BoolLiteral(false))?,
+                zero,
+            ])?)
+        }
+        MakeOptional(ref mut value) => Some(Expr::new_make_struct(vec![
+            Expr::new_literal(BoolLiteral(true))?,
+            *value.take(),
+        ])?),
+        IsNull(ref mut value) => Some(Expr::new_not(Expr::new_get_field(
+            *value.take(),
+            VALID_INDEX,
+        )?)?),
+        Coalesce {
+            ref mut value,
+            ref mut default,
+        } => {
+            let name = sym_gen.new_symbol("optional");
+            let ident = Expr::new_ident(name.clone(), value.ty.clone())?;
+            let valid = Expr::new_get_field(ident.clone(), VALID_INDEX)?;
+            let inner = Expr::new_get_field(ident, VALUE_INDEX)?;
+            let default = *default.take();
+            // Only evaluate the default if the value is null, unless it is trivial.
+            let result = match default.kind {
+                Literal(_) | Ident(_) => Expr::new_select(valid, inner, default)?,
+                _ => Expr::new_if(valid, inner, default)?,
+            };
+            Some(Expr::new_let(name, *value.take(), result)?)
+        }
+        BinOp {
+            kind,
+            ref mut left,
+            ref mut right,
+        } => {
+            if let Some((left_optional, right_optional)) = optional_operands {
+                Some(lower_bin_op(
+                    kind,
+                    *left.take(),
+                    left_optional,
+                    *right.take(),
+                    right_optional,
+                    sym_gen,
+                )?)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *expr = lowered;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tests::*;
+
+/// Checks that `code` lowers to `expected`, ignoring symbol names.
+#[cfg(test)]
+fn check_lowering(code: &str, expected: &str) {
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    e.lower_optionals().unwrap();
+    let mut expected = parse_expr(expected).unwrap();
+    expected.infer_types().unwrap();
+    assert!(e.compare_ignoring_symbols(&expected).unwrap());
+}
+
+#[test]
+fn lower_optional_expressions() {
+    check_lowering(
+        "|a: optional[i32]| {isnull(a), coalesce(a, 1), optional(2), null[i32]}",
+        "|a: {bool,i32}| {!(a.$0), (let o = a; select(o.$0, o.$1, 1)), {true, 2}, {false, 0}}",
+    );
+    check_lowering(
+        "|a: optional[vec[i8]], b: vec[i8]| coalesce(a, strconcat(b, b))",
+        "|a: {bool,vec[i8]}, b: vec[i8]| let o = a; if(o.$0, o.$1, strconcat(b, b))",
+    );
+}
+
+#[test]
+fn lower_optional_bin_ops() {
+    check_lowering(
+        "|a: optional[f64], b: optional[f64]| a < b",
+        "|a: {bool,f64}, b: {bool,f64}| let l = a; let r = b;
+            {l.$0 && r.$0, select(l.$0 && r.$0, l.$1 < r.$1, false)}",
+    );
+
+    // Null divisors are replaced with one.
+    check_lowering(
+        "|a: i64, b: optional[i64]| a % b",
+        "|a: i64, b: {bool,i64}| let l = a; let r = b;
+            {r.$0, select(r.$0, l % select(r.$0, r.$1, 1L), 0L)}",
+    );
+}
@@ -161,6 +161,14 @@ fn print_iters(iters: &[Iter], config: &mut PrettyPrintConfig) -> String {
     }
 }
 
+/// Prints the element type of a window operator with the vector type `ty`.
+fn print_elem_type(ty: &Type) -> String {
+    match *ty {
+        Type::Vector(ref elem) => elem.to_string(),
+        _ => Type::Unknown.to_string(),
+    }
+}
+
 /// Pretty print each expression in an AST recursively.
 fn to_string_impl(expr: &Expr, config: &mut PrettyPrintConfig) -> String {
     let (less_indent_str, indent_str, newline) = indentation(config);
@@ -189,6 +197,24 @@ fn to_string_impl(expr: &Expr, config: &mut PrettyPrintConfig) -> String {
 
         UnaryOp { kind, ref value } => format!("({}({}))", kind, to_string_impl(value, config)),
 
+        StringOp { kind, ref args } => {
+            let args = join(
+                "(",
+                ",",
+                ")",
+                args.iter().map(|e| to_string_impl(e, config)),
+            );
+            format!("{}{}", kind, args)
+        }
+
+        DateOp { kind, ref value } => {
+            let value = to_string_impl(value, config);
+            match kind {
+                DateOpKind::Trunc(unit) => format!("{}(\"{}\",{})", kind, unit, value),
+                _ => format!("{}({})", kind, value),
+            }
+        }
+
         Negate(ref e) => format!("(-{})", to_string_impl(e, config)),
 
         Not(ref e) => format!("(!{})", to_string_impl(e, config)),
@@ -315,6 +341,8 @@ fn to_string_impl(expr: &Expr, config: &mut PrettyPrintConfig) -> String {
             to_string_impl(key, config)
         ),
 
+        Hash(ref value) => format!("hash({})", to_string_impl(value, config)),
+
         Slice {
             ref data,
             ref index,
@@ -326,6 +354,67 @@ fn to_string_impl(expr: &Expr, config: &mut PrettyPrintConfig) -> String {
             to_string_impl(size, config)
         ),
 
+        Null => match expr.ty {
+            Type::Optional(ref value) => format!("null[{}]", value),
+            _ => "null".to_string(),
+        },
+
+        MakeOptional(ref value) => format!("optional({})", to_string_impl(value, config)),
+
+        IsNull(ref value) => format!("isnull({})", to_string_impl(value, config)),
+
+        Coalesce {
+            ref value,
+            ref default,
+        } => format!(
+            "coalesce({},{})",
+            to_string_impl(value, config),
+            to_string_impl(default, config)
+        ),
+
+        DistinctCount(ref sketch) => format!("distinctcount({})", to_string_impl(sketch, config)),
+
+        Quantile {
+            ref sketch,
+            ref quantile,
+        } => format!(
+            "quantile({},{})",
+            to_string_impl(sketch, config),
+            to_string_impl(quantile, config)
+        ),
+
+        Scan { ref data, op } => format!(
+            "scan[{},{}]({})",
+            print_elem_type(&expr.ty),
+            op,
+            to_string_impl(data, config)
+        ),
+
+        Window {
+            ref data,
+            ref size,
+            op,
+        } => format!(
+            "window[{},{}]({},{})",
+            print_elem_type(&expr.ty),
+            op,
+            to_string_impl(data, config),
+            to_string_impl(size, config)
+        ),
+
+        Shift {
+            kind,
+            ref data,
+            ref offset,
+            ref default,
+        } => format!(
+            "{}({},{},{})",
+            kind,
+            to_string_impl(data, config),
+            to_string_impl(offset, config),
+            to_string_impl(default, config)
+        ),
+
         Sort {
             ref data,
             ref cmpfunc,
@@ -0,0 +1,207 @@
+//! Lowers sets to dictionaries.
+//!
+//! A `set[K]` is represented as a `dict[K,{}]` and a `setmerger[K]` as a `dictmerger[K,{},+]`,
+//! so sets reuse the hash tables of dictionaries. The values are empty structs, which take no
+//! space in the slots of the table or in serialized dictionaries.
+//!
+//! `contains` and `len` have the same meaning for the lowered dictionaries, so only merges into
+//! set mergers and `tovec` over sets are rewritten.
+
+use crate::ast::BinOpKind::*;
+use crate::ast::BuilderKind::*;
+use crate::ast::ExprKind::*;
+use crate::ast::Type::*;
+use crate::ast::*;
+use crate::error::*;
+use crate::util::SymbolGenerator;
+
+/// Index of the key in a key/value pair of the lowered dictionary.
+const KEY_INDEX: u32 = 0;
+
+/// A trait for lowering sets to dictionaries.
+pub trait LowerSets {
+    /// Replaces set types and expressions over sets with dictionaries, in place.
+    ///
+    /// The expression must be type checked.
+    fn lower_sets(&mut self) -> WeldResult<()>;
+}
+
+impl LowerSets for Expr {
+    fn lower_sets(&mut self) -> WeldResult<()> {
+        let mut sym_gen = SymbolGenerator::from_expression(self);
+        lower_expr(self, &mut sym_gen)
+    }
+}
+
+/// Returns the zero-size value type of lowered sets.
+fn value_type() -> Type {
+    Struct(vec![])
+}
+
+/// Replaces a set or set merger type with its dictionary representation.
+fn lower_type(ty: &mut Type) {
+    for ty in ty.children_mut() {
+        lower_type(ty);
+    }
+
+    let lowered = match *ty {
+        Set(ref key) => Some(Dict(key.clone(), Box::new(value_type()))),
+        Builder(SetMerger(ref key), ref annotations) => Some(Builder(
+            DictMerger(key.clone(), Box::new(value_type()), Add),
+            annotations.clone(),
+        )),
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *ty = lowered;
+    }
+}
+
+/// Returns whether `ty` is a set merger.
+fn is_set_merger(ty: &Type) -> bool {
+    match *ty {
+        Builder(SetMerger(_), _) => true,
+        _ => false,
+    }
+}
+
+/// Returns the keys of the lowered set `set` as a vector.
+///
+/// The keys are copied out of the key/value pairs of the dictionary.
+fn lower_to_vec(set: Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<Expr> {
+    let key_type = match set.ty {
+        Dict(ref key, _) => key.as_ref().clone(),
+        _ => return compile_err!("Expected lowered set type, got {}", set.ty),
+    };
+
+    let name = sym_gen.new_symbol("set");
+    let ident = Expr::new_ident(name.clone(), set.ty.clone())?;
+
+    let pairs = Expr::new_to_vec(ident.clone())?;
+    let pair_type = match pairs.ty {
+        Vector(ref elem) => elem.as_ref().clone(),
+        _ => unreachable!(),
+    };
+    let iter = Iter {
+        data: Box::new(pairs),
+        start: None,
+        end: None,
+        stride: None,
+        kind: IterKind::ScalarIter,
+        strides: None,
+        shape: None,
+    };
+
+    let size = Expr::new_length(ident)?;
+    let builder = Expr::new_new_builder(Appender(Box::new(key_type)), Some(size))?;
+
+    let params = vec![
+        Parameter {
+            name: sym_gen.new_symbol("b"),
+            ty: builder.ty.clone(),
+        },
+        Parameter {
+            name: sym_gen.new_symbol("i"),
+            ty: Scalar(ScalarKind::I64),
+        },
+        Parameter {
+            name: sym_gen.new_symbol("e"),
+            ty: pair_type,
+        },
+    ];
+    let b = Expr::new_ident(params[0].name.clone(), params[0].ty.clone())?;
+    let e = Expr::new_ident(params[2].name.clone(), params[2].ty.clone())?;
+    let body = Expr::new_merge(b, Expr::new_get_field(e, KEY_INDEX)?)?;
+    let func = Expr::new_lambda(params, body)?;
+
+    let keys = Expr::new_result(Expr::new_for(vec![iter], builder, func)?)?;
+    Expr::new_let(name, set, keys)
+}
+
+/// Lowers sets in an expression and its subexpressions.
+fn lower_expr(expr: &mut Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<()> {
+    // Merges and conversions are rewritten if they operate on sets, which we can only tell
+    // before lowering the operands.
+    let over_set = match expr.kind {
+        Merge { ref builder, .. } => is_set_merger(&builder.ty),
+        ToVec { ref child_expr } => match child_expr.ty {
+            Set(_) => true,
+            _ => false,
+        },
+        _ => false,
+    };
+
+    for child in expr.children_mut() {
+        lower_expr(child, sym_gen)?;
+    }
+
+    match expr.kind {
+        Lambda { ref mut params, .. } => {
+            for param in params.iter_mut() {
+                lower_type(&mut param.ty);
+            }
+        }
+        CUDF {
+            ref mut return_ty, ..
+        } => {
+            lower_type(return_ty);
+        }
+        Deserialize {
+            ref mut value_ty, ..
+        } => {
+            lower_type(value_ty);
+        }
+        _ => (),
+    }
+    lower_type(&mut expr.ty);
+
+    if !over_set {
+        return Ok(());
+    }
+
+    let lowered = match expr.kind {
+        Merge {
+            ref mut builder,
+            ref mut value,
+        } => {
+            let pair = Expr::new_make_struct(vec![*value.take(), Expr::new_make_struct(vec![])?])?;
+            Expr::new_merge(*builder.take(), pair)?
+        }
+        ToVec { ref mut child_expr } => lower_to_vec(*child_expr.take(), sym_gen)?,
+        _ => unreachable!(),
+    };
+    *expr = lowered;
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tests::*;
+
+/// Checks that `code` lowers to `expected`, ignoring symbol names.
+#[cfg(test)]
+fn check_lowering(code: &str, expected: &str) {
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    e.lower_sets().unwrap();
+    let mut expected = parse_expr(expected).unwrap();
+    expected.infer_types().unwrap();
+    assert!(e.compare_ignoring_symbols(&expected).unwrap());
+}
+
+#[test]
+fn lower_set_expressions() {
+    check_lowering(
+        "|v: vec[i32]| result(for(v, setmerger[i32], |b, i, e| merge(b, e)))",
+        "|v: vec[i32]| result(for(v, dictmerger[i32,{},+], |b, i, e| merge(b, {e, {}})))",
+    );
+    check_lowering(
+        "|s: set[i64], k: i64| {contains(s, k), len(s)}",
+        "|s: dict[i64,{}], k: i64| {keyexists(s, k), len(s)}",
+    );
+    check_lowering(
+        "|s: set[i64]| tovec(s)",
+        "|s: dict[i64,{}]| let t = s;
+            result(for(tovec(t), appender[i64](len(t)), |b, i, e| merge(b, e.$0)))",
+    );
+}
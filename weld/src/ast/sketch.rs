@@ -0,0 +1,593 @@
+//! Lowers sketches to vectors and dictionaries.
+//!
+//! An `hll[K]` is a HyperLogLog sketch with `2^12` registers, represented as a `vec[u8]`. An
+//! `hllmerger[K]` is a `vecmerger[u8,max]` over zeroed registers: merging a key hashes it, picks
+//! a register with the top bits of the hash and keeps the position of the first set bit in the
+//! remaining bits. `distinctcount` computes the usual HyperLogLog estimate from the registers.
+//!
+//! A `quantiles[T]` is a sketch with logarithmic buckets in the style of DDSketch, represented as
+//! a `dict[i32,i64]` from bucket keys to counts. A `quantilemerger[T]` is a `dictmerger[i32,i64,+]`
+//! and `quantile` walks the sorted buckets until it reaches the requested rank. Quantiles are
+//! estimated with a relative error of at most one percent.
+//!
+//! Both representations are merged with associative operators, so sketch mergers can be fused
+//! and parallelized like any other merger, and partial sketches can be serialized and merged
+//! into other sketch mergers of the same kind.
+
+use super::temporal::Bindings;
+
+use crate::ast::BinOpKind::*;
+use crate::ast::BuilderKind::*;
+use crate::ast::ExprKind::*;
+use crate::ast::LiteralKind::*;
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::*;
+use crate::ast::UnaryOpKind::*;
+use crate::ast::*;
+use crate::error::*;
+use crate::util::SymbolGenerator;
+
+/// The number of hash bits used to pick a HyperLogLog register.
+const HLL_PRECISION: u32 = 12;
+/// The number of registers of a HyperLogLog sketch.
+const HLL_REGISTERS: i64 = 1 << HLL_PRECISION;
+/// The bias correction of the HyperLogLog estimate for `HLL_REGISTERS` registers.
+const HLL_ALPHA: f64 = 0.7213 / (1.0 + 1.079 / HLL_REGISTERS as f64);
+/// The number of distinct values of the 32-bit hashes used by HyperLogLog sketches.
+const HASH_SPACE: f64 = 4_294_967_296.0;
+
+/// The relative accuracy of quantile sketches.
+const RELATIVE_ACCURACY: f64 = 0.01;
+/// Added to the magnitude of bucket indices, so that the sign of a key is the sign of its values
+/// and the key zero is left for zeros.
+const QUANTILE_KEY_OFFSET: i32 = 65_536;
+/// The largest magnitude of a bucket index, which covers values between `1e-174` and `1e174`.
+const QUANTILE_MAX_INDEX: f64 = 40_000.0;
+
+/// A trait for lowering sketches to vectors and dictionaries.
+pub trait LowerSketches {
+    /// Replaces sketch types and expressions over sketches with vectors and dictionaries, in
+    /// place.
+    ///
+    /// The expression must be type checked.
+    fn lower_sketches(&mut self) -> WeldResult<()>;
+}
+
+impl LowerSketches for Expr {
+    fn lower_sketches(&mut self) -> WeldResult<()> {
+        let mut sym_gen = SymbolGenerator::from_expression(self);
+        lower_expr(self, &mut sym_gen)
+    }
+}
+
+/// Returns the ratio of the bounds of a quantile bucket.
+fn gamma() -> f64 {
+    (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+}
+
+/// Returns an `i64` literal.
+fn int(value: i64) -> WeldResult<Expr> {
+    Expr::new_literal(I64Literal(value))
+}
+
+/// Returns a `u32` literal.
+fn uint(value: u32) -> WeldResult<Expr> {
+    Expr::new_literal(U32Literal(value))
+}
+
+/// Returns an `f64` literal.
+fn float(value: f64) -> WeldResult<Expr> {
+    Expr::new_literal(F64Literal(value.to_bits()))
+}
+
+/// Replaces a sketch or sketch merger type with its representation.
+fn lower_type(ty: &mut Type) {
+    for ty in ty.children_mut() {
+        lower_type(ty);
+    }
+
+    let lowered = match *ty {
+        Sketch(SketchKind::HyperLogLog, _) => Some(Vector(Box::new(Scalar(U8)))),
+        Sketch(SketchKind::Quantiles, _) => Some(quantile_dict()),
+        Builder(SketchMerger(SketchKind::HyperLogLog, _), ref annotations) => Some(Builder(
+            VecMerger(Box::new(Scalar(U8)), Max),
+            annotations.clone(),
+        )),
+        Builder(SketchMerger(SketchKind::Quantiles, _), ref annotations) => Some(Builder(
+            DictMerger(Box::new(Scalar(I32)), Box::new(Scalar(I64)), Add),
+            annotations.clone(),
+        )),
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *ty = lowered;
+    }
+}
+
+/// Returns the representation of quantile sketches.
+fn quantile_dict() -> Type {
+    Dict(Box::new(Scalar(I32)), Box::new(Scalar(I64)))
+}
+
+/// Returns the kind of sketch merger `ty` is.
+fn sketch_merger_kind(ty: &Type) -> Option<SketchKind> {
+    match *ty {
+        Builder(SketchMerger(kind, _), _) => Some(kind),
+        _ => None,
+    }
+}
+
+/// Returns an iterator over all elements of a vector.
+fn scalar_iter(data: Expr) -> Iter {
+    Iter {
+        data: Box::new(data),
+        start: None,
+        end: None,
+        stride: None,
+        kind: IterKind::ScalarIter,
+        strides: None,
+        shape: None,
+    }
+}
+
+/// Returns a loop over the vector `data` that merges into `builder`.
+///
+/// `body` receives the builder, index and element parameters of the loop function, and bindings
+/// for the function body.
+fn for_each<F>(b: &mut Bindings<'_>, data: Expr, builder: Expr, body: F) -> WeldResult<Expr>
+where
+    F: FnOnce(&mut Bindings<'_>, Expr, Expr, Expr) -> WeldResult<Expr>,
+{
+    let elem_type = match data.ty {
+        Vector(ref elem) => elem.as_ref().clone(),
+        _ => return compile_err!("Expected vector in lowered sketch loop, got {}", data.ty),
+    };
+    let (builder_param, builder_ident) = b.param("b", builder.ty.clone())?;
+    let (index_param, index_ident) = b.param("i", Scalar(I64))?;
+    let (elem_param, elem_ident) = b.param("e", elem_type)?;
+
+    let mut inner = b.nested();
+    let result = body(&mut inner, builder_ident, index_ident, elem_ident)?;
+    let body = inner.wrap(result)?;
+
+    let func = Expr::new_lambda(vec![builder_param, index_param, elem_param], body)?;
+    Expr::new_for(vec![scalar_iter(data)], builder, func)
+}
+
+/// Returns the zeroed registers that HyperLogLog mergers start from.
+fn hll_registers(b: &mut Bindings<'_>) -> WeldResult<Expr> {
+    let range = Iter {
+        data: Box::new(Expr::new_make_vector_typed(vec![], Scalar(I64))?),
+        start: Some(Box::new(int(0)?)),
+        end: Some(Box::new(int(HLL_REGISTERS)?)),
+        stride: Some(Box::new(int(1)?)),
+        kind: IterKind::RangeIter,
+        strides: None,
+        shape: None,
+    };
+    let builder = Expr::new_new_builder(Appender(Box::new(Scalar(U8))), Some(int(HLL_REGISTERS)?))?;
+    let (builder_param, builder_ident) = b.param("b", builder.ty.clone())?;
+    let (index_param, _) = b.param("i", Scalar(I64))?;
+    let (elem_param, _) = b.param("e", Scalar(I64))?;
+    let body = Expr::new_merge(builder_ident, Expr::new_literal(U8Literal(0))?)?;
+    let func = Expr::new_lambda(vec![builder_param, index_param, elem_param], body)?;
+    Expr::new_result(Expr::new_for(vec![range], builder, func)?)
+}
+
+/// Returns the register index and value that a key updates in a HyperLogLog sketch.
+fn hll_update(b: &mut Bindings<'_>, key: Expr) -> WeldResult<Expr> {
+    // Mix the bits of the hash with the MurmurHash3 finalizer, since registers are picked with
+    // its top bits.
+    let mut hash = b.bind("h", Expr::new_hash(key)?)?;
+    for &(shift, multiplier) in &[(16, 0x85eb_ca6b), (13, 0xc2b2_ae35), (16, 1)] {
+        let shifted = Expr::new_bin_op(ShiftRight, hash.clone(), uint(shift)?)?;
+        let mut mixed = Expr::new_bin_op(Xor, hash, shifted)?;
+        if multiplier != 1 {
+            mixed = Expr::new_bin_op(Multiply, mixed, uint(multiplier)?)?;
+        }
+        hash = b.bind("h", mixed)?;
+    }
+
+    let index = Expr::new_bin_op(ShiftRight, hash.clone(), uint(32 - HLL_PRECISION)?)?;
+    // The sentinel bit bounds the rank when the remaining bits are all zero.
+    let rest = Expr::new_bin_op(
+        BitwiseOr,
+        Expr::new_bin_op(ShiftLeft, hash, uint(HLL_PRECISION)?)?,
+        uint(1 << (HLL_PRECISION - 1))?,
+    )?;
+    let rank = Expr::new_bin_op(Add, Expr::new_unary_op(Clz, rest)?, uint(1)?)?;
+    Expr::new_make_struct(vec![Expr::new_cast(I64, index)?, Expr::new_cast(U8, rank)?])
+}
+
+/// Returns the key of the bucket that a value falls into in a quantile sketch.
+fn quantile_key(b: &mut Bindings<'_>, value: Expr) -> WeldResult<Expr> {
+    let value = b.bind("value", Expr::new_cast(F64, value)?)?;
+    let magnitude = Expr::new_unary_op(Abs, value.clone())?;
+    let index = Expr::new_bin_op(
+        Divide,
+        Expr::new_unary_op(Log, magnitude)?,
+        float(gamma().ln())?,
+    )?;
+    let index = Expr::new_bin_op(
+        Min,
+        Expr::new_bin_op(Max, index, float(-QUANTILE_MAX_INDEX)?)?,
+        float(QUANTILE_MAX_INDEX)?,
+    )?;
+    let index = Expr::new_cast(I32, Expr::new_unary_op(Ceil, index)?)?;
+    let key = b.bind(
+        "key",
+        Expr::new_bin_op(
+            Add,
+            index,
+            Expr::new_literal(I32Literal(QUANTILE_KEY_OFFSET))?,
+        )?,
+    )?;
+    Expr::new_select(
+        Expr::new_bin_op(GreaterThan, value.clone(), float(0.0)?)?,
+        key.clone(),
+        Expr::new_select(
+            Expr::new_bin_op(LessThan, value, float(0.0)?)?,
+            Expr::new_negate(key)?,
+            Expr::new_literal(I32Literal(0))?,
+        )?,
+    )
+}
+
+/// Lowers a merge into a sketch merger. `partial` is whether the merged value is a sketch.
+fn lower_merge(
+    kind: SketchKind,
+    partial: bool,
+    builder: Expr,
+    value: Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Expr> {
+    let mut b = Bindings::new(sym_gen);
+    let result = match (kind, partial) {
+        (SketchKind::HyperLogLog, false) => {
+            let update = hll_update(&mut b, value)?;
+            Expr::new_merge(builder, update)?
+        }
+        (SketchKind::HyperLogLog, true) => {
+            for_each(&mut b, value, builder, |_, builder, index, register| {
+                Expr::new_merge(builder, Expr::new_make_struct(vec![index, register])?)
+            })?
+        }
+        (SketchKind::Quantiles, false) => {
+            let key = quantile_key(&mut b, value)?;
+            Expr::new_merge(builder, Expr::new_make_struct(vec![key, int(1)?])?)?
+        }
+        (SketchKind::Quantiles, true) => {
+            let buckets = Expr::new_to_vec(value)?;
+            for_each(&mut b, buckets, builder, |_, builder, _, bucket| {
+                Expr::new_merge(builder, bucket)
+            })?
+        }
+    };
+    b.wrap(result)
+}
+
+/// Lowers `distinctcount` over the registers of a HyperLogLog sketch.
+fn lower_distinct_count(registers: Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<Expr> {
+    let mut b = Bindings::new(sym_gen);
+    let registers = b.bind("registers", registers)?;
+
+    // Sum 2^-register over the registers, and count the registers that are still zero.
+    let builder = Expr::new_make_struct(vec![
+        Expr::new_new_builder(Merger(Box::new(Scalar(F64)), Add), None)?,
+        Expr::new_new_builder(Merger(Box::new(Scalar(I64)), Add), None)?,
+    ])?;
+    let sums = for_each(&mut b, registers, builder, |_, builder, _, register| {
+        let power = Expr::new_unary_op(
+            Exp,
+            Expr::new_bin_op(
+                Multiply,
+                Expr::new_cast(F64, register.clone())?,
+                float(-std::f64::consts::LN_2)?,
+            )?,
+        )?;
+        let is_zero = Expr::new_select(
+            Expr::new_bin_op(Equal, register, Expr::new_literal(U8Literal(0))?)?,
+            int(1)?,
+            int(0)?,
+        )?;
+        Expr::new_make_struct(vec![
+            Expr::new_merge(Expr::new_get_field(builder.clone(), 0)?, power)?,
+            Expr::new_merge(Expr::new_get_field(builder, 1)?, is_zero)?,
+        ])
+    })?;
+    let sums = b.bind("sums", sums)?;
+    let sum = Expr::new_result(Expr::new_get_field(sums.clone(), 0)?)?;
+    let zeros = b.bind("zeros", Expr::new_result(Expr::new_get_field(sums, 1)?)?)?;
+
+    let m = HLL_REGISTERS as f64;
+    let raw = Expr::new_bin_op(Divide, float(HLL_ALPHA * m * m)?, sum)?;
+    let raw = b.bind("estimate", raw)?;
+
+    // Use linear counting for small cardinalities, where some registers are still zero.
+    let linear = Expr::new_bin_op(
+        Multiply,
+        float(m)?,
+        Expr::new_unary_op(
+            Log,
+            Expr::new_bin_op(Divide, float(m)?, Expr::new_cast(F64, zeros.clone())?)?,
+        )?,
+    )?;
+    let small = Expr::new_bin_op(
+        LogicalAnd,
+        Expr::new_bin_op(LessThanOrEqual, raw.clone(), float(2.5 * m)?)?,
+        Expr::new_bin_op(GreaterThan, zeros, int(0)?)?,
+    )?;
+    let estimate = b.bind("estimate", Expr::new_select(small, linear, raw)?)?;
+
+    // Correct for collisions of the 32-bit hashes for large cardinalities.
+    let collisions = Expr::new_bin_op(
+        Multiply,
+        float(-HASH_SPACE)?,
+        Expr::new_unary_op(
+            Log,
+            Expr::new_bin_op(
+                Subtract,
+                float(1.0)?,
+                Expr::new_bin_op(Divide, estimate.clone(), float(HASH_SPACE)?)?,
+            )?,
+        )?,
+    )?;
+    let large = Expr::new_bin_op(GreaterThan, estimate.clone(), float(HASH_SPACE / 30.0)?)?;
+    let estimate = Expr::new_select(large, collisions, estimate)?;
+
+    let result = Expr::new_cast(I64, Expr::new_unary_op(Round, estimate)?)?;
+    b.wrap(result)
+}
+
+/// Lowers `quantile` over the buckets of a quantile sketch. `kind` is the type of the values.
+fn lower_quantile(
+    kind: ScalarKind,
+    sketch: Expr,
+    quantile: Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Expr> {
+    let mut b = Bindings::new(sym_gen);
+    let quantile = b.bind("q", quantile)?;
+
+    // Sort the buckets by key, which sorts them by the values they hold.
+    let bucket_type = Struct(vec![Scalar(I32), Scalar(I64)]);
+    let (left_param, left) = b.param("a", bucket_type.clone())?;
+    let (right_param, right) = b.param("b", bucket_type)?;
+    let cmp = Expr::new_lambda(
+        vec![left_param, right_param],
+        Expr::new_bin_op(
+            Subtract,
+            Expr::new_get_field(left, 0)?,
+            Expr::new_get_field(right, 0)?,
+        )?,
+    )?;
+    let buckets = Expr::new_sort(Expr::new_to_vec(sketch)?, cmp)?;
+    let buckets = b.bind("buckets", buckets)?;
+    let len = b.bind("n", Expr::new_length(buckets.clone())?)?;
+
+    let mut found = b.nested();
+    let builder = Expr::new_new_builder(Merger(Box::new(Scalar(I64)), Add), None)?;
+    let total = for_each(
+        &mut found,
+        buckets.clone(),
+        builder,
+        |_, builder, _, bucket| Expr::new_merge(builder, Expr::new_get_field(bucket, 1)?),
+    )?;
+    let total = Expr::new_result(total)?;
+    let rank = Expr::new_cast(
+        I64,
+        Expr::new_bin_op(
+            Multiply,
+            quantile,
+            Expr::new_cast(F64, Expr::new_bin_op(Subtract, total, int(1)?)?)?,
+        )?,
+    )?;
+    let rank = found.bind("rank", rank)?;
+
+    // Walk the buckets with their cumulative count until the count exceeds the rank.
+    let state_type = Struct(vec![Scalar(I64), Scalar(I64)]);
+    let (state_param, state) = found.param("s", state_type)?;
+    let mut step = found.nested();
+    let index = step.bind("index", Expr::new_get_field(state.clone(), 0)?)?;
+    let count = Expr::new_get_field(Expr::new_lookup(buckets.clone(), index.clone())?, 1)?;
+    let count = Expr::new_bin_op(Add, Expr::new_get_field(state, 1)?, count)?;
+    let count = step.bind("count", count)?;
+    let next = Expr::new_bin_op(Add, index.clone(), int(1)?)?;
+    let more = Expr::new_bin_op(
+        LogicalAnd,
+        Expr::new_bin_op(LessThanOrEqual, count.clone(), rank)?,
+        Expr::new_bin_op(LessThan, next.clone(), len.clone())?,
+    )?;
+    let more = step.bind("more", more)?;
+    let next_state =
+        Expr::new_make_struct(vec![Expr::new_select(more.clone(), next, index)?, count])?;
+    let update = step.wrap(Expr::new_make_struct(vec![next_state, more])?)?;
+    let update = Expr::new_lambda(vec![state_param], update)?;
+    let walk = Expr::new_iterate(Expr::new_make_struct(vec![int(0)?, int(0)?])?, update)?;
+    let index = Expr::new_get_field(walk, 0)?;
+    let key = found.bind(
+        "key",
+        Expr::new_get_field(Expr::new_lookup(buckets, index)?, 0)?,
+    )?;
+
+    // Estimate the value in the middle of the bucket, relative to its bounds.
+    let gamma = gamma();
+    let exponent = Expr::new_cast(
+        F64,
+        Expr::new_bin_op(
+            Subtract,
+            Expr::new_unary_op(Abs, key.clone())?,
+            Expr::new_literal(I32Literal(QUANTILE_KEY_OFFSET))?,
+        )?,
+    )?;
+    let magnitude = Expr::new_bin_op(
+        Multiply,
+        Expr::new_unary_op(
+            Exp,
+            Expr::new_bin_op(Multiply, exponent, float(gamma.ln())?)?,
+        )?,
+        float(2.0 / (gamma + 1.0))?,
+    )?;
+    let magnitude = found.bind("magnitude", magnitude)?;
+    let value = Expr::new_select(
+        Expr::new_bin_op(GreaterThan, key.clone(), Expr::new_literal(I32Literal(0))?)?,
+        magnitude.clone(),
+        Expr::new_select(
+            Expr::new_bin_op(LessThan, key, Expr::new_literal(I32Literal(0))?)?,
+            Expr::new_negate(magnitude)?,
+            float(0.0)?,
+        )?,
+    )?;
+    let value = found.wrap(value)?;
+
+    // Quantiles of empty sketches are undefined.
+    let empty = Expr::new_bin_op(Equal, len, int(0)?)?;
+    let result = Expr::new_if(empty, float(std::f64::NAN)?, value)?;
+    b.wrap(Expr::new_cast(kind, result)?)
+}
+
+/// Lowers sketches in an expression and its subexpressions.
+fn lower_expr(expr: &mut Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<()> {
+    // Merges are rewritten if they merge into sketch mergers, which we can only tell before
+    // lowering the operands.
+    let merge = match expr.kind {
+        Merge {
+            ref builder,
+            ref value,
+        } => sketch_merger_kind(&builder.ty).map(|kind| match value.ty {
+            Sketch(_, _) => (kind, true),
+            _ => (kind, false),
+        }),
+        _ => None,
+    };
+    let new_hll = match expr.kind {
+        NewBuilder(_) => sketch_merger_kind(&expr.ty) == Some(SketchKind::HyperLogLog),
+        _ => false,
+    };
+
+    for child in expr.children_mut() {
+        lower_expr(child, sym_gen)?;
+    }
+
+    match expr.kind {
+        Lambda { ref mut params, .. } => {
+            for param in params.iter_mut() {
+                lower_type(&mut param.ty);
+            }
+        }
+        CUDF {
+            ref mut return_ty, ..
+        } => {
+            lower_type(return_ty);
+        }
+        Deserialize {
+            ref mut value_ty, ..
+        } => {
+            lower_type(value_ty);
+        }
+        _ => (),
+    }
+    lower_type(&mut expr.ty);
+
+    let lowered = match (&mut expr.kind, merge) {
+        (
+            &mut Merge {
+                ref mut builder,
+                ref mut value,
+            },
+            Some((kind, partial)),
+        ) => Some(lower_merge(
+            kind,
+            partial,
+            *builder.take(),
+            *value.take(),
+            sym_gen,
+        )?),
+        (&mut NewBuilder(_), _) if new_hll => {
+            let registers = hll_registers(&mut Bindings::new(sym_gen))?;
+            Some(Expr::new_with_type(
+                NewBuilder(Some(Box::new(registers))),
+                expr.ty.clone(),
+            )?)
+        }
+        (&mut DistinctCount(ref mut child), _) => {
+            Some(lower_distinct_count(*child.take(), sym_gen)?)
+        }
+        (
+            &mut Quantile {
+                ref mut sketch,
+                ref mut quantile,
+            },
+            _,
+        ) => {
+            let kind = match expr.ty {
+                Scalar(kind) => kind,
+                _ => return compile_err!("Expected floating point type for quantile"),
+            };
+            Some(lower_quantile(
+                kind,
+                *sketch.take(),
+                *quantile.take(),
+                sym_gen,
+            )?)
+        }
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *expr = lowered;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tests::*;
+
+/// Parses and lowers `code`, and returns the type of the lowered expression.
+#[cfg(test)]
+fn lowered_type(code: &str) -> String {
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    e.lower_sketches().unwrap();
+    // The lowered expression must still type check.
+    e.infer_types().unwrap();
+    format!("{}", e.ty)
+}
+
+#[test]
+fn lower_sketch_types() {
+    assert_eq!(
+        lowered_type("|v: vec[i64]| result(for(v, hllmerger[i64], |b, i, e| merge(b, e)))"),
+        "|vec[i64]|(vec[u8])"
+    );
+    assert_eq!(
+        lowered_type("|v: vec[f64]| result(for(v, quantilemerger[f64], |b, i, e| merge(b, e)))"),
+        "|vec[f64]|(dict[i32,i64])"
+    );
+    assert_eq!(
+        lowered_type("|s: hll[i32]| distinctcount(s)"),
+        "|vec[u8]|(i64)"
+    );
+    assert_eq!(
+        lowered_type("|s: quantiles[f32]| quantile(s, 0.5)"),
+        "|dict[i32,i64]|(f32)"
+    );
+}
+
+#[test]
+fn lower_partial_sketch_merges() {
+    assert_eq!(
+        lowered_type(
+            "|v: vec[hll[i64]]| distinctcount(result(for(v, hllmerger[i64], |b, i, e| merge(b, e))))"
+        ),
+        "|vec[vec[u8]]|(i64)"
+    );
+    assert_eq!(
+        lowered_type(
+            "|v: vec[quantiles[f64]]|
+                quantile(result(for(v, quantilemerger[f64], |b, i, e| merge(b, e))), 0.9)"
+        ),
+        "|vec[dict[i32,i64]]|(f64)"
+    );
+}
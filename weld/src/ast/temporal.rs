@@ -0,0 +1,472 @@
+//! Lowers calendar functions and date arithmetic to integer arithmetic.
+//!
+//! Dates are stored as `i32` days since 1970-01-01, and timestamps and intervals as `i64`
+//! microseconds. Code generators support comparisons of values with the same temporal type,
+//! `min` and `max`, and adding and subtracting intervals, by operating on the stored integers.
+//! This pass rewrites everything else: calendar functions, arithmetic that mixes dates or
+//! timestamps with intervals, and casts between dates and timestamps.
+//!
+//! Calendar fields are computed with the days-from-civil algorithms described in
+//! http://howardhinnant.github.io/date_algorithms.html.
+
+use crate::ast::BinOpKind::*;
+use crate::ast::ExprKind::*;
+use crate::ast::LiteralKind::*;
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::*;
+use crate::ast::*;
+use crate::error::*;
+use crate::util::SymbolGenerator;
+
+/// Microseconds per second.
+const MICROS_PER_SECOND: i64 = 1_000_000;
+/// Microseconds per minute.
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+/// Microseconds per hour.
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+/// Microseconds per day.
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// The number of days from 0000-03-01 to 1970-01-01.
+const EPOCH_OFFSET_DAYS: i64 = 719_468;
+/// The number of days in a 400 year era of the Gregorian calendar.
+const DAYS_PER_ERA: i64 = 146_097;
+
+/// A trait for lowering calendar functions and date arithmetic to integer arithmetic.
+pub trait LowerTemporals {
+    /// Rewrites calendar functions and date arithmetic using integer arithmetic, in place.
+    ///
+    /// The expression must be type checked.
+    fn lower_temporals(&mut self) -> WeldResult<()>;
+}
+
+impl LowerTemporals for Expr {
+    fn lower_temporals(&mut self) -> WeldResult<()> {
+        let mut sym_gen = SymbolGenerator::from_expression(self);
+        lower_expr(self, &mut sym_gen)
+    }
+}
+
+/// A sequence of `let` bindings that wraps a lowered expression.
+///
+/// Values that are used more than once are bound to a symbol so they are only computed once.
+pub(super) struct Bindings<'a> {
+    sym_gen: &'a mut SymbolGenerator,
+    bindings: Vec<(Symbol, Expr)>,
+}
+
+impl<'a> Bindings<'a> {
+    pub(super) fn new(sym_gen: &'a mut SymbolGenerator) -> Bindings<'a> {
+        Bindings {
+            sym_gen,
+            bindings: vec![],
+        }
+    }
+
+    /// Returns bindings for the body of a nested function, which share the symbol generator.
+    pub(super) fn nested(&mut self) -> Bindings<'_> {
+        Bindings::new(self.sym_gen)
+    }
+
+    /// Returns a new parameter with a unique name and an identifier for it.
+    pub(super) fn param(&mut self, name: &str, ty: Type) -> WeldResult<(Parameter, Expr)> {
+        let name = self.sym_gen.new_symbol(name);
+        let ident = Expr::new_ident(name.clone(), ty.clone())?;
+        Ok((Parameter { name, ty }, ident))
+    }
+
+    /// Binds a value to a new symbol and returns an identifier for it.
+    ///
+    /// Identifiers are returned as they are.
+    pub(super) fn bind(&mut self, name: &str, value: Expr) -> WeldResult<Expr> {
+        if let Ident(_) = value.kind {
+            return Ok(value);
+        }
+        let name = self.sym_gen.new_symbol(name);
+        let ident = Expr::new_ident(name.clone(), value.ty.clone())?;
+        self.bindings.push((name, value));
+        Ok(ident)
+    }
+
+    /// Wraps `body` in the bindings.
+    pub(super) fn wrap(self, body: Expr) -> WeldResult<Expr> {
+        let mut result = body;
+        for (name, value) in self.bindings.into_iter().rev() {
+            result = Expr::new_let(name, value, result)?;
+        }
+        Ok(result)
+    }
+}
+
+/// Returns an `i64` literal.
+fn int(value: i64) -> WeldResult<Expr> {
+    Expr::new_literal(I64Literal(value))
+}
+
+/// Returns the stored value of a temporal expression as an `i64`.
+fn to_i64(expr: Expr) -> WeldResult<Expr> {
+    Expr::new_cast(I64, expr)
+}
+
+/// Returns `value / divisor` rounded toward negative infinity.
+///
+/// The divisor must be positive.
+fn floor_div(b: &mut Bindings<'_>, value: Expr, divisor: i64) -> WeldResult<Expr> {
+    let value = b.bind("value", value)?;
+    let negative = Expr::new_bin_op(LessThan, value.clone(), int(0)?)?;
+    let adjust = Expr::new_select(negative, int(divisor - 1)?, int(0)?)?;
+    let adjusted = Expr::new_bin_op(Subtract, value, adjust)?;
+    Expr::new_bin_op(Divide, adjusted, int(divisor)?)
+}
+
+/// Returns `value` rounded down to a multiple of `unit`.
+fn floor_to(b: &mut Bindings<'_>, value: Expr, unit: i64) -> WeldResult<Expr> {
+    let quotient = floor_div(b, value, unit)?;
+    Expr::new_bin_op(Multiply, quotient, int(unit)?)
+}
+
+/// The fields of a date in the proleptic Gregorian calendar, as `i64` expressions.
+struct Civil {
+    year: Expr,
+    month: Expr,
+    day: Expr,
+}
+
+/// Returns the calendar fields of a number of days since 1970-01-01.
+fn civil_from_days(b: &mut Bindings<'_>, days: Expr) -> WeldResult<Civil> {
+    let z = b.bind("z", Expr::new_bin_op(Add, days, int(EPOCH_OFFSET_DAYS)?)?)?;
+    let era = floor_div(b, z.clone(), DAYS_PER_ERA)?;
+    let era = b.bind("era", era)?;
+
+    // The day and year of the era, where years start on March 1st.
+    let day_of_era = Expr::new_bin_op(
+        Subtract,
+        z,
+        Expr::new_bin_op(Multiply, era.clone(), int(DAYS_PER_ERA)?)?,
+    )?;
+    let doe = b.bind("doe", day_of_era)?;
+    let div = |divisor| Expr::new_bin_op(Divide, doe.clone(), int(divisor)?);
+    let year_of_era = Expr::new_bin_op(
+        Divide,
+        Expr::new_bin_op(
+            Subtract,
+            Expr::new_bin_op(
+                Add,
+                Expr::new_bin_op(Subtract, doe.clone(), div(1460)?)?,
+                div(36_524)?,
+            )?,
+            div(DAYS_PER_ERA - 1)?,
+        )?,
+        int(365)?,
+    )?;
+    let yoe = b.bind("yoe", year_of_era)?;
+    let days_before_year = Expr::new_bin_op(
+        Subtract,
+        Expr::new_bin_op(
+            Add,
+            Expr::new_bin_op(Multiply, int(365)?, yoe.clone())?,
+            Expr::new_bin_op(Divide, yoe.clone(), int(4)?)?,
+        )?,
+        Expr::new_bin_op(Divide, yoe.clone(), int(100)?)?,
+    )?;
+    let doy = b.bind(
+        "doy",
+        Expr::new_bin_op(Subtract, doe.clone(), days_before_year)?,
+    )?;
+
+    // The month, where March is month zero.
+    let month_index = Expr::new_bin_op(
+        Divide,
+        Expr::new_bin_op(
+            Add,
+            Expr::new_bin_op(Multiply, int(5)?, doy.clone())?,
+            int(2)?,
+        )?,
+        int(153)?,
+    )?;
+    let mp = b.bind("mp", month_index)?;
+    let month = Expr::new_select(
+        Expr::new_bin_op(LessThan, mp.clone(), int(10)?)?,
+        Expr::new_bin_op(Add, mp.clone(), int(3)?)?,
+        Expr::new_bin_op(Subtract, mp.clone(), int(9)?)?,
+    )?;
+    let month = b.bind("m", month)?;
+
+    let year = Expr::new_bin_op(
+        Add,
+        Expr::new_bin_op(Add, yoe, Expr::new_bin_op(Multiply, era, int(400)?)?)?,
+        Expr::new_select(
+            Expr::new_bin_op(LessThanOrEqual, month.clone(), int(2)?)?,
+            int(1)?,
+            int(0)?,
+        )?,
+    )?;
+    let days_before_month = Expr::new_bin_op(
+        Divide,
+        Expr::new_bin_op(Add, Expr::new_bin_op(Multiply, int(153)?, mp)?, int(2)?)?,
+        int(5)?,
+    )?;
+    let day = Expr::new_bin_op(
+        Add,
+        Expr::new_bin_op(Subtract, doy, days_before_month)?,
+        int(1)?,
+    )?;
+    Ok(Civil { year, month, day })
+}
+
+/// Returns the number of days from 1970-01-01 to January 1st of a year.
+fn days_from_year(b: &mut Bindings<'_>, year: Expr) -> WeldResult<Expr> {
+    // Years start on March 1st in the algorithm, so January belongs to the previous year.
+    let y = b.bind("y", Expr::new_bin_op(Subtract, year, int(1)?)?)?;
+    let era = floor_div(b, y.clone(), 400)?;
+    let era = b.bind("era", era)?;
+    let yoe = b.bind(
+        "yoe",
+        Expr::new_bin_op(
+            Subtract,
+            y,
+            Expr::new_bin_op(Multiply, era.clone(), int(400)?)?,
+        )?,
+    )?;
+    // January 1st is day 306 of a year that starts on March 1st.
+    let day_of_era = Expr::new_bin_op(
+        Add,
+        Expr::new_bin_op(
+            Subtract,
+            Expr::new_bin_op(
+                Add,
+                Expr::new_bin_op(Multiply, yoe.clone(), int(365)?)?,
+                Expr::new_bin_op(Divide, yoe.clone(), int(4)?)?,
+            )?,
+            Expr::new_bin_op(Divide, yoe, int(100)?)?,
+        )?,
+        int(306)?,
+    )?;
+    Expr::new_bin_op(
+        Add,
+        Expr::new_bin_op(Multiply, era, int(DAYS_PER_ERA)?)?,
+        Expr::new_bin_op(Subtract, day_of_era, int(EPOCH_OFFSET_DAYS)?)?,
+    )
+}
+
+/// Returns the first day of the unit that contains a number of days since 1970-01-01.
+fn trunc_days(b: &mut Bindings<'_>, days: Expr, unit: DateUnit) -> WeldResult<Expr> {
+    let days = b.bind("days", days)?;
+    match unit {
+        DateUnit::Year => {
+            let civil = civil_from_days(b, days)?;
+            let year = b.bind("y", civil.year)?;
+            days_from_year(b, year)
+        }
+        DateUnit::Month => {
+            let civil = civil_from_days(b, days.clone())?;
+            Expr::new_bin_op(Add, Expr::new_bin_op(Subtract, days, civil.day)?, int(1)?)
+        }
+        _ => Ok(days),
+    }
+}
+
+/// Lowers a calendar function applied to a date or timestamp.
+fn lower_date_op(kind: DateOpKind, value: Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<Expr> {
+    let value_kind = match value.ty {
+        Scalar(kind) => kind,
+        _ => return compile_err!("Expected date or timestamp for '{}'", kind),
+    };
+
+    let mut b = Bindings::new(sym_gen);
+    let value = b.bind("value", to_i64(value)?)?;
+    let days = |b: &mut Bindings<'_>| {
+        if value_kind == Timestamp {
+            floor_div(b, value.clone(), MICROS_PER_DAY)
+        } else {
+            Ok(value.clone())
+        }
+    };
+
+    let result = match kind {
+        DateOpKind::Year => {
+            let days = days(&mut b)?;
+            Expr::new_cast(I32, civil_from_days(&mut b, days)?.year)?
+        }
+        DateOpKind::Month => {
+            let days = days(&mut b)?;
+            Expr::new_cast(I32, civil_from_days(&mut b, days)?.month)?
+        }
+        DateOpKind::Day => {
+            let days = days(&mut b)?;
+            Expr::new_cast(I32, civil_from_days(&mut b, days)?.day)?
+        }
+        DateOpKind::Hour => {
+            let days = days(&mut b)?;
+            let day_start = Expr::new_bin_op(Multiply, days, int(MICROS_PER_DAY)?)?;
+            let time = Expr::new_bin_op(Subtract, value, day_start)?;
+            Expr::new_cast(I32, Expr::new_bin_op(Divide, time, int(MICROS_PER_HOUR)?)?)?
+        }
+        DateOpKind::Trunc(unit) if value_kind == Date => {
+            Expr::new_cast(Date, trunc_days(&mut b, value, unit)?)?
+        }
+        DateOpKind::Trunc(unit) => {
+            let micros = match unit {
+                DateUnit::Hour => floor_to(&mut b, value, MICROS_PER_HOUR)?,
+                DateUnit::Minute => floor_to(&mut b, value, MICROS_PER_MINUTE)?,
+                DateUnit::Second => floor_to(&mut b, value, MICROS_PER_SECOND)?,
+                _ => {
+                    let days = days(&mut b)?;
+                    Expr::new_bin_op(
+                        Multiply,
+                        trunc_days(&mut b, days, unit)?,
+                        int(MICROS_PER_DAY)?,
+                    )?
+                }
+            };
+            Expr::new_cast(Timestamp, micros)?
+        }
+    };
+    b.wrap(result)
+}
+
+/// Lowers a binary operator whose operands have different temporal types, or that subtracts
+/// two dates or timestamps. Returns `None` if the operator is supported by code generators.
+fn lower_bin_op(
+    kind: BinOpKind,
+    left: &mut Expr,
+    right: &mut Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Option<Expr>> {
+    let (left_kind, right_kind) = match (&left.ty, &right.ty) {
+        (&Scalar(left_kind), &Scalar(right_kind)) => (left_kind, right_kind),
+        _ => return Ok(None),
+    };
+
+    let result = match (left_kind, right_kind) {
+        (Date, Date) if kind == Subtract => {
+            let days = Expr::new_bin_op(Subtract, to_i64(left.take())?, to_i64(right.take())?)?;
+            let micros = Expr::new_bin_op(Multiply, days, int(MICROS_PER_DAY)?)?;
+            Expr::new_cast(Interval, micros)?
+        }
+        (Timestamp, Timestamp) if kind == Subtract => {
+            let micros = Expr::new_bin_op(Subtract, to_i64(left.take())?, to_i64(right.take())?)?;
+            Expr::new_cast(Interval, micros)?
+        }
+        (Timestamp, Interval) => {
+            let micros = Expr::new_bin_op(kind, to_i64(left.take())?, to_i64(right.take())?)?;
+            Expr::new_cast(Timestamp, micros)?
+        }
+        (Interval, Timestamp) => {
+            let micros = Expr::new_bin_op(kind, to_i64(left.take())?, to_i64(right.take())?)?;
+            Expr::new_cast(Timestamp, micros)?
+        }
+        (Date, Interval) | (Interval, Date) => {
+            // Move the date to midnight, apply the interval, and round down to a whole day.
+            let (date, interval) = if left_kind == Date {
+                (left.take(), right.take())
+            } else {
+                (right.take(), left.take())
+            };
+            let midnight = Expr::new_bin_op(Multiply, to_i64(date)?, int(MICROS_PER_DAY)?)?;
+            let micros = Expr::new_bin_op(kind, midnight, to_i64(interval)?)?;
+            let mut b = Bindings::new(sym_gen);
+            let days = floor_div(&mut b, micros, MICROS_PER_DAY)?;
+            b.wrap(Expr::new_cast(Date, days)?)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
+
+/// Lowers a cast between a date and a timestamp. Returns `None` for other casts, which convert
+/// the stored values of temporal types.
+fn lower_cast(
+    kind: ScalarKind,
+    child_expr: &mut Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Option<Expr>> {
+    let result = match (&child_expr.ty, kind) {
+        (&Scalar(Date), Timestamp) => {
+            let micros =
+                Expr::new_bin_op(Multiply, to_i64(child_expr.take())?, int(MICROS_PER_DAY)?)?;
+            Expr::new_cast(Timestamp, micros)?
+        }
+        (&Scalar(Timestamp), Date) => {
+            let mut b = Bindings::new(sym_gen);
+            let days = floor_div(&mut b, to_i64(child_expr.take())?, MICROS_PER_DAY)?;
+            b.wrap(Expr::new_cast(Date, days)?)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
+
+/// Lowers calendar functions and date arithmetic in an expression and its subexpressions.
+fn lower_expr(expr: &mut Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<()> {
+    for child in expr.children_mut() {
+        lower_expr(child, sym_gen)?;
+    }
+
+    let lowered = match expr.kind {
+        DateOp {
+            kind,
+            ref mut value,
+        } => Some(lower_date_op(kind, *value.take(), sym_gen)?),
+        BinOp {
+            kind,
+            ref mut left,
+            ref mut right,
+        } => lower_bin_op(kind, left, right, sym_gen)?,
+        Cast {
+            kind,
+            ref mut child_expr,
+        } => lower_cast(kind, child_expr, sym_gen)?,
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *expr = lowered;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tests::*;
+
+/// Checks that `code` lowers to `expected`, ignoring symbol names.
+#[cfg(test)]
+fn check_lowering(code: &str, expected: &str) {
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    e.lower_temporals().unwrap();
+    let mut expected = parse_expr(expected).unwrap();
+    expected.infer_types().unwrap();
+    assert!(e.compare_ignoring_symbols(&expected).unwrap());
+}
+
+#[test]
+fn lower_temporal_arithmetic() {
+    check_lowering(
+        "|a: timestamp, b: timestamp, i: interval| {a - b, a + i, a < b}",
+        "|a: timestamp, b: timestamp, i: interval|
+            {interval(i64(a) - i64(b)), timestamp(i64(a) + i64(i)), a < b}",
+    );
+    check_lowering(
+        "|a: date, b: date| a - b",
+        "|a: date, b: date| interval((i64(a) - i64(b)) * 86400000000L)",
+    );
+    check_lowering(
+        "|a: date, i: interval| a - i",
+        "|a: date, i: interval| let v = i64(a) * 86400000000L - i64(i);
+            date((v - select(v < 0L, 86399999999L, 0L)) / 86400000000L)",
+    );
+}
+
+#[test]
+fn lower_calendar_functions() {
+    check_lowering(
+        r#"|t: timestamp| date_trunc("hour", t)"#,
+        "|t: timestamp| let v = i64(t);
+            timestamp(((v - select(v < 0L, 3599999999L, 0L)) / 3600000000L) * 3600000000L)",
+    );
+    check_lowering(
+        r#"|d: date| date_trunc("day", d)"#,
+        "|d: date| let v = i64(d); date(v)",
+    );
+}
@@ -78,12 +78,54 @@ trait PushType {
 
 fn key_hashable(key: &Type) -> WeldResult<()> {
     if *key != Unknown && !key.is_hashable() {
-        compile_err!("Non-hashable type {} as dictionary key", key)
+        compile_err!("Non-hashable type {} as dictionary or set key", key)
     } else {
         Ok(())
     }
 }
 
+/// Checks that `elem` can be ordered by the default comparator, as required by `topk`.
+///
+/// The default comparator supports the same types as the default hash function.
+fn elem_comparable(elem: &Type) -> WeldResult<()> {
+    if *elem != Unknown && !elem.is_hashable() {
+        compile_err!("Non-comparable type {} in topk", elem)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that `item` can be summarized by a sketch of the given kind.
+///
+/// HyperLogLog sketches summarize hashable items, and quantiles sketches summarize floating point
+/// items.
+fn sketch_item_valid(kind: SketchKind, item: &Type) -> WeldResult<()> {
+    match (kind, item) {
+        _ if item.partial_type() => Ok(()),
+        (SketchKind::HyperLogLog, _) if item.is_hashable() => Ok(()),
+        (SketchKind::Quantiles, &Scalar(k)) if k.is_float() => Ok(()),
+        _ => compile_err!("Unsupported item type {} in {}", item, kind.merger_name()),
+    }
+}
+
+/// Checks that the vector type `ty` can be aggregated by the window operator `op`.
+///
+/// Window aggregates are computed with mergers, so the elements must be numeric scalars.
+fn window_elem_valid(ty: &Type, op: BinOpKind) -> WeldResult<()> {
+    match *ty {
+        Vector(ref elem) => match **elem {
+            Scalar(kind) if kind.is_numeric() => Ok(()),
+            Unknown => Ok(()),
+            _ => compile_err!(
+                "Non-numeric element type {} in window operator '{}'",
+                elem,
+                op
+            ),
+        },
+        _ => compile_err!("Expected vector type in window operator, got {}", ty),
+    }
+}
+
 impl PushType for Type {
     /// Sets this `Type` to be `other`.
     fn push_complete(&mut self, other: Type) -> WeldResult<bool> {
@@ -125,11 +167,24 @@ impl PushType for Type {
             (&mut Scalar(a), &Scalar(b)) if a == b => Ok(false),
             (&mut Simd(a), &Simd(b)) if a == b => Ok(false),
             (&mut Vector(ref mut elem), &Vector(ref other_elem)) => elem.push(other_elem),
+            (&mut Optional(ref mut value), &Optional(ref other_value)) => value.push(other_value),
             (&mut Dict(ref mut key, ref mut value), &Dict(ref other_key, ref other_value)) => {
                 let changed = key.push(other_key)? || value.push(other_value)?;
                 key_hashable(key.as_ref())?;
                 Ok(changed)
             }
+            (&mut Set(ref mut key), &Set(ref other_key)) => {
+                let changed = key.push(other_key)?;
+                key_hashable(key.as_ref())?;
+                Ok(changed)
+            }
+            (&mut Sketch(kind, ref mut item), &Sketch(other_kind, ref other_item))
+                if kind == other_kind =>
+            {
+                let changed = item.push(other_item)?;
+                sketch_item_valid(kind, item.as_ref())?;
+                Ok(changed)
+            }
             (&mut Struct(ref mut types), &Struct(ref other_types))
                 if types.len() == other_types.len() =>
             {
@@ -183,14 +238,39 @@ impl PushType for Type {
                         &mut Merger(ref mut elem, ref mut op),
                         &Merger(ref other_elem, ref other_op),
                     ) if *op == *other_op => elem.push(other_elem),
+                    (&mut TopK(ref mut elem, ref mut k), &TopK(ref other_elem, ref other_k))
+                        if *k == *other_k =>
+                    {
+                        let changed = elem.push(other_elem)?;
+                        elem_comparable(elem.as_ref())?;
+                        Ok(changed)
+                    }
+                    (&mut SetMerger(ref mut key), &SetMerger(ref other_key)) => {
+                        let changed = key.push(other_key)?;
+                        key_hashable(key.as_ref())?;
+                        Ok(changed)
+                    }
+                    (
+                        &mut SketchMerger(kind, ref mut item),
+                        &SketchMerger(other_kind, ref other_item),
+                    ) if kind == other_kind => {
+                        let changed = item.push(other_item)?;
+                        sketch_item_valid(kind, item.as_ref())?;
+                        Ok(changed)
+                    }
                     // Mismatches in the binary operator of DictMerger, VecMerger, and GroupMerger,
-                    // or other type mismatches in the BuilderKind. We list them explicitly so the
-                    // compiler will throw an error if we add new types.
+                    // mismatches in the size of TopK, mismatches in the kind of SketchMerger, or
+                    // other type mismatches in the BuilderKind.
+                    // We list them explicitly so the compiler will throw an error if we add new
+                    // types.
                     (&mut Appender(_), _)
                     | (&mut DictMerger(_, _, _), _)
                     | (&mut GroupMerger(_, _), _)
                     | (&mut VecMerger(_, _), _)
-                    | (&mut Merger(_, _), _) => {
+                    | (&mut Merger(_, _), _)
+                    | (&mut TopK(_, _), _)
+                    | (&mut SetMerger(_), _)
+                    | (&mut SketchMerger(_, _), _) => {
                         compile_err!("Type mismatch: expected builder type {}", other)
                     }
                 };
@@ -228,6 +308,110 @@ fn sync_function(expr: &mut Expr, tys: Vec<&Type>) -> WeldResult<bool> {
     }
 }
 
+/// Returns the type of the value in `ty` if `ty` is optional, and `ty` otherwise.
+fn optional_value(ty: &Type) -> &Type {
+    match *ty {
+        Optional(ref value) => value.as_ref(),
+        _ => ty,
+    }
+}
+
+/// Push `other` into the value type of `ty` if `ty` is optional, and into `ty` otherwise.
+fn push_optional_value(ty: &mut Type, other: &Type) -> WeldResult<bool> {
+    match *ty {
+        Optional(ref mut value) => value.push(other),
+        ref mut ty => ty.push(other),
+    }
+}
+
+/// Infer the types of a binary operator over optional values.
+///
+/// The operator is applied to the values of the operands, and its result is null if any operand
+/// is null. Non-optional operands are treated as values that are never null, so comparisons
+/// return an `optional[bool]`.
+fn infer_optional_bin_op(
+    op: BinOpKind,
+    left: &mut Expr,
+    right: &mut Expr,
+    ty: &mut Type,
+) -> WeldResult<bool> {
+    // We cannot tell whether an operand with an unknown type is optional.
+    if left.ty == Unknown || right.ty == Unknown {
+        return Ok(false);
+    }
+
+    let elem_type = &mut Unknown;
+    elem_type.push(optional_value(&left.ty))?;
+    elem_type.push(optional_value(&right.ty))?;
+
+    if !op.is_comparison() {
+        elem_type.push(optional_value(ty))?;
+    }
+
+    let mut changed = push_optional_value(&mut left.ty, elem_type)?;
+    changed |= push_optional_value(&mut right.ty, elem_type)?;
+
+    if op.is_comparison() {
+        changed |= ty.push(&Scalar(Bool).optional_type())?;
+    } else {
+        changed |= ty.push(&elem_type.optional_type())?;
+    }
+    Ok(changed)
+}
+
+/// Returns whether a type is a date, timestamp or interval scalar.
+fn is_temporal(ty: &Type) -> bool {
+    match *ty {
+        Scalar(kind) => kind.is_temporal(),
+        _ => false,
+    }
+}
+
+/// Infers the types of a binary operator where at least one operand is a date, timestamp or
+/// interval.
+///
+/// Dates and timestamps can be compared with values of the same type, and subtracted from each
+/// other to produce an interval. Intervals can be added to and subtracted from dates, timestamps
+/// and other intervals.
+fn infer_temporal_bin_op(
+    op: BinOpKind,
+    left: &mut Expr,
+    right: &mut Expr,
+    ty: &mut Type,
+) -> WeldResult<bool> {
+    use crate::ast::BinOpKind::*;
+    let (left_kind, right_kind) = match (&left.ty, &right.ty) {
+        (&Scalar(left_kind), &Scalar(right_kind)) => (left_kind, right_kind),
+        (&Unknown, _) | (_, &Unknown) => return Ok(false),
+        _ => {
+            return compile_err!(
+                "Mismatched types {} and {} for binary operator '{}'",
+                left.ty,
+                right.ty,
+                op
+            )
+        }
+    };
+
+    let result = match (left_kind, right_kind) {
+        (l, r) if l == r && op.is_comparison() => Bool,
+        (l, r) if l == r && (op == Max || op == Min) => l,
+        (Interval, Interval) if op == Add || op == Subtract => Interval,
+        (Date, Date) | (Timestamp, Timestamp) if op == Subtract => Interval,
+        (Date, Interval) | (Timestamp, Interval) if op == Add || op == Subtract => left_kind,
+        (Interval, Date) | (Interval, Timestamp) if op == Add => right_kind,
+        _ => {
+            return compile_err!(
+                "Binary operator '{}' is not supported for types {} and {}",
+                op,
+                left.ty,
+                right.ty
+            )
+        }
+    };
+    ty.push(&Scalar(result))
+}
+
 /// A module-internal implementation of type inference.
 ///
 /// This trait contains additional helper methods that are not exposed outside this module.
@@ -336,6 +520,14 @@ impl InferTypesInternal for Expr {
                 ref mut left,
                 ref mut right,
             } => {
+                if left.ty.is_optional() || right.ty.is_optional() || self.ty.is_optional() {
+                    return infer_optional_bin_op(op, left, right, &mut self.ty);
+                }
+
+                if is_temporal(&left.ty) || is_temporal(&right.ty) {
+                    return infer_temporal_bin_op(op, left, right, &mut self.ty);
+                }
+
                 // First, sync the left and right types into the elem_type.
                 let elem_type = &mut Unknown;
                 elem_type.push(&left.ty)?;
@@ -345,6 +537,15 @@ impl InferTypesInternal for Expr {
                     elem_type.push(&self.ty)?;
                 }
 
+                if op == BinOpKind::ShiftLeft || op == BinOpKind::ShiftRight {
+                    match *elem_type {
+                        Scalar(ref kind) | Simd(ref kind) if !kind.is_integer() => {
+                            return compile_err!("Expected integer type for binary op '{}'", op);
+                        }
+                        _ => (),
+                    }
+                }
+
                 // Now, attempt to push the elem_type back into left and right to "sync" them.
                 let mut changed = left.ty.push(elem_type)?;
                 changed |= right.ty.push(elem_type)?;
@@ -366,14 +567,94 @@ impl InferTypesInternal for Expr {
                 ref value,
                 ref kind,
             } => match value.ty {
+                Scalar(ref scalar) | Simd(ref scalar) if kind.is_integer_op() => {
+                    if scalar.is_integer() {
+                        self.ty.push(&value.ty)
+                    } else {
+                        compile_err!("Expected integer type for unary op '{}'", kind)
+                    }
+                }
+                Scalar(ref scalar) | Simd(ref scalar) if kind.is_numeric_op() => {
+                    if scalar.is_numeric() {
+                        self.ty.push(&value.ty)
+                    } else {
+                        compile_err!("Expected numeric type for unary op '{}'", kind)
+                    }
+                }
                 Scalar(ref kind) | Simd(ref kind) if kind.is_float() => self.ty.push(&value.ty),
                 Unknown => Ok(false),
                 _ => compile_err!("Expected floating-point type for unary op '{}'", kind),
             },
 
+            StringOp { kind, ref mut args } => {
+                use crate::ast::StringOpKind::*;
+                if args.len() != kind.num_args() {
+                    return compile_err!(
+                        "Expected {} arguments for '{}', got {}",
+                        kind.num_args(),
+                        kind,
+                        args.len()
+                    );
+                }
+
+                let string = Type::string_type();
+                let mut changed = false;
+                match kind {
+                    Substr => {
+                        changed |= args[0].ty.push(&string)?;
+                        changed |= args[1].ty.push(&Scalar(I64))?;
+                        changed |= args[2].ty.push(&Scalar(I64))?;
+                    }
+                    ToString => match args[0].ty {
+                        Scalar(ref scalar) if scalar.is_numeric() || *scalar == Bool => (),
+                        Unknown => return Ok(false),
+                        _ => return compile_err!("Expected numeric or bool type for '{}'", kind),
+                    },
+                    _ => {
+                        for arg in args.iter_mut() {
+                            changed |= arg.ty.push(&string)?;
+                        }
+                    }
+                }
+
+                let result = match kind {
+                    Concat | Substr | ToUpper | ToLower | ToString => string,
+                    Find | ParseI64 => Scalar(I64),
+                    StartsWith | EndsWith => Scalar(Bool),
+                    ParseF64 => Scalar(F64),
+                };
+                changed |= self.ty.push(&result)?;
+                Ok(changed)
+            }
+
+            DateOp { kind, ref value } => {
+                use crate::ast::DateOpKind::*;
+                let value_kind = match value.ty {
+                    Scalar(scalar @ Date) | Scalar(scalar @ Timestamp) => scalar,
+                    Unknown => return Ok(false),
+                    _ => return compile_err!("Expected date or timestamp for '{}'", kind),
+                };
+                let result = match kind {
+                    Hour if value_kind == Date => {
+                        return compile_err!("Expected timestamp for '{}'", kind)
+                    }
+                    Trunc(unit) if value_kind == Date && !unit.is_calendar_unit() => {
+                        return compile_err!("Cannot truncate a date to unit '{}'", unit)
+                    }
+                    Year | Month | Day | Hour => Scalar(I32),
+                    Trunc(_) => Scalar(value_kind),
+                };
+                self.ty.push_complete(result)
+            }
+
             Cast { kind, .. } => self.ty.push_complete(Scalar(kind)),
 
             ToVec { ref mut child_expr } => {
+                // The keys of a set are returned without values.
+                if let Set(ref key) = child_expr.ty {
+                    return self.ty.push(&Vector(key.clone()));
+                }
+
                 // The base type is vec[{?,?}] - infer the key and value type.
                 let base_type = &mut Vector(Box::new(Struct(vec![Unknown, Unknown])));
                 let mut changed = self.ty.push(base_type)?;
@@ -395,7 +676,7 @@ impl InferTypesInternal for Expr {
 
                 if !set_types {
                     compile_err!(
-                        "Expected dictionary argument for tovec(...), got {}",
+                        "Expected dictionary or set argument for tovec(...), got {}",
                         &child_expr.ty
                     )
                 } else {
@@ -537,15 +818,11 @@ impl InferTypesInternal for Expr {
                 }
             }
 
-            Length { ref mut data } => {
-                if let Vector(_) = data.ty {
-                    self.ty.push_complete(Scalar(I64))
-                } else if data.ty == Unknown {
-                    Ok(false)
-                } else {
-                    compile_err!("Expected vector type in len, got {}", &data.ty)
-                }
-            }
+            Length { ref mut data } => match data.ty {
+                Vector(_) | Dict(_, _) | Set(_) => self.ty.push_complete(Scalar(I64)),
+                Unknown => Ok(false),
+                _ => compile_err!("Expected vector, dict or set type in len, got {}", &data.ty),
+            },
 
             Slice {
                 ref mut data,
@@ -628,20 +905,110 @@ impl InferTypesInternal for Expr {
                 }
             }
 
+            Null => self.ty.push(&Unknown.optional_type()),
+
+            MakeOptional(ref mut value) => {
+                let mut changed = false;
+                if let Optional(ref value_type) = self.ty {
+                    changed |= value.ty.push(value_type)?;
+                }
+                changed |= self.ty.push(&value.ty.optional_type())?;
+                Ok(changed)
+            }
+
+            IsNull(ref mut value) => {
+                let mut changed = value.ty.push(&Unknown.optional_type())?;
+                changed |= self.ty.push_complete(Scalar(Bool))?;
+                Ok(changed)
+            }
+
+            Coalesce {
+                ref mut value,
+                ref mut default,
+            } => {
+                let mut changed = value.ty.push(&Unknown.optional_type())?;
+                if let Optional(ref mut value_type) = value.ty {
+                    changed |= value_type.sync(&mut default.ty)?;
+                    changed |= self.ty.sync(value_type)?;
+                }
+                Ok(changed)
+            }
+
+            DistinctCount(ref sketch) => match sketch.ty {
+                Sketch(SketchKind::HyperLogLog, _) => self.ty.push_complete(Scalar(I64)),
+                Unknown => Ok(false),
+                _ => compile_err!("Expected hll type in distinctcount, got {}", &sketch.ty),
+            },
+
+            Quantile {
+                ref sketch,
+                ref mut quantile,
+            } => {
+                let mut changed = quantile.ty.push_complete(Scalar(F64))?;
+                match sketch.ty {
+                    Sketch(SketchKind::Quantiles, ref item) => {
+                        changed |= self.ty.push(item)?;
+                        Ok(changed)
+                    }
+                    Unknown => Ok(changed),
+                    _ => compile_err!("Expected quantiles type in quantile, got {}", &sketch.ty),
+                }
+            }
+
+            Scan { ref mut data, op } => {
+                let changed = self.ty.sync(&mut data.ty)?;
+                window_elem_valid(&self.ty, op)?;
+                Ok(changed)
+            }
+
+            Window {
+                ref mut data,
+                ref mut size,
+                op,
+            } => {
+                let mut changed = size.ty.push_complete(Scalar(I64))?;
+                changed |= self.ty.sync(&mut data.ty)?;
+                window_elem_valid(&self.ty, op)?;
+                Ok(changed)
+            }
+
+            Shift {
+                kind,
+                ref mut data,
+                ref mut offset,
+                ref mut default,
+            } => {
+                let mut changed = offset.ty.push_complete(Scalar(I64))?;
+                changed |= self.ty.push(&Vector(Box::new(Unknown)))?;
+                changed |= self.ty.sync(&mut data.ty)?;
+                match self.ty {
+                    Vector(ref mut elem) => {
+                        changed |= elem.sync(&mut default.ty)?;
+                        Ok(changed)
+                    }
+                    _ => compile_err!("Expected vector type in {}, got {}", kind, &self.ty),
+                }
+            }
+
             KeyExists {
                 ref mut data,
                 ref mut key,
-            } => {
-                if let Dict(ref key_type, _) = data.ty {
+            } => match data.ty {
+                Dict(ref key_type, _) | Set(ref key_type) => {
                     let mut changed = false;
                     changed |= key.ty.push(&key_type)?;
                     changed |= self.ty.push_complete(Scalar(Bool))?;
                     Ok(changed)
-                } else if data.ty == Unknown {
-                    Ok(false)
-                } else {
-                    compile_err!("Expected dict type in lookup, got {}", &data.ty)
                 }
+                Unknown => Ok(false),
+                _ => compile_err!("Expected dict or set type in lookup, got {}", &data.ty),
+            },
+
+            Hash(ref value) => {
+                if !value.ty.partial_type() && !value.ty.is_hashable() {
+                    return compile_err!("Non-hashable type {} in hash", &value.ty);
+                }
+                self.ty.push_complete(Scalar(U32))
             }
 
             Lambda {
@@ -756,6 +1123,10 @@ impl InferTypesInternal for Expr {
                                 Ok(false)
                             }
                         }
+                        SketchMerger(kind, ref item) => {
+                            sketch_item_valid(kind, item)?;
+                            Ok(false)
+                        }
                         _ => Ok(false),
                     }
                 } else if self.ty == Unknown {
@@ -777,6 +1148,12 @@ impl InferTypesInternal for Expr {
                 // back into the builder.
                 let (mut merge_type, mut kind) = if let Builder(ref builder_kind, _) = builder.ty {
                     let mut merge_type = builder_kind.merge_type();
+                    // Sketch mergers also accept partial sketches of the same kind.
+                    if let SketchMerger(kind, ref item) = *builder_kind {
+                        if let Sketch(_, _) = value.ty {
+                            merge_type = Sketch(kind, item.clone());
+                        }
+                    }
                     // If we are merging a SIMD value, sync with the SIMD merge type.
                     // NOTE: This currently only works under the assumption that each
                     // value in a SIMD program is SIMD-valued.
@@ -806,6 +1183,22 @@ impl InferTypesInternal for Expr {
                     Merger(ref mut elem, _) => {
                         **elem = merge_type;
                     }
+                    TopK(ref mut elem, _) => {
+                        elem_comparable(&merge_type)?;
+                        **elem = merge_type;
+                    }
+                    SetMerger(ref mut key) => {
+                        key_hashable(&merge_type)?;
+                        **key = merge_type;
+                    }
+                    SketchMerger(sketch_kind, ref mut item) => {
+                        if let Sketch(_, sketch_item) = merge_type {
+                            **item = *sketch_item;
+                        } else {
+                            sketch_item_valid(sketch_kind, &merge_type)?;
+                            **item = merge_type;
+                        }
+                    }
                     DictMerger(ref mut key, ref mut value, _) => {
                         if let Struct(mut tys) = merge_type {
                             mem::swap(key.as_mut(), &mut tys[0]);
@@ -1035,3 +1428,127 @@ fn infer_types_test() {
         "for([1],appender[i32],|b:appender[i32],i:i64,x:i32|merge(b:appender[i32],x:i32))"
     );
 }
+
+#[test]
+fn infer_optional_types_test() {
+    use crate::tests::*;
+    let mut e = parse_expr("|a:optional[i32], b:i32| a + b").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|optional[i32],i32|(optional[i32])");
+
+    let mut e = parse_expr("|a:optional[i64]| a < 1L").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|optional[i64]|(optional[bool])");
+
+    let mut e = parse_expr("|a:optional[f64]| {coalesce(a, 0.0), isnull(a)}").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|optional[f64]|({f64,bool})");
+
+    let mut e = parse_expr("|a:i32| if(a > 0, optional(a), null)").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|i32|(optional[i32])");
+
+    // The default of a coalesce must have the type of the value.
+    let mut e = parse_expr("|a:optional[i32]| coalesce(a, 1L)").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|a:i32| isnull(a)").unwrap();
+    assert!(e.infer_types().is_err());
+}
+
+#[test]
+fn infer_integer_op_types_test() {
+    use crate::tests::*;
+    let mut e =
+        parse_expr("|a:u32, b:i64| {a << a, b >> 1L, popcount(a), clz(b), abs(b)}").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|u32,i64|({u32,i64,u32,i64,i64})");
+
+    let mut e = parse_expr("|a:f64| {abs(a), floor(a), round(a), cbrt(a)}").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|f64|({f64,f64,f64,f64})");
+
+    let mut e = parse_expr("|a:f64| a << 1.0").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|a:f32| ctz(a)").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|a:i32| ceil(a)").unwrap();
+    assert!(e.infer_types().is_err());
+}
+
+#[test]
+fn infer_topk_types_test() {
+    use crate::tests::*;
+    let mut e = parse_expr("|v:vec[f64]| result(for(v, topk[?,10], |b,i,x| merge(b,x)))").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|vec[f64]|(vec[f64])");
+
+    // Builders of different sizes do not have the same type.
+    let mut e = parse_expr("|b:topk[i32,3]| if(true, b, topk[i32,4])").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|d:dict[i32,i32]| merge(topk[?,3], d)").unwrap();
+    assert!(e.infer_types().is_err());
+}
+
+#[test]
+fn infer_set_types_test() {
+    use crate::tests::*;
+    let code = "|v:vec[i64]| let s = result(for(v, setmerger[?], |b,i,x| merge(b,x)));
+        {contains(s, 1L), len(s), tovec(s)}";
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|vec[i64]|({bool,i64,vec[i64]})");
+
+    let mut e = parse_expr("|s:set[i32]| lookup(s, 1)").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|d:dict[i32,i32]| merge(setmerger[?], d)").unwrap();
+    assert!(e.infer_types().is_err());
+}
+
+#[test]
+fn infer_sketch_types_test() {
+    use crate::tests::*;
+    let code = "|v:vec[f32]| let q = result(for(v, quantilemerger[?], |b,i,x| merge(b,x)));
+        {quantile(q, 0.5), distinctcount(result(for(v, hllmerger[?], |b,i,x| merge(b,x))))}";
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|vec[f32]|({f32,i64})");
+
+    // Partial sketches can be merged into mergers of the same kind.
+    let mut e = parse_expr("|s:quantiles[f64]| result(merge(quantilemerger[?], s))").unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(e.ty.to_string(), "|quantiles[f64]|(quantiles[f64])");
+
+    let mut e = parse_expr("|s:hll[i32]| merge(quantilemerger[f64], s)").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e =
+        parse_expr("|v:vec[i32]| result(for(v, quantilemerger[?], |b,i,x| merge(b,x)))").unwrap();
+    assert!(e.infer_types().is_err());
+}
+
+#[test]
+fn infer_window_types_test() {
+    use crate::tests::*;
+    let code = "|v:vec[i32]| {scan[?,+](v), window[?,max](v, 3L), lag(v, 1L, 0), lead(v, 1L, 0)}";
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    assert_eq!(
+        e.ty.to_string(),
+        "|vec[i32]|({vec[i32],vec[i32],vec[i32],vec[i32]})"
+    );
+
+    let mut e = parse_expr("|v:vec[{i32,i32}]| lag(v, 1L, {0, 0})").unwrap();
+    e.infer_types().unwrap();
+
+    // Window aggregates need numeric elements, and defaults need the element type.
+    let mut e = parse_expr("|v:vec[{i32,i32}]| scan[?,+](v)").unwrap();
+    assert!(e.infer_types().is_err());
+
+    let mut e = parse_expr("|v:vec[i32]| lag(v, 1L, 0L)").unwrap();
+    assert!(e.infer_types().is_err());
+}
@@ -0,0 +1,394 @@
+//! Lowers window operators to loops over vectors.
+//!
+//! `lag` and `lead` become a parallel loop that looks up the shifted element of each position,
+//! and sliding windows become a parallel loop with a nested merger over the frame of each
+//! position, which code generators can vectorize. This takes O(n * size) work for `n` elements.
+//! Sliding sums of integers over large frames, or frames whose size is not a constant, instead
+//! subtract prefix sums computed with a scan.
+//!
+//! Scans are computed with the Hillis-Steele algorithm: each pass combines every element with
+//! the element a fixed distance before it, and the distance doubles until it covers the vector.
+//! This takes a logarithmic number of passes, each of which is a parallel loop, so a scan takes
+//! O(n log n) work.
+
+use super::optional::scalar_literal;
+use super::temporal::Bindings;
+
+use crate::ast::BinOpKind::*;
+use crate::ast::BuilderKind::*;
+use crate::ast::ExprKind::*;
+use crate::ast::LiteralKind::*;
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::*;
+use crate::ast::*;
+use crate::error::*;
+use crate::util::SymbolGenerator;
+
+/// The largest constant frame size of integer sliding sums that are summed frame by frame.
+///
+/// Larger frames use prefix sums, whose scan makes a logarithmic number of passes over the data.
+const MAX_MERGED_FRAME_SIZE: i64 = 32;
+
+/// A trait for lowering window operators to loops.
+pub trait LowerWindows {
+    /// Replaces scans, sliding windows, `lag` and `lead` with loops over vectors, in place.
+    ///
+    /// The expression must be type checked.
+    fn lower_windows(&mut self) -> WeldResult<()>;
+}
+
+impl LowerWindows for Expr {
+    fn lower_windows(&mut self) -> WeldResult<()> {
+        let mut sym_gen = SymbolGenerator::from_expression(self);
+        lower_expr(self, &mut sym_gen)
+    }
+}
+
+/// Returns an `i64` literal.
+fn int(value: i64) -> WeldResult<Expr> {
+    Expr::new_literal(I64Literal(value))
+}
+
+/// Returns the element type of the vector `data`.
+fn elem_type(data: &Expr) -> WeldResult<Type> {
+    match data.ty {
+        Vector(ref elem) => Ok(elem.as_ref().clone()),
+        _ => compile_err!("Expected vector type in window operator, got {}", data.ty),
+    }
+}
+
+/// Returns a loop over the elements of the vector `data` that appends one value per element.
+///
+/// `body` receives the index and element parameters of the loop function and bindings for the
+/// function body, and returns the appended value.
+fn map_with_index<F>(b: &mut Bindings<'_>, data: Expr, len: Expr, body: F) -> WeldResult<Expr>
+where
+    F: FnOnce(&mut Bindings<'_>, Expr, Expr) -> WeldResult<Expr>,
+{
+    let elem_type = elem_type(&data)?;
+    let builder = Expr::new_new_builder(Appender(Box::new(elem_type.clone())), Some(len))?;
+    let (builder_param, builder_ident) = b.param("b", builder.ty.clone())?;
+    let (index_param, index_ident) = b.param("i", Scalar(I64))?;
+    let (elem_param, elem_ident) = b.param("e", elem_type)?;
+
+    let mut inner = b.nested();
+    let value = body(&mut inner, index_ident, elem_ident)?;
+    let body = inner.wrap(Expr::new_merge(builder_ident, value)?)?;
+
+    let func = Expr::new_lambda(vec![builder_param, index_param, elem_param], body)?;
+    let iter = Iter {
+        data: Box::new(data),
+        start: None,
+        end: None,
+        stride: None,
+        kind: IterKind::ScalarIter,
+        strides: None,
+        shape: None,
+    };
+    Expr::new_result(Expr::new_for(vec![iter], builder, func)?)
+}
+
+/// Lowers a scan of `data` with `op`.
+fn lower_scan(op: BinOpKind, data: Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<Expr> {
+    let mut b = Bindings::new(sym_gen);
+    let data = b.bind("data", data)?;
+    let len = b.bind("n", Expr::new_length(data.clone())?)?;
+    let scanned = scan(&mut b, op, data, len)?;
+    b.wrap(scanned)
+}
+
+/// Returns a scan of `data`, which has `len` elements, with `op`.
+fn scan(b: &mut Bindings<'_>, op: BinOpKind, data: Expr, len: Expr) -> WeldResult<Expr> {
+    // The state of the passes is the partially scanned vector and the distance of the next pass.
+    let state_type = Struct(vec![data.ty.clone(), Scalar(I64)]);
+    let (state_param, state) = b.param("s", state_type)?;
+    let mut step = b.nested();
+    let prev = step.bind("prev", Expr::new_get_field(state.clone(), 0)?)?;
+    let distance = step.bind("d", Expr::new_get_field(state, 1)?)?;
+
+    let earlier_prev = prev.clone();
+    let earlier_distance = distance.clone();
+    let next = map_with_index(&mut step, prev, len.clone(), |_, index, elem| {
+        // Clamp the index of the earlier element, since both sides of a select are evaluated.
+        let earlier = Expr::new_bin_op(
+            Max,
+            Expr::new_bin_op(Subtract, index.clone(), earlier_distance.clone())?,
+            int(0)?,
+        )?;
+        let earlier = Expr::new_lookup(earlier_prev, earlier)?;
+        Expr::new_select(
+            Expr::new_bin_op(GreaterThanOrEqual, index, earlier_distance)?,
+            Expr::new_bin_op(op, earlier, elem.clone())?,
+            elem,
+        )
+    })?;
+    let doubled = step.bind("d", Expr::new_bin_op(Multiply, distance, int(2)?)?)?;
+    let more = Expr::new_bin_op(LessThan, doubled.clone(), len)?;
+    let update = Expr::new_make_struct(vec![Expr::new_make_struct(vec![next, doubled])?, more])?;
+    let update = Expr::new_lambda(vec![state_param], step.wrap(update)?)?;
+
+    let initial = Expr::new_make_struct(vec![data, int(1)?])?;
+    Expr::new_get_field(Expr::new_iterate(initial, update)?, 0)
+}
+
+/// Lowers a sliding window over `data` with frames of `size` elements.
+fn lower_window(
+    op: BinOpKind,
+    data: Expr,
+    size: Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Expr> {
+    let large_frames = match size.kind {
+        Literal(I64Literal(value)) => value > MAX_MERGED_FRAME_SIZE,
+        _ => true,
+    };
+    let mut b = Bindings::new(sym_gen);
+    let data = b.bind("data", data)?;
+    let size = b.bind("size", size)?;
+    let len = b.bind("n", Expr::new_length(data.clone())?)?;
+    let elem_type = elem_type(&data)?;
+
+    match elem_type {
+        Scalar(kind) if op == Add && kind.is_integer() && large_frames => {
+            return running_window(b, data, size, len, kind);
+        }
+        _ => (),
+    }
+
+    let frame_data = data.clone();
+    let result = map_with_index(&mut b, data, len, |b, index, _| {
+        // The frame is empty if the size is not positive.
+        let end = b.bind("end", Expr::new_bin_op(Add, index, int(1)?)?)?;
+        let start = Expr::new_bin_op(
+            Min,
+            Expr::new_bin_op(Max, Expr::new_bin_op(Subtract, end.clone(), size)?, int(0)?)?,
+            end.clone(),
+        )?;
+        let frame = Iter {
+            data: Box::new(frame_data),
+            start: Some(Box::new(start)),
+            end: Some(Box::new(end)),
+            stride: Some(Box::new(int(1)?)),
+            kind: IterKind::ScalarIter,
+            strides: None,
+            shape: None,
+        };
+
+        let merger = Expr::new_new_builder(Merger(Box::new(elem_type.clone()), op), None)?;
+        let (builder_param, builder_ident) = b.param("b", merger.ty.clone())?;
+        let (index_param, _) = b.param("i", Scalar(I64))?;
+        let (elem_param, elem_ident) = b.param("e", elem_type)?;
+        let func = Expr::new_lambda(
+            vec![builder_param, index_param, elem_param],
+            Expr::new_merge(builder_ident, elem_ident)?,
+        )?;
+        Expr::new_result(Expr::new_for(vec![frame], merger, func)?)
+    })?;
+    b.wrap(result)
+}
+
+/// Lowers a sliding sum of integers over `data` with frames of `size` elements.
+///
+/// The sum of each frame is the difference of two prefix sums, so the work does not grow with
+/// the frame size. Floats are summed frame by frame instead, since the difference of prefix sums
+/// loses precision, and so are small frames, for which the scan costs more than it saves.
+fn running_window(
+    mut b: Bindings<'_>,
+    data: Expr,
+    size: Expr,
+    len: Expr,
+    kind: ScalarKind,
+) -> WeldResult<Expr> {
+    let sums = scan(&mut b, Add, data.clone(), len.clone())?;
+    let sums = b.bind("sums", sums)?;
+    let result = map_with_index(&mut b, data, len, |b, index, _| {
+        // The frame is empty if the size is not positive.
+        let end = b.bind("end", Expr::new_bin_op(Add, index.clone(), int(1)?)?)?;
+        let start = Expr::new_bin_op(
+            Min,
+            Expr::new_bin_op(Max, Expr::new_bin_op(Subtract, end.clone(), size)?, int(0)?)?,
+            end,
+        )?;
+        let start = b.bind("start", start)?;
+        // Clamp the index, since both sides of a select are evaluated.
+        let before = Expr::new_lookup(
+            sums.clone(),
+            Expr::new_bin_op(
+                Max,
+                Expr::new_bin_op(Subtract, start.clone(), int(1)?)?,
+                int(0)?,
+            )?,
+        )?;
+        let before = Expr::new_select(
+            Expr::new_bin_op(GreaterThan, start, int(0)?)?,
+            before,
+            scalar_literal(kind, 0)?,
+        )?;
+        Expr::new_bin_op(Subtract, Expr::new_lookup(sums, index)?, before)
+    })?;
+    b.wrap(result)
+}
+
+/// Lowers `lag` or `lead` over `data`.
+fn lower_shift(
+    kind: ShiftKind,
+    data: Expr,
+    offset: Expr,
+    default: Expr,
+    sym_gen: &mut SymbolGenerator,
+) -> WeldResult<Expr> {
+    let mut b = Bindings::new(sym_gen);
+    let data = b.bind("data", data)?;
+    let offset = b.bind("offset", offset)?;
+    let default = b.bind("default", default)?;
+    let len = b.bind("n", Expr::new_length(data.clone())?)?;
+
+    let source_data = data.clone();
+    let result = map_with_index(&mut b, data, len.clone(), |b, index, _| {
+        let source = match kind {
+            ShiftKind::Lag => Expr::new_bin_op(Subtract, index, offset)?,
+            ShiftKind::Lead => Expr::new_bin_op(Add, index, offset)?,
+        };
+        let source = b.bind("j", source)?;
+        let in_bounds = Expr::new_bin_op(
+            LogicalAnd,
+            Expr::new_bin_op(GreaterThanOrEqual, source.clone(), int(0)?)?,
+            Expr::new_bin_op(LessThan, source.clone(), len.clone())?,
+        )?;
+        // Clamp the index, since both sides of a select are evaluated.
+        let clamped = Expr::new_bin_op(
+            Min,
+            Expr::new_bin_op(Max, source, int(0)?)?,
+            Expr::new_bin_op(Subtract, len, int(1)?)?,
+        )?;
+        Expr::new_select(in_bounds, Expr::new_lookup(source_data, clamped)?, default)
+    })?;
+    b.wrap(result)
+}
+
+/// Lowers window operators in an expression and its subexpressions.
+fn lower_expr(expr: &mut Expr, sym_gen: &mut SymbolGenerator) -> WeldResult<()> {
+    for child in expr.children_mut() {
+        lower_expr(child, sym_gen)?;
+    }
+
+    let lowered = match expr.kind {
+        Scan { ref mut data, op } => Some(lower_scan(op, *data.take(), sym_gen)?),
+        Window {
+            ref mut data,
+            ref mut size,
+            op,
+        } => Some(lower_window(op, *data.take(), *size.take(), sym_gen)?),
+        Shift {
+            kind,
+            ref mut data,
+            ref mut offset,
+            ref mut default,
+        } => Some(lower_shift(
+            kind,
+            *data.take(),
+            *offset.take(),
+            *default.take(),
+            sym_gen,
+        )?),
+        _ => None,
+    };
+
+    if let Some(lowered) = lowered {
+        *expr = lowered;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tests::*;
+
+/// Checks that `code` lowers to `expected`, ignoring symbol names.
+#[cfg(test)]
+fn check_lowering(code: &str, expected: &str) {
+    let mut e = parse_expr(code).unwrap();
+    e.infer_types().unwrap();
+    e.lower_windows().unwrap();
+    let mut expected = parse_expr(expected).unwrap();
+    expected.infer_types().unwrap();
+    assert!(e.compare_ignoring_symbols(&expected).unwrap());
+}
+
+#[test]
+fn lower_shifts() {
+    check_lowering(
+        "|v: vec[i64]| lag(v, 1L, 0L)",
+        "|v: vec[i64]| let k = 1L; let d = 0L; let n = len(v);
+            result(for(v, appender[i64](n), |b, i, e| let j = i - k;
+                merge(b, select(j >= 0L && j < n, lookup(v, min(max(j, 0L), n - 1L)), d))))",
+    );
+    check_lowering(
+        "|v: vec[f64], k: i64, d: f64| lead(v, k, d)",
+        "|v: vec[f64], k: i64, d: f64| let n = len(v);
+            result(for(v, appender[f64](n), |b, i, e| let j = i + k;
+                merge(b, select(j >= 0L && j < n, lookup(v, min(max(j, 0L), n - 1L)), d))))",
+    );
+}
+
+#[test]
+fn lower_sliding_window() {
+    check_lowering(
+        "|v: vec[f32]| window[f32,+](v, 3L)",
+        "|v: vec[f32]| let w = 3L; let n = len(v);
+            result(for(v, appender[f32](n), |b, i, e| let end = i + 1L;
+                merge(b, result(for(iter(v, min(max(end - w, 0L), end), end, 1L), merger[f32,+],
+                    |b2, i2, e2| merge(b2, e2))))))",
+    );
+}
+
+#[test]
+fn lower_small_integer_window() {
+    check_lowering(
+        "|v: vec[i32]| window[i32,+](v, 3L)",
+        "|v: vec[i32]| let w = 3L; let n = len(v);
+            result(for(v, appender[i32](n), |b, i, e| let end = i + 1L;
+                merge(b, result(for(iter(v, min(max(end - w, 0L), end), end, 1L), merger[i32,+],
+                    |b2, i2, e2| merge(b2, e2))))))",
+    );
+}
+
+#[test]
+fn lower_running_window() {
+    check_lowering(
+        "|v: vec[i32]| window[i32,+](v, 100L)",
+        "|v: vec[i32]| let w = 100L; let n = len(v);
+            let sums = iterate({v, 1L}, |s| let p = s.$0; let d = s.$1; let d2 = d * 2L;
+                {{result(for(p, appender[i32](n), |b, i, e|
+                    merge(b, select(i >= d, lookup(p, max(i - d, 0L)) + e, e)))), d2}, d2 < n}).$0;
+            result(for(v, appender[i32](n), |b, i, e| let end = i + 1L;
+                let start = min(max(end - w, 0L), end);
+                merge(b, lookup(sums, i) -
+                    select(start > 0L, lookup(sums, max(start - 1L, 0L)), 0))))",
+    );
+}
+
+#[test]
+fn lower_window_of_variable_size() {
+    check_lowering(
+        "|v: vec[i64], w: i64| window[i64,+](v, w)",
+        "|v: vec[i64], w: i64| let n = len(v);
+            let sums = iterate({v, 1L}, |s| let p = s.$0; let d = s.$1; let d2 = d * 2L;
+                {{result(for(p, appender[i64](n), |b, i, e|
+                    merge(b, select(i >= d, lookup(p, max(i - d, 0L)) + e, e)))), d2}, d2 < n}).$0;
+            result(for(v, appender[i64](n), |b, i, e| let end = i + 1L;
+                let start = min(max(end - w, 0L), end);
+                merge(b, lookup(sums, i) -
+                    select(start > 0L, lookup(sums, max(start - 1L, 0L)), 0L))))",
+    );
+}
+
+#[test]
+fn lower_scan_passes() {
+    check_lowering(
+        "|v: vec[i64]| scan[?,+](v)",
+        "|v: vec[i64]| let n = len(v);
+            iterate({v, 1L}, |s| let p = s.$0; let d = s.$1; let d2 = d * 2L;
+                {{result(for(p, appender[i64](n), |b, i, e|
+                    merge(b, select(i >= d, lookup(p, max(i - d, 0L)) + e, e)))), d2}, d2 < n}).$0",
+    );
+}
@@ -18,7 +18,6 @@ use self::llvm_sys::prelude::*;
 use crate::ast::Type;
 use crate::codegen::c::intrinsic::Intrinsics;
 use crate::codegen::c::CodeGenExt;
-use crate::codegen::c::LLVM_VECTOR_WIDTH;
 use crate::codegen::c::CContextRef;
 use crate::codegen::c::c_u64_type;
 
@@ -28,7 +27,8 @@ pub const CAPACITY_INDEX: u32 = 2;
 
 /// The default Appender capacity.
 ///
-/// This *must* be larger than the `LLVM_VECTOR_WIDTH`.
+/// Merging a SIMD value grows the appender to fit all of its lanes, so this may be smaller than
+/// the vector length of the target.
 pub const DEFAULT_CAPACITY: i64 = 16;
 
 pub struct Appender {
@@ -43,6 +43,7 @@ pub struct Appender {
     c_merge: String,
     c_vmerge: String,
     c_result: String,
+    c_combine: String,
 }
 
 impl CodeGenExt for Appender {
@@ -92,6 +93,7 @@ impl Appender {
             c_merge: String::new(),
             c_vmerge: String::new(),
             c_result: String::new(),
+            c_combine: String::new(),
         }
     }
 
@@ -174,10 +176,8 @@ impl Appender {
     ) -> WeldResult<()> {
         // Number of elements merged in at once.
         let (c_merge_ty, num_elements) = if vectorized {
-            (
-                self.c_simd_type(&self.c_elem_ty, LLVM_VECTOR_WIDTH),
-                LLVM_VECTOR_WIDTH,
-            )
+            let width = self.c_simd_width();
+            (self.c_simd_type(&self.c_elem_ty, width), width)
         } else {
             (self.c_elem_ty.clone(), 1)
         };
@@ -217,6 +217,7 @@ impl Appender {
             u64=self.c_u64_type(),
             app=appender,
         ));
+        c_code.add("if (newCap < newSize) newCap = newSize;");
         let elem_size = self.c_size_of(&self.c_elem_ty);
         c_code.add(format!(
             "{} = {};",
@@ -232,11 +233,19 @@ impl Appender {
             app=appender,
         ));
         c_code.add("}");
-        c_code.add(format!(
-            "{app}->data[{app}->size] = {val};",
-            app=appender,
-            val=merge_value,
-        ));
+        if vectorized {
+            c_code.add(self.c_lane_loop("i", &format!(
+                "{app}->data[{app}->size + i] = {val}.e[i];",
+                app=appender,
+                val=merge_value,
+            )));
+        } else {
+            c_code.add(format!(
+                "{app}->data[{app}->size] = {val};",
+                app=appender,
+                val=merge_value,
+            ));
+        }
         c_code.add(format!(
             "{app}->size = newSize;",
             app=appender,
@@ -259,7 +268,7 @@ impl Appender {
     ) -> WeldResult<()> {
         // Number of elements merged in at once.
         let c_merge_ty = if vectorized {
-            self.c_simd_type(&self.c_elem_ty, LLVM_VECTOR_WIDTH)
+            self.c_simd_type(&self.c_elem_ty, self.c_simd_width())
         } else {
             self.c_elem_ty.clone()
         };
@@ -282,11 +291,24 @@ impl Appender {
         c_code.add("{");
         let appender = self.c_get_param(0);
         let merge_value = self.c_get_param(1);
-        c_code.add(format!(
-            "{app}->data[{app}->size++] = {val};",
-            app=appender,
-            val=merge_value,
-        ));
+        if vectorized {
+            c_code.add(self.c_lane_loop("i", &format!(
+                "{app}->data[{app}->size + i] = {val}.e[i];",
+                app=appender,
+                val=merge_value,
+            )));
+            c_code.add(format!(
+                "{app}->size += {num};",
+                app=appender,
+                num=self.c_simd_width(),
+            ));
+        } else {
+            c_code.add(format!(
+                "{app}->data[{app}->size++] = {val};",
+                app=appender,
+                val=merge_value,
+            ));
+        }
         c_code.add("}");
         (*self.ccontext()).prelude_code.add(c_code.result());
 
@@ -378,4 +400,86 @@ impl Appender {
         }
         Ok(format!("{}(&{})", self.c_result, builder_arg))
     }
+
+    /// Defines a function that appends the contents of one appender to another.
+    ///
+    /// This is used to merge the thread-local appenders of a parallel loop in order.
+    unsafe fn define_combine(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+    ) -> WeldResult<()> {
+        let c_arg_tys = [
+            self.c_pointer_type(&self.name),
+            self.name.clone(),
+            self.c_run_handle_type(),
+        ];
+        let c_ret_ty = &self.c_void_type();
+
+        // Use C name.
+        let name = format!("{}_combine", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        // for C
+        c_code.add("{");
+        let appender = self.c_get_param(0);
+        let other = self.c_get_param(1);
+        c_code.add(format!(
+            "{u64} newSize = {app}->size + {other}.size;",
+            u64=self.c_u64_type(),
+            app=appender,
+            other=other,
+        ));
+        c_code.add(format!(
+            "if (newSize > {app}->capacity) {{",
+            app=appender,
+        ));
+        let elem_size = self.c_size_of(&self.c_elem_ty);
+        c_code.add(format!(
+            "{app}->data = {realloc};",
+            app=appender,
+            realloc=intrinsics.c_call_weld_run_realloc(
+                &self.c_get_run(),
+                &format!("{app}->data", app=appender),
+                &format!("newSize * {elem_size}", elem_size=elem_size),
+            ),
+        ));
+        c_code.add(format!("{app}->capacity = newSize;", app=appender));
+        c_code.add("}");
+        c_code.add(format!(
+            "for ({u64} i = 0; i < {other}.size; ++i) {{",
+            u64=self.c_u64_type(),
+            other=other,
+        ));
+        c_code.add(format!(
+            "{app}->data[{app}->size + i] = {other}.data[i];",
+            app=appender,
+            other=other,
+        ));
+        c_code.add("}");
+        c_code.add(format!("{app}->size = newSize;", app=appender));
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_combine = name;
+        Ok(())
+    }
+
+    /// Generates code to append the contents of the appender `other` to `builder_arg`.
+    pub unsafe fn c_gen_combine(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        run_arg: &str,
+        builder_arg: &str,
+        other: &str,
+    ) -> WeldResult<String> {
+        if self.c_combine.is_empty() {
+            self.define_combine(intrinsics)?;
+        }
+        Ok(format!(
+            "{}(&{}, {}, {})",
+            self.c_combine,
+            builder_arg,
+            other,
+            run_arg,
+        ))
+    }
 }
@@ -1,8 +1,16 @@
 //! Code generation for the parallel for loop.
 //!
-//! This backend currently only generates single threaded code, but its structure is amenable to
-//! parallelization (e.g., loops are still divided out into their own function, albeit marked with
-//! `alwaysinline` at the moment).
+//! Each loop body is divided out into its own function that runs a range of iterations. When the
+//! module is configured with more than one thread (`weld.threads`), the outermost loops split
+//! their iterations across OpenMP threads. Each thread merges into its own thread-local builder,
+//! and the thread-local builders are merged into the loop's builder in thread order afterwards,
+//! so appenders keep the order of a single thread. Merging the thread-local results of a
+//! floating-point `+` or `*` reassociates the operation, so such results may differ slightly from
+//! the result with a single thread.
+//!
+//! An error raised on a thread of a parallel loop jumps back to that thread's part of the loop.
+//! The first error is kept, the other threads skip their remaining iterations, and the error is
+//! raised again on the thread that started the loop.
 //!
 //! The `GenForLoopInternal` is the main workhorse of this module, and provides methods for
 //! building a loop, creating bounds checks, loading elements, and so forth.
@@ -13,9 +21,14 @@ use crate::error::*;
 use crate::runtime::WeldRuntimeErrno;
 use crate::sir::*;
 
+use crate::codegen::c::dict;
 use crate::codegen::c::vector::VectorExt;
 
-use super::{CodeGenExt, FunctionContext, CGenerator};
+use super::appender;
+use super::{BuilderExpressionGen, CodeGenExt, FunctionContext, CGenerator};
+
+/// The minimum number of iterations for which a loop is split across threads.
+const PARALLEL_GRAIN: i64 = 4096;
 
 /// An internal trait for generating parallel For loops.
 pub trait ForLoopGenInternal {
@@ -55,6 +68,46 @@ pub trait ForLoopGenInternal {
         parfor: &ParallelForData,
         without_resize: bool,
     ) -> WeldResult<()>;
+    /// Generates the function that runs iterations `[lo, hi)` of the loop body.
+    ///
+    /// The function dispatches to the variant of the body that does not resize the builder if
+    /// the builder has enough capacity for the range. Each iteration merges up to `lanes`
+    /// elements.
+    unsafe fn gen_loop_range_function(
+        &mut self,
+        func: &SirFunction,
+        c_ret_ty: &str,
+        builder_index: usize,
+        try_no_resize: bool,
+        lanes: u32,
+    ) -> WeldResult<()>;
+    /// Generates code that splits the loop across OpenMP threads and combines the results.
+    unsafe fn gen_parallel_loop(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        func: &SirFunction,
+        builder_ty: &Type,
+        builder_index: usize,
+        c_max: &str,
+        lanes: u32,
+    ) -> WeldResult<()>;
+    /// Generates code to initialize `local` as an empty builder of the same kind as `builder`.
+    unsafe fn gen_thread_local_builder(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        ty: &Type,
+        local: &str,
+        builder: &str,
+        iterations: &str,
+    ) -> WeldResult<()>;
+    /// Generates code to merge the builder `other` into `builder`.
+    unsafe fn gen_combine_builders(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        ty: &Type,
+        builder: &str,
+        other: &str,
+    ) -> WeldResult<()>;
     /// Generates a bounds check for the given iterator.
     ///
     /// Returns a value representing the number of iterations the iterator will produce.
@@ -211,19 +264,25 @@ impl ForLoopGenInternal for CGenerator {
 
         // for C
         ctx.body.add(format!("{}:", c_fail_boundscheck_block));
-        let error = WeldRuntimeErrno::BadIteratorLength.to_string();
         let run = ctx.c_get_run();
         ctx.body.add(format!(
             "{};",
-            self.intrinsics.c_call_weld_run_set_errno(run, &error),
+            self.intrinsics.c_call_weld_run_raise(
+                run,
+                WeldRuntimeErrno::BadIteratorLength,
+                "iterator out of bounds",
+            ),
         ));
 
         // for C
         ctx.body.add(format!("{}:", c_fail_zip_block));
-        let error = WeldRuntimeErrno::MismatchedZipSize.to_string();
         ctx.body.add(format!(
             "{};",
-            self.intrinsics.c_call_weld_run_set_errno(run, &error),
+            self.intrinsics.c_call_weld_run_raise(
+                run,
+                WeldRuntimeErrno::MismatchedZipSize,
+                "zipped vectors have different lengths",
+            ),
         ));
 
         // for C
@@ -236,16 +295,16 @@ impl ForLoopGenInternal for CGenerator {
     ///
     /// A loop body function has the following layout:
     ///
-    /// { builders } FuncName(arg1, arg2, ..., iterations, run):
+    /// { builders } FuncName(arg1, arg2, ..., lo, hi, run):
     /// entry:
     ///     alloca all variables except the local builder.
     ///     alias builder argument with parfor.builder_arg
     ///     br loop.begin
     /// loop.begin:
-    ///     i = <initializer code>
+    ///     i = lo
     ///     br loop.entry
     /// loop.entry:
-    ///     if i >= end:
+    ///     if i >= hi:
     ///         br loop.exit
     ///     else
     ///         br loop.body
@@ -283,10 +342,12 @@ impl ForLoopGenInternal for CGenerator {
         // Generate function definition.
         // for C
         let mut c_arg_tys = self.c_argument_types(func)?;
-        // The second-to-last argument is the *total* number of iterations across all threads (in a
-        // multi-threaded setting) that this loop will execute for.
+        // The two arguments before the run handle are the range of iterations [lo, hi) that
+        // this function executes.
         c_arg_tys.push(self.c_i64_type());
-        let c_num_iterations_index = c_arg_tys.len() - 1;
+        let c_lo_index = c_arg_tys.len() - 1;
+        c_arg_tys.push(self.c_i64_type());
+        let c_hi_index = c_arg_tys.len() - 1;
         // Last argument is run handle, as always.
         c_arg_tys.push(self.c_run_handle_type());
 
@@ -305,8 +366,9 @@ impl ForLoopGenInternal for CGenerator {
         ));
         self.c_functions.insert(func.id, name);
         context.body.add("{");
-        // Reference to the parameter storing the max number of iterations.
-        let c_max = self.c_get_param(c_num_iterations_index);
+        // References to the parameters storing the range of iterations.
+        let c_lo = self.c_get_param(c_lo_index);
+        let c_hi = self.c_get_param(c_hi_index);
         // Create the entry basic block, where we define alloca'd variables.
         self.gen_allocas(context)?;
         self.gen_store_parameters(context)?;
@@ -364,10 +426,16 @@ impl ForLoopGenInternal for CGenerator {
             context.c_get_value(&parfor.builder)?,
         ));
         let c_idx = context.c_get_value(&parfor.idx_arg)?;
+        if independent_iterations(weld_ty, without_resize) {
+            if let Some(directives) = (*self.ccontext()).vector_target.independent_loop_directives() {
+                context.body.add(directives);
+            }
+        }
         context.body.add(format!(
-            "for ({idx} = 0; {idx} < {max}; ++{idx}) {{",
+            "for ({idx} = {lo}; {idx} < {hi}; ++{idx}) {{",
             idx=c_idx,
-            max=c_max,
+            lo=c_lo,
+            hi=c_hi,
         ));
         // Add the SIR function basic blocks.
         self.gen_basic_block_defs(context)?;
@@ -511,58 +579,426 @@ impl ForLoopGenInternal for CGenerator {
         // The other is for not one-to-one mapping.  Former may be vectorized
         // because it doesn't call realloc.  Latter is not vectorized because
         // it calls realloc.
+        // VE-Weld NO_RESIZE begin
+        if try_no_resize {
+            self.gen_loop_body_function_internal(
+                context.sir_program, func, parfor, true)?;
+        }
+        // VE-Weld NO_RESIZE end
+        self.gen_loop_body_function_internal(
+            context.sir_program, func, parfor, false)?;
+        // Each iteration of a vectorized loop merges a whole SIMD value.
+        let lanes = if parfor.data.iter().any(|iter| iter.kind == SimdIter) {
+            self.c_simd_width()
+        } else {
+            1
+        };
+        self.gen_loop_range_function(func, c_ret_ty, builder_index, try_no_resize, lanes)?;
 
-        // The parameters of the body function are:
+        // Split the loop across threads if it is large enough. Loops with an NdIter are not
+        // split since their counters assume that iteration starts at 0.
+        if self.conf.threads > 1 && check_any_nditer(parfor).is_none() {
+            self.gen_parallel_loop(context, func, weld_ty, builder_index, &c_max, lanes)?;
+        }
+
+        // The parameters of the range function are:
         //   0..N: each parameter in func.params
-        //   N+1:  loop max
-        //   N+2:  run handler
+        //   N+1:  first iteration
+        //   N+2:  loop max
+        //   N+3:  run handler
         let mut c_arguments = vec![];
-        for i in 0..func.params.len() + 1 {
+        for i in 0..func.params.len() {
             c_arguments.push(self.c_get_param(i));
         }
+        c_arguments.push("0".to_string());
+        c_arguments.push(c_max);
         // Last argument is always the run handle.
         c_arguments.push(context.c_get_run().to_string());
+        let args_line = self.c_call_args(&c_arguments);
+        context.body.add(format!("return {}_range({});", name, args_line));
+        context.body.add("}");
+        (*self.ccontext()).prelude_code.add(context.body.result());
+        Ok(())
+    }
+
+    unsafe fn gen_loop_range_function(
+        &mut self,
+        func: &SirFunction,
+        c_ret_ty: &str,
+        builder_index: usize,
+        try_no_resize: bool,
+        lanes: u32,
+    ) -> WeldResult<()> {
+        let mut c_arg_tys = self.c_argument_types(func)?;
+        c_arg_tys.push(self.c_i64_type());
+        c_arg_tys.push(self.c_i64_type());
+        c_arg_tys.push(self.c_run_handle_type());
 
-        // Prepare to call the body function.
+        let name = format!("f{}_loop", func.id);
+        let mut c_code =
+            self.c_define_function(c_ret_ty, &c_arg_tys, format!("{}_range", name), true);
+
+        let num_params = func.params.len();
+        let mut c_arguments = vec![];
+        for i in 0..num_params + 2 {
+            c_arguments.push(self.c_get_param(i));
+        }
+        c_arguments.push(self.c_get_run().to_string());
         let args_line = self.c_call_args(&c_arguments);
 
+        c_code.add("{");
         // VE-Weld NO_RESIZE begin
         if try_no_resize {
-            self.gen_loop_body_function_internal(
-                context.sir_program, func, parfor, true)?;
-            self.gen_loop_body_function_internal(
-                context.sir_program, func, parfor, false)?;
-
-            context.body.add(format!(
-                "if ( {}.capacity >= {} ) {{",
-                self.c_get_param(builder_index),
-                c_max,
+            c_code.add(format!(
+                "if ( {b}.capacity - {b}.size >= ({u64})({hi} - {lo}) * {lanes} ) {{",
+                b=self.c_get_param(builder_index),
+                u64=self.c_u64_type(),
+                lanes=lanes,
+                lo=self.c_get_param(num_params),
+                hi=self.c_get_param(num_params + 1),
             ));
+            c_code.add(format!("return {}_no_resize({});", name, args_line));
+            c_code.add("}");
+        }
+        // VE-Weld NO_RESIZE end
+        c_code.add(format!("return {}_resize({});", name, args_line));
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        Ok(())
+    }
 
-            context.body.add(format!(
-                "return {}_no_resize({});",
-                name,
-                args_line,
-            ));
-            context.body.add("} else {");
-            context.body.add(format!(
-                "return {}_resize({});",
-                name,
-                args_line,
-            ));
-            context.body.add("}");
-        } else {
-            self.gen_loop_body_function_internal(
-                context.sir_program, func, parfor, false)?;
+    unsafe fn gen_parallel_loop(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        func: &SirFunction,
+        builder_ty: &Type,
+        builder_index: usize,
+        c_max: &str,
+        lanes: u32,
+    ) -> WeldResult<()> {
+        let i32_ty = self.c_i32_type();
+        let i64_ty = self.c_i64_type();
+        let c_builder_ty = self.c_type(builder_ty)?;
+        let nworkers = "((WeldRuntimeContextRef)run)->nworkers";
 
-            context.body.add(format!(
-                "return {}_resize({});",
-                name,
-                args_line,
-            ));
+        // Nested loops run on the thread that executes them.
+        ctx.body.add(format!(
+            "if ({nworkers} > 1 && {max} >= {grain} && !omp_in_parallel()) {{",
+            nworkers=nworkers,
+            max=c_max,
+            grain=PARALLEL_GRAIN,
+        ));
+        ctx.body.add(format!("{} nworkers = {};", i32_ty, nworkers));
+        ctx.body.add(format!("{} partials[nworkers];", c_builder_ty));
+        ctx.body.add(format!("{} nthreads = 1;", i32_ty));
+        ctx.body.add("#pragma omp parallel num_threads(nworkers)");
+        ctx.body.add("{");
+        ctx.body.add(format!("{} tid = omp_get_thread_num();", i32_ty));
+        ctx.body.add(format!("{} nt = omp_get_num_threads();", i32_ty));
+        // Each thread runs a contiguous range of iterations, so merging the thread-local
+        // builders in thread order preserves the order of appended elements.
+        ctx.body.add(format!("{} lo = {} * tid / nt;", i64_ty, c_max));
+        ctx.body.add(format!("{} hi = {} * (tid + 1) / nt;", i64_ty, c_max));
+        ctx.body.add(format!("{} local;", c_builder_ty));
+        // An error raised on this thread jumps back here instead of to the entry function, which
+        // runs on another thread. The handler of the thread is restored afterwards, since the
+        // loop may run within the handler of an enclosing one.
+        ctx.body.add("jmp_buf* outer_handler = weld_thread_handler;");
+        ctx.body.add("jmp_buf thread_handler;");
+        ctx.body.add("if (setjmp(thread_handler) == 0) {");
+        ctx.body.add("weld_thread_handler = &thread_handler;");
+        ctx.body.add("if (tid == 0) {");
+        ctx.body.add("nthreads = nt;");
+        ctx.body.add(format!("local = {};", self.c_get_param(builder_index)));
+        ctx.body.add("} else {");
+        let builder = self.c_get_param(builder_index);
+        let iterations = format!("(hi - lo) * {}", lanes);
+        self.gen_thread_local_builder(ctx, builder_ty, "local", &builder, &iterations)?;
+        ctx.body.add("}");
+
+        let mut c_arguments = vec![];
+        for i in 0..func.params.len() {
+            if i == builder_index {
+                c_arguments.push("local".to_string());
+            } else {
+                c_arguments.push(self.c_get_param(i));
+            }
+        }
+        c_arguments.push("b".to_string());
+        c_arguments.push("e".to_string());
+        c_arguments.push(ctx.c_get_run().to_string());
+        let args_line = self.c_call_args(&c_arguments);
+        let c_errno = self.intrinsics.c_call_weld_run_get_errno(ctx.c_get_run());
+        // Run the range in blocks, so that the thread skips its remaining iterations once any
+        // thread raised an error.
+        ctx.body.add(format!(
+            "for ({i64} b = lo; b < hi && {errno} == {success}; b += {grain}) {{",
+            i64=i64_ty,
+            errno=c_errno,
+            success=WeldRuntimeErrno::Success,
+            grain=PARALLEL_GRAIN,
+        ));
+        ctx.body.add(format!(
+            "{i64} e = hi - b > {grain} ? b + {grain} : hi;",
+            i64=i64_ty,
+            grain=PARALLEL_GRAIN,
+        ));
+        ctx.body.add(format!("local = f{}_loop_range({});", func.id, args_line));
+        ctx.body.add("}");
+        ctx.body.add("partials[tid] = local;");
+        ctx.body.add("}");
+        ctx.body.add("weld_thread_handler = outer_handler;");
+        ctx.body.add("}");
+
+        // Raise the first error of a thread again on the thread that started the loop.
+        ctx.body.add(format!(
+            "if ({errno} != {success}) {{",
+            errno=c_errno,
+            success=WeldRuntimeErrno::Success,
+        ));
+        let raise = self.intrinsics.c_call_weld_run_set_errno(ctx.c_get_run(), &c_errno);
+        ctx.body.add(format!("{};", raise));
+        ctx.body.add("}");
+
+        ctx.body.add(format!("{} result = partials[0];", c_builder_ty));
+        ctx.body.add(format!("for ({} t = 1; t < nthreads; ++t) {{", i32_ty));
+        self.gen_combine_builders(ctx, builder_ty, "result", "partials[t]")?;
+        ctx.body.add("}");
+        ctx.body.add("return result;");
+        ctx.body.add("}");
+        Ok(())
+    }
+
+    unsafe fn gen_thread_local_builder(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        ty: &Type,
+        local: &str,
+        builder: &str,
+        iterations: &str,
+    ) -> WeldResult<()> {
+        use crate::ast::BuilderKind::*;
+        use crate::ast::Type::*;
+        match *ty {
+            Struct(ref elems) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    self.gen_thread_local_builder(
+                        ctx,
+                        elem,
+                        &format!("{}.f{}", local, i),
+                        &format!("{}.f{}", builder, i),
+                        iterations,
+                    )?;
+                }
+            }
+            Builder(ref kind, _) => match *kind {
+                Appender(_) => {
+                    let capacity = format!(
+                        "({it} > {cap} ? {it} : {cap})",
+                        it=iterations,
+                        cap=appender::DEFAULT_CAPACITY,
+                    );
+                    let methods = self.appenders.get_mut(kind).unwrap();
+                    let new = methods.c_gen_new(
+                        ctx.builder,
+                        &mut self.intrinsics,
+                        ctx.c_get_run(),
+                        &capacity,
+                    )?;
+                    ctx.body.add(format!("{} = {};", local, new));
+                }
+                Merger(_, _) => {
+                    let methods = self.mergers.get_mut(kind).unwrap();
+                    let identity = methods.c_binop_identity(methods.op, methods.scalar_kind)?;
+                    let new = methods.c_gen_new(ctx.builder, &identity)?;
+                    ctx.body.add(format!("{} = {};", local, new));
+                }
+                DictMerger(ref key, ref val, _) => {
+                    let dict_type = &Dict(key.clone(), val.clone());
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    let new = methods.c_gen_new(
+                        &mut self.intrinsics,
+                        &format!("{}", dict::INITIAL_CAPACITY),
+                        ctx.c_get_run(),
+                    )?;
+                    ctx.body.add(format!("{} = {};", local, new));
+                }
+                GroupMerger(ref key, ref val) => {
+                    let dict_type = &Dict(key.clone(), Box::new(Vector(val.clone())));
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    let new = methods.c_gen_new(
+                        &mut self.intrinsics,
+                        &format!("{}", dict::INITIAL_CAPACITY),
+                        ctx.c_get_run(),
+                    )?;
+                    ctx.body.add(format!("{} = {};", local, new));
+                }
+                VecMerger(ref elem, ref binop) => {
+                    // A vector of the same size, filled with the identity of the operator.
+                    let identity = self.c_binop_identity_value(*binop, elem)?;
+                    let c_elem_ty = self.c_type(elem)?;
+                    let bytes = format!("{}.size * {}", builder, self.c_size_of(&c_elem_ty));
+                    let malloc = self.intrinsics.c_call_weld_run_malloc(ctx.c_get_run(), &bytes);
+                    let i = ctx.var_ids.next();
+                    ctx.body.add(format!("{}.data = ({}*){};", local, c_elem_ty, malloc));
+                    ctx.body.add(format!("{}.size = {}.size;", local, builder));
+                    ctx.body.add(format!(
+                        "for ({u64} {i} = 0; {i} < {local}.size; ++{i}) {{",
+                        u64=self.c_u64_type(),
+                        i=i,
+                        local=local,
+                    ));
+                    ctx.body.add(format!("{}.data[{}] = {};", local, i, identity));
+                    ctx.body.add("}");
+                }
+                TopK(_, _) => {
+                    let methods = self.topks.get_mut(kind).unwrap();
+                    let new = methods.c_gen_new(&mut self.intrinsics, ctx.c_get_run())?;
+                    ctx.body.add(format!("{} = {};", local, new));
+                }
+                SetMerger(_) | SketchMerger(_, _) => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    unsafe fn gen_combine_builders(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        ty: &Type,
+        builder: &str,
+        other: &str,
+    ) -> WeldResult<()> {
+        use crate::ast::BuilderKind::*;
+        use crate::ast::Type::*;
+        match *ty {
+            Struct(ref elems) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    self.gen_combine_builders(
+                        ctx,
+                        elem,
+                        &format!("{}.f{}", builder, i),
+                        &format!("{}.f{}", other, i),
+                    )?;
+                }
+            }
+            Builder(ref kind, _) => match *kind {
+                Appender(_) => {
+                    let methods = self.appenders.get_mut(kind).unwrap();
+                    let combine = methods.c_gen_combine(
+                        &mut self.intrinsics,
+                        ctx.c_get_run(),
+                        builder,
+                        other,
+                    )?;
+                    ctx.body.add(format!("{};", combine));
+                }
+                Merger(_, _) => {
+                    let methods = self.mergers.get_mut(kind).unwrap();
+                    let combine = methods.c_gen_combine(builder, other)?;
+                    ctx.body.add(format!("{};", combine));
+                }
+                DictMerger(ref key, ref val, ref binop) => {
+                    // Upsert each key of the other dictionary and merge its value.
+                    let dict_type = &Dict(key.clone(), val.clone());
+                    let default = self.c_binop_identity_value(*binop, val)?;
+                    let i = ctx.var_ids.next();
+                    let slot = ctx.var_ids.next();
+                    let other_slot = format!("{}->slots[{}]", other, i);
+                    let (c_slot_ty, upsert) = {
+                        let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                        let upsert = methods.c_gen_upsert(
+                            &mut self.intrinsics,
+                            builder,
+                            &format!("&{}.key", other_slot),
+                            &format!("{}.hash", other_slot),
+                            &default,
+                            ctx.c_get_run(),
+                        )?;
+                        (methods.c_slot_type().to_string(), upsert)
+                    };
+                    let merge = self.c_merge_values(
+                        val,
+                        *binop,
+                        &format!("{}->value", slot),
+                        &format!("{}.value", other_slot),
+                    )?;
+                    ctx.body.add(format!(
+                        "for ({i64} {i} = 0; {i} < {other}->capacity; ++{i}) {{",
+                        i64=self.c_i64_type(),
+                        i=i,
+                        other=other,
+                    ));
+                    ctx.body.add(format!("if ({}.filled) {{", other_slot));
+                    ctx.body.add(format!("{}* {} = {};", c_slot_ty, slot, upsert));
+                    ctx.body.add(merge);
+                    ctx.body.add("}");
+                    ctx.body.add("}");
+                }
+                GroupMerger(ref key, ref val) => {
+                    // Append each group of the other dictionary to the corresponding group.
+                    use crate::codegen::c::dict::GroupingDict;
+                    let dict_type = &Dict(key.clone(), Box::new(Vector(val.clone())));
+                    let i = ctx.var_ids.next();
+                    let j = ctx.var_ids.next();
+                    let other_slot = format!("{}->slots[{}]", other, i);
+                    let merge = {
+                        let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                        methods.c_gen_merge_grouped(
+                            &mut self.intrinsics,
+                            self.vectors.get_mut(val).unwrap(),
+                            builder,
+                            &format!("&{}.key", other_slot),
+                            &format!("{}.hash", other_slot),
+                            &format!("{}.value.data[{}]", other_slot, j),
+                            ctx.c_get_run(),
+                        )?
+                    };
+                    ctx.body.add(format!(
+                        "for ({i64} {i} = 0; {i} < {other}->capacity; ++{i}) {{",
+                        i64=self.c_i64_type(),
+                        i=i,
+                        other=other,
+                    ));
+                    ctx.body.add(format!("if ({}.filled) {{", other_slot));
+                    ctx.body.add(format!(
+                        "for ({u64} {j} = 0; {j} < {slot}.value.size; ++{j}) {{",
+                        u64=self.c_u64_type(),
+                        j=j,
+                        slot=other_slot,
+                    ));
+                    ctx.body.add(format!("{};", merge));
+                    ctx.body.add("}");
+                    ctx.body.add("}");
+                    ctx.body.add("}");
+                }
+                VecMerger(ref elem, ref binop) => {
+                    let i = ctx.var_ids.next();
+                    let merge = self.c_merge_values(
+                        elem,
+                        *binop,
+                        &format!("{}.data[{}]", builder, i),
+                        &format!("{}.data[{}]", other, i),
+                    )?;
+                    ctx.body.add(format!(
+                        "for ({u64} {i} = 0; {i} < {b}.size; ++{i}) {{",
+                        u64=self.c_u64_type(),
+                        i=i,
+                        b=builder,
+                    ));
+                    ctx.body.add(merge);
+                    ctx.body.add("}");
+                }
+                TopK(_, _) => {
+                    let methods = self.topks.get_mut(kind).unwrap();
+                    let combine = methods.c_gen_combine(builder, other)?;
+                    ctx.body.add(format!("{};", combine));
+                }
+                SetMerger(_) | SketchMerger(_, _) => unreachable!(),
+            },
+            _ => unreachable!(),
         }
-        context.body.add("}");
-        (*self.ccontext()).prelude_code.add(context.body.result());
         Ok(())
     }
 
@@ -633,10 +1069,28 @@ impl ForLoopGenInternal for CGenerator {
                 SimdIter if iter.start.is_some() => unreachable!(),
                 SimdIter => {
                     // for C
-                    ctx.body.add(format!(
-                        "#error gen_loop_element for SimdIter {} is not implemented yet",
-                        ctx.c_get_value(&iter.data)?,
+                    // Loads the lanes at Index = i * VECTOR_WIDTH + lane.
+                    let vector = &ctx.c_get_value(&iter.data)?;
+                    let vector_type = ctx.sir_function.symbol_type(&iter.data)?;
+                    let lane = ctx.var_ids.next();
+                    let index = format!("{} * {} + {}", c_i, self.c_simd_width(), lane);
+                    let element_pointer = self.c_gen_at(
+                        ctx.builder, vector_type, vector, &index)?;
+                    let element = ctx.var_ids.next();
+                    if let Type::Vector(elem_type) = vector_type {
+                        ctx.body.add(format!(
+                            "{} {};",
+                            self.c_type(&elem_type.simd_type()?)?,
+                            element,
+                        ));
+                    } else {
+                        unreachable!()
+                    }
+                    ctx.body.add(self.c_lane_loop(
+                        &lane,
+                        &format!("{}.e[{}] = *{};", element, lane, element_pointer),
                     ));
+                    c_values.push(element);
                     // for LLVM
                     /*
                     let i = LLVMBuildNSWMul(
@@ -655,10 +1109,31 @@ impl ForLoopGenInternal for CGenerator {
                 FringeIter if iter.start.is_some() => unreachable!(),
                 FringeIter => {
                     // for C
-                    ctx.body.add(format!(
-                        "#error gen_loop_element for FringeIter {} is not implemented yet",
-                        ctx.c_get_value(&iter.data)?,
-                    ));
+                    // Start = Len(vector) - Len(vector) % VECTOR_WIDTH
+                    // Index = start + i
+                    let vector = &ctx.c_get_value(&iter.data)?;
+                    let vector_type = ctx.sir_function.symbol_type(&iter.data)?;
+                    let size = self.c_gen_size(ctx.builder, vector_type, vector)?;
+                    let index = format!(
+                        "{size} - {size} % {width} + {i}",
+                        size=size,
+                        width=self.c_simd_width(),
+                        i=c_i,
+                    );
+                    let element_pointer = self.c_gen_at(
+                        ctx.builder, vector_type, vector, &index)?;
+                    let element = ctx.var_ids.next();
+                    if let Type::Vector(elem_type) = vector_type {
+                        ctx.body.add(format!(
+                            "{} {} = *{};",
+                            self.c_type(elem_type)?,
+                            element,
+                            element_pointer,
+                        ));
+                    } else {
+                        unreachable!()
+                    }
+                    c_values.push(element);
                     // for LLVM
                     /*
                     let vector = self.load(ctx.builder, ctx.get_value(&iter.data)?)?;
@@ -851,10 +1326,7 @@ impl ForLoopGenInternal for CGenerator {
             SimdIter if iter.start.is_some() => unreachable!(),
             SimdIter => {
                 // for C
-                ctx.body.add(format!(
-                    "#error gen_iter_bounds_check for SimdIter {} is not implemented yet",
-                    ctx.c_get_value(&iter.data)?,
-                ));
+                ctx.body.add(format!("goto {};", c_pass_block));
                 // for LLVM
                 /*
                 let iterations = LLVMBuildSDiv(
@@ -865,15 +1337,12 @@ impl ForLoopGenInternal for CGenerator {
                 );
                 let _ = LLVMBuildBr(ctx.builder, pass_block);
                 */
-                Ok("not implemented".to_string())
+                Ok(format!("({} / {})", c_size, self.c_simd_width()))
             }
             FringeIter if iter.start.is_some() => unreachable!(),
             FringeIter => {
                 // for C
-                ctx.body.add(format!(
-                    "#error gen_iter_bounds_check for FringeIter {} is not implemented yet",
-                    ctx.c_get_value(&iter.data)?,
-                ));
+                ctx.body.add(format!("goto {};", c_pass_block));
                 // for LLVM
                 /*
                 let iterations = LLVMBuildSRem(
@@ -884,7 +1353,7 @@ impl ForLoopGenInternal for CGenerator {
                 );
                 let _ = LLVMBuildBr(ctx.builder, pass_block);
                 */
-                Ok("not implemented".to_string())
+                Ok(format!("({} % {})", c_size, self.c_simd_width()))
             }
             RangeIter => {
                 // for C
@@ -999,6 +1468,22 @@ impl ForLoopGenInternal for CGenerator {
     }
 }
 
+/// Returns whether iterations of a loop that merges into a builder of type `ty` only depend on
+/// each other through the builder.
+///
+/// This holds for mergers, and for appenders when they are not resized within the loop.
+fn independent_iterations(ty: &Type, without_resize: bool) -> bool {
+    use crate::ast::BuilderKind::*;
+    match *ty {
+        Type::Struct(ref elems) => elems
+            .iter()
+            .all(|elem| independent_iterations(elem, without_resize)),
+        Type::Builder(Merger(_, _), _) => true,
+        Type::Builder(Appender(_), _) => without_resize,
+        _ => false,
+    }
+}
+
 /// Helper function to check if any of the iters are of kind NdIter,
 /// and returns it if found.
 fn check_any_nditer(par_for: &ParallelForData) -> Option<ParallelForIter> {
@@ -16,7 +16,6 @@ use self::llvm_sys::prelude::*;
 
 use crate::codegen::c::numeric::c_gen_binop;
 use crate::codegen::c::CodeGenExt;
-use crate::codegen::c::LLVM_VECTOR_WIDTH;
 use crate::codegen::c::CContextRef;
 
 const SCALAR_INDEX: u32 = 0;
@@ -36,6 +35,7 @@ pub struct Merger {
     c_merge: String,
     c_vmerge: String,
     c_result: String,
+    c_combine: String,
 }
 
 impl CodeGenExt for Merger {
@@ -69,7 +69,7 @@ impl Merger {
         def.add(format!(
             "{elem_ty} vdata[{size}];",
             elem_ty=c_elem_ty,
-            size=LLVM_VECTOR_WIDTH,
+            size=(*ccontext).vector_target.width,
         ));
         def.add(format!("}} {};", name.as_ref()));
         (*ccontext).prelude_code.add(def.result());
@@ -89,6 +89,7 @@ impl Merger {
             c_merge: String::new(),
             c_vmerge: String::new(),
             c_result: String::new(),
+            c_combine: String::new(),
         }
     }
 
@@ -109,13 +110,7 @@ impl Merger {
         // Initialize only scalar value using first parameter.
         c_code.add(format!("ret.data = {};", self.c_get_param(0)));
         // Initialize vector data using identity.
-        c_code.add(format!("\
-            for (int i = 0; i < {}; ++i) {{
-                ret.vdata[i] = {};
-            }}",
-            LLVM_VECTOR_WIDTH,
-            c_identity,
-        ));
+        c_code.add(self.c_lane_loop("i", &format!("ret.vdata[i] = {};", c_identity)));
         c_code.add("return ret;");
         c_code.add("}");
 
@@ -160,16 +155,13 @@ impl Merger {
             )?;
             c_code.add(format!("p0->data = {};", merge));
         } else {
-            c_code.add(format!("for (int i = 0; i < {}; ++i) {{",
-                LLVM_VECTOR_WIDTH));
             let merge = c_gen_binop(
                 self.op,
                 "p0->vdata[i]",
-                "p1[i]",
+                "p1.e[i]",
                 &Scalar(self.scalar_kind),
             )?;
-            c_code.add(format!("p0->vdata[i] = {};", merge));
-            c_code.add("}");
+            c_code.add(self.c_lane_loop("i", &format!("p0->vdata[i] = {};", merge)));
         }
         c_code.add("}");
 
@@ -195,7 +187,7 @@ impl Merger {
             if self.c_vmerge.is_empty() {
                 let c_arg_tys = [
                     self.c_pointer_type(&self.name),
-                    self.c_simd_type(&self.c_elem_ty, LLVM_VECTOR_WIDTH as u32),
+                    self.c_simd_type(&self.c_elem_ty, self.c_simd_width()),
                 ];
                 let name = format!("{}_vmerge", self.name);
                 self.define_merge(name, &c_arg_tys, VECTOR_INDEX)?;
@@ -226,7 +218,7 @@ impl Merger {
         c_code.add("{");
         c_code.add(format!("{} ret = p0->data;", self.c_elem_ty));
         c_code.add(format!("for (int i = 0; i < {}; ++i) {{",
-            LLVM_VECTOR_WIDTH));
+            self.c_simd_width()));
         let merge = c_gen_binop(
             self.op,
             "ret",
@@ -252,4 +244,50 @@ impl Merger {
         }
         Ok(format!("{}(&{})", self.c_result, builder))
     }
+
+    /// Builds the `Combine` function, which merges the state of one merger into another.
+    ///
+    /// This is used to merge the thread-local mergers of a parallel loop.
+    unsafe fn define_combine(&mut self) -> WeldResult<()> {
+        let c_ret_ty = &self.c_void_type();
+        let c_arg_tys = [self.c_pointer_type(&self.name), self.name.clone()];
+
+        // Use C name.
+        let name = format!("{}_combine", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), true);
+
+        // for C
+        c_code.add("{");
+        let merge = c_gen_binop(
+            self.op,
+            "p0->data",
+            "p1.data",
+            &Scalar(self.scalar_kind),
+        )?;
+        c_code.add(format!("p0->data = {};", merge));
+        let merge = c_gen_binop(
+            self.op,
+            "p0->vdata[i]",
+            "p1.vdata[i]",
+            &Scalar(self.scalar_kind),
+        )?;
+        c_code.add(self.c_lane_loop("i", &format!("p0->vdata[i] = {};", merge)));
+        c_code.add("}");
+
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_combine = name;
+        Ok(())
+    }
+
+    /// Generates code to merge the merger `other` into the merger `builder`.
+    pub unsafe fn c_gen_combine(
+        &mut self,
+        builder: &str,
+        other: &str,
+    ) -> WeldResult<String> {
+        if self.c_combine.is_empty() {
+            self.define_combine()?;
+        }
+        Ok(format!("{}(&{}, {})", self.c_combine, builder, other))
+    }
 }
@@ -23,7 +23,7 @@ use crate::sir::*;
 use self::llvm_sys::core::*;
 use self::llvm_sys::prelude::*;
 
-// use super::dict;
+use super::dict;
 
 use super::{CodeGenExt, FunctionContext, CGenerator};
 
@@ -34,6 +34,7 @@ mod for_loop;
 
 pub mod appender;
 pub mod merger;
+pub mod topk;
 
 /// A trait for generating builder code.
 ///
@@ -51,6 +52,21 @@ pub trait BuilderExpressionGen {
         builder_value_pointer: LLVMValueRef,
         merge_value_pointer: LLVMValueRef,
     ) -> WeldResult<()>;
+    /// Generates C code to merge two values using the provided binary operator.
+    ///
+    /// Specifically, performs `builder_value = builder_value <binop> merge_value`, where both
+    /// arguments are lvalues.
+    unsafe fn c_merge_values(
+        &mut self,
+        merge_ty: &Type,
+        binop: BinOpKind,
+        builder_value: &str,
+        merge_value: &str,
+    ) -> WeldResult<String>;
+    /// Generates a C expression for the identity of `binop` over `ty`.
+    ///
+    /// `ty` is either a scalar or a struct of scalars.
+    unsafe fn c_binop_identity_value(&mut self, binop: BinOpKind, ty: &Type) -> WeldResult<String>;
     /// Generates code for the `NewBuilder` statement.
     unsafe fn gen_new_builder(
         &mut self,
@@ -205,6 +221,50 @@ impl BuilderExpressionGen for CGenerator {
         Ok(())
     }
 
+    unsafe fn c_merge_values(
+        &mut self,
+        merge_ty: &Type,
+        binop: BinOpKind,
+        builder_value: &str,
+        merge_value: &str,
+    ) -> WeldResult<String> {
+        match *merge_ty {
+            Scalar(_) => {
+                let merged = numeric::c_gen_binop(binop, builder_value, merge_value, merge_ty)?;
+                Ok(format!("{} = {};", builder_value, merged))
+            }
+            Struct(ref elems) => {
+                let mut code = vec![];
+                for (i, elem) in elems.iter().enumerate() {
+                    let builder_elem = format!("{}.f{}", builder_value, i);
+                    let merge_elem = format!("{}.f{}", merge_value, i);
+                    let merged = numeric::c_gen_binop(binop, &builder_elem, &merge_elem, elem)?;
+                    code.push(format!("{} = {};", builder_elem, merged));
+                }
+                Ok(code.join("\n"))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn c_binop_identity_value(&mut self, binop: BinOpKind, ty: &Type) -> WeldResult<String> {
+        match *ty {
+            Scalar(ref kind) => self.c_binop_identity(binop, *kind),
+            Struct(ref elems) => {
+                let mut fields = vec![];
+                for elem in elems.iter() {
+                    if let Scalar(ref kind) = *elem {
+                        fields.push(self.c_binop_identity(binop, *kind)?);
+                    } else {
+                        unreachable!()
+                    }
+                }
+                Ok(format!("({}){{{}}}", self.c_type(ty)?, fields.join(", ")))
+            }
+            _ => unreachable!(),
+        }
+    }
+
     unsafe fn gen_new_builder(
         &mut self,
         ctx: &mut FunctionContext<'_>,
@@ -251,8 +311,16 @@ impl BuilderExpressionGen for CGenerator {
             }
             DictMerger(ref key, ref val, _) => {
                 // for C
-                ctx.body.add(
-                    "#error NewBuilder for DictMerger is not implemented yet");
+                let dict_type = &Dict(key.clone(), val.clone());
+                let dictmerger = {
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    methods.c_gen_new(
+                        &mut self.intrinsics,
+                        &format!("{}", dict::INITIAL_CAPACITY),
+                        ctx.c_get_run(),
+                    )?
+                };
+                ctx.body.add(format!("{} = {};", c_output_pointer, dictmerger));
 
                 // for LLVM
                 /*
@@ -273,8 +341,16 @@ impl BuilderExpressionGen for CGenerator {
             }
             GroupMerger(ref key, ref val) => {
                 // for C
-                ctx.body.add(
-                    "#error NewBuilder for GroupMerger is not implemented yet");
+                let dict_type = &Dict(key.clone(), Box::new(Vector(val.clone())));
+                let groupmerger = {
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    methods.c_gen_new(
+                        &mut self.intrinsics,
+                        &format!("{}", dict::INITIAL_CAPACITY),
+                        ctx.c_get_run(),
+                    )?
+                };
+                ctx.body.add(format!("{} = {};", c_output_pointer, groupmerger));
 
                 // for LLVM
                 /*
@@ -330,8 +406,27 @@ impl BuilderExpressionGen for CGenerator {
             }
             VecMerger(ref elem, _) => {
                 // for C
-                ctx.body.add(
-                    "#error NewBuilder for VecMerger is not implemented yet");
+                // Unlike the LLVM version, this is a copy of the data, so merges never modify the
+                // argument vector.
+                let argument = ctx.c_get_value(nb.arg.unwrap())?;
+                let c_elem_ty = self.c_type(elem)?;
+                let bytes = format!("{}.size * {}", argument, self.c_size_of(&c_elem_ty));
+                let i = ctx.var_ids.next();
+                ctx.body.add(format!(
+                    "{out}.data = ({elem}*){malloc};",
+                    out=c_output_pointer,
+                    elem=c_elem_ty,
+                    malloc=self.intrinsics.c_call_weld_run_malloc(ctx.c_get_run(), &bytes),
+                ));
+                ctx.body.add(format!("{}.size = {}.size;", c_output_pointer, argument));
+                ctx.body.add(format!(
+                    "for ({u64} {i} = 0; {i} < {arg}.size; ++{i}) {{",
+                    u64=self.c_u64_type(),
+                    i=i,
+                    arg=argument,
+                ));
+                ctx.body.add(format!("{}.data[{i}] = {}.data[{i}];", c_output_pointer, argument, i=i));
+                ctx.body.add("}");
 
                 // for LLVM
                 /*
@@ -345,6 +440,13 @@ impl BuilderExpressionGen for CGenerator {
                 */
                 Ok(())
             }
+            TopK(_, _) => {
+                let methods = self.topks.get_mut(nb.kind).unwrap();
+                let new = methods.c_gen_new(&mut self.intrinsics, ctx.c_get_run())?;
+                ctx.body.add(format!("{} = {};", c_output_pointer, new));
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
         }
     }
 
@@ -358,7 +460,9 @@ impl BuilderExpressionGen for CGenerator {
         // let builder_pointer = ctx.get_value(m.builder)?;
         let c_builder_pointer = ctx.c_get_value(m.builder)?;
         match *m.kind {
-            Appender(ref ty) => {
+            Appender(_) => {
+                // The merge value is a SIMD value in vectorized loops.
+                let merge_type = ctx.sir_function.symbol_type(m.value)?;
                 let methods = self.appenders.get_mut(m.kind).unwrap();
                 ctx.body.add(format!(
                     "{};",
@@ -367,7 +471,7 @@ impl BuilderExpressionGen for CGenerator {
                         ctx.c_get_run(),
                         &c_builder_pointer,
                         &ctx.c_get_value(m.value)?,
-                        ty,
+                        merge_type,
                         try_no_resize,    // VE-Weld NO_RESIZE
                     )?,
                 ));
@@ -376,8 +480,39 @@ impl BuilderExpressionGen for CGenerator {
             }
             DictMerger(ref key, ref val, ref binop) => {
                 // for C
-                ctx.body.add(
-                    "#error Merge for DictMerger is not implemented yet");
+                use super::hash::GenHash;
+                // Build the default value that we upsert if the key is not present in the
+                // dictionary yet.
+                let default = self.c_binop_identity_value(*binop, val)?;
+                // The type of the merge value is {key, value}.
+                let merge_value = ctx.c_get_value(m.value)?;
+                let key_pointer = format!("&{}.f0", merge_value);
+                let hash = self.c_gen_hash(key, &key_pointer)?;
+
+                let dict_type = &Dict(key.clone(), val.clone());
+                let slot = {
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    methods.c_gen_upsert(
+                        &mut self.intrinsics,
+                        &c_builder_pointer,
+                        &key_pointer,
+                        &hash,
+                        &default,
+                        ctx.c_get_run(),
+                    )?
+                };
+                let c_slot_ty = self.dictionaries[dict_type].c_slot_type().to_string();
+                let slot_var = ctx.var_ids.next();
+                let merge = self.c_merge_values(
+                    val,
+                    *binop,
+                    &format!("{}->value", slot_var),
+                    &format!("{}.f1", merge_value),
+                )?;
+                ctx.body.add("{");
+                ctx.body.add(format!("{}* {} = {};", c_slot_ty, slot_var, slot));
+                ctx.body.add(merge);
+                ctx.body.add("}");
 
                 // for LLVM
                 /*
@@ -457,8 +592,25 @@ impl BuilderExpressionGen for CGenerator {
             }
             GroupMerger(ref key, ref value) => {
                 // for C
-                ctx.body.add(
-                    "#error Merge for GroupMerger is not implemented yet");
+                use super::dict::GroupingDict;
+                use super::hash::GenHash;
+                // The merge value is a {K, V} struct.
+                let merge_value = ctx.c_get_value(m.value)?;
+                let key_pointer = format!("&{}.f0", merge_value);
+                let hash = self.c_gen_hash(key, &key_pointer)?;
+
+                let dict_type = &Dict(key.clone(), Box::new(Vector(value.clone())));
+                let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                let merge = methods.c_gen_merge_grouped(
+                    &mut self.intrinsics,
+                    self.vectors.get_mut(value).unwrap(),
+                    &c_builder_pointer,
+                    &key_pointer,
+                    &hash,
+                    &format!("{}.f1", merge_value),
+                    ctx.c_get_run(),
+                )?;
+                ctx.body.add(format!("{};", merge));
 
                 // for LLVM
                 /*
@@ -489,20 +641,36 @@ impl BuilderExpressionGen for CGenerator {
                 */
                 Ok(())
             }
-            Merger(ref ty, _) => {
+            Merger(_, _) => {
+                let merge_type = ctx.sir_function.symbol_type(m.value)?;
                 let c_merge_value = ctx.c_get_value(m.value)?;
                 let methods = self.mergers.get_mut(m.kind).unwrap();
-                let merge = methods.c_gen_merge(ctx.builder, &c_builder_pointer, &c_merge_value, ty)?;
+                let merge = methods.c_gen_merge(ctx.builder, &c_builder_pointer, &c_merge_value, merge_type)?;
                 ctx.body.add(format!(
                     "{};",
                     merge,
                 ));
                 Ok(())
             }
+            TopK(_, _) => {
+                let c_merge_value = ctx.c_get_value(m.value)?;
+                let methods = self.topks.get_mut(m.kind).unwrap();
+                let merge = methods.c_gen_merge(&c_builder_pointer, &c_merge_value)?;
+                ctx.body.add(format!("{};", merge));
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
             VecMerger(ref elem, ref binop) => {
                 // for C
-                ctx.body.add(
-                    "#error Merge for VecMerger is not implemented yet");
+                // The type of the merge value is {index, value}.
+                let merge_value = ctx.c_get_value(m.value)?;
+                let merge = self.c_merge_values(
+                    elem,
+                    *binop,
+                    &format!("{}.data[{}.f0]", c_builder_pointer, merge_value),
+                    &format!("{}.f1", merge_value),
+                )?;
+                ctx.body.add(merge);
 
                 // for LLVM
                 /*
@@ -580,8 +748,9 @@ impl BuilderExpressionGen for CGenerator {
             }
             DictMerger(_, _, _) | GroupMerger(_, _) => {
                 // for C
-                ctx.body.add(
-                    "#error Res for DictMerger/GroupMerger is not implemented yet");
+                // A dictmerger just updates a dictionary in-place, so return the produced
+                // dictionary.
+                ctx.body.add(format!("{} = {};", c_output_pointer, c_builder_pointer));
 
                 // for LLVM
                 // A dictmerger just updates a dictionary in-place, so return the produced
@@ -616,8 +785,8 @@ impl BuilderExpressionGen for CGenerator {
             }
             VecMerger(_, _) => {
                 // for C
-                ctx.body.add(
-                    "#error Res for VecMerger is not implemented yet");
+                // VecMergers update a vector in place, so just return the produced vector.
+                ctx.body.add(format!("{} = {};", c_output_pointer, c_builder_pointer));
 
                 // for LLVM
                 // VecMergers update a vector in place, so just return the produced vector.
@@ -627,6 +796,17 @@ impl BuilderExpressionGen for CGenerator {
                 */
                 Ok(())
             }
+            TopK(ref elem_type, _) => {
+                let vector = &Vector(elem_type.clone());
+                let c_vector_type = &self.c_type(vector)?.to_string();
+                let result = {
+                    let methods = self.topks.get_mut(m.kind).unwrap();
+                    methods.c_gen_result(c_vector_type, &c_builder_pointer)?
+                };
+                ctx.body.add(format!("{} = {};", c_output_pointer, result));
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
         }
     }
 
@@ -700,6 +880,12 @@ impl BuilderExpressionGen for CGenerator {
                     let vec_type = &Vector(elem.clone());
                     self.llvm_type(vec_type)
                 }
+                TopK(_, _) => {
+                    // The topk is only defined along with its C type.
+                    self.c_builder_type(builder)?;
+                    Ok(self.topks[kind].topk_ty)
+                }
+                SetMerger(_) | SketchMerger(_, _) => unreachable!(),
             }
         } else {
             unreachable!()
@@ -762,6 +948,27 @@ impl BuilderExpressionGen for CGenerator {
                     let vec_type = &Vector(elem.clone());
                     self.c_type(vec_type)
                 }
+                TopK(ref elem_type, k) => {
+                    use super::cmp::GenCmp;
+                    if !self.topks.contains_key(kind) {
+                        let name = format!("topk{}", self.topk_index);
+                        self.topk_index += 1;
+                        let c_elem_type = self.c_type(elem_type)?.to_string();
+                        let c_cmp = self.c_gen_cmp_fn(elem_type)?;
+                        let topk = topk::TopK::define(
+                            name,
+                            c_elem_type,
+                            k,
+                            c_cmp,
+                            self.context,
+                            self.module,
+                            self.ccontext,
+                        );
+                        self.topks.insert(kind.clone(), topk);
+                    }
+                    Ok(self.topks[kind].name.clone())
+                }
+                SetMerger(_) | SketchMerger(_, _) => unreachable!(),
             }
         } else {
             unreachable!()
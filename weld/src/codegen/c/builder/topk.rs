@@ -0,0 +1,344 @@
+//! Code generation for the topk builder type.
+//!
+//! A topk builder keeps the `k` largest values merged into it in a binary min-heap, so the
+//! smallest kept value is always at the root. The heap is stored in a buffer of capacity `k`
+//! that is allocated when the builder is created. Values are ordered using the default
+//! comparator of the element type. The result sorts the heap in place into descending order.
+
+use llvm_sys;
+
+use std::ffi::CString;
+use code_builder::CodeBuilder;
+
+use crate::error::*;
+
+use self::llvm_sys::core::*;
+use self::llvm_sys::prelude::*;
+
+use crate::codegen::c::intrinsic::Intrinsics;
+use crate::codegen::c::CodeGenExt;
+use crate::codegen::c::CContextRef;
+use crate::codegen::c::c_u64_type;
+
+pub struct TopK {
+    pub topk_ty: LLVMTypeRef,
+    pub c_elem_ty: String,
+    pub name: String,
+    /// The maximum number of values kept.
+    pub k: i64,
+    /// The name of the comparator function over pointers to elements.
+    c_cmp: String,
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    ccontext: CContextRef,
+    c_new: String,
+    c_merge: String,
+    c_sift_down: String,
+    c_result: String,
+    c_combine: String,
+}
+
+impl CodeGenExt for TopK {
+    fn module(&self) -> LLVMModuleRef {
+        self.module
+    }
+
+    fn context(&self) -> LLVMContextRef {
+        self.context
+    }
+
+    fn ccontext(&self) -> CContextRef {
+        self.ccontext
+    }
+}
+
+impl TopK {
+    pub unsafe fn define<T: AsRef<str>>(
+        name: T,
+        c_elem_ty: String,
+        k: i64,
+        c_cmp: String,
+        context: LLVMContextRef,
+        module: LLVMModuleRef,
+        ccontext: CContextRef,
+    ) -> TopK {
+        // for C
+        let mut def = CodeBuilder::new();
+        def.add("typedef struct {");
+        def.add(format!("{elem_ty}* data;", elem_ty=c_elem_ty));
+        def.add(format!("{u64} size;", u64=c_u64_type(ccontext)));
+        def.add(format!("}} {};", name.as_ref()));
+        (*ccontext).prelude_code.add(def.result());
+        // for LLVM
+        let c_name = CString::new(name.as_ref()).unwrap();
+        let topk = LLVMStructCreateNamed(context, c_name.as_ptr());
+        TopK {
+            topk_ty: topk,
+            c_elem_ty,
+            name: c_name.into_string().unwrap(),
+            k,
+            c_cmp,
+            context,
+            module,
+            ccontext,
+            c_new: String::new(),
+            c_merge: String::new(),
+            c_sift_down: String::new(),
+            c_result: String::new(),
+            c_combine: String::new(),
+        }
+    }
+
+    /// Define code for a new topk builder.
+    unsafe fn define_new(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+    ) {
+        let c_arg_tys = [self.c_run_handle_type()];
+        let c_ret_ty = &self.name.clone();
+
+        // Use C name.
+        let name = format!("{}_new", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        c_code.add("{");
+        c_code.add(format!("{} ret;", self.name));
+        c_code.add(format!(
+            "ret.data = {};",
+            intrinsics.c_call_weld_run_malloc(
+                &self.c_get_run(),
+                &format!("{k} * {elem_size}",
+                         k=self.k,
+                         elem_size=self.c_size_of(&self.c_elem_ty),
+                ),
+            ),
+        ));
+        c_code.add("ret.size = 0;");
+        c_code.add("return ret;");
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_new = name;
+    }
+
+    /// Generates code for a new topk builder.
+    pub unsafe fn c_gen_new(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        run: &str,
+    ) -> WeldResult<String> {
+        if self.c_new.is_empty() {
+            self.define_new(intrinsics);
+        }
+        Ok(format!("{}({})", self.c_new, run))
+    }
+
+    /// Defines a function that places the value at a pointer into the heap, starting at the root.
+    ///
+    /// The function has the signature `void (T* data, u64 size, T* value)`. The root of the heap
+    /// is treated as a hole, so its previous value is overwritten.
+    unsafe fn define_sift_down(&mut self) {
+        let c_arg_tys = [
+            self.c_pointer_type(&self.c_elem_ty),
+            self.c_u64_type(),
+            self.c_pointer_type(&self.c_elem_ty),
+        ];
+        let c_ret_ty = &self.c_void_type();
+
+        // Use C name.
+        let name = format!("{}_sift_down", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        // for C
+        let data = self.c_get_param(0);
+        let size = self.c_get_param(1);
+        let value = self.c_get_param(2);
+        c_code.add("{");
+        c_code.add(format!("{} hole = 0;", self.c_u64_type()));
+        c_code.add("while (1) {");
+        c_code.add(format!("{} child = 2 * hole + 1;", self.c_u64_type()));
+        c_code.add(format!("if (child >= {}) break;", size));
+        // Move to the smaller of the two children.
+        c_code.add(format!(
+            "if (child + 1 < {size} && {cmp}(&{data}[child + 1], &{data}[child]) < 0) child++;",
+            size=size,
+            cmp=self.c_cmp,
+            data=data,
+        ));
+        c_code.add(format!(
+            "if ({cmp}(&{data}[child], {value}) >= 0) break;",
+            cmp=self.c_cmp,
+            data=data,
+            value=value,
+        ));
+        c_code.add(format!("{data}[hole] = {data}[child];", data=data));
+        c_code.add("hole = child;");
+        c_code.add("}");
+        c_code.add(format!("{}[hole] = *{};", data, value));
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_sift_down = name;
+    }
+
+    /// Defines a function that merges a value into a topk builder.
+    ///
+    /// While the heap holds fewer than `k` values, the value is added to it. Afterwards, the value
+    /// replaces the root of the heap if it is larger than the root.
+    unsafe fn define_merge(&mut self) {
+        if self.c_sift_down.is_empty() {
+            self.define_sift_down();
+        }
+
+        let c_arg_tys = [self.c_pointer_type(&self.name), self.c_elem_ty.clone()];
+        let c_ret_ty = &self.c_void_type();
+
+        // Use C name.
+        let name = format!("{}_merge", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), true);
+
+        // for C
+        let topk = self.c_get_param(0);
+        let value = self.c_get_param(1);
+        c_code.add("{");
+        c_code.add(format!("if ({}->size < {}) {{", topk, self.k));
+        // Add the value as a leaf and sift it up.
+        c_code.add(format!("{} hole = {}->size++;", self.c_u64_type(), topk));
+        c_code.add("while (hole > 0) {");
+        c_code.add(format!("{} parent = (hole - 1) / 2;", self.c_u64_type()));
+        c_code.add(format!(
+            "if ({cmp}(&{value}, &{topk}->data[parent]) >= 0) break;",
+            cmp=self.c_cmp,
+            value=value,
+            topk=topk,
+        ));
+        c_code.add(format!("{topk}->data[hole] = {topk}->data[parent];", topk=topk));
+        c_code.add("hole = parent;");
+        c_code.add("}");
+        c_code.add(format!("{}->data[hole] = {};", topk, value));
+        c_code.add(format!(
+            "}} else if ({cmp}({topk}->data, &{value}) < 0) {{",
+            cmp=self.c_cmp,
+            topk=topk,
+            value=value,
+        ));
+        c_code.add(format!(
+            "{sift_down}({topk}->data, {topk}->size, &{value});",
+            sift_down=self.c_sift_down,
+            topk=topk,
+            value=value,
+        ));
+        c_code.add("}");
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_merge = name;
+    }
+
+    /// Generates code to merge a value into a topk builder.
+    pub unsafe fn c_gen_merge(
+        &mut self,
+        builder_arg: &str,
+        value_arg: &str,
+    ) -> WeldResult<String> {
+        if self.c_merge.is_empty() {
+            self.define_merge();
+        }
+        Ok(format!("{}(&{}, {})", self.c_merge, builder_arg, value_arg))
+    }
+
+    /// Defines a function that merges the values of one topk builder into another.
+    ///
+    /// This is used to merge the thread-local builders of a parallel loop.
+    unsafe fn define_combine(&mut self) {
+        if self.c_merge.is_empty() {
+            self.define_merge();
+        }
+
+        let c_arg_tys = [self.c_pointer_type(&self.name), self.name.clone()];
+        let c_ret_ty = &self.c_void_type();
+
+        // Use C name.
+        let name = format!("{}_combine", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        // for C
+        let topk = self.c_get_param(0);
+        let other = self.c_get_param(1);
+        c_code.add("{");
+        c_code.add(format!(
+            "for ({u64} i = 0; i < {other}.size; ++i) {{",
+            u64=self.c_u64_type(),
+            other=other,
+        ));
+        c_code.add(format!("{}({}, {}.data[i]);", self.c_merge, topk, other));
+        c_code.add("}");
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_combine = name;
+    }
+
+    /// Generates code to merge the values of the topk builder `other` into `builder_arg`.
+    pub unsafe fn c_gen_combine(
+        &mut self,
+        builder_arg: &str,
+        other: &str,
+    ) -> WeldResult<String> {
+        if self.c_combine.is_empty() {
+            self.define_combine();
+        }
+        Ok(format!("{}(&{}, {})", self.c_combine, builder_arg, other))
+    }
+
+    /// Defines a function that gets the result from a topk builder.
+    ///
+    /// The result is a vector of the kept values in descending order. It reuses the heap's
+    /// buffer, which is sorted in place by repeatedly moving the root to the end of the heap.
+    unsafe fn define_result(
+        &mut self,
+        c_vector_ty: &str,
+    ) {
+        if self.c_sift_down.is_empty() {
+            self.define_sift_down();
+        }
+
+        let c_arg_tys = [self.c_pointer_type(&self.name)];
+        let c_ret_ty = &c_vector_ty;
+
+        // Use C name.
+        let name = format!("{}_result", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        // for C
+        let topk = self.c_get_param(0);
+        c_code.add("{");
+        c_code.add(format!("{} ret;", c_vector_ty));
+        c_code.add(format!(
+            "for ({u64} end = {topk}->size; end > 1; ) {{",
+            u64=self.c_u64_type(),
+            topk=topk,
+        ));
+        c_code.add("--end;");
+        c_code.add(format!("{} last = {}->data[end];", self.c_elem_ty, topk));
+        c_code.add(format!("{topk}->data[end] = {topk}->data[0];", topk=topk));
+        c_code.add(format!("{}({}->data, end, &last);", self.c_sift_down, topk));
+        c_code.add("}");
+        c_code.add(format!("ret.data = {}->data;", topk));
+        c_code.add(format!("ret.size = {}->size;", topk));
+        c_code.add("return ret;");
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_result = name;
+    }
+
+    /// Generates code to get the result from a topk builder.
+    ///
+    /// The topk's result is a vector.
+    pub unsafe fn c_gen_result(
+        &mut self,
+        c_vector_ty: &str,
+        builder_arg: &str,
+    ) -> WeldResult<String> {
+        if self.c_result.is_empty() {
+            self.define_result(c_vector_ty);
+        }
+        Ok(format!("{}(&{})", self.c_result, builder_arg))
+    }
+}
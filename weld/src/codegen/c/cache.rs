@@ -0,0 +1,113 @@
+//!
+//! This module manages an on-disk cache of shared objects built from generated C code.
+//!
+//! Entries are content-addressed: the key combines the symbol-agnostic hash of the optimized
+//! program with a fingerprint of everything that affects the compiled artifact, i.e., the
+//! generated C code, the compiler, its flags and libraries, the backend and the code generation
+//! options.
+
+use fnv;
+
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use crate::conf::ParsedConf;
+use crate::error::*;
+use crate::util::dump::unique_filename;
+
+/// A directory of cached shared objects.
+pub struct ArtifactCache {
+    directory: PathBuf,
+}
+
+impl ArtifactCache {
+    /// Returns the cache configured in `conf`, or `None` if caching is disabled.
+    pub fn from_conf(conf: &ParsedConf) -> Option<ArtifactCache> {
+        if conf.compile_cache.enabled {
+            Some(ArtifactCache {
+                directory: PathBuf::from(&conf.compile_cache.directory),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the path under which the artifact for `key` is cached.
+    fn path(&self, key: &str) -> PathBuf {
+        let mut path = self.directory.clone();
+        path.push(format!("lib{}.so", key));
+        path
+    }
+
+    /// Returns the path of the cached artifact for `key`, if it exists.
+    pub fn lookup(&self, key: &str) -> Option<String> {
+        let path = self.path(key);
+        if path.is_file() {
+            path.to_str().map(|s| s.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Moves the freshly built `shared_object` into the cache under `key`.
+    ///
+    /// Returns the path of the cached artifact. The object is first copied to a unique
+    /// temporary name and then renamed, so concurrent compilers never observe a partially
+    /// written file.
+    pub fn insert(&self, key: &str, shared_object: &str) -> WeldResult<String> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.path(key);
+        let mut tmp = self.directory.clone();
+        tmp.push(format!(".{}-{}.tmp", key, unique_filename()));
+        fs::copy(shared_object, &tmp)?;
+        fs::rename(&tmp, &path)?;
+        let _ = fs::remove_file(shared_object);
+        Ok(path.to_str().unwrap().to_string())
+    }
+}
+
+/// Computes the cache key of the C `code` of a program, compiled with `compiler` and `cflags` and
+/// linked with `libs`.
+///
+/// The code is hashed as well as the program, since settings that are not part of `conf`, such
+/// as environment variables, can change the code generated for the same program.
+pub fn cache_key(
+    program_hash: u64,
+    code: &str,
+    compiler: &str,
+    cflags: &str,
+    libs: &str,
+    conf: &ParsedConf,
+) -> String {
+    use crate::util::env::get_veweld_cflags;
+
+    let mut hasher = fnv::FnvHasher::default();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    code.hash(&mut hasher);
+    compiler.hash(&mut hasher);
+    cflags.hash(&mut hasher);
+    libs.hash(&mut hasher);
+    get_veweld_cflags().hash(&mut hasher);
+    conf.backend.hash(&mut hasher);
+    conf.threads.hash(&mut hasher);
+    conf.vector_length.hash(&mut hasher);
+    conf.trace_run.hash(&mut hasher);
+    conf.enable_bounds_checks.hash(&mut hasher);
+    conf.llvm.run_func_name.hash(&mut hasher);
+    format!("weld-{:016x}-{:016x}", program_hash, hasher.finish())
+}
+
+#[test]
+fn cache_key_covers_code_and_flags() {
+    let conf = ParsedConf::default();
+    let key = cache_key(1, "int x;", "cc", "-O2", "-lm", &conf);
+    assert_eq!(key, cache_key(1, "int x;", "cc", "-O2", "-lm", &conf));
+    assert_ne!(key, cache_key(1, "long x;", "cc", "-O2", "-lm", &conf));
+    assert_ne!(key, cache_key(1, "int x;", "ncc", "-O2", "-lm", &conf));
+    assert_ne!(
+        key,
+        cache_key(1, "int x;", "cc", "-O2 -fopenmp", "-lm", &conf)
+    );
+    assert_ne!(key, cache_key(1, "int x;", "cc", "-O2", "", &conf));
+}
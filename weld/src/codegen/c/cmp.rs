@@ -4,6 +4,8 @@ use llvm_sys;
 
 use std::ffi::CStr;
 
+use code_builder::CodeBuilder;
+
 use crate::ast::BinOpKind::*;
 use crate::ast::ScalarKind::I64;
 use crate::ast::Type;
@@ -76,6 +78,12 @@ pub trait GenCmp {
         cf_id: FunctionId,
         cmpfunc: LLVMValueRef,
     ) -> WeldResult<LLVMValueRef>;
+
+    /// Generates a C comparator for a type.
+    ///
+    /// Like the LLVM version, the comparator is over pointers of the type, e.g., `i32 (i32*, i32*)`.
+    /// This method returns the name of the generated function.
+    unsafe fn c_gen_cmp_fn(&mut self, ty: &Type) -> WeldResult<String>;
 }
 
 impl GenCmp for CGenerator {
@@ -356,7 +364,9 @@ impl GenCmp for CGenerator {
 
                 LLVMBuildRet(builder, result);
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
 
         LLVMDisposeBuilder(builder);
@@ -365,6 +375,64 @@ impl GenCmp for CGenerator {
         Ok(function)
     }
 
+    unsafe fn c_gen_cmp_fn(&mut self, ty: &Type) -> WeldResult<String> {
+        use crate::ast::Type::*;
+        if let Some(name) = self.c_cmp_fns.get(ty) {
+            return Ok(name.clone());
+        }
+
+        let c_ty = &self.c_type(ty)?;
+
+        // Define comparators of the element types first, since this function calls them.
+        let mut body = CodeBuilder::new();
+        match *ty {
+            Scalar(_) => {
+                body.add("return (*p0 > *p1) - (*p0 < *p1);");
+            }
+            Struct(ref elems) => {
+                body.add(format!("{} result;", self.c_i32_type()));
+                for (i, elem) in elems.iter().enumerate() {
+                    let func = self.c_gen_cmp_fn(elem)?;
+                    body.add(format!("result = {}(&p0->f{i}, &p1->f{i});", func, i=i));
+                    body.add("if (result != 0) {");
+                    body.add("return result;");
+                    body.add("}");
+                }
+                body.add("return 0;");
+            }
+            Vector(ref elem) => {
+                // Compare elements up to the shorter length: if all of them are equal, the
+                // shorter vector is the lesser one.
+                let func = self.c_gen_cmp_fn(elem)?;
+                let u64 = self.c_u64_type();
+                body.add(format!(
+                    "{u64} size = p0->size < p1->size ? p0->size : p1->size;",
+                    u64=u64,
+                ));
+                body.add(format!("for ({} i = 0; i < size; ++i) {{", u64));
+                body.add(format!("{} result = {}(&p0->data[i], &p1->data[i]);", self.c_i32_type(), func));
+                body.add("if (result != 0) {");
+                body.add("return result;");
+                body.add("}");
+                body.add("}");
+                body.add("return (p0->size > p1->size) - (p0->size < p1->size);");
+            }
+            _ => return compile_err!("Unsupported comparison type {}", ty),
+        }
+
+        let name = format!("{}_cmp", c_ty);
+        let c_arg_tys = [self.c_pointer_type(c_ty), self.c_pointer_type(c_ty)];
+        let c_ret_ty = &self.c_i32_type();
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, &name, false);
+        c_code.add("{");
+        c_code.add(body.result());
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+
+        self.c_cmp_fns.insert(ty.clone(), name.clone());
+        Ok(name)
+    }
+
     unsafe fn gen_custom_cmp(
         &mut self,
         elem_ty: LLVMTypeRef,
@@ -6,24 +6,28 @@ use libc;
 use llvm_sys;
 use time;
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::ptr;
-use std::sync::{Once, ONCE_INIT};
-use std::process::Command;
-use std::fs::OpenOptions;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::env;
+use std::process::{Command, Output};
+use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
 
-use libc::c_char;
+use regex::Regex;
+use uuid::Uuid;
+
+use libc::{c_char, c_void};
 
 use self::time::PreciseTime;
 
-use crate::conf::ParsedConf;
+use crate::conf::{Backend, ParsedConf, VEConfig};
 use crate::error::*;
 use crate::ast::Type;
 use crate::util::stats::CompilationStats;
-use crate::util::dump::unique_filename;
+use crate::util::veoffload::{OffloadTransport, TransferModel};
 
 use self::llvm_sys::core::*;
 use self::llvm_sys::execution_engine::*;
@@ -33,13 +37,19 @@ use self::llvm_sys::target_machine::*;
 
 use crate::codegen::c::intrinsic;
 use crate::codegen::c::llvm_exts::*;
+use crate::codegen::c::marshal::Layout;
+use crate::codegen::c::partition::Partition;
+use crate::codegen::c::target::VectorTarget;
 
 static ONCE: Once = ONCE_INIT;
 static ONCE_VEO: Once = ONCE_INIT;
 static mut INITIALIZE_FAILED: bool = false;
 
 /// The callable function type.
-type I64Func = extern "C" fn(i64) -> i64;
+pub type I64Func = extern "C" fn(i64) -> i64;
+
+/// The type of `weld_runst_release` in the generated code.
+pub type ReleaseFunc = extern "C" fn(i64);
 
 /// A compiled, runnable LLVM module.
 pub struct CompiledModule {
@@ -47,10 +57,22 @@ pub struct CompiledModule {
     module: LLVMModuleRef,
     // engine: LLVMExecutionEngineRef,
     // for C
+    pub offload: *mut dyn OffloadTransport,
+    /// Devices of all configured VE nodes, starting with `offload`.
+    pub pool: Vec<*mut dyn OffloadTransport>,
+    /// How to split runs across `pool`, if the program can be split.
+    pub partition: Option<Partition>,
     pub filename: String,
+    pub work_dir: WorkDir,
     pub encoded_params: String,
     pub params: Type,
     pub ret_ty: Type,
+    /// Options for offloading to a VE.
+    pub ve: VEConfig,
+    /// Measured cost of sending parameters, used by the adaptive serialize policy.
+    pub transfer_model: Mutex<TransferModel>,
+    /// Layout of the C types in the generated code.
+    pub layout: Layout,
 }
 
 // Runnable implementation is moved out to run.rs
@@ -76,34 +98,62 @@ pub unsafe fn init() {
     }
 }
 
-unsafe fn init_veo() {
+unsafe fn init_offload(conf: &ParsedConf) -> WeldResult<Vec<*mut dyn OffloadTransport>> {
+    use crate::util::fakeve::*;
     use crate::util::veoffload::*;
     use libc::atexit;
 
+    let offload = get_offload_pool(conf.backend, &conf.ve.pool())?;
     ONCE_VEO.call_once(|| {
-        // without veorun and dynamic lib
-        initialize_veo_global(None, None);
-
-        let ret = atexit(finalize_veo_global);
-        if ret as isize != 0 {
+        if atexit(finalize_veo_global) as isize != 0 ||
+            atexit(finalize_fake_ve_global) as isize != 0 {
             panic!("fail to add veo finalization at exit");
         }
     });
+    Ok(offload)
+}
+
+/// A directory holding the files generated for a single module.
+///
+/// The directory and everything in it are removed when this value is dropped, so it should
+/// live as long as the module that loaded the shared object built in it.
+pub struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    /// Creates a new, uniquely named work directory under the system temporary directory.
+    pub fn new() -> WeldResult<WorkDir> {
+        let mut path = env::temp_dir();
+        path.push(format!("weld-{}", Uuid::new_v4().to_simple()));
+        fs::create_dir_all(&path)?;
+        debug!("Created work directory {}", path.display());
+        Ok(WorkDir { path })
+    }
+
+    /// Returns the path of the file `name` in this directory.
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.path) {
+            warn!("Could not remove work directory {}: {}", self.path.display(), err);
+        }
+    }
 }
 
 pub fn write_code(
-    code: String,
+    code: &str,
+    work_dir: &WorkDir,
 ) -> WeldResult<String> {
-    let path = &mut PathBuf::new();
-    path.push(".");
-    path.push(&format!("gen-{}", unique_filename()));
-    path.set_extension("c");
-
-    let filename = path.to_str().unwrap().to_string();
+    let filename = work_dir.file("gen.c");
     info!("Writing code to {}", filename);
 
     let mut options = OpenOptions::new();
-    let mut file = options.write(true).create_new(true).open(path)?;
+    let mut file = options.write(true).create_new(true).open(&filename)?;
 
     file.write_all(code.as_bytes())?;
 
@@ -113,54 +163,46 @@ pub fn write_code(
 /// Compile a constructed module in the given LLVM context.
 pub unsafe fn compile(
     code: String,
+    program_hash: u64,
     params: Type,
     ret_ty: Type,
     context: LLVMContextRef,
     module: LLVMModuleRef,
     _mappings: &[intrinsic::Mapping],
-    _conf: &ParsedConf,
+    partition: Option<Partition>,
+    conf: &ParsedConf,
     stats: &mut CompilationStats,
 ) -> WeldResult<CompiledModule> {
 
     let start = PreciseTime::now();
-    init_veo();
+    let pool = init_offload(conf)?;
     let end = PreciseTime::now();
     stats
         .llvm_times
         .push(("VE Offload initialization".to_string(), start.to(end)));
 
-    // Write code to a file
-    let filename = write_code(code)?;
-
     // Compile it using CC
     use crate::util::env::{get_cc,get_cflags,get_home};
-    let compiler = get_cc();
-    let cflags = get_cflags();
-    let home = get_home();
-    if home.is_empty() {
-        error!("WELD_HOME is not defined");
-    }
-    let shared_object = format!("libverun-{}.so", unique_filename());
+    use crate::util::env::{get_host_cc,get_host_cflags};
+    let (compiler, cflags, libs) = if conf.backend == Backend::CFakeVE {
+        // Kernels of the fake VE run in this process.
+        (get_host_cc(), get_host_cflags(), "-lm".to_string())
+    } else {
+        let home = get_home();
+        if home.is_empty() {
+            error!("WELD_HOME is not defined");
+        }
+        let libs = format!(concat!("-L{home}/weld_rt/cpp ",
+                                   "-Wl,-rpath,{home}/weld_rt/cpp ",
+                                   "-lpthread -ldl"),
+                           home=home);
+        (get_cc(), get_cflags(), libs)
+    };
 
     // Execute C compiler
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(&format!(concat!("{compiler} {cflags} {shared} -o {out} {file} ",
-                              "-L{home}/weld_rt/cpp ",
-                              "-Wl,-rpath,{home}/weld_rt/cpp ",
-                              "{lib}"),
-                      compiler=compiler,
-                      cflags=cflags,
-                      shared="-shared -fpic",
-                      out=shared_object,
-                      file=filename,
-                      home=home,
-                      lib="-lpthread -ldl"))
-        .output()
-        .expect("failed to execute process");
-    println!("status: \n{}", output.status);
-    println!("stdout: \n{}", String::from_utf8_lossy(&output.stdout));
-    println!("stderr: \n{}", String::from_utf8_lossy(&output.stderr));
+    let work_dir = WorkDir::new()?;
+    let shared_object = build_shared_object(
+        code, program_hash, &compiler, &cflags, &libs, &work_dir, conf, stats)?;
 
     init();
 
@@ -181,10 +223,268 @@ pub unsafe fn compile(
         context,
         module,
         // engine,
+        offload: pool[0],
+        pool,
+        partition,
         filename: shared_object,
+        work_dir,
         encoded_params: "".to_string(),
         params,
         ret_ty,
+        ve: conf.ve.clone(),
+        transfer_model: Mutex::new(TransferModel::default()),
+        layout: Layout::new(VectorTarget::from_conf(conf).width),
+    };
+    Ok(result)
+}
+
+/// Builds `code` into a shared object and returns its path.
+///
+/// Generated files are placed in `work_dir`. If the artifact cache is enabled, a cached shared
+/// object for the same program and compiler settings is reused instead of running the compiler.
+fn build_shared_object(
+    code: String,
+    program_hash: u64,
+    compiler: &str,
+    cflags: &str,
+    libs: &str,
+    work_dir: &WorkDir,
+    conf: &ParsedConf,
+    stats: &mut CompilationStats,
+) -> WeldResult<String> {
+    use crate::codegen::c::cache::{cache_key, ArtifactCache};
+
+    let cflags = &thread_cflags(cflags, conf);
+    let cache = ArtifactCache::from_conf(conf);
+    let key = cache_key(program_hash, &code, compiler, cflags, libs, conf);
+    if let Some(ref cache) = cache {
+        if let Some(path) = cache.lookup(&key) {
+            info!("Reusing cached artifact {}", path);
+            return Ok(path);
+        }
+    }
+
+    // Write code to a file
+    let filename = write_code(&code, work_dir)?;
+
+    let start = PreciseTime::now();
+    let shared_object = work_dir.file("libweldrun.so");
+    let output = run_compiler(compiler, cflags, libs, &filename, &shared_object)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return compile_err!("C compiler '{}' failed with {}:\n{}",
+                            compiler,
+                            output.status,
+                            map_diagnostics(&stderr, &filename, &code));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        debug!("C compiler output:\n{}", stderr);
+    }
+    let end = PreciseTime::now();
+    stats
+        .llvm_times
+        .push(("C Compilation".to_string(), start.to(end)));
+
+    match cache {
+        Some(ref cache) => cache.insert(&key, &shared_object),
+        None => Ok(shared_object),
+    }
+}
+
+/// Returns `cflags` extended with the flags that multi-threaded programs need.
+///
+/// Code generated for more than one thread uses OpenMP, which both the host compiler and `ncc`
+/// enable with `-fopenmp`.
+fn thread_cflags(cflags: &str, conf: &ParsedConf) -> String {
+    if conf.threads > 1 {
+        format!("{} -fopenmp", cflags)
+    } else {
+        cflags.to_string()
+    }
+}
+
+/// Builds a shared object from the C source `filename` using `compiler`.
+fn run_compiler(
+    compiler: &str,
+    cflags: &str,
+    libs: &str,
+    filename: &str,
+    shared_object: &str,
+) -> WeldResult<Output> {
+    let command = format!("{compiler} {cflags} {shared} -o {out} {file} {libs}",
+                          compiler=compiler,
+                          cflags=cflags,
+                          shared="-shared -fpic",
+                          out=shared_object,
+                          file=filename,
+                          libs=libs);
+    debug!("Running {}", command);
+    match Command::new("sh").arg("-c").arg(&command).output() {
+        Ok(output) => Ok(output),
+        Err(err) => compile_err!("Could not run C compiler '{}': {}", compiler, err),
+    }
+}
+
+/// Returns, for each line of generated `code`, the ID of the SIR function it belongs to.
+///
+/// SIR functions are emitted as `f<id>` and their loop bodies as `f<id>_loop*`. Lines outside
+/// of such functions (e.g., the prelude or runtime helpers) map to `None`.
+fn sir_function_of_lines(code: &str) -> Vec<Option<usize>> {
+    lazy_static! {
+        static ref SIR_FUNC_RE: Regex = Regex::new(r"\bf(\d+)(_loop(_[a-z_]+)?)?\(").unwrap();
+    }
+    let lines: Vec<&str> = code.lines().collect();
+    let mut result = Vec::with_capacity(lines.len());
+    let mut current = None;
+    for (i, line) in lines.iter().enumerate() {
+        // A function definition is a signature followed by an opening brace.
+        let is_definition = !line.trim_end().ends_with(';') &&
+            lines.get(i + 1).map(|l| l.trim() == "{").unwrap_or(false);
+        if is_definition && !line.starts_with(' ') {
+            current = SIR_FUNC_RE.captures(line)
+                .and_then(|c| c.at(1))
+                .and_then(|id| id.parse().ok());
+        }
+        result.push(current);
+    }
+    result
+}
+
+/// Annotates compiler diagnostics for `filename` with the SIR function and source line they
+/// refer to.
+fn map_diagnostics(stderr: &str, filename: &str, code: &str) -> String {
+    let functions = sir_function_of_lines(code);
+    let code_lines: Vec<&str> = code.lines().collect();
+    let mut result = String::new();
+    for line in stderr.lines() {
+        result.push_str(line);
+        result.push('\n');
+        if !line.starts_with(filename) {
+            continue;
+        }
+        // Diagnostics have the form `<file>:<line>:<column>: <message>`.
+        let lineno = line[filename.len()..]
+            .split(':')
+            .nth(1)
+            .and_then(|l| l.trim().parse::<usize>().ok());
+        if let Some(lineno) = lineno {
+            if lineno == 0 || lineno > code_lines.len() {
+                continue;
+            }
+            match functions[lineno - 1] {
+                Some(id) => result.push_str(&format!("  in SIR function F{}:", id)),
+                None => result.push_str("  in generated runtime code:"),
+            }
+            result.push_str(&format!(" {}\n", code_lines[lineno - 1].trim()));
+        }
+    }
+    result
+}
+
+/// A C module compiled for and loaded into the host process.
+///
+/// This is used to run the code generated by the C backend without a VE.
+pub struct HostCompiledModule {
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    /// The loaded library, which runs whose memory is still in use keep open.
+    pub library: Arc<HostLibrary>,
+    pub run: I64Func,
+    /// Frees a run of the generated code and all memory it allocated.
+    pub release: ReleaseFunc,
+    pub filename: String,
+    pub work_dir: WorkDir,
+}
+
+// The loaded library is never modified after it is opened.
+unsafe impl Send for HostCompiledModule {}
+unsafe impl Sync for HostCompiledModule {}
+
+impl Drop for HostCompiledModule {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMContextDispose(self.context);
+        }
+    }
+}
+
+/// A shared library loaded into the host process, which is closed when it is dropped.
+#[derive(Debug)]
+pub struct HostLibrary {
+    handle: *mut c_void,
+}
+
+// The loaded library is never modified after it is opened.
+unsafe impl Send for HostLibrary {}
+unsafe impl Sync for HostLibrary {}
+
+impl Drop for HostLibrary {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+/// Returns the last error reported by the dynamic linker.
+unsafe fn dlerror_string() -> String {
+    let err = libc::dlerror();
+    if err.is_null() {
+        "unknown error".to_string()
+    } else {
+        CStr::from_ptr(err).to_string_lossy().into_owned()
+    }
+}
+
+/// Compile the generated C code with the host compiler and load it into this process.
+pub unsafe fn compile_host(
+    code: String,
+    program_hash: u64,
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    conf: &ParsedConf,
+    stats: &mut CompilationStats,
+) -> WeldResult<HostCompiledModule> {
+    use crate::util::env::{get_host_cc,get_host_cflags};
+
+    let work_dir = WorkDir::new()?;
+    let shared_object = build_shared_object(
+        code, program_hash, &get_host_cc(), &get_host_cflags(), "-lm",
+        &work_dir, conf, stats)?;
+
+    init();
+
+    let start = PreciseTime::now();
+    let c_libname = CString::new(shared_object.as_str()).unwrap();
+    let handle = libc::dlopen(c_libname.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+    if handle.is_null() {
+        return compile_err!("Couldn't load library {}: {}", shared_object, dlerror_string());
+    }
+    let mut functions = vec![];
+    for name in [conf.llvm.run_func_name.as_str(), "weld_runst_release"].iter() {
+        let c_name = CString::new(*name).unwrap();
+        let function = libc::dlsym(handle, c_name.as_ptr());
+        if function.is_null() {
+            let err = dlerror_string();
+            libc::dlclose(handle);
+            return compile_err!("Couldn't find function {}: {}", name, err);
+        }
+        functions.push(function);
+    }
+    let end = PreciseTime::now();
+    stats
+        .llvm_times
+        .push(("Find Run Func Address".to_string(), start.to(end)));
+
+    let result = HostCompiledModule {
+        context,
+        module,
+        library: Arc::new(HostLibrary { handle }),
+        run: mem::transmute::<*mut c_void, I64Func>(functions[0]),
+        release: mem::transmute::<*mut c_void, ReleaseFunc>(functions[1]),
+        filename: shared_object,
+        work_dir,
     };
     Ok(result)
 }
@@ -278,3 +578,68 @@ unsafe fn verify_module(module: LLVMModuleRef) -> WeldResult<()> {
     libc::free(error_str as *mut libc::c_void);
     result
 }
+
+#[test]
+fn map_diagnostics_to_sir_functions() {
+    let code = "\
+typedef long i64;
+i64 f0(i64 a, void* run);
+static inline i64 f1_loop_resize(i64 a, void* run)
+{
+    return a + b;
+}
+i64 f0(i64 a, void* run)
+{
+    return f1_loop_resize(a, run);
+}
+";
+    let stderr = "\
+/tmp/gen.c: In function 'f1_loop_resize':
+/tmp/gen.c:5:16: error: 'b' undeclared (first use in this function)
+/tmp/gen.c:1:1: note: something in the prelude
+";
+    let result = map_diagnostics(stderr, "/tmp/gen.c", code);
+    assert!(result.contains("in SIR function F1: return a + b;"));
+    assert!(result.contains("in generated runtime code: typedef long i64;"));
+    assert!(!result.contains("F0"));
+}
+
+#[test]
+fn map_diagnostics_of_generated_code() {
+    use crate::sir::ast_to_sir;
+    use crate::tests::typed_expression;
+
+    let program = ast_to_sir(&typed_expression(
+        "|v: vec[i32]| result(for(v, appender[i32], |b, i, e| merge(b, e + 1)))",
+    ))
+    .unwrap();
+    let code = unsafe { super::CGenerator::generate(ParsedConf::default(), &program) }
+        .unwrap()
+        .gen_c_code();
+
+    // Report an error on the first statement of each generated loop function.
+    let lines: Vec<&str> = code.lines().collect();
+    let loop_functions: Vec<usize> = (0..lines.len() - 1)
+        .filter(|&i| lines[i].contains("_loop") && lines[i + 1].trim() == "{")
+        .collect();
+    assert!(!loop_functions.is_empty());
+    for i in loop_functions {
+        let stderr = format!("/tmp/gen.c:{}:1: error: something is wrong\n", i + 3);
+        let result = map_diagnostics(&stderr, "/tmp/gen.c", &code);
+        assert!(
+            result.contains("in SIR function F"),
+            "no SIR function for '{}'",
+            lines[i]
+        );
+    }
+}
+
+#[test]
+fn work_dir_removed_on_drop() {
+    let work_dir = WorkDir::new().unwrap();
+    let filename = write_code("int x;\n", &work_dir).unwrap();
+    assert!(std::path::Path::new(&filename).is_file());
+    let path = work_dir.path.clone();
+    drop(work_dir);
+    assert!(!path.exists());
+}
@@ -15,6 +15,7 @@
 use llvm_sys;
 
 use std::ffi::CString;
+use code_builder::CodeBuilder;
 
 use crate::error::*;
 
@@ -27,6 +28,7 @@ use crate::codegen::c::intrinsic::Intrinsics;
 use crate::codegen::c::llvm_exts::LLVMExtAttribute::*;
 use crate::codegen::c::llvm_exts::*;
 use crate::codegen::c::CContextRef;
+use crate::codegen::c::{c_i32_type, c_i64_type, c_u8_type};
 
 // Need vector type for ToVec and Serialize.
 use crate::codegen::c::vector;
@@ -95,6 +97,18 @@ pub struct Dict {
 
     // For grouping
     merge_grouped: Option<LLVMValueRef>, // DONE
+
+    // C versions of the methods above.
+    c_key_eq: String,
+    c_slot_for_key: String,
+    c_new: String,
+    c_lookup: String,
+    c_opt_lookup: String,
+    c_upsert: String,
+    c_resize: String,
+    c_key_exists: String,
+    c_to_vec: String,
+    c_merge_grouped: String,
 }
 
 /// Extensions for grouping dictionaries (i.e., the GroupMerger).
@@ -120,6 +134,20 @@ pub trait GroupingDict {
         value: LLVMValueRef,
         run: LLVMValueRef,
     ) -> WeldResult<LLVMValueRef>;
+
+    /// Generates C code to merge `value` into the group for `key` with the given `hash`.
+    ///
+    /// `key` is an expression for a pointer to the key.
+    unsafe fn c_gen_merge_grouped(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        group_vector: &mut Vector,
+        dict: &str,
+        key: &str,
+        hash: &str,
+        value: &str,
+        run: &str,
+    ) -> WeldResult<String>;
 }
 
 impl CodeGenExt for Dict {
@@ -316,6 +344,7 @@ impl Dict {
         key_ty: LLVMTypeRef,
         c_key_ty: &str,
         key_comparator: LLVMValueRef,
+        c_key_eq: &str,
         val_ty: LLVMTypeRef,
         c_val_ty: &str,
         context: LLVMContextRef,
@@ -324,7 +353,7 @@ impl Dict {
     ) -> Dict {
         let c_name = CString::new(name.as_ref()).unwrap();
         let slot_ty = SlotType::new(
-            format!("{}.slot", name.as_ref()),
+            format!("{}_slot", name.as_ref()),
             key_ty,
             c_key_ty.to_string(),
             val_ty,
@@ -356,6 +385,23 @@ impl Dict {
 
         let name = c_name.into_string().unwrap();
 
+        // for C
+        // Like in LLVM, the external view of a dictionary is a pointer to its inner struct.
+        let mut def = CodeBuilder::new();
+        def.add("typedef struct {");
+        def.add(format!("{} key;", c_key_ty));
+        def.add(format!("{} value;", c_val_ty));
+        def.add(format!("{} hash;", c_i32_type(ccontext)));
+        def.add(format!("{} filled;", c_u8_type(ccontext)));
+        def.add(format!("}} {};", slot_ty.c_slot_ty));
+        def.add("typedef struct {");
+        def.add(format!("{}* slots;", slot_ty.c_slot_ty));
+        def.add(format!("{} capacity;", c_i64_type(ccontext)));
+        def.add(format!("{} size;", c_i64_type(ccontext)));
+        def.add(format!("}} {}_inner;", name));
+        def.add(format!("typedef {name}_inner* {name};", name=name));
+        (*ccontext).prelude_code.add(def.result());
+
         Dict {
             name,
             dict_ty: LLVMPointerType(dict_inner_ty, 0),
@@ -374,6 +420,16 @@ impl Dict {
             key_exists: None,
             to_vec: None,
             merge_grouped: None,
+            c_key_eq: c_key_eq.to_string(),
+            c_slot_for_key: String::new(),
+            c_new: String::new(),
+            c_lookup: String::new(),
+            c_opt_lookup: String::new(),
+            c_upsert: String::new(),
+            c_resize: String::new(),
+            c_key_exists: String::new(),
+            c_to_vec: String::new(),
+            c_merge_grouped: String::new(),
         }
     }
 
@@ -1883,4 +1939,431 @@ impl GroupingDict for Dict {
             c_str!(""),
         ))
     }
+
+    unsafe fn c_gen_merge_grouped(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        group_vector: &mut Vector,
+        dict: &str,
+        key: &str,
+        hash: &str,
+        value: &str,
+        run: &str,
+    ) -> WeldResult<String> {
+        if self.c_merge_grouped.is_empty() {
+            let c_arg_tys = [
+                self.name.clone(),
+                self.c_pointer_type(&self.slot_ty.c_key_ty),
+                self.c_hash_type(),
+                group_vector.c_elem_ty.clone(),
+                self.c_run_handle_type(),
+            ];
+            let c_ret_ty = &self.c_void_type();
+            let name = format!("{}_merge_grouped", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+            // Unlike the LLVM version, the group capacity is not stored in the filled byte. It is
+            // always max(DEFAULT_GROUP_CAPACITY, next power of two of the size), so the vector is
+            // grown when a full power-of-two sized group receives another element.
+            let elem_size = self.c_size_of(&group_vector.c_elem_ty);
+            let upsert = self.c_gen_upsert(
+                intrinsics,
+                "p0",
+                "p1",
+                "p2",
+                "empty",
+                "run",
+            )?;
+            c_code.add("{");
+            c_code.add(format!("{} empty;", group_vector.name));
+            c_code.add("empty.data = 0;");
+            c_code.add("empty.size = 0;");
+            c_code.add(format!(
+                "{vec}* group = &{upsert}->value;",
+                vec=group_vector.name,
+                upsert=upsert,
+            ));
+            c_code.add(format!("{} size = group->size;", self.c_u64_type()));
+            c_code.add("if (size == 0) {");
+            c_code.add(format!(
+                "group->data = {};",
+                intrinsics.c_call_weld_run_malloc(
+                    "run",
+                    &format!("{} * {}", DEFAULT_GROUP_CAPACITY, elem_size),
+                ),
+            ));
+            c_code.add(format!(
+                "}} else if (size >= {} && (size & (size - 1)) == 0) {{",
+                DEFAULT_GROUP_CAPACITY,
+            ));
+            c_code.add(format!(
+                "group->data = {};",
+                intrinsics.c_call_weld_run_realloc(
+                    "run",
+                    "group->data",
+                    &format!("2 * size * {}", elem_size),
+                ),
+            ));
+            c_code.add("}");
+            c_code.add("group->data[size] = p3;");
+            c_code.add("group->size = size + 1;");
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_merge_grouped = name;
+        }
+        Ok(format!(
+            "{}({}, {}, {}, {}, {})",
+            self.c_merge_grouped, dict, key, hash, value, run,
+        ))
+    }
+}
+
+/// C code generation for dictionaries.
+///
+/// The C methods mirror the LLVM ones above: they take a pointer to the key along with its hash,
+/// and return pointers to slots where the LLVM methods do.
+impl Dict {
+    /// Returns the name of the C slot type.
+    pub fn c_slot_type(&self) -> &str {
+        &self.slot_ty.c_slot_ty
+    }
+
+    /// Defines the C function that returns the slot for a key in a slot array.
+    ///
+    /// The returned slot is either filled with the key or is the empty slot where the key should
+    /// be inserted.
+    unsafe fn c_define_slot_for_key(&mut self) {
+        let c_slot_ty = self.slot_ty.c_slot_ty.clone();
+        let c_arg_tys = [
+            self.c_pointer_type(&c_slot_ty),
+            self.c_i64_type(),
+            self.c_hash_type(),
+            self.c_pointer_type(&self.slot_ty.c_key_ty),
+        ];
+        let c_ret_ty = &self.c_pointer_type(&c_slot_ty);
+        let name = format!("{}_slot_for_key", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), true);
+
+        // The load factor is always below one, so the probe terminates at an empty slot.
+        c_code.add("{");
+        c_code.add(format!("{} mask = p1 - 1;", self.c_i64_type()));
+        c_code.add(format!("{} i = p2 & mask;", self.c_i64_type()));
+        c_code.add("for (;;) {");
+        c_code.add(format!("{}* slot = &p0[i];", c_slot_ty));
+        c_code.add("if (!slot->filled) {");
+        c_code.add("return slot;");
+        c_code.add("}");
+        c_code.add(format!(
+            "if (slot->hash == p2 && {}(&slot->key, p3)) {{",
+            self.c_key_eq,
+        ));
+        c_code.add("return slot;");
+        c_code.add("}");
+        c_code.add("i = (i + 1) & mask;");
+        c_code.add("}");
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_slot_for_key = name;
+    }
+
+    /// Returns an expression for the slot of `key` in the dictionary `dict`.
+    unsafe fn c_gen_slot_for_key(&mut self, dict: &str, key: &str, hash: &str) -> String {
+        if self.c_slot_for_key.is_empty() {
+            self.c_define_slot_for_key();
+        }
+        format!(
+            "{}({d}->slots, {d}->capacity, {}, {})",
+            self.c_slot_for_key,
+            hash,
+            key,
+            d=dict,
+        )
+    }
+
+    /// Generates code to allocate a slot array of the given capacity with every slot empty.
+    unsafe fn c_gen_new_slots(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        c_code: &mut CodeBuilder,
+        slots: &str,
+        capacity: &str,
+        run: &str,
+    ) {
+        c_code.add(format!(
+            "{} = ({}*){};",
+            slots,
+            self.slot_ty.c_slot_ty,
+            intrinsics.c_call_weld_run_malloc(
+                run,
+                &format!("{} * {}", capacity, self.c_size_of(&self.slot_ty.c_slot_ty)),
+            ),
+        ));
+        c_code.add(format!("for ({i64} i = 0; i < {}; ++i) {{", capacity, i64=self.c_i64_type()));
+        c_code.add(format!("{}[i].filled = 0;", slots));
+        c_code.add("}");
+    }
+
+    /// Generates C code to create a new dictionary.
+    pub unsafe fn c_gen_new(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        capacity: &str,
+        run: &str,
+    ) -> WeldResult<String> {
+        if self.c_new.is_empty() {
+            let c_arg_tys = [self.c_i64_type(), self.c_run_handle_type()];
+            let c_ret_ty = &self.name.clone();
+            let name = format!("{}_new", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+            c_code.add("{");
+            // Use a minimum initial capacity.
+            c_code.add(format!(
+                "{i64} capacity = p0 < {cap} ? {cap} : p0;",
+                i64=self.c_i64_type(),
+                cap=INITIAL_CAPACITY,
+            ));
+            c_code.add(format!(
+                "{name} dict = ({name}){malloc};",
+                name=self.name,
+                malloc=intrinsics.c_call_weld_run_malloc(
+                    "run",
+                    &self.c_size_of(&format!("{}_inner", self.name)),
+                ),
+            ));
+            self.c_gen_new_slots(intrinsics, &mut c_code, "dict->slots", "capacity", "run");
+            c_code.add("dict->capacity = capacity;");
+            c_code.add("dict->size = 0;");
+            c_code.add("return dict;");
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_new = name;
+        }
+        Ok(format!("{}({}, {})", self.c_new, capacity, run))
+    }
+
+    /// Defines the C function that doubles the capacity of a dictionary if it is too full.
+    unsafe fn c_define_resize(&mut self, intrinsics: &mut Intrinsics) {
+        let c_slot_ty = self.slot_ty.c_slot_ty.clone();
+        let c_arg_tys = [self.name.clone(), self.c_run_handle_type()];
+        let c_ret_ty = &self.c_void_type();
+        let name = format!("{}_resize", self.name);
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+        let slot_for_key = self.c_gen_slot_for_key("p0", "&old[i].key", "old[i].hash");
+        c_code.add("{");
+        c_code.add(format!(
+            "if (p0->size * 10 < p0->capacity * {}) {{",
+            MAX_LOAD_FACTOR,
+        ));
+        c_code.add("return;");
+        c_code.add("}");
+        c_code.add(format!("{}* old = p0->slots;", c_slot_ty));
+        c_code.add(format!("{} old_capacity = p0->capacity;", self.c_i64_type()));
+        c_code.add("p0->capacity = old_capacity * 2;");
+        self.c_gen_new_slots(intrinsics, &mut c_code, "p0->slots", "p0->capacity", "run");
+        c_code.add(format!("for ({} i = 0; i < old_capacity; ++i) {{", self.c_i64_type()));
+        c_code.add("if (old[i].filled) {");
+        c_code.add(format!("*{} = old[i];", slot_for_key));
+        c_code.add("}");
+        c_code.add("}");
+        c_code.add(format!("{};", intrinsics.c_call_weld_run_free("run", "old")));
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+        self.c_resize = name;
+    }
+
+    /// Generates C code that returns the slot for a key, inserting `default` if the key is absent.
+    ///
+    /// The insertion may resize the dictionary, so the returned slot pointer is only valid until
+    /// the next insertion.
+    pub unsafe fn c_gen_upsert(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        dict: &str,
+        key: &str,
+        hash: &str,
+        default: &str,
+        run: &str,
+    ) -> WeldResult<String> {
+        if self.c_upsert.is_empty() {
+            if self.c_resize.is_empty() {
+                self.c_define_resize(intrinsics);
+            }
+            let c_slot_ty = self.slot_ty.c_slot_ty.clone();
+            let c_arg_tys = [
+                self.name.clone(),
+                self.c_pointer_type(&self.slot_ty.c_key_ty),
+                self.c_hash_type(),
+                self.slot_ty.c_val_ty.clone(),
+                self.c_run_handle_type(),
+            ];
+            let c_ret_ty = &self.c_pointer_type(&c_slot_ty);
+            let name = format!("{}_upsert", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+            let slot_for_key = self.c_gen_slot_for_key("p0", "p1", "p2");
+            c_code.add("{");
+            c_code.add(format!("{}* slot = {};", c_slot_ty, slot_for_key));
+            c_code.add("if (slot->filled) {");
+            c_code.add("return slot;");
+            c_code.add("}");
+            c_code.add("slot->key = *p1;");
+            c_code.add("slot->value = p3;");
+            c_code.add("slot->hash = p2;");
+            c_code.add("slot->filled = 1;");
+            c_code.add("p0->size++;");
+            c_code.add(format!("{}(p0, run);", self.c_resize));
+            // The dictionary may have been resized, so reacquire the slot.
+            c_code.add(format!("return {};", slot_for_key));
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_upsert = name;
+        }
+        Ok(format!("{}({}, {}, {}, {}, {})", self.c_upsert, dict, key, hash, default, run))
+    }
+
+    /// Generates C code that returns the slot for a key, which may be unfilled.
+    pub unsafe fn c_gen_opt_lookup(
+        &mut self,
+        dict: &str,
+        key: &str,
+        hash: &str,
+    ) -> WeldResult<String> {
+        if self.c_opt_lookup.is_empty() {
+            let c_slot_ty = self.slot_ty.c_slot_ty.clone();
+            let c_arg_tys = [
+                self.name.clone(),
+                self.c_pointer_type(&self.slot_ty.c_key_ty),
+                self.c_hash_type(),
+            ];
+            let c_ret_ty = &self.c_pointer_type(&c_slot_ty);
+            let name = format!("{}_optlookup", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), true);
+
+            let slot_for_key = self.c_gen_slot_for_key("p0", "p1", "p2");
+            c_code.add("{");
+            c_code.add(format!("return {};", slot_for_key));
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_opt_lookup = name;
+        }
+        Ok(format!("{}({}, {}, {})", self.c_opt_lookup, dict, key, hash))
+    }
+
+    /// Generates C code that returns the slot for a key.
+    ///
+    /// If the key does not exist, raises a KeyNotFoundError.
+    pub unsafe fn c_gen_lookup(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        dict: &str,
+        key: &str,
+        hash: &str,
+        run: &str,
+    ) -> WeldResult<String> {
+        use crate::runtime::WeldRuntimeErrno::KeyNotFoundError;
+
+        if self.c_lookup.is_empty() {
+            let c_slot_ty = self.slot_ty.c_slot_ty.clone();
+            let c_arg_tys = [
+                self.name.clone(),
+                self.c_pointer_type(&self.slot_ty.c_key_ty),
+                self.c_hash_type(),
+                self.c_run_handle_type(),
+            ];
+            let c_ret_ty = &self.c_pointer_type(&c_slot_ty);
+            let name = format!("{}_lookup", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+            let slot_for_key = self.c_gen_slot_for_key("p0", "p1", "p2");
+            c_code.add("{");
+            c_code.add(format!("{}* slot = {};", c_slot_ty, slot_for_key));
+            c_code.add("if (!slot->filled) {");
+            c_code.add(format!(
+                "{};",
+                intrinsics.c_call_weld_run_raise("run", KeyNotFoundError, "key not found"),
+            ));
+            c_code.add("}");
+            c_code.add("return slot;");
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_lookup = name;
+        }
+        Ok(format!("{}({}, {}, {}, {})", self.c_lookup, dict, key, hash, run))
+    }
+
+    /// Generates C code that returns whether a key exists.
+    pub unsafe fn c_gen_key_exists(
+        &mut self,
+        dict: &str,
+        key: &str,
+        hash: &str,
+    ) -> WeldResult<String> {
+        if self.c_key_exists.is_empty() {
+            let c_arg_tys = [
+                self.name.clone(),
+                self.c_pointer_type(&self.slot_ty.c_key_ty),
+                self.c_hash_type(),
+            ];
+            let c_ret_ty = &self.c_bool_type();
+            let name = format!("{}_keyexists", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), true);
+
+            let slot_for_key = self.c_gen_slot_for_key("p0", "p1", "p2");
+            c_code.add("{");
+            c_code.add(format!("return {}->filled != 0;", slot_for_key));
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_key_exists = name;
+        }
+        Ok(format!("{}({}, {}, {})", self.c_key_exists, dict, key, hash))
+    }
+
+    /// Generates C code that returns the number of keys in the dictionary.
+    pub unsafe fn c_gen_size(&mut self, dict: &str) -> WeldResult<String> {
+        Ok(format!("{}->size", dict))
+    }
+
+    /// Generates C code to convert this dictionary to a vector of key/value pairs.
+    pub unsafe fn c_gen_to_vec(
+        &mut self,
+        intrinsics: &mut Intrinsics,
+        kv_vector: &mut Vector,
+        dict: &str,
+        run: &str,
+    ) -> WeldResult<String> {
+        if self.c_to_vec.is_empty() {
+            let c_arg_tys = [self.name.clone(), self.c_run_handle_type()];
+            let c_ret_ty = &kv_vector.name;
+            let name = format!("{}_tovec", self.name);
+            let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, name.clone(), false);
+
+            let i64_ty = self.c_i64_type();
+            c_code.add("{");
+            c_code.add(format!("{} ret;", kv_vector.name));
+            c_code.add(format!(
+                "ret.data = ({}*){};",
+                kv_vector.c_elem_ty,
+                intrinsics.c_call_weld_run_malloc(
+                    "run",
+                    &format!("p0->size * {}", self.c_size_of(&kv_vector.c_elem_ty)),
+                ),
+            ));
+            c_code.add("ret.size = p0->size;");
+            c_code.add(format!("{} j = 0;", i64_ty));
+            c_code.add(format!("for ({} i = 0; i < p0->capacity; ++i) {{", i64_ty));
+            c_code.add("if (p0->slots[i].filled) {");
+            c_code.add("ret.data[j].f0 = p0->slots[i].key;");
+            c_code.add("ret.data[j].f1 = p0->slots[i].value;");
+            c_code.add("++j;");
+            c_code.add("}");
+            c_code.add("}");
+            c_code.add("return ret;");
+            c_code.add("}");
+            (*self.ccontext()).prelude_code.add(c_code.result());
+            self.c_to_vec = name;
+        }
+        Ok(format!("{}({}, {})", self.c_to_vec, dict, run))
+    }
 }
@@ -4,6 +4,8 @@ use llvm_sys;
 
 use std::ffi::CStr;
 
+use code_builder::CodeBuilder;
+
 use super::vector::VectorExt;
 use crate::ast::ScalarKind::{I32, I64};
 use crate::ast::Type;
@@ -35,7 +37,7 @@ impl SupportsMemCmpEq for Type {
         use crate::ast::Type::*;
         // Structs do not support memcmp because they may be padded.
         match *self {
-            Scalar(ref kind) => kind.is_integer(),
+            Scalar(ref kind) => kind.is_integer() || kind.is_temporal(),
             _ => false,
         }
     }
@@ -56,6 +58,12 @@ pub trait GenEq {
     /// arguments. These are used in, e.g., the dictionary implementation. This method returns the
     /// generated function.
     unsafe fn gen_opaque_eq_fn(&mut self, ty: &Type) -> WeldResult<LLVMValueRef>;
+
+    /// Generates a C equality function for a type.
+    ///
+    /// Like the LLVM version, the function is over pointers of the type, e.g., `i1 (i32*, i32*)`.
+    /// This method returns the name of the generated function.
+    unsafe fn c_gen_eq_fn(&mut self, ty: &Type) -> WeldResult<String>;
 }
 
 impl GenEq for CGenerator {
@@ -262,7 +270,9 @@ impl GenEq for CGenerator {
                 );
                 result
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
 
         LLVMBuildRet(builder, result);
@@ -338,4 +348,59 @@ impl GenEq for CGenerator {
         self.opaque_eq_fns.insert(ty.clone(), function);
         Ok(function)
     }
+
+    /// Generates a C equality function for a type.
+    unsafe fn c_gen_eq_fn(&mut self, ty: &Type) -> WeldResult<String> {
+        use crate::ast::Type::*;
+        if let Some(name) = self.c_eq_fns.get(ty) {
+            return Ok(name.clone());
+        }
+
+        let c_ty = &self.c_type(ty)?;
+
+        // Define equality functions of the element types first, since this function calls them.
+        let mut body = CodeBuilder::new();
+        match *ty {
+            Scalar(_) => {
+                body.add("return *p0 == *p1;");
+            }
+            Struct(ref elems) => {
+                let mut fields = vec![];
+                for (i, elem) in elems.iter().enumerate() {
+                    let func = self.c_gen_eq_fn(elem)?;
+                    fields.push(format!("{}(&p0->f{i}, &p1->f{i})", func, i=i));
+                }
+                if fields.is_empty() {
+                    body.add("return 1;");
+                } else {
+                    body.add(format!("return {};", fields.join(" && ")));
+                }
+            }
+            Vector(ref elem) => {
+                let func = self.c_gen_eq_fn(elem)?;
+                body.add("if (p0->size != p1->size) {");
+                body.add("return 0;");
+                body.add("}");
+                body.add(format!("for ({} i = 0; i < p0->size; ++i) {{", self.c_u64_type()));
+                body.add(format!("if (!{}(&p0->data[i], &p1->data[i])) {{", func));
+                body.add("return 0;");
+                body.add("}");
+                body.add("}");
+                body.add("return 1;");
+            }
+            _ => return compile_err!("Unsupported equality type {}", ty),
+        }
+
+        let name = format!("{}_eq", c_ty);
+        let c_arg_tys = [self.c_pointer_type(c_ty), self.c_pointer_type(c_ty)];
+        let c_ret_ty = &self.c_i1_type();
+        let mut c_code = self.c_define_function(c_ret_ty, &c_arg_tys, &name, true);
+        c_code.add("{");
+        c_code.add(body.result());
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+
+        self.c_eq_fns.insert(ty.clone(), name.clone());
+        Ok(name)
+    }
 }
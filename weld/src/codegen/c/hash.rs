@@ -9,6 +9,8 @@ use llvm_sys;
 
 use std::ffi::CStr;
 
+use code_builder::CodeBuilder;
+
 use crate::ast::ScalarKind::*;
 use crate::ast::Type::*;
 use crate::ast::*;
@@ -48,6 +50,13 @@ pub trait GenHash {
         value_pointer: LLVMValueRef,
         seed: Option<LLVMValueRef>,
     ) -> WeldResult<LLVMValueRef>;
+
+    /// Generates a C hash function for a type and returns a call to it.
+    ///
+    /// The C hash function has the same signature as the LLVM one, `(T*, i32) -> i32`. Since the
+    /// CRC32 intrinsics are not available on every target, it mixes values with a 64-bit
+    /// finalizer instead. `value_pointer` is an expression for a pointer to the value to hash.
+    unsafe fn c_gen_hash(&mut self, ty: &Type, value_pointer: &str) -> WeldResult<String>;
 }
 
 impl GenHash for CGenerator {
@@ -155,6 +164,98 @@ impl GenHash for CGenerator {
             c_str!(""),
         ))
     }
+
+    unsafe fn c_gen_hash(&mut self, ty: &Type, value_pointer: &str) -> WeldResult<String> {
+        let function = self.c_gen_hash_fn(ty)?;
+        Ok(format!("{}({}, ({}){}U)", function, value_pointer, self.c_hash_type(), CRC32_SEED))
+    }
+}
+
+impl CGenerator {
+    /// Generates a C hash function for `ty` and returns its name.
+    unsafe fn c_gen_hash_fn(&mut self, ty: &Type) -> WeldResult<String> {
+        if let Some(name) = self.c_hash_fns.get(ty) {
+            return Ok(name.clone());
+        }
+
+        if self.c_hash_fns.is_empty() {
+            self.c_define_hash_mix();
+        }
+
+        let c_ty = self.c_type(ty)?;
+        let c_hash_ty = self.c_hash_type();
+        let u64_ty = self.c_u64_type();
+
+        // Define hash functions of the element types first, since this function calls them.
+        let mut body = CodeBuilder::new();
+        match *ty {
+            Scalar(F32) => {
+                body.add(format!("union {{ {} f; {} u; }} v;", self.c_f32_type(), self.c_u32_type()));
+                body.add("v.f = *p0;");
+                body.add(format!("return weld_hash_mix(p1, ({})v.u);", u64_ty));
+            }
+            Scalar(F64) => {
+                body.add(format!("union {{ {} f; {} u; }} v;", self.c_f64_type(), u64_ty));
+                body.add("v.f = *p0;");
+                body.add("return weld_hash_mix(p1, v.u);");
+            }
+            Scalar(_) => {
+                body.add(format!("return weld_hash_mix(p1, ({})*p0);", u64_ty));
+            }
+            Struct(ref elems) => {
+                body.add(format!("{} hash = p1;", c_hash_ty));
+                for (i, elem) in elems.iter().enumerate() {
+                    let elem_hash = self.c_gen_hash_fn(elem)?;
+                    body.add(format!("hash = {}(&p0->f{}, hash);", elem_hash, i));
+                }
+                body.add("return hash;");
+            }
+            Vector(ref elem) => {
+                let elem_hash = self.c_gen_hash_fn(elem)?;
+                body.add(format!("{} hash = weld_hash_mix(p1, p0->size);", c_hash_ty));
+                body.add(format!("for ({} i = 0; i < p0->size; ++i) {{", u64_ty));
+                body.add(format!("hash = {}(&p0->data[i], hash);", elem_hash));
+                body.add("}");
+                body.add("return hash;");
+            }
+            _ => return compile_err!("Unsupported hash type {}", ty),
+        }
+
+        let name = format!("{}_hash", c_ty);
+        let c_arg_tys = [self.c_pointer_type(&c_ty), c_hash_ty.clone()];
+        let mut c_code = self.c_define_function(&c_hash_ty, &c_arg_tys, &name, true);
+        c_code.add("{");
+        c_code.add(body.result());
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+
+        self.c_hash_fns.insert(ty.clone(), name.clone());
+        Ok(name)
+    }
+
+    /// Defines `weld_hash_mix`, which combines a hash with a 64-bit bitstring.
+    ///
+    /// The mixing steps are the 64-bit finalizer of MurmurHash3.
+    unsafe fn c_define_hash_mix(&mut self) {
+        let u64_ty = self.c_u64_type();
+        let c_hash_ty = self.c_hash_type();
+        let c_arg_tys = [c_hash_ty.clone(), u64_ty.clone()];
+        let mut c_code = self.c_define_function(&c_hash_ty, &c_arg_tys, "weld_hash_mix", true);
+        c_code.add("{");
+        c_code.add(format!(
+            "{u64} h = p1 ^ (({u64})({u32})p0 * 0x9e3779b97f4a7c15UL);",
+            u64=u64_ty,
+            u32=self.c_u32_type(),
+        ));
+        c_code.add("h ^= h >> 33;");
+        c_code.add("h *= 0xff51afd7ed558ccdUL;");
+        c_code.add("h ^= h >> 33;");
+        c_code.add("h *= 0xc4ceb9fe1a85ec53UL;");
+        c_code.add("h ^= h >> 33;");
+        c_code.add(format!("return ({})h;", c_hash_ty));
+        c_code.add("}");
+        (*self.ccontext()).prelude_code.add(c_code.result());
+    }
 }
 
 /// A wrapper for holding hash functions for different native widths.
@@ -250,7 +351,7 @@ impl Hash for CGenerator {
         }
 
         match kind {
-            I64 | U64 | F64 => {
+            I64 | U64 | F64 | Decimal(_, _) | Timestamp | Interval => {
                 // Extend the 32-bit hash so we can pass it to the hashing function.
                 let mut hash = LLVMBuildZExt(builder, hash, self.u64_type(), c_str!(""));
                 let value = LLVMBuildBitCast(builder, value, self.u64_type(), c_str!(""));
@@ -264,7 +365,7 @@ impl Hash for CGenerator {
                 );
                 LLVMBuildTrunc(builder, hash, self.hash_type(), c_str!(""))
             }
-            I32 | U32 | F32 => {
+            I32 | U32 | F32 | Date => {
                 let value = LLVMBuildBitCast(builder, value, self.u32_type(), c_str!(""));
                 let mut args = [hash, value];
                 LLVMBuildCall(
@@ -627,7 +728,14 @@ impl Hash for CGenerator {
                 );
                 result
             }
-            Dict(_, _) | Builder(_, _) | Function(_, _) | Unknown | Alias(_, _) => {
+            Dict(_, _)
+            | Builder(_, _)
+            | Function(_, _)
+            | Optional(_)
+            | Set(_)
+            | Sketch(_, _)
+            | Unknown
+            | Alias(_, _) => {
                 return compile_err!("Unhashable type {}", ty);
             }
         };
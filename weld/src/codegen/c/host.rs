@@ -0,0 +1,108 @@
+//!
+//! This module runs a C module compiled for the host in-process.
+//!
+//! Unlike the VE path in `run.rs`, the loaded code shares the address space with the
+//! caller, so parameters and results are passed through without any conversion.
+
+use libc;
+use time;
+
+use libc::c_char;
+
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use self::time::PreciseTime;
+
+use crate::WeldError;
+use crate::codegen::{Runnable, WeldInputArgs, WeldOutputArgs};
+use crate::runtime::{WeldRuntimeContext, WeldRuntimeErrno};
+use crate::runtime::ffi::{weld_runst_init, weld_runst_malloc};
+use crate::util::stats::RunStats;
+
+use crate::codegen::c::compile::{HostCompiledModule, HostLibrary, ReleaseFunc};
+
+/// A run of the generated code, whose memory is freed when it is dropped.
+///
+/// Results of a run point into memory of its runtime state, so the host's context keeps the
+/// run until it is dropped itself.
+#[derive(Debug)]
+pub struct HostRun {
+    /// The runtime state of the generated code.
+    run: i64,
+    /// Frees the runtime state and all memory allocated by it.
+    release: ReleaseFunc,
+    /// The library of the generated code, which must stay loaded until the run is freed.
+    _library: Arc<HostLibrary>,
+}
+
+impl Drop for HostRun {
+    fn drop(&mut self) {
+        (self.release)(self.run);
+    }
+}
+
+impl Runnable for HostCompiledModule {
+    fn run(&self, arg: i64, stats: &mut RunStats) -> Result<i64, WeldError> {
+        use std::mem::size_of;
+        unsafe {
+            let input_ptr = arg as *const WeldInputArgs;
+            let nworkers = (*input_ptr).nworkers;
+            let mem_limit = (*input_ptr).mem_limit;
+            let mut run = (*input_ptr).run;
+            let context: *mut WeldRuntimeContext;
+            if run == 0 {
+                context = weld_runst_init(nworkers, mem_limit);
+                run = context as i64;
+            } else {
+                context = run as *mut WeldRuntimeContext;
+            }
+
+            // The generated code keeps its own runtime state whose layout differs from the
+            // host's `WeldRuntimeContext`, so always let it create a new one.
+            let input = WeldInputArgs {
+                input: (*input_ptr).input,
+                nworkers,
+                mem_limit,
+                run: 0,
+            };
+
+            let start = PreciseTime::now();
+            let ret_c = (self.run)(&input as *const WeldInputArgs as i64)
+                as *const WeldOutputArgs;
+            let end = PreciseTime::now();
+            stats.run_times.push(("call run".to_string(), start.to(end)));
+
+            // The output refers to memory of the generated code's runtime state, so the host's
+            // context keeps the state until it is dropped. The output of a failed run points to
+            // a message in that state, which is copied before the state is released.
+            let host_run = HostRun {
+                run: (*ret_c).run,
+                release: self.release,
+                _library: self.library.clone(),
+            };
+            let errno = (*ret_c).errno;
+            if errno != WeldRuntimeErrno::Success {
+                let message = CStr::from_ptr((*ret_c).output as *const c_char).to_string_lossy();
+                let message = if message.is_empty() {
+                    format!("Weld program failed with error {:?}", errno)
+                } else {
+                    format!("Weld program failed with error {:?}: {}", errno, message)
+                };
+                drop(host_run);
+                return Err(WeldError::new(message, errno));
+            }
+
+            // Copy the output arguments into memory owned by the host's context, which is how
+            // the caller expects to release them.
+            let output_size = size_of::<WeldOutputArgs>();
+            let ret = weld_runst_malloc(context, output_size as i64) as *mut WeldOutputArgs;
+            (*ret).output = (*ret_c).output;
+            (*ret).run = run;
+            (*ret).errno = errno;
+            (*context).add_host_run(host_run);
+
+            Ok(ret as i64)
+        }
+    }
+}
@@ -28,10 +28,14 @@ use self::llvm_sys::core::*;
 use self::llvm_sys::prelude::*;
 
 use crate::runtime::ffi;
+use crate::runtime::WeldRuntimeErrno;
 use libc::c_void;
 
 use crate::codegen::c::CContextRef;
 
+/// Size in bytes of the buffer that holds the message of an error raised by generated code.
+pub const ERROR_MESSAGE_SIZE: usize = 256;
+
 /// A single intrinsic.
 #[derive(Debug, Clone)]
 pub enum Intrinsic {
@@ -113,16 +117,19 @@ impl Intrinsics {
 
         result.push_str(match kind {
             Bool => "i1",
-            I8 => "i32",
+            I8 => "i8",
             I16 => "i16",
             I32 => "i32",
             I64 => "i64",
-            U8 => "i32",
+            U8 => "i8",
             U16 => "i16",
             U32 => "i32",
             U64 => "i64",
             F32 => "f32",
             F64 => "f64",
+            Decimal(_, _) => "i64",
+            Date => "i32",
+            Timestamp | Interval => "i64",
         });
         result
     }
@@ -363,6 +370,15 @@ impl Intrinsics {
         )
     }
 
+    pub unsafe fn c_call_weld_run_free(
+        &mut self,
+        run: &str,
+        pointer: &str,
+    ) -> String {
+        let args = [run, pointer];
+        self.c_call("weld_runst_free", &args)
+    }
+
     /// Convinience wrapper for calling the `weld_run_get_errno` intrinsic.
     pub unsafe fn call_weld_run_get_errno(
         &mut self,
@@ -413,6 +429,21 @@ impl Intrinsics {
         self.c_call("weld_runst_set_errno", &args)
     }
 
+    /// Returns C code that sets `errno` with a `message` and ends the run.
+    ///
+    /// The message must not contain quotes or backslashes.
+    pub unsafe fn c_call_weld_run_raise(
+        &mut self,
+        run: &str,
+        errno: WeldRuntimeErrno,
+        message: &str,
+    ) -> String {
+        let errno = errno.to_string();
+        let message = format!("\"{}\"", message);
+        let args = [run, errno.as_str(), message.as_str()];
+        self.c_call("weld_runst_raise", &args)
+    }
+
     /// Convinience wrapper for calling the `weld_runst_assert` intrinsic.
     pub unsafe fn call_weld_run_assert(
         &mut self,
@@ -545,15 +576,30 @@ typedef {i64} WeldRuntimeErrno;
 #define KeyNotFoundError        11
 /// An assertion evaluated to `false`.
 #define AssertionError          12
+/// An offload device failed.
+#define DeviceError             13
+/// A string could not be parsed as a number.
+#define ParseError              14
 /// Maximum errno value.
 ///
 /// All errors will have a value less than this value and greater than 0.
-#define ErrnoMax                13
+#define ErrnoMax                15
+
+/// Header of a block allocated by a run.
+///
+/// The blocks of a run are linked, so that the run can free all of them when it is released.
+typedef struct WeldAllocation {{
+    struct WeldAllocation* prev;
+    struct WeldAllocation* next;
+    /// Size of the block in bytes, without the header.
+    {u64} size;
+    /// Keeps the blocks 16-byte aligned.
+    {u64} pad;
+}} WeldAllocation;
 
 typedef struct {{
-    /// Maps pointers to allocation size in bytes.
-    // allocations: FnvHashMap<Ptr, Layout>,
-    // ...void* to layout map...
+    /// The blocks allocated by the run.
+    WeldAllocation* allocations;
     /// An error code set for the context.
     WeldRuntimeErrno errno;
     /// A result pointer set by the runtime.
@@ -564,13 +610,24 @@ typedef struct {{
     {u64} memlimit;
     /// Number of allocated bytes so far.
     ///
-    /// This will always be equal to `allocations.values().sum()`.
+    /// This will always be equal to the sum of the sizes of `allocations`.
     {u64} allocated;
+    /// Where a raised error jumps to, or NULL to abort.
+    jmp_buf* handler;
+    /// Describes the error set for the context.
+    char message[{message_size}];
 }} WeldRuntimeContext;
-typedef WeldRuntimeContext* WeldRuntimeContextRef;",
+typedef WeldRuntimeContext* WeldRuntimeContextRef;
+
+/// Where an error raised on a thread of a parallel loop jumps to, or NULL outside of one.
+static _Thread_local jmp_buf* weld_thread_handler = 0;
+
+void weld_runst_raise({run_handle} run, {i64} errno, const char* message);",
             i32=self.c_i32_type(),
             i64=self.c_i64_type(),
             u64=self.c_u64_type(),
+            run_handle=self.c_run_handle_type(),
+            message_size=ERROR_MESSAGE_SIZE,
         ));
 
         let int8p = LLVMPointerType(self.i8_type(), 0);
@@ -596,12 +653,14 @@ extern void* malloc({u64});
     WeldRuntimeContextRef run =
         (WeldRuntimeContextRef)malloc(sizeof(WeldRuntimeContext));
     assert(run != 0);
-    // run->allocations = FnvHashMap::default();
+    run->allocations = 0;
     run->errno = Success;
     run->result = 0;
     run->nworkers = nworkers;
     run->memlimit = memlimit;
     run->allocated = 0;
+    run->handler = 0;
+    run->message[0] = 0;
     return ({run_handle})run;
 }}",
             run_handle=self.c_run_handle_type(),
@@ -671,7 +730,24 @@ void* weld_runst_set_result({run_handle} run, {voidp} p)
         (*self.ccontext()).prelude_code.add(format!("\
 void* weld_runst_malloc({run_handle} run, {u64} size)
 {{
-    return malloc(size);
+    WeldRuntimeContextRef ctx = (WeldRuntimeContextRef)run;
+    WeldAllocation* a = (WeldAllocation*)malloc(sizeof(WeldAllocation) + size);
+    if (a == 0) {{
+        weld_runst_raise(run, OutOfMemory, \"out of memory\");
+    }}
+    a->size = size;
+    a->prev = 0;
+    // Workers of parallel loops allocate concurrently.
+#pragma omp critical(weld_runst_allocations)
+    {{
+        a->next = ctx->allocations;
+        if (a->next != 0) {{
+            a->next->prev = a;
+        }}
+        ctx->allocations = a;
+        ctx->allocated += size;
+    }}
+    return a + 1;
 }}",
             run_handle=self.c_run_handle_type(),
             u64=self.c_u64_type(),
@@ -726,7 +802,33 @@ pub unsafe extern "C" fn weld_runst_malloc(run: WeldRuntimeContextRef, size: int
 extern void* realloc(void*, {u64});
 void* weld_runst_realloc({run_handle} run, void* ptr, {u64} size)
 {{
-    return realloc(ptr, size);
+    if (ptr == 0) {{
+        return weld_runst_malloc(run, size);
+    }}
+    WeldRuntimeContextRef ctx = (WeldRuntimeContextRef)run;
+    WeldAllocation* a = (WeldAllocation*)ptr - 1;
+    WeldAllocation* b;
+    // The neighbours of the block are relinked before another thread can unlink them.
+#pragma omp critical(weld_runst_allocations)
+    {{
+        b = (WeldAllocation*)realloc(a, sizeof(WeldAllocation) + size);
+        if (b != 0) {{
+            if (b->prev != 0) {{
+                b->prev->next = b;
+            }} else {{
+                ctx->allocations = b;
+            }}
+            if (b->next != 0) {{
+                b->next->prev = b;
+            }}
+            ctx->allocated = ctx->allocated - b->size + size;
+            b->size = size;
+        }}
+    }}
+    if (b == 0) {{
+        weld_runst_raise(run, OutOfMemory, \"out of memory\");
+    }}
+    return b + 1;
 }}",
             run_handle=self.c_run_handle_type(),
             u64=self.c_u64_type(),
@@ -775,6 +877,44 @@ void* weld_runst_realloc({run_handle} run, void* ptr, {u64} size)
             name.into_string().unwrap(),
             Intrinsic::FunctionPointer(function, ffi::weld_runst_free as *mut c_void),
         );
+        (*self.ccontext()).prelude_code.add(format!("\
+extern void free(void*);
+void weld_runst_free({run_handle} run, void* ptr)
+{{
+    if (ptr == 0) {{
+        return;
+    }}
+    WeldRuntimeContextRef ctx = (WeldRuntimeContextRef)run;
+    WeldAllocation* a = (WeldAllocation*)ptr - 1;
+#pragma omp critical(weld_runst_allocations)
+    {{
+        if (a->prev != 0) {{
+            a->prev->next = a->next;
+        }} else {{
+            ctx->allocations = a->next;
+        }}
+        if (a->next != 0) {{
+            a->next->prev = a->prev;
+        }}
+        ctx->allocated -= a->size;
+    }}
+    free(a);
+}}
+
+/// Frees all memory allocated by a run, and the run itself.
+void weld_runst_release({run_handle} run)
+{{
+    WeldRuntimeContextRef ctx = (WeldRuntimeContextRef)run;
+    WeldAllocation* a = ctx->allocations;
+    while (a != 0) {{
+        WeldAllocation* next = a->next;
+        free(a);
+        a = next;
+    }}
+    free(ctx);
+}}",
+            run_handle=self.c_run_handle_type(),
+        ));
 
         let mut params = vec![self.run_handle_type()];
         let name = CString::new("weld_runst_get_errno").unwrap();
@@ -795,7 +935,11 @@ void* weld_runst_realloc({run_handle} run, void* ptr, {u64} size)
         (*self.ccontext()).prelude_code.add(format!("\
 {i64} weld_runst_get_errno({run_handle} run)
 {{
-    return ((WeldRuntimeContextRef)run)->errno;
+    {i64} code;
+    // Threads of a parallel loop read the error code while others may raise.
+#pragma omp atomic read
+    code = ((WeldRuntimeContextRef)run)->errno;
+    return code;
 }}",
             run_handle=self.c_run_handle_type(),
             i64=self.c_i64_type(),
@@ -817,16 +961,39 @@ void* weld_runst_realloc({run_handle} run, void* ptr, {u64} size)
             Intrinsic::FunctionPointer(function, ffi::weld_runst_set_errno as *mut c_void),
         );
         (*self.ccontext()).prelude_code.add(format!("\
-void weld_runst_set_errno({run_handle} run, {i64} errno)
+void weld_runst_raise({run_handle} run, {i64} errno, const char* message)
 {{
-    ((WeldRuntimeContextRef)run)->errno = errno;
+    WeldRuntimeContextRef ctx = (WeldRuntimeContextRef)run;
+    // Threads of a parallel loop may raise concurrently; the first error is kept.
+#pragma omp critical(weld_runst_error)
+    {{
+        if (ctx->errno == Success) {{
+            if (message != 0) {{
+                strncpy(ctx->message, message, {message_size} - 1);
+                ctx->message[{message_size} - 1] = 0;
+            }}
+#pragma omp atomic write
+            ctx->errno = errno;
+        }}
+    }}
+    // Return to the parallel loop that runs this thread, which re-raises the error on the
+    // thread that started the loop, or else to the entry function, which reports the error.
+    if (weld_thread_handler != 0) {{
+        longjmp(*weld_thread_handler, 1);
+    }}
+    if (ctx->handler != 0) {{
+        longjmp(*ctx->handler, 1);
+    }}
     abort();
-    // FIXME: need to throw termination signal to caller here.
-    //        weld for llvm call panic function in rust like below.
-    // panic!(\"Weld runtime threw error: {{}}\", self.errno)
+}}
+
+void weld_runst_set_errno({run_handle} run, {i64} errno)
+{{
+    weld_runst_raise(run, errno, 0);
 }}",
             run_handle=self.c_run_handle_type(),
             i64=self.c_i64_type(),
+            message_size=ERROR_MESSAGE_SIZE,
         ));
 
         let mut params = vec![self.run_handle_type(), self.bool_type()];
@@ -878,6 +1045,50 @@ void weld_runst_print({run_handle} run, {i8}* string)
             i8=self.c_i8_type(),
         ));
 
+        // Decimal multiplication and division need a 128-bit intermediate value. It is computed
+        // from 64-bit halves, since not every C compiler for a target has a 128-bit integer type.
+        (*self.ccontext()).prelude_code.add(format!("\
+/// Returns `a * b / d` rounded toward zero, computing `a * b` with 128 bits.
+///
+/// Like the LLVM backend, the result wraps around if it does not fit in 64 bits.
+static {i64} weld_decimal_muldiv({i64} a, {i64} b, {i64} d)
+{{
+    int negative = (a < 0) != (b < 0) != (d < 0);
+    {u64} ua = a < 0 ? -({u64})a : ({u64})a;
+    {u64} ub = b < 0 ? -({u64})b : ({u64})b;
+    {u64} ud = d < 0 ? -({u64})d : ({u64})d;
+    {u64} p00 = (ua & 0xffffffff) * (ub & 0xffffffff);
+    {u64} p01 = (ua & 0xffffffff) * (ub >> 32);
+    {u64} p10 = (ua >> 32) * (ub & 0xffffffff);
+    {u64} p11 = (ua >> 32) * (ub >> 32);
+    {u64} mid = (p00 >> 32) + (p01 & 0xffffffff) + (p10 & 0xffffffff);
+    {u64} lo = (mid << 32) | (p00 & 0xffffffff);
+    {u64} hi = p11 + (p01 >> 32) + (p10 >> 32) + (mid >> 32);
+    {u64} q = 0;
+    if (hi == 0) {{
+        q = lo / ud;
+    }} else {{
+        // Long division of the 128-bit product, one bit at a time.
+        {u64} r = 0;
+        int i;
+        for (i = 127; i >= 0; i--) {{
+            {u64} bit = i >= 64 ? (hi >> (i - 64)) & 1 : (lo >> i) & 1;
+            {u64} carry = r >> 63;
+            r = (r << 1) | bit;
+            if (carry || r >= ud) {{
+                r -= ud;
+                if (i < 64) {{
+                    q |= 1ULL << i;
+                }}
+            }}
+        }}
+    }}
+    return ({i64})(negative ? -q : q);
+}}",
+            i64=self.c_i64_type(),
+            u64=self.c_u64_type(),
+        ));
+
         let mut params = vec![
             int8p,
             int8p,
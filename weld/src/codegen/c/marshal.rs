@@ -0,0 +1,550 @@
+//! Deep copies of dictionaries and builders between host and VE memory.
+//!
+//! The generated code grows and frees dictionaries and builders with the Weld runtime, so on the
+//! VE they must live in memory owned by the run that uses them. An upload therefore packs every
+//! buffer of a value into a single staging buffer, and the `{run}_unpack` function of the module
+//! copies the buffers into memory allocated with `weld_runst_malloc` before linking them up. A
+//! download reads each buffer into host memory and fixes up the pointers on the host.
+//!
+//! The layouts mirror the C types of the backend, where `W` is the vector length:
+//!
+//! ```text
+//! vec[T]                 { T* data; u64 size; }
+//! appender[T]            { T* data; u64 size; u64 capacity; }
+//! merger[T, op]          { T data; T vdata[W]; }
+//! vecmerger[T, op]       vec[T]
+//! topk[T, k]             vec[T], with room for k elements
+//! dict[K, V]             pointer to { slot* slots; i64 capacity; i64 size; }
+//!                        where a slot is { K key; V value; i32 hash; u8 filled; }
+//! dictmerger[K, V, op]   dict[K, V]
+//! groupmerger[K, V]      dict[K, vec[V]], where each group has room for
+//!                        max(8, next power of two of its size) elements
+//! ```
+
+use libc::c_void;
+
+use std::ptr;
+use std::time::Duration;
+
+use crate::ast::BuilderKind::*;
+use crate::ast::ScalarKind;
+use crate::ast::SketchKind;
+use crate::ast::Type;
+use crate::ast::Type::*;
+use crate::util::veoffload::{OffloadTransport, TransferQueue, VeoHandle};
+use crate::WeldResult;
+
+use super::run::{alloc, scalar_kind_size};
+
+/// Size of the inner struct of a dictionary.
+const DICT_INNER_SIZE: usize = 24;
+
+/// Minimum capacity of a group in a `groupmerger` (see `dict::DEFAULT_GROUP_CAPACITY`).
+const MIN_GROUP_CAPACITY: usize = 8;
+
+/// Sizes and alignments of the C types of a module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Number of lanes of SIMD values and mergers.
+    pub vector_width: usize,
+}
+
+/// Offsets within a dictionary slot.
+struct SlotLayout {
+    key: usize,
+    value: usize,
+    filled: usize,
+    size: usize,
+}
+
+impl Layout {
+    pub fn new(vector_width: u32) -> Layout {
+        Layout {
+            vector_width: vector_width as usize,
+        }
+    }
+
+    /// Returns the size of the C representation of `ty`.
+    pub fn size_of(&self, ty: &Type) -> WeldResult<usize> {
+        Ok(self.size_align(ty)?.0)
+    }
+
+    /// Returns the size and alignment of the C representation of `ty`.
+    pub fn size_align(&self, ty: &Type) -> WeldResult<(usize, usize)> {
+        let result = match *ty {
+            Scalar(kind) => (scalar_kind_size(kind), scalar_kind_size(kind)),
+            Simd(kind) => (
+                scalar_kind_size(kind) * self.vector_width,
+                scalar_kind_size(kind),
+            ),
+            Vector(_) => (16, 8),
+            Dict(_, _) | Set(_) | Sketch(SketchKind::Quantiles, _) => (8, 8),
+            Sketch(SketchKind::HyperLogLog, _) => (16, 8),
+            Struct(ref fields) => {
+                let (_, size, align) = self.struct_layout(fields)?;
+                (size, align)
+            }
+            Builder(ref kind, _) => match *kind {
+                Appender(_) => (24, 8),
+                Merger(ref elem, _) => {
+                    let (size, align) = self.size_align(elem)?;
+                    (size * (1 + self.vector_width), align)
+                }
+                DictMerger(_, _, _) | GroupMerger(_, _) | SetMerger(_) => (8, 8),
+                SketchMerger(SketchKind::Quantiles, _) => (8, 8),
+                VecMerger(_, _) | TopK(_, _) | SketchMerger(SketchKind::HyperLogLog, _) => (16, 8),
+            },
+            _ => return weld_err!("Unsupported type {}", ty),
+        };
+        Ok(result)
+    }
+
+    /// Returns the field offsets, size and alignment of a C struct with `fields`.
+    ///
+    /// An empty struct has a one-byte dummy field in C.
+    pub fn struct_layout(&self, fields: &[Type]) -> WeldResult<(Vec<usize>, usize, usize)> {
+        if fields.is_empty() {
+            return Ok((vec![], 1, 1));
+        }
+        let mut offsets = Vec::with_capacity(fields.len());
+        let mut size = 0;
+        let mut align = 1;
+        for f in fields.iter() {
+            let (field_size, field_align) = self.size_align(f)?;
+            size = round_up(size, field_align);
+            offsets.push(size);
+            size += field_size;
+            align = align.max(field_align);
+        }
+        Ok((offsets, round_up(size, align), align))
+    }
+
+    fn slot_layout(&self, key: &Type, value: &Type) -> WeldResult<SlotLayout> {
+        let fields = [
+            key.clone(),
+            value.clone(),
+            Scalar(ScalarKind::I32),
+            Scalar(ScalarKind::U8),
+        ];
+        let (offsets, size, _) = self.struct_layout(&fields)?;
+        Ok(SlotLayout {
+            key: offsets[0],
+            value: offsets[1],
+            filled: offsets[3],
+            size,
+        })
+    }
+}
+
+/// Returns whether `ty` contains a dictionary or a builder, which must be marshalled.
+pub fn needs_marshal(ty: &Type) -> bool {
+    match *ty {
+        Dict(_, _) | Builder(_, _) => true,
+        Vector(ref elem) => needs_marshal(elem),
+        Struct(ref fields) => fields.iter().any(needs_marshal),
+        _ => false,
+    }
+}
+
+/// Returns whether the C representation of `ty` holds pointers.
+fn has_pointers(ty: &Type) -> bool {
+    match *ty {
+        Vector(_) | Dict(_, _) => true,
+        Struct(ref fields) => fields.iter().any(has_pointers),
+        Builder(ref kind, _) => match *kind {
+            Merger(ref elem, _) => has_pointers(elem),
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+/// Returns the number of elements a group of `size` elements has room for.
+fn group_capacity(size: usize) -> usize {
+    if size == 0 {
+        0
+    } else {
+        size.next_power_of_two().max(MIN_GROUP_CAPACITY)
+    }
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) / align * align
+}
+
+/// Where a pointer to a packed buffer is stored.
+#[derive(Clone, Copy)]
+pub enum Dest {
+    /// A pointer in host memory, which is patched once the VE address is known.
+    Host(*mut u8),
+    /// A pointer at an offset in a packed buffer, which is patched on the VE.
+    Buffer(usize, usize),
+}
+
+impl Dest {
+    fn offset(self, offset: usize) -> Dest {
+        match self {
+            Dest::Host(ptr) => Dest::Host(unsafe { ptr.add(offset) }),
+            Dest::Buffer(buffer, base) => Dest::Buffer(buffer, base + offset),
+        }
+    }
+}
+
+/// Packs the buffers of host values for `{run}_unpack`.
+///
+/// The staging buffer has the following layout, where buffer offsets are relative to the
+/// packed data:
+///
+/// ```text
+///  0: u64 nbuffers
+///  8: u64 nrelocations
+/// 16: { u64 offset; u64 size; } buffers[nbuffers]
+///     { u64 buffer; u64 offset; u64 target; } relocations[nrelocations]
+///     packed data
+/// ```
+///
+/// `{run}_unpack` replaces the offset of each buffer with its address in VE memory.
+pub struct Packer {
+    layout: Layout,
+    /// Contents of the buffers, each starting at an 8-byte aligned offset.
+    data: Vec<u8>,
+    /// Offset and size of each buffer in `data`.
+    buffers: Vec<(u64, u64)>,
+    /// Pointers to buffers within buffers, as (buffer, offset, target buffer).
+    relocations: Vec<(u64, u64, u64)>,
+    /// Pointers to buffers in host memory, as (pointer, target buffer).
+    patches: Vec<(*mut u8, usize)>,
+}
+
+impl Packer {
+    pub fn new(layout: Layout) -> Packer {
+        Packer {
+            layout,
+            data: Vec::new(),
+            buffers: Vec::new(),
+            relocations: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    /// Packs the buffers that the value of type `ty` at `src` points to.
+    ///
+    /// The value itself must already be copied to `dst`, whose pointers are then redirected
+    /// to the packed buffers.
+    pub unsafe fn pack(&mut self, ty: &Type, src: *const u8, dst: Dest) -> WeldResult<()> {
+        match *ty {
+            Scalar(_) | Simd(_) => Ok(()),
+            Struct(ref fields) => {
+                let (offsets, _, _) = self.layout.struct_layout(fields)?;
+                for (f, offset) in fields.iter().zip(offsets) {
+                    self.pack(f, src.add(offset), dst.offset(offset))?;
+                }
+                Ok(())
+            }
+            Vector(ref elem) | Builder(VecMerger(ref elem, _), _) => {
+                let size = *(src.add(8) as *const u64) as usize;
+                self.pack_elements(elem, src, size, size, dst)
+            }
+            Builder(Appender(ref elem), _) => {
+                let size = *(src.add(8) as *const u64) as usize;
+                let capacity = *(src.add(16) as *const u64) as usize;
+                self.pack_elements(elem, src, size, capacity, dst)
+            }
+            Builder(TopK(ref elem, k), _) => {
+                let size = *(src.add(8) as *const u64) as usize;
+                self.pack_elements(elem, src, size, k as usize, dst)
+            }
+            Builder(Merger(_, _), _) => Ok(()),
+            Dict(ref key, ref value) | Builder(DictMerger(ref key, ref value, _), _) => {
+                self.pack_dict(key, value, false, src, dst)
+            }
+            Builder(GroupMerger(ref key, ref value), _) => {
+                self.pack_dict(key, &Vector(value.clone()), true, src, dst)
+            }
+            _ => weld_err!("Unsupported type {}", ty),
+        }
+    }
+
+    /// Packs `size` elements of the array that `src` points to into a buffer with room for
+    /// `capacity` elements.
+    unsafe fn pack_elements(
+        &mut self,
+        elem: &Type,
+        src: *const u8,
+        size: usize,
+        capacity: usize,
+        dst: Dest,
+    ) -> WeldResult<()> {
+        let data = *(src as *const *const u8);
+        if data.is_null() || capacity == 0 {
+            self.point(dst, None);
+            return Ok(());
+        }
+        let elem_size = self.layout.size_of(elem)?;
+        let capacity = capacity.max(size);
+        let buffer = self.add_buffer(data, size * elem_size, capacity * elem_size);
+        self.point(dst, Some(buffer));
+        if has_pointers(elem) {
+            for i in 0..size {
+                let offset = i * elem_size;
+                self.pack(elem, data.add(offset), Dest::Buffer(buffer, offset))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Packs the dictionary that `src` points to, along with its slots.
+    ///
+    /// Values of grouping dictionaries are packed with the capacity the generated code expects.
+    unsafe fn pack_dict(
+        &mut self,
+        key: &Type,
+        value: &Type,
+        group: bool,
+        src: *const u8,
+        dst: Dest,
+    ) -> WeldResult<()> {
+        let inner = *(src as *const *const u8);
+        if inner.is_null() {
+            self.point(dst, None);
+            return Ok(());
+        }
+        let inner_buffer = self.add_buffer(inner, DICT_INNER_SIZE, DICT_INNER_SIZE);
+        self.point(dst, Some(inner_buffer));
+
+        let slot = self.layout.slot_layout(key, value)?;
+        let capacity = *(inner.add(8) as *const i64) as usize;
+        let slots = *(inner as *const *const u8);
+        let slots_size = capacity * slot.size;
+        let slots_buffer = self.add_buffer(slots, slots_size, slots_size);
+        self.point(Dest::Buffer(inner_buffer, 0), Some(slots_buffer));
+
+        if !has_pointers(key) && !has_pointers(value) {
+            return Ok(());
+        }
+        for i in 0..capacity {
+            let offset = i * slot.size;
+            let src_slot = slots.add(offset);
+            if *src_slot.add(slot.filled) == 0 {
+                continue;
+            }
+            let key_dst = Dest::Buffer(slots_buffer, offset + slot.key);
+            self.pack(key, src_slot.add(slot.key), key_dst)?;
+
+            let value_src = src_slot.add(slot.value);
+            let value_dst = Dest::Buffer(slots_buffer, offset + slot.value);
+            match (group, value) {
+                (true, &Vector(ref elem)) => {
+                    let size = *(value_src.add(8) as *const u64) as usize;
+                    self.pack_elements(elem, value_src, size, group_capacity(size), value_dst)?;
+                }
+                _ => self.pack(value, value_src, value_dst)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `len` bytes from `src` into a new zeroed buffer of `size` bytes.
+    unsafe fn add_buffer(&mut self, src: *const u8, len: usize, size: usize) -> usize {
+        let offset = round_up(self.data.len(), 8);
+        self.data.resize(offset + size, 0);
+        ptr::copy_nonoverlapping(src, self.data.as_mut_ptr().add(offset), len);
+        self.buffers.push((offset as u64, size as u64));
+        self.buffers.len() - 1
+    }
+
+    /// Makes the pointer at `dst` point to `buffer`, or null if there is none.
+    unsafe fn point(&mut self, dst: Dest, buffer: Option<usize>) {
+        match (dst, buffer) {
+            (Dest::Host(ptr), Some(buffer)) => self.patches.push((ptr, buffer)),
+            (Dest::Host(ptr), None) => *(ptr as *mut u64) = 0,
+            (Dest::Buffer(buffer, offset), Some(target)) => {
+                self.relocations
+                    .push((buffer as u64, offset as u64, target as u64))
+            }
+            (Dest::Buffer(buffer, offset), None) => {
+                let start = self.buffers[buffer].0 as usize + offset;
+                ptr::write_bytes(self.data.as_mut_ptr().add(start), 0, 8);
+            }
+        }
+    }
+
+    /// Copies the packed buffers into memory owned by `run` on the VE, and patches the host
+    /// pointers to point to them.
+    ///
+    /// `unpack` is the name of the `{run}_unpack` function in the library `libhdl`, which may
+    /// take at most `timeout`.
+    pub unsafe fn unpack(
+        self,
+        offload: &mut dyn OffloadTransport,
+        libhdl: VeoHandle,
+        unpack: &str,
+        run: i64,
+        timeout: Option<Duration>,
+    ) -> WeldResult<()> {
+        if self.buffers.is_empty() {
+            return Ok(());
+        }
+        let mut table = vec![self.buffers.len() as u64, self.relocations.len() as u64];
+        for (offset, size) in self.buffers.iter() {
+            table.push(*offset);
+            table.push(*size);
+        }
+        for (buffer, offset, target) in self.relocations.iter() {
+            table.push(*buffer);
+            table.push(*offset);
+            table.push(*target);
+        }
+        let table_size = table.len() * 8;
+
+        let staging = offload.alloc_mem(table_size + self.data.len())?;
+        offload.write_mem(table.as_ptr() as *const c_void, staging, table_size)?;
+        offload.write_mem(
+            self.data.as_ptr() as *const c_void,
+            staging + table_size as u64,
+            self.data.len(),
+        )?;
+        offload.call_and_wait(libhdl, unpack, &[run as u64, staging], timeout)?;
+
+        let mut buffers = vec![0u64; self.buffers.len() * 2];
+        offload.read_mem(
+            staging + 16,
+            buffers.as_mut_ptr() as *mut c_void,
+            buffers.len() * 8,
+        )?;
+        offload.free_mem(staging)?;
+
+        for (ptr, buffer) in self.patches.iter() {
+            *(*ptr as *mut u64) = buffers[buffer * 2];
+        }
+        Ok(())
+    }
+}
+
+/// Copies the buffers that the value of type `ty` at `addr` points to from VE memory, and
+/// redirects its pointers to the copies.
+///
+/// The value itself must already be in host memory. Copies may still be in flight in
+/// `transfers` when this returns.
+pub unsafe fn download(
+    transfers: &mut TransferQueue<'_>,
+    layout: &Layout,
+    ty: &Type,
+    addr: *mut u8,
+) -> WeldResult<()> {
+    match *ty {
+        Scalar(_) | Simd(_) | Builder(Merger(_, _), _) => Ok(()),
+        Struct(ref fields) => {
+            let (offsets, _, _) = layout.struct_layout(fields)?;
+            for (f, offset) in fields.iter().zip(offsets) {
+                download(transfers, layout, f, addr.add(offset))?;
+            }
+            Ok(())
+        }
+        Vector(ref elem) | Builder(VecMerger(ref elem, _), _) => {
+            let size = *(addr.add(8) as *const u64) as usize;
+            download_elements(transfers, layout, elem, addr, size, size)
+        }
+        Builder(Appender(ref elem), _) => {
+            let size = *(addr.add(8) as *const u64) as usize;
+            let capacity = *(addr.add(16) as *const u64) as usize;
+            download_elements(transfers, layout, elem, addr, size, capacity)
+        }
+        Builder(TopK(ref elem, k), _) => {
+            let size = *(addr.add(8) as *const u64) as usize;
+            download_elements(transfers, layout, elem, addr, size, k as usize)
+        }
+        Dict(ref key, ref value) | Builder(DictMerger(ref key, ref value, _), _) => {
+            download_dict(transfers, layout, key, value, false, addr)
+        }
+        Builder(GroupMerger(ref key, ref value), _) => {
+            download_dict(transfers, layout, key, &Vector(value.clone()), true, addr)
+        }
+        _ => weld_err!("Unsupported type {}", ty),
+    }
+}
+
+/// Copies `size` elements of the array that `addr` points to into host memory with room for
+/// `capacity` elements.
+unsafe fn download_elements(
+    transfers: &mut TransferQueue<'_>,
+    layout: &Layout,
+    elem: &Type,
+    addr: *mut u8,
+    size: usize,
+    capacity: usize,
+) -> WeldResult<()> {
+    let data = *(addr as *const u64);
+    if data == 0 {
+        return Ok(());
+    }
+    let elem_size = layout.size_of(elem)?;
+    let ptr = alloc(capacity.max(size) * elem_size);
+    *(addr as *mut u64) = ptr as u64;
+    transfers.read(data, ptr as *mut c_void, size * elem_size)?;
+    if has_pointers(elem) && size > 0 {
+        transfers.wait_all()?;
+        for i in 0..size {
+            download(transfers, layout, elem, ptr.add(i * elem_size))?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the dictionary that `addr` points to into host memory, along with its slots.
+unsafe fn download_dict(
+    transfers: &mut TransferQueue<'_>,
+    layout: &Layout,
+    key: &Type,
+    value: &Type,
+    group: bool,
+    addr: *mut u8,
+) -> WeldResult<()> {
+    let inner_addr = *(addr as *const u64);
+    if inner_addr == 0 {
+        return Ok(());
+    }
+    let inner = alloc(DICT_INNER_SIZE);
+    transfers.read(inner_addr, inner as *mut c_void, DICT_INNER_SIZE)?;
+    transfers.wait_all()?;
+    *(addr as *mut u64) = inner as u64;
+
+    let slot = layout.slot_layout(key, value)?;
+    let capacity = *(inner.add(8) as *const i64) as usize;
+    let slots = alloc(capacity * slot.size);
+    transfers.read(
+        *(inner as *const u64),
+        slots as *mut c_void,
+        capacity * slot.size,
+    )?;
+    *(inner as *mut u64) = slots as u64;
+
+    if !has_pointers(key) && !has_pointers(value) {
+        return Ok(());
+    }
+    transfers.wait_all()?;
+    for i in 0..capacity {
+        let dst_slot = slots.add(i * slot.size);
+        if *dst_slot.add(slot.filled) == 0 {
+            continue;
+        }
+        download(transfers, layout, key, dst_slot.add(slot.key))?;
+
+        let value_addr = dst_slot.add(slot.value);
+        match (group, value) {
+            (true, &Vector(ref elem)) => {
+                let size = *(value_addr.add(8) as *const u64) as usize;
+                download_elements(
+                    transfers,
+                    layout,
+                    elem,
+                    value_addr,
+                    size,
+                    group_capacity(size),
+                )?;
+            }
+            _ => download(transfers, layout, value, value_addr)?,
+        }
+    }
+    Ok(())
+}
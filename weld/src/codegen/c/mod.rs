@@ -36,8 +36,12 @@
 //!
 //! * The `compile` module manages compiling a constructed C module.
 //!
+//! * The `cache` module caches shared objects built from generated C code on disk.
+//!
 //! * The `run` module manages compiled module into a runnable executable.
 //!
+//! * The `host` module runs a module compiled for the host without a VE.
+//!
 //! The `llvm_exts` modules uses `libllvmext` to provide LLVM functionality that `llvm_sys` (and by
 //! extension, the `llvm-c` API) does not provide. It is effectively a wrapper around a few
 //! required C++ library calls.
@@ -61,7 +65,7 @@ use std::ffi::{CStr, CString};
 use std::fmt;
 use std::mem;
 
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use libc::{c_char, c_uint, c_ulonglong};
 
 use crate::conf::ParsedConf;
@@ -69,6 +73,7 @@ use crate::error::*;
 use crate::sir::*;
 use crate::ast::Type::Struct;
 use crate::util::stats::CompilationStats;
+use crate::runtime::WeldRuntimeErrno;
 use crate::util::id::IdGenerator;
 
 use self::llvm_sys::core::*;
@@ -82,8 +87,9 @@ lazy_static! {
     static ref RUN_HANDLE_NAME: CString = CString::new("RunHandle").unwrap();
 }
 
-/// Width of a SIMD vector.
-// TODO This should be based on the type!
+/// Width of an LLVM SIMD vector.
+///
+/// Generated C code uses the width of the `VectorTarget` in the `CContext` instead.
 pub const LLVM_VECTOR_WIDTH: u32 = 4;
 
 /// Calling convention for SIR function.
@@ -101,6 +107,8 @@ static PRELUDE_CODE: &'static str = "\
 #include <math.h>
 #include <float.h>
 #include <limits.h>
+#include <setjmp.h>
+#include <string.h>
 
 // Disable several common warnin messages of ncc
 #pragma diag_suppress labeled_declaration
@@ -110,21 +118,31 @@ static PRELUDE_CODE: &'static str = "\
 ";
 
 mod builder;
+mod cache;
 mod cmp;
 mod dict;
 mod eq;
 mod hash;
 mod intrinsic;
 mod compile;
+mod host;
 mod run;
 mod llvm_exts;
+mod marshal;
+mod partition;
 mod numeric;
 mod serde;
+mod strings;
 mod target;
 mod vector;
 
 use self::builder::appender;
 use self::builder::merger;
+use self::builder::topk;
+
+pub use self::run::download;
+pub(crate) use self::host::HostRun;
+pub(crate) use self::intrinsic::ERROR_MESSAGE_SIZE;
 
 /// Loads a dynamic library from a file using LLVMLoadLibraryPermanently.
 ///
@@ -153,14 +171,20 @@ pub fn size_of(ty: &Type) -> usize {
 /// calls.
 pub fn compile(
     program: &SirProgram,
+    program_hash: u64,
     conf: &ParsedConf,
     stats: &mut CompilationStats,
 ) -> WeldResult<Box<dyn Runnable + Send + Sync>> {
     use crate::runtime;
     use crate::util::dump::{write_code, DumpCodeFormat};
+    use crate::conf::Backend;
 
     info!("Compiling using single thread runtime");
-    info!("Target architecture is VE");
+    if conf.backend == Backend::CHost {
+        info!("Target architecture is host");
+    } else {
+        info!("Target architecture is VE");
+    }
 
     let codegen = unsafe { CGenerator::generate(conf.clone(), &program)? };
 
@@ -180,15 +204,36 @@ pub fn compile(
         runtime::ffi::weld_init();
     }
 
+    if conf.backend == Backend::CHost {
+        let module = unsafe {
+            compile::compile_host(
+                codegen.gen_c_code(),
+                program_hash,
+                codegen.context,
+                codegen.module,
+                conf,
+                stats,
+            )? };
+        return Ok(Box::new(module));
+    }
+
     let mappings = &codegen.intrinsics.mappings();
+    // Results that stay on a VE cannot be merged from several VEs.
+    let partition = if conf.ve.pool().len() > 1 && !conf.ve.device_results {
+        partition::find_partition(program)
+    } else {
+        None
+    };
     let module = unsafe {
         compile::compile(
             codegen.gen_c_code(),
+            program_hash,
             Struct(program.top_params.iter().map(|a| a.ty.clone()).collect()),
             program.ret_ty.clone(),
             codegen.context,
             codegen.module,
             mappings,
+            partition,
             conf,
             stats,
         )? };
@@ -322,9 +367,8 @@ typedef struct {{
 /// is that boolean types are "externally visible", whereas `i1`s only appear in internal code.
 unsafe fn c_bool_type(ccontext: CContextRef) -> String {
     use crate::ast::ScalarKind::*;
-    use crate::util::env::{get_veweld_conv_bool_to_int};
     if !(*ccontext).basic_types.contains_key(&Bool) {
-        if get_veweld_conv_bool_to_int() {
+        if (*ccontext).conv_bool_to_int {
             (*ccontext).prelude_code.add("typedef int bool;");
         } else {
             (*ccontext).prelude_code.add("typedef char bool;");
@@ -444,8 +488,76 @@ unsafe fn c_pointer_type(_ccontext: CContextRef, ty: &str) -> String {
     format!("{}*", ty)
 }
 
-unsafe fn c_simd_type(_ccontext: CContextRef, ty: &str, size: u32) -> String {
-    format!("simd_{}_{}", ty, size)
+unsafe fn c_simd_type(ccontext: CContextRef, ty: &str, size: u32) -> String {
+    let name = format!("simd_{}_{}", ty, size);
+    if !(*ccontext).simd_types.contains(&name) {
+        (*ccontext).prelude_code.add(format!(
+            "typedef struct {{ {ty} e[{size}]; }} {name};",
+            ty=ty,
+            size=size,
+            name=name,
+        ));
+        (*ccontext).simd_types.insert(name.clone());
+    }
+    name
+}
+
+unsafe fn c_lane_loop(ccontext: CContextRef, lane: &str, body: &str) -> String {
+    let target = &(*ccontext).vector_target;
+    format!("\
+        {directives}
+        for (int {lane} = 0; {lane} < {width}; ++{lane}) {{
+            {body}
+        }}",
+        directives=target.lane_loop_directives(),
+        lane=lane,
+        width=target.width,
+        body=body,
+    )
+}
+
+/// Appends the statements that select lane `lane` of the SIMD values in `ty`, a SIMD value or a
+/// struct of them, to `body`.
+///
+/// `out`, `on_true` and `on_false` are values of type `ty`, and `cond` is the condition of the
+/// lane.
+fn c_select_lanes(
+    ty: &Type,
+    out: &str,
+    cond: &str,
+    on_true: &str,
+    on_false: &str,
+    lane: &str,
+    body: &mut Vec<String>,
+) -> WeldResult<()> {
+    use crate::ast::Type::*;
+    match *ty {
+        Simd(_) => {
+            body.push(format!(
+                "{out}.e[{l}] = {c} ? {t}.e[{l}] : {f}.e[{l}];",
+                out=out,
+                c=cond,
+                t=on_true,
+                f=on_false,
+                l=lane,
+            ));
+        }
+        Struct(ref fields) => {
+            for (i, field) in fields.iter().enumerate() {
+                c_select_lanes(
+                    field,
+                    &format!("{}.f{}", out, i),
+                    cond,
+                    &format!("{}.f{}", on_true, i),
+                    &format!("{}.f{}", on_false, i),
+                    lane,
+                    body,
+                )?;
+            }
+        }
+        _ => return compile_err!("Select of SIMD structs with {} fields is not supported", ty),
+    }
+    Ok(())
 }
 
 unsafe fn c_type_of(_ccontext: CContextRef, ty: &str) -> String {
@@ -476,7 +588,9 @@ impl HasPointer for Type {
             Dict(_, _) => true,
             Builder(_, _) => true,
             Struct(ref tys) => tys.iter().any(|ref t| t.has_pointer()),
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         }
     }
 }
@@ -487,13 +601,20 @@ pub struct CContext {
     /// the name is declared.  For example, i16_type() outputs declaration of
     /// i16 and set the name to this map.
     basic_types: FnvHashMap<ScalarKind, String>,
-    simd_types: FnvHashMap<ScalarKind, String>,
+    /// Names of the defined SIMD types.
+    simd_types: FnvHashSet<String>,
+
+    /// The vector unit targeted by the generated code.
+    vector_target: target::VectorTarget,
 
     i1_defined: bool,
     input_arg_defined: bool,
     output_arg_defined: bool,
     run_handle_defined: bool,
 
+    /// Whether to represent booleans as `int` instead of `char`.
+    conv_bool_to_int: bool,
+
     /// A ID generator for prelude functions and an entry function.
     var_ids: IdGenerator,
 
@@ -540,10 +661,18 @@ pub struct CGenerator {
     appenders: FnvHashMap<BuilderKind, appender::Appender>,
     /// Counter for unique appender names.
     appender_index: u32,
+    /// A map tracking generated topk builders.
+    ///
+    /// The key maps the topk type to the topk's type reference and methods on it.
+    topks: FnvHashMap<BuilderKind, topk::TopK>,
+    /// Counter for unique topk names.
+    topk_index: u32,
     /// A map tracking generated dictionaries.
     ///
     /// The key maps the dictionary's `Dict` type to the type reference and methods on it.
     dictionaries: FnvHashMap<Type, dict::Dict>,
+    /// Counter for unique dictionary names.
+    dict_index: u32,
     /// Common intrinsics defined in the module.
     ///
     /// An intrinsic is any function defined outside of module (i.e., is not code generated).
@@ -560,6 +689,14 @@ pub struct CGenerator {
     cmp_fns: FnvHashMap<Type, LLVMValueRef>,
     /// Opaque comparison functions that contain a key function, indexed by the ID of the key function.
     hash_fns: FnvHashMap<Type, LLVMValueRef>,
+    /// Names of C equality functions on various types.
+    c_eq_fns: FnvHashMap<Type, String>,
+    /// Names of C comparison functions on various types.
+    c_cmp_fns: FnvHashMap<Type, String>,
+    /// Names of C functions implementing string operations.
+    c_string_fns: FnvHashSet<String>,
+    /// Names of C hash functions on various types.
+    c_hash_fns: FnvHashMap<Type, String>,
     /// Serialization functions on various types.
     serialize_fns: FnvHashMap<Type, LLVMValueRef>,
     /// Deserialization functions on various types.
@@ -964,6 +1101,17 @@ pub trait CodeGenExt {
                     _ => unreachable!(),
                 }
             }
+            Decimal(_, _) => {
+                match op {
+                    Add => Ok("0".to_string()),
+                    // The stored value of a decimal one is scaled.
+                    Multiply => Ok(format!("{}L", kind.scale_factor())),
+                    Max => Ok("LONG_MIN".to_string()),
+                    Min => Ok("LONG_MAX".to_string()),
+                    _ => unreachable!(),
+                }
+            }
+            Date | Timestamp | Interval => self.c_binop_identity(op, kind.storage_kind()),
         }
     }
 
@@ -1122,6 +1270,18 @@ pub trait CodeGenExt {
         c_simd_type(self.ccontext(), ty, size)
     }
 
+    /// Returns the number of lanes in a SIMD value.
+    unsafe fn c_simd_width(&self) -> u32 {
+        (*self.ccontext()).vector_target.width
+    }
+
+    /// Returns a loop that runs `body` for each lane `lane` of a SIMD value.
+    ///
+    /// The loop is annotated with the target's directives for independent lanes.
+    unsafe fn c_lane_loop(&self, lane: &str, body: &str) -> String {
+        c_lane_loop(self.ccontext(), lane, body)
+    }
+
     unsafe fn c_run_handle_type(&self) -> String {
         c_run_handle_type(self.ccontext())
     }
@@ -1359,20 +1519,31 @@ impl CodeGenExt for CGenerator {
 impl CGenerator {
     /// Initialize a new CGenerator.
     unsafe fn new(conf: ParsedConf) -> WeldResult<CGenerator> {
+        use crate::conf::Backend;
+        use crate::util::env::get_veweld_conv_bool_to_int;
         let context = LLVMContextCreate();
         let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
         let mut ccontext_data = Box::new(CContext {
             basic_types: FnvHashMap::default(),
-            simd_types: FnvHashMap::default(),
+            simd_types: FnvHashSet::default(),
+            vector_target: target::VectorTarget::from_conf(&conf),
             i1_defined: false,
             input_arg_defined: false,
             output_arg_defined: false,
             run_handle_defined: false,
+            // Data is passed to the host backend as is, so booleans must keep Weld's
+            // one-byte layout there.
+            conv_bool_to_int: get_veweld_conv_bool_to_int() &&
+                conf.backend != Backend::CHost,
             var_ids: IdGenerator::new("g"),
             prelude_code: CodeBuilder::new(),
             body_code: CodeBuilder::new(),
         });
         ccontext_data.prelude_code.add(PRELUDE_CODE);
+        if conf.threads > 1 {
+            // Loops run in parallel with OpenMP (see `builder::for_loop`).
+            ccontext_data.prelude_code.add("#include <omp.h>");
+        }
         let ccontext: *mut CContext = Box::into_raw(ccontext_data);
 
         // These methods *must* be called before using any of the `CodeGenExt` extension methods.
@@ -1404,12 +1575,19 @@ impl CGenerator {
             merger_index: 0,
             appenders: FnvHashMap::default(),
             appender_index: 0,
+            topks: FnvHashMap::default(),
+            topk_index: 0,
             dictionaries: FnvHashMap::default(),
+            dict_index: 0,
             strings: FnvHashMap::default(),
             eq_fns: FnvHashMap::default(),
             opaque_eq_fns: FnvHashMap::default(),
             cmp_fns: FnvHashMap::default(),
             hash_fns: FnvHashMap::default(),
+            c_eq_fns: FnvHashMap::default(),
+            c_cmp_fns: FnvHashMap::default(),
+            c_string_fns: FnvHashSet::default(),
+            c_hash_fns: FnvHashMap::default(),
             serialize_fns: FnvHashMap::default(),
             deserialize_fns: FnvHashMap::default(),
             struct_names: FnvHashMap::default(),
@@ -1532,27 +1710,41 @@ impl CGenerator {
         // Push the run handle.
         c_func_args.push("run".to_string());
 
-        // Run the Weld program.
+        // Run the Weld program. Errors raised by the program jump back here, so that they are
+        // reported to the caller instead of ending the process.
         let args_line = self.c_call_args(&c_func_args);
         let ret_ty = self.c_type(&program.funcs[0].return_type)?;
         let res = (*self.ccontext()).var_ids.next();
-        (*self.ccontext()).body_code.add(format!(
-            "{} {} = {};",
-            ret_ty,
-            res,
-            self.c_call_sir_function(
+        (*self.ccontext()).body_code.add(format!("\
+            WeldRuntimeContextRef run_ctx = (WeldRuntimeContextRef)run;
+            jmp_buf handler;
+            run_ctx->errno = Success;
+            run_ctx->message[0] = 0;
+            run_ctx->handler = &handler;
+            {ret_ty} {res};
+            if (setjmp(handler) == 0) {{
+                {res} = {call};
+            }}
+            run_ctx->handler = 0;",
+            ret_ty=ret_ty,
+            res=res,
+            call=self.c_call_sir_function(
                 &program.funcs[0],
                 &args_line,
             ),
         ));
 
-        // Generate Output
+        // Generate Output. The output of a failed run points to the error message.
         let return_size = self.c_size_of(c_output_type);
         (*self.ccontext()).body_code.add(format!("\
             {out_ty}* output = ({out_ty}*){malloc};
-            output->output = ({i64}){get_result};
             output->run = ({i64})run;
-            output->errno = {get_errno};",
+            output->errno = {get_errno};
+            if (output->errno == Success) {{
+                output->output = ({i64}){get_result};
+            }} else {{
+                output->output = ({i64})run_ctx->message;
+            }}",
             i64=self.c_i64_type(),
             out_ty=c_output_type,
             malloc=self.intrinsics.c_call_weld_run_malloc("run", &return_size),
@@ -1565,6 +1757,71 @@ impl CGenerator {
             "return ({})output;\n}}",
             self.c_i64_type(),
         ));
+
+        self.gen_marshal_entries()
+    }
+
+    /// Generate the functions the host uses to manage runs on the device and to marshal
+    /// dictionaries and builders.
+    ///
+    /// `{run}_init` creates a run on the device, and `{run}_free` frees it with all memory it
+    /// allocated. `{run}_unpack` copies the buffers of a staging area packed by the host into
+    /// memory of a run (see `marshal`).
+    unsafe fn gen_marshal_entries(&mut self) -> WeldResult<()> {
+        let run_func_name = self.conf.llvm.run_func_name.clone();
+        let handle = self.c_run_handle_type();
+        let i64_ty = self.c_i64_type();
+        let u64_ty = self.c_u64_type();
+
+        (*self.ccontext()).body_code.add(format!("\
+            {i64} {run}_init({i64} nworkers, {i64} memlimit)
+            {{
+                return ({i64}){call_init};
+            }}",
+            i64=i64_ty,
+            run=run_func_name,
+            call_init=self.intrinsics.c_call_weld_run_init("nworkers", "memlimit"),
+        ));
+
+        (*self.ccontext()).body_code.add(format!("\
+            {i64} {run}_free({i64} run)
+            {{
+                weld_runst_release(({handle})run);
+                return 0;
+            }}",
+            i64=i64_ty,
+            run=run_func_name,
+            handle=handle,
+        ));
+
+        (*self.ccontext()).body_code.add(format!("\
+            {i64} {run}_unpack({i64} run, {i64} staging)
+            {{
+                {u64}* header = ({u64}*)staging;
+                {u64} nbuffers = header[0];
+                {u64} nrelocations = header[1];
+                {u64}* buffers = header + 2;
+                {u64}* relocations = buffers + 2 * nbuffers;
+                char* data = (char*)(relocations + 3 * nrelocations);
+                for ({u64} i = 0; i < nbuffers; i++) {{
+                    void* buffer = {malloc};
+                    memcpy(buffer, data + buffers[2 * i], buffers[2 * i + 1]);
+                    buffers[2 * i] = ({u64})buffer;
+                }}
+                for ({u64} i = 0; i < nrelocations; i++) {{
+                    {u64}* r = relocations + 3 * i;
+                    *({u64}*)(buffers[2 * r[0]] + r[1]) = buffers[2 * r[2]];
+                }}
+                return 0;
+            }}",
+            i64=i64_ty,
+            u64=u64_ty,
+            run=run_func_name,
+            malloc=self.intrinsics.c_call_weld_run_malloc(
+                &format!("({})run", handle),
+                "buffers[2 * i + 1]",
+            ),
+        ));
         Ok(())
     }
 
@@ -1783,7 +2040,14 @@ impl CGenerator {
             }
             Broadcast(ref child) => {
                 // for C
-                context.body.add("#error Broadcast is not implemented yet");
+                let lane = (*self.ccontext()).var_ids.next();
+                let body = format!(
+                    "{}.e[{}] = {};",
+                    context.c_get_value(output)?,
+                    lane,
+                    context.c_get_value(child)?,
+                );
+                context.body.add(self.c_lane_loop(&lane, &body));
 
                 // for LLVM
                 /*
@@ -1880,7 +2144,19 @@ impl CGenerator {
             }
             KeyExists { ref child, ref key } => {
                 // for C
-                context.body.add("#error KeyExists is not implemented yet");
+                use self::hash::GenHash;
+                let child_type = context.sir_function.symbol_type(child)?;
+                let key_pointer = format!("&{}", context.c_get_value(key)?);
+                let hash = if let Dict(ref key, _) = *child_type {
+                    self.c_gen_hash(key, &key_pointer)?
+                } else {
+                    unreachable!()
+                };
+                let result = {
+                    let methods = self.dictionaries.get_mut(child_type).unwrap();
+                    methods.c_gen_key_exists(&context.c_get_value(child)?, &key_pointer, &hash)?
+                };
+                context.body.add(format!("{} = {};", context.c_get_value(output)?, result));
 
                 // for LLVM
                 /*
@@ -1908,6 +2184,25 @@ impl CGenerator {
                 */
                 Ok(())
             }
+            Hash(ref child) => {
+                // for C
+                use self::hash::GenHash;
+                let child_type = context.sir_function.symbol_type(child)?;
+                let child_pointer = format!("&{}", context.c_get_value(child)?);
+                let hash = self.c_gen_hash(child_type, &child_pointer)?;
+                context.body.add(format!("{} = {};", context.c_get_value(output)?, hash));
+
+                // for LLVM
+                /*
+                use self::hash::GenHash;
+                let output_pointer = context.get_value(output)?;
+                let child_pointer = context.get_value(child)?;
+                let child_type = context.sir_function.symbol_type(child)?;
+                let hash = self.gen_hash(child_type, context.builder, child_pointer, None)?;
+                LLVMBuildStore(context.builder, hash, output_pointer);
+                */
+                Ok(())
+            }
             Length(ref child) => {
                 let child_type = context.sir_function.symbol_type(child)?;
                 if let Vector(ref elem_type) = *child_type {
@@ -1930,7 +2225,11 @@ impl CGenerator {
                     Ok(())
                 } else if let Dict(_, _) = *child_type {
                     // for C
-                    context.body.add("#error Length for dict is not implemented yet");
+                    let result = {
+                        let methods = self.dictionaries.get_mut(child_type).unwrap();
+                        methods.c_gen_size(&context.c_get_value(child)?)?
+                    };
+                    context.body.add(format!("{} = {};", context.c_get_value(output)?, result));
 
                     // for LLVM
                     /*
@@ -1970,7 +2269,24 @@ impl CGenerator {
                     Ok(())
                 } else if let Dict(ref key, _) = *child_type {
                     // for C
-                    context.body.add("#error Lookup is not implemented yet");
+                    use self::hash::GenHash;
+                    let key_pointer = format!("&{}", context.c_get_value(index)?);
+                    let hash = self.c_gen_hash(key, &key_pointer)?;
+                    let slot = {
+                        let methods = self.dictionaries.get_mut(child_type).unwrap();
+                        methods.c_gen_lookup(
+                            &mut self.intrinsics,
+                            &context.c_get_value(child)?,
+                            &key_pointer,
+                            &hash,
+                            context.c_get_run(),
+                        )?
+                    };
+                    context.body.add(format!(
+                        "{} = {}->value;",
+                        context.c_get_value(output)?,
+                        slot,
+                    ));
 
                     // for LLVM
                     /*
@@ -2006,7 +2322,26 @@ impl CGenerator {
                 let child_type = context.sir_function.symbol_type(child)?;
                 if let Dict(ref key, _) = *child_type {
                     // for C
-                    context.body.add("#error OptLookup is not implemented yet");
+                    use self::hash::GenHash;
+                    let key_pointer = format!("&{}", context.c_get_value(index)?);
+                    let hash = self.c_gen_hash(key, &key_pointer)?;
+                    let (c_slot_ty, slot) = {
+                        let methods = self.dictionaries.get_mut(child_type).unwrap();
+                        let slot = methods.c_gen_opt_lookup(
+                            &context.c_get_value(child)?,
+                            &key_pointer,
+                            &hash,
+                        )?;
+                        (methods.c_slot_type().to_string(), slot)
+                    };
+                    // NOTE: The value is invalid if the slot is not filled -- code should check
+                    // the boolean.
+                    let c_output = context.c_get_value(output)?;
+                    context.body.add("{");
+                    context.body.add(format!("{}* slot = {};", c_slot_ty, slot));
+                    context.body.add(format!("{}.f0 = slot->filled != 0;", c_output));
+                    context.body.add(format!("{}.f1 = slot->value;", c_output));
+                    context.body.add("}");
 
                     // for LLVM
                     /*
@@ -2099,7 +2434,16 @@ impl CGenerator {
             }
             Assert(ref cond) => {
                 // for C
-                context.body.add("#error Assert is not implemented yet");
+                let cond = context.c_get_value(cond)?;
+                let output = context.c_get_value(statement.output.as_ref().unwrap())?;
+                let raise = self.intrinsics.c_call_weld_run_raise(
+                    context.c_get_run(),
+                    WeldRuntimeErrno::AssertionError,
+                    "assertion failed",
+                );
+                context.body.add(format!("if (!{}) {{ {}; }}", cond, raise));
+                // If assert returns, this expression returns true.
+                context.body.add(format!("{} = 1;", output));
 
                 // for LLVM
                 /*
@@ -2133,13 +2477,49 @@ impl CGenerator {
                 ref on_true,
                 ref on_false,
             } => {
-                context.body.add(format!(
-                    "{} = {} ? {} : {};",
-                    context.c_get_value(output)?,
-                    context.c_get_value(cond)?,
-                    context.c_get_value(on_true)?,
-                    context.c_get_value(on_false)?,
-                ));
+                match *context.sir_function.symbol_type(output)? {
+                    Simd(_) => {
+                        let lane = (*self.ccontext()).var_ids.next();
+                        let body = format!(
+                            "{out}.e[{l}] = {c}.e[{l}] ? {t}.e[{l}] : {f}.e[{l}];",
+                            out=context.c_get_value(output)?,
+                            c=context.c_get_value(cond)?,
+                            t=context.c_get_value(on_true)?,
+                            f=context.c_get_value(on_false)?,
+                            l=lane,
+                        );
+                        context.body.add(self.c_lane_loop(&lane, &body));
+                    }
+                    Struct(ref fields) if fields.iter().any(|f| f.is_simd()) => {
+                        // Select each lane of each SIMD field.
+                        let lane = (*self.ccontext()).var_ids.next();
+                        let c_cond = context.c_get_value(cond)?;
+                        let c_cond = match *context.sir_function.symbol_type(cond)? {
+                            Simd(_) => format!("{}.e[{}]", c_cond, lane),
+                            _ => c_cond,
+                        };
+                        let mut body = vec![];
+                        c_select_lanes(
+                            context.sir_function.symbol_type(output)?,
+                            &context.c_get_value(output)?,
+                            &c_cond,
+                            &context.c_get_value(on_true)?,
+                            &context.c_get_value(on_false)?,
+                            &lane,
+                            &mut body,
+                        )?;
+                        context.body.add(self.c_lane_loop(&lane, &body.join("\n")));
+                    }
+                    _ => {
+                        context.body.add(format!(
+                            "{} = {} ? {} : {};",
+                            context.c_get_value(output)?,
+                            context.c_get_value(cond)?,
+                            context.c_get_value(on_true)?,
+                            context.c_get_value(on_false)?,
+                        ));
+                    }
+                }
                 Ok(())
             }
             Serialize(_) => {
@@ -2292,9 +2672,33 @@ impl CGenerator {
                     unreachable!()
                 }
             }
+            StringOp { .. } => {
+                // for C
+                use self::strings::StringOpGen;
+                self.gen_string_op(context, statement)
+            }
             ToVec(ref child) => {
                 // for C
-                context.body.add("#error ToVec is not implemented yet");
+                let child_type = context.sir_function.symbol_type(child)?;
+                // This is the type of the resulting key/value vector (vec[{K,V}])
+                let output_type = context.sir_function.symbol_type(output)?;
+                let elem = if let Vector(ref elem) = *output_type {
+                    elem
+                } else {
+                    unreachable!()
+                };
+                let _ = self.c_type(output_type)?;
+                let result = {
+                    let vector_methods = self.vectors.get_mut(elem).unwrap();
+                    let methods = self.dictionaries.get_mut(child_type).unwrap();
+                    methods.c_gen_to_vec(
+                        &mut self.intrinsics,
+                        vector_methods,
+                        &context.c_get_value(child)?,
+                        context.c_get_run(),
+                    )?
+                };
+                context.body.add(format!("{} = {};", context.c_get_value(output)?, result));
 
                 // for LLVM
                 /*
@@ -2423,7 +2827,12 @@ impl CGenerator {
             }
             Crash => {
                 // for C
-                context.body.add("#error Crash is not implemented yet");
+                let raise = self.intrinsics.c_call_weld_run_raise(
+                    context.c_get_run(),
+                    WeldRuntimeErrno::Unknown,
+                    "program crashed",
+                );
+                context.body.add(format!("{};", raise));
 
                 // for LLVM
                 /*
@@ -2454,27 +2863,8 @@ impl CGenerator {
                 use self::builder::BuilderExpressionGen;
                 self.builder_type(ty)?
             }
-            Dict(ref key, ref value) => {
-                use self::eq::GenEq;
-                if !self.dictionaries.contains_key(ty) {
-                    let key_ty = self.llvm_type(key)?;
-                    let c_key_ty = &self.c_type(key)?.to_string();
-                    let value_ty = self.llvm_type(value)?;
-                    let c_value_ty = &self.c_type(value)?.to_string();
-                    let key_comparator = self.gen_eq_fn(key)?;
-                    let dict = dict::Dict::define(
-                        "dict",
-                        key_ty,
-                        c_key_ty,
-                        key_comparator,
-                        value_ty,
-                        c_value_ty,
-                        self.context,
-                        self.module,
-                        self.ccontext,
-                    );
-                    self.dictionaries.insert(ty.clone(), dict);
-                }
+            Dict(_, _) => {
+                self.define_dict(ty)?;
                 self.dictionaries[ty].dict_ty
             }
             Scalar(kind) => match kind {
@@ -2483,9 +2873,9 @@ impl CGenerator {
                 U8 => self.u8_type(),
                 I16 => self.i16_type(),
                 U16 => self.u16_type(),
-                I32 => self.i32_type(),
+                I32 | Date => self.i32_type(),
                 U32 => self.u32_type(),
-                I64 => self.i64_type(),
+                I64 | Decimal(_, _) | Timestamp | Interval => self.i64_type(),
                 U64 => self.u64_type(),
                 F32 => self.f32_type(),
                 F64 => self.f64_type(),
@@ -2529,7 +2919,9 @@ impl CGenerator {
                 }
                 self.vectors[elem_type].vector_ty
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
         Ok(result)
     }
@@ -2546,27 +2938,8 @@ impl CGenerator {
                 use self::builder::BuilderExpressionGen;
                 self.c_builder_type(ty)?
             }
-            Dict(ref key, ref value) => {
-                use self::eq::GenEq;
-                if !self.dictionaries.contains_key(ty) {
-                    let key_ty = self.llvm_type(key)?;
-                    let c_key_ty = &self.c_type(key)?.to_string();
-                    let value_ty = self.llvm_type(value)?;
-                    let c_value_ty = &self.c_type(value)?.to_string();
-                    let key_comparator = self.gen_eq_fn(key)?;
-                    let dict = dict::Dict::define(
-                        "dict",
-                        key_ty,
-                        c_key_ty,
-                        key_comparator,
-                        value_ty,
-                        c_value_ty,
-                        self.context,
-                        self.module,
-                        self.ccontext,
-                    );
-                    self.dictionaries.insert(ty.clone(), dict);
-                }
+            Dict(_, _) => {
+                self.define_dict(ty)?;
                 self.dictionaries[ty].name.clone()
             }
             Scalar(kind) => match kind {
@@ -2575,22 +2948,16 @@ impl CGenerator {
                 U8 => self.c_u8_type(),
                 I16 => self.c_i16_type(),
                 U16 => self.c_u16_type(),
-                I32 => self.c_i32_type(),
+                I32 | Date => self.c_i32_type(),
                 U32 => self.c_u32_type(),
-                I64 => self.c_i64_type(),
+                I64 | Decimal(_, _) | Timestamp | Interval => self.c_i64_type(),
                 U64 => self.c_u64_type(),
                 F32 => self.c_f32_type(),
                 F64 => self.c_f64_type(),
             },
             Simd(kind) => {
-                if !(*self.ccontext()).simd_types.contains_key(&kind) {
-                    (*self.ccontext()).prelude_code.add(
-                        format!("// typedef ... simd_{};",
-                        self.c_type(&Scalar(kind))?));
-                    (*self.ccontext()).simd_types.insert(
-                        kind, format!("simd_{}", self.c_type(&Scalar(kind))?));
-                }
-                (*self.ccontext()).simd_types.get(&kind).unwrap().to_string()
+                let elem = self.c_type(&Scalar(kind))?;
+                self.c_simd_type(&elem, self.c_simd_width())
             }
             Struct(ref elems) => {
                 if !self.c_struct_names.contains_key(ty) {
@@ -2601,6 +2968,11 @@ impl CGenerator {
                     for (i, e) in elems.iter().enumerate() {
                         def.add(format!("{} f{};", self.c_type(e)?, i));
                     }
+                    // Empty structs (e.g., the values of a set) are a GNU extension, so they get
+                    // a dummy field (see `marshal::Layout::struct_layout`).
+                    if elems.is_empty() {
+                        def.add("char unused;");
+                    }
                     def.add(format!("}} {};", name));
                     (*self.ccontext()).prelude_code.add(def.result());
                     self.c_struct_names.insert(ty.clone(), name);
@@ -2620,11 +2992,47 @@ impl CGenerator {
                 }
                 self.vectors[elem_type].name.clone()
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
         Ok(result)
     }
 
+    /// Defines the dictionary type `ty` if it has not been defined yet.
+    unsafe fn define_dict(&mut self, ty: &Type) -> WeldResult<()> {
+        use self::eq::GenEq;
+        use crate::ast::Type::Dict;
+        if let Dict(ref key, ref value) = *ty {
+            if !self.dictionaries.contains_key(ty) {
+                let name = format!("dict{}", self.dict_index);
+                self.dict_index += 1;
+                let key_ty = self.llvm_type(key)?;
+                let c_key_ty = &self.c_type(key)?.to_string();
+                let value_ty = self.llvm_type(value)?;
+                let c_value_ty = &self.c_type(value)?.to_string();
+                let key_comparator = self.gen_eq_fn(key)?;
+                let c_key_eq = &self.c_gen_eq_fn(key)?;
+                let dict = dict::Dict::define(
+                    name,
+                    key_ty,
+                    c_key_ty,
+                    key_comparator,
+                    c_key_eq,
+                    value_ty,
+                    c_value_ty,
+                    self.context,
+                    self.module,
+                    self.ccontext,
+                );
+                self.dictionaries.insert(ty.clone(), dict);
+            }
+            Ok(())
+        } else {
+            unreachable!()
+        }
+    }
+
     unsafe fn size_of_ty(&mut self, ty: &Type) -> usize {
         let ll_ty = self.llvm_type(ty).unwrap();
         (self.size_of_bits(ll_ty) / 8) as usize
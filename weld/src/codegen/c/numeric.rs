@@ -189,9 +189,6 @@ impl NumericExpressionGenInternal for CGenerator {
                 Ok(result)
             }
             Simd(kind) if kind.is_float() => {
-                // for C
-                ctx.body.add(format!("#error gen_pow BinOp pow for simd {} is not implemented yet", self.c_type(ty)?));
-                // for LLVM
                 let name = Intrinsics::llvm_numeric("pow", kind, false);
                 use crate::ast::ScalarKind::{F32, F64};
                 let c_name = match kind {
@@ -204,6 +201,20 @@ impl NumericExpressionGenInternal for CGenerator {
                 let mut arg_tys = [ret_ty, ret_ty];
                 let c_arg_tys = [c_ret_ty, c_ret_ty];
                 self.intrinsics.add(&name, &c_name, ret_ty, c_ret_ty, &mut arg_tys, &c_arg_tys);
+                // for C
+                // Apply the function to each lane.
+                let result = (*self.ccontext()).var_ids.next();
+                let lane = (*self.ccontext()).var_ids.next();
+                let call = self.intrinsics.c_call(&c_name, &[
+                    &format!("{}.e[{}]", left, lane),
+                    &format!("{}.e[{}]", right, lane),
+                ]);
+                ctx.body.add(format!("{} {};", self.c_type(ty)?, result));
+                ctx.body.add(self.c_lane_loop(
+                    &lane,
+                    &format!("{}.e[{}] = {};", result, lane, call),
+                ));
+                // for LLVM
                 /*
                 // Unroll vector and apply function to each element.
                 let mut result = LLVMGetUndef(LLVMVectorType(ret_ty, LLVM_VECTOR_WIDTH));
@@ -224,7 +235,7 @@ impl NumericExpressionGenInternal for CGenerator {
                     );
                 }
                 */
-                Ok("not implemented".to_string() /* result */)
+                Ok(result)
             }
             _ => unreachable!(),
         }
@@ -245,6 +256,10 @@ impl UnaryOpSupport for UnaryOpKind {
             Sqrt => Some("sqrt"),
             Sin => Some("sin"),
             Cos => Some("cos"),
+            Floor => Some("floor"),
+            Ceil => Some("ceil"),
+            Round => Some("round"),
+            Abs => Some("fabs"),
             _ => None,
         }
     }
@@ -269,9 +284,25 @@ impl NumericExpressionGen for CGenerator {
             let c_child = ctx.c_get_value(child)?;
             // let child = self.load(ctx.builder, ctx.get_value(child)?)?;
 
+            // Integer operators are plain C expressions, so SIMD values just apply them per lane.
             // Use the LLVM intrinsic if one is available, since LLVM may be able to vectorize it.
             // Otherwise, fall back to the libc math variant and unroll SIMD values manually.
-            if let Some(name) = op.llvm_intrinsic() {
+            if kind.is_integer() {
+                let c_ty = self.c_type(&Scalar(kind))?;
+                let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+                if !simd {
+                    let result = c_gen_integer_unaryop(op, &c_child, kind, &c_ty)?;
+                    ctx.body.add(format!("{} = {};", output, result));
+                } else {
+                    let lane = (*self.ccontext()).var_ids.next();
+                    let element = format!("{}.e[{}]", c_child, lane);
+                    let result = c_gen_integer_unaryop(op, &element, kind, &c_ty)?;
+                    ctx.body.add(self.c_lane_loop(
+                        &lane,
+                        &format!("{}.e[{}] = {};", output, lane, result),
+                    ));
+                }
+            } else if let Some(name) = op.llvm_intrinsic() {
                 use crate::ast::ScalarKind::{F32, F64};
                 use crate::ast::UnaryOpKind::*;
                 let c_name = match (op, kind) {
@@ -280,11 +311,19 @@ impl NumericExpressionGen for CGenerator {
                     (Sqrt, F32) => "sqrtf",
                     (Sin, F32) => "sinf",
                     (Cos, F32) => "cosf",
+                    (Floor, F32) => "floorf",
+                    (Ceil, F32) => "ceilf",
+                    (Round, F32) => "roundf",
+                    (Abs, F32) => "fabsf",
                     (Exp, F64) => "exp",
                     (Log, F64) => "log",
                     (Sqrt, F64) => "sqrt",
                     (Sin, F64) => "sin",
                     (Cos, F64) => "cos",
+                    (Floor, F64) => "floor",
+                    (Ceil, F64) => "ceil",
+                    (Round, F64) => "round",
+                    (Abs, F64) => "fabs",
                     _ => unreachable!(),
                 };
                 let name = Intrinsics::llvm_numeric(name, kind, simd);
@@ -295,12 +334,18 @@ impl NumericExpressionGen for CGenerator {
                 let c_arg_tys = [c_ret_ty];
                 self.intrinsics.add(&name, &c_name, ret_ty, c_ret_ty, &mut arg_tys, &c_arg_tys);
                 // for C
-                let call = self.intrinsics.c_call(&c_name, &[&c_child]);
-                ctx.body.add(format!(
-                    "{} = {};",
-                    ctx.c_get_value(statement.output.as_ref().unwrap())?,
-                    call,
-                ));
+                let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+                if !simd {
+                    let call = self.intrinsics.c_call(&c_name, &[&c_child]);
+                    ctx.body.add(format!("{} = {};", output, call));
+                } else {
+                    let lane = (*self.ccontext()).var_ids.next();
+                    let call = self.intrinsics.c_call(&c_name, &[&format!("{}.e[{}]", c_child, lane)]);
+                    ctx.body.add(self.c_lane_loop(
+                        &lane,
+                        &format!("{}.e[{}] = {};", output, lane, call),
+                    ));
+                }
                 // for LLVM
                 // self.intrinsics.call(ctx.builder, name, &mut [child])?
             } else {
@@ -315,6 +360,7 @@ impl NumericExpressionGen for CGenerator {
                     (Cosh, F32) => "coshf",
                     (Tanh, F32) => "tanhf",
                     (Erf, F32) => "erff",
+                    (Cbrt, F32) => "cbrtf",
                     (Tan, F64) => "tan",
                     (ASin, F64) => "asin",
                     (ACos, F64) => "acos",
@@ -323,6 +369,7 @@ impl NumericExpressionGen for CGenerator {
                     (Cosh, F64) => "cosh",
                     (Tanh, F64) => "tanh",
                     (Erf, F64) => "erf",
+                    (Cbrt, F64) => "cbrt",
                     _ => unreachable!(),
                 };
                 let ret_ty = self.llvm_type(&Scalar(kind))?;
@@ -344,7 +391,17 @@ impl NumericExpressionGen for CGenerator {
                     // self.intrinsics.call(ctx.builder, name, &mut [child])?
                 } else {
                     // for C
-                    ctx.body.add(format!("#error UnaryOp of SIMD for {} is not implemented yet", name));
+                    let lane = (*self.ccontext()).var_ids.next();
+                    let call = self.intrinsics.c_call(&name, &[&format!("{}.e[{}]", c_child, lane)]);
+                    ctx.body.add(self.c_lane_loop(
+                        &lane,
+                        &format!(
+                            "{}.e[{}] = {};",
+                            ctx.c_get_value(statement.output.as_ref().unwrap())?,
+                            lane,
+                            call,
+                        ),
+                    ));
                     // for LLVM
                     /*
                     let mut result = LLVMGetUndef(LLVMVectorType(ret_ty, LLVM_VECTOR_WIDTH));
@@ -460,7 +517,14 @@ impl NumericExpressionGen for CGenerator {
             };
 
             if simd {
-                ctx.body.add("#error simd of Negate is not implemented yet");
+                let lane = (*self.ccontext()).var_ids.next();
+                let child = format!("{}.e[{}]", ctx.c_get_value(child)?, lane);
+                let result = c_gen_binop(Subtract, &zero, &child, &Scalar(kind))?;
+                let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+                ctx.body.add(self.c_lane_loop(
+                    &lane,
+                    &format!("{}.e[{}] = {};", output, lane, result),
+                ));
                 Ok(())
                 /*
                 zero = LLVMConstVector(
@@ -496,6 +560,18 @@ impl NumericExpressionGen for CGenerator {
         {
             let ty = ctx.sir_function.symbol_type(left)?;
             match *ty {
+                Simd(kind) if op != BinOpKind::Pow => {
+                    // Apply the operator to each lane.
+                    let lane = (*self.ccontext()).var_ids.next();
+                    let c_left = format!("{}.e[{}]", ctx.c_get_value(left)?, lane);
+                    let c_right = format!("{}.e[{}]", ctx.c_get_value(right)?, lane);
+                    let result = c_gen_binop(op, &c_left, &c_right, &Scalar(kind))?;
+                    let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+                    ctx.body.add(self.c_lane_loop(
+                        &lane,
+                        &format!("{}.e[{}] = {};", output, lane, result),
+                    ));
+                }
                 Scalar(_) | Simd(_) => {
                     let c_left = ctx.c_get_value(left)?;
                     let c_right = ctx.c_get_value(right)?;
@@ -512,7 +588,29 @@ impl NumericExpressionGen for CGenerator {
                 }
                 Vector(_) | Struct(_) if op.is_comparison() => {
                     // for C
-                    ctx.body.add(format!("#error vector/struct BinOp cmp for {} is not implemented yet", self.c_type(ty)?));
+                    // Note that we assume values being compared have the same type.
+                    let c_left = ctx.c_get_value(left)?;
+                    let c_right = ctx.c_get_value(right)?;
+                    let result = match op {
+                        BinOpKind::Equal | BinOpKind::NotEqual => {
+                            use super::eq::GenEq;
+                            let func = self.c_gen_eq_fn(ty)?;
+                            let equal = format!("{}(&{}, &{})", func, c_left, c_right);
+                            if op == BinOpKind::Equal {
+                                equal
+                            } else {
+                                format!("!{}", equal)
+                            }
+                        }
+                        _ => {
+                            use super::cmp::GenCmp;
+                            let func = self.c_gen_cmp_fn(ty)?;
+                            let cmp = format!("{}(&{}, &{})", func, c_left, c_right);
+                            c_gen_binop(op, &cmp, "0", &Scalar(ScalarKind::I32))?
+                        }
+                    };
+                    let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+                    ctx.body.add(format!("{} = {};", output, result));
                     // for LLVM
                     /*
                     // Note that we assume structs being compared have the same type.
@@ -613,14 +711,26 @@ impl NumericExpressionGen for CGenerator {
         if let AssignLiteral(ref value) = statement.kind {
             let output = statement.output.as_ref().unwrap();
             let output_type = ctx.sir_function.symbol_type(output)?;
-            let mut result = if let LiteralKind::StringLiteral(ref val) = value {
-                val.to_string()
+            let result = if let LiteralKind::StringLiteral(ref val) = value {
+                // A string is a vector pointing to the data of a C string literal.
+                format!(
+                    "({}){{({}*){}, {}}}",
+                    self.c_type(output_type)?,
+                    self.c_i8_type(),
+                    super::strings::c_string_literal(val),
+                    val.len(),
+                )
             } else {
                 self.c_scalar_literal(value)
             };
             if let Type::Simd(_) = output_type {
-                result =
-                    format!("simd vector of {} is not implemented", result);
+                // Broadcast the literal to each lane.
+                let lane = (*self.ccontext()).var_ids.next();
+                ctx.body.add(self.c_lane_loop(
+                    &lane,
+                    &format!("{}.e[{}] = {};", ctx.c_get_value(output)?, lane, result),
+                ));
+                return Ok(());
                 /*
                 result = LLVMConstVector(
                     [result; LLVM_VECTOR_WIDTH as usize].as_mut_ptr(),
@@ -648,13 +758,20 @@ impl NumericExpressionGen for CGenerator {
         let output = &statement.output.clone().unwrap();
         let output_type = ctx.sir_function.symbol_type(output)?;
         if let Cast(ref child, _) = statement.kind {
+            use crate::ast::Type::Scalar;
             let c_output_pointer = ctx.c_get_value(output)?;
-            ctx.body.add(format!(
-                "{} = ({}){};",
-                c_output_pointer,
-                &self.c_type(output_type)?,
-                ctx.c_get_value(child)?,
-            ));
+            let c_output_type = self.c_type(output_type)?;
+            let c_child = ctx.c_get_value(child)?;
+            let result = match (ctx.sir_function.symbol_type(child)?, output_type) {
+                (&Scalar(from), &Scalar(to)) if from.is_decimal() || to.is_decimal() => {
+                    // Dates, timestamps and intervals are cast as their stored values.
+                    let from = if from.is_temporal() { from.storage_kind() } else { from };
+                    let to = if to.is_temporal() { to.storage_kind() } else { to };
+                    c_gen_decimal_cast(&c_child, from, to, &c_output_type)?
+                }
+                _ => format!("({}){}", c_output_type, c_child),
+            };
+            ctx.body.add(format!("{} = {};", c_output_pointer, result));
             Ok(())
         } else {
             unreachable!()
@@ -680,9 +797,34 @@ pub unsafe fn gen_binop(
     use self::llvm_sys::LLVMIntPredicate::*;
     use self::llvm_sys::LLVMRealPredicate::*;
     use crate::ast::BinOpKind::*;
+    use crate::ast::ScalarKind::{Decimal, Interval, I64};
     use crate::ast::Type::*;
     let name = c_str!("");
     let result = match *ty {
+        // Dates, timestamps and intervals are stored as integers. Other arithmetic on them is
+        // lowered before code generation.
+        Scalar(s) | Simd(s) if s.is_temporal() => {
+            let storage = match *ty {
+                Simd(_) => Simd(s.storage_kind()),
+                _ => Scalar(s.storage_kind()),
+            };
+            match op {
+                Add | Subtract if s == Interval => gen_binop(builder, op, left, right, &storage)?,
+                Max | Min => gen_binop(builder, op, left, right, &storage)?,
+                _ if op.is_comparison() => gen_binop(builder, op, left, right, &storage)?,
+                _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+            }
+        }
+        // Decimals are stored as `i64` values with the same scale, so only multiplication and
+        // division need to rescale their results.
+        Scalar(Decimal(_, _)) => match op {
+            Multiply | Divide => gen_decimal_binop(builder, op, left, right, ty)?,
+            Add | Subtract | Modulo | Max | Min => {
+                gen_binop(builder, op, left, right, &Scalar(I64))?
+            }
+            _ if op.is_comparison() => gen_binop(builder, op, left, right, &Scalar(I64))?,
+            _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+        },
         Scalar(s) | Simd(s) => match op {
             Add if s.is_integer() => LLVMBuildAdd(builder, left, right, name),
             Add if s.is_float() => LLVMBuildFAdd(builder, left, right, name),
@@ -755,6 +897,19 @@ pub unsafe fn gen_binop(
 
             Xor if s.is_integer() || s.is_bool() => LLVMBuildXor(builder, left, right, name),
 
+            ShiftLeft if s.is_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildShl(builder, left, right, name)
+            }
+            ShiftRight if s.is_signed_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildAShr(builder, left, right, name)
+            }
+            ShiftRight if s.is_unsigned_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildLShr(builder, left, right, name)
+            }
+
             Max => {
                 let compare = gen_binop(builder, GreaterThanOrEqual, left, right, ty)?;
                 LLVMBuildSelect(builder, compare, left, right, c_str!(""))
@@ -771,6 +926,69 @@ pub unsafe fn gen_binop(
     };
     Ok(result)
 }
+
+/// Masks a shift amount of a value with type `ty` to the bit width of the type.
+///
+/// Shifting by a negative amount or by at least the bit width is otherwise undefined.
+unsafe fn gen_shift_amount(
+    builder: LLVMBuilderRef,
+    amount: LLVMValueRef,
+    ty: &Type,
+) -> LLVMValueRef {
+    let (kind, simd) = match *ty {
+        Type::Scalar(kind) => (kind, false),
+        Type::Simd(kind) => (kind, true),
+        _ => unreachable!(),
+    };
+    let elem_ty = if simd {
+        LLVMGetElementType(LLVMTypeOf(amount))
+    } else {
+        LLVMTypeOf(amount)
+    };
+    let mut mask = LLVMConstInt(elem_ty, u64::from(kind.bits() - 1), 0);
+    if simd {
+        mask = LLVMConstVector(
+            [mask; LLVM_VECTOR_WIDTH as usize].as_mut_ptr(),
+            LLVM_VECTOR_WIDTH,
+        );
+    }
+    LLVMBuildAnd(builder, amount, mask, c_str!(""))
+}
+
+/// Generates a multiplication or division of decimals with type `ty`.
+///
+/// The operation uses 128-bit intermediate values, and the result is rounded toward zero.
+unsafe fn gen_decimal_binop(
+    builder: LLVMBuilderRef,
+    op: BinOpKind,
+    left: LLVMValueRef,
+    right: LLVMValueRef,
+    ty: &Type,
+) -> WeldResult<LLVMValueRef> {
+    use crate::ast::BinOpKind::*;
+    let factor = match *ty {
+        Type::Scalar(kind) => kind.scale_factor(),
+        _ => unreachable!(),
+    };
+    let i64_type = LLVMTypeOf(left);
+    let i128_type = LLVMIntTypeInContext(LLVMGetTypeContext(i64_type), 128);
+    let factor = LLVMConstInt(i128_type, factor as u64, 1);
+    let left = LLVMBuildSExt(builder, left, i128_type, c_str!(""));
+    let right = LLVMBuildSExt(builder, right, i128_type, c_str!(""));
+    let result = match op {
+        Multiply => {
+            let product = LLVMBuildMul(builder, left, right, c_str!(""));
+            LLVMBuildSDiv(builder, product, factor, c_str!(""))
+        }
+        Divide => {
+            let dividend = LLVMBuildMul(builder, left, factor, c_str!(""));
+            LLVMBuildSDiv(builder, dividend, right, c_str!(""))
+        }
+        _ => unreachable!(),
+    };
+    Ok(LLVMBuildTrunc(builder, result, i64_type, c_str!("")))
+}
+
 pub unsafe fn c_gen_binop(
     op: BinOpKind,
     left: &str,
@@ -778,8 +996,43 @@ pub unsafe fn c_gen_binop(
     ty: &Type,
 ) -> WeldResult<String> {
     use crate::ast::BinOpKind::*;
+    use crate::ast::ScalarKind::{Decimal, Interval, I64};
     use crate::ast::Type::*;
     let result = match *ty {
+        // Dates, timestamps and intervals are stored as integers. Other arithmetic on them is
+        // lowered before code generation.
+        Scalar(s) | Simd(s) if s.is_temporal() => {
+            let storage = match *ty {
+                Simd(_) => Simd(s.storage_kind()),
+                _ => Scalar(s.storage_kind()),
+            };
+            match op {
+                Add | Subtract if s == Interval => c_gen_binop(op, left, right, &storage)?,
+                Max | Min => c_gen_binop(op, left, right, &storage)?,
+                _ if op.is_comparison() => c_gen_binop(op, left, right, &storage)?,
+                _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+            }
+        }
+        // Decimals are stored as `i64` values with the same scale, so only multiplication and
+        // division need to rescale their results. They use 128-bit intermediate values (see
+        // `weld_decimal_muldiv` in the intrinsics).
+        Scalar(s @ Decimal(_, _)) => match op {
+            Multiply => format!(
+                "weld_decimal_muldiv({}, {}, {}L)",
+                left,
+                right,
+                s.scale_factor()
+            ),
+            Divide => format!(
+                "weld_decimal_muldiv({}, {}L, {})",
+                left,
+                s.scale_factor(),
+                right
+            ),
+            Add | Subtract | Modulo | Max | Min => c_gen_binop(op, left, right, &Scalar(I64))?,
+            _ if op.is_comparison() => c_gen_binop(op, left, right, &Scalar(I64))?,
+            _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+        },
         Scalar(s) | Simd(s) => match op {
             Add if s.is_integer() => format!("{} + {}", left, right),
             Add if s.is_float() => format!("{} + {}", left, right),
@@ -828,6 +1081,30 @@ pub unsafe fn c_gen_binop(
 
             Xor if s.is_integer() || s.is_bool() => format!("{} ^ {}", left, right),
 
+            // Shift amounts are masked to the bit width like in the LLVM backend, since shifting
+            // by a negative amount or by at least the bit width is undefined in C. Signed values
+            // are shifted left as unsigned ones, since shifting a negative value is undefined too.
+            ShiftLeft if s.is_signed_integer() => {
+                let (signed, unsigned) = match s {
+                    ScalarKind::I8 => ("i8", "unsigned char"),
+                    ScalarKind::I16 => ("i16", "unsigned short"),
+                    ScalarKind::I32 => ("i32", "unsigned int"),
+                    _ => ("i64", "unsigned long"),
+                };
+                format!(
+                    "({})(({}){} << ({} & {}))",
+                    signed,
+                    unsigned,
+                    left,
+                    right,
+                    s.bits() - 1
+                )
+            }
+            ShiftLeft if s.is_unsigned_integer() => {
+                format!("{} << ({} & {})", left, right, s.bits() - 1)
+            }
+            ShiftRight if s.is_integer() => format!("{} >> ({} & {})", left, right, s.bits() - 1),
+
             Max => format!("{} >= {} ? {} : {}", left, right, left, right),
 
             Min => format!("{} <= {} ? {} : {}", left, right, left, right),
@@ -838,3 +1115,90 @@ pub unsafe fn c_gen_binop(
     };
     Ok(result)
 }
+
+/// Generates a unary op over an integer `value` with C type `ty`.
+///
+/// Bit counts use the two's complement bits of `value`, and counting the zeros of zero returns the
+/// bit width.
+fn c_gen_integer_unaryop(
+    op: UnaryOpKind,
+    value: &str,
+    kind: ScalarKind,
+    ty: &str,
+) -> WeldResult<String> {
+    use crate::ast::UnaryOpKind::*;
+    let bits = kind.bits();
+    let mask = if bits == 64 {
+        u64::max_value()
+    } else {
+        (1u64 << bits) - 1
+    };
+    let bits_of = format!("((unsigned long){} & {}UL)", value, mask);
+    let result = match op {
+        // Negate the unsigned representation so that the minimum value wraps around.
+        Abs if kind.is_signed_integer() => format!(
+            "({} < 0 ? ({})(0UL - (unsigned long){}) : {})",
+            value, ty, value, value
+        ),
+        Abs => value.to_string(),
+        Popcount => format!("({})__builtin_popcountl({})", ty, bits_of),
+        Clz => format!(
+            "({} == 0 ? ({}){} : ({})(__builtin_clzl({}) - {}))",
+            value,
+            ty,
+            bits,
+            ty,
+            bits_of,
+            64 - bits
+        ),
+        Ctz => format!(
+            "({} == 0 ? ({}){} : ({})__builtin_ctzl({}))",
+            value, ty, bits, ty, bits_of
+        ),
+        _ => return compile_err!("Unsupported unary op: {} on {}", op, kind),
+    };
+    Ok(result)
+}
+
+/// Generates a cast from or to a decimal.
+///
+/// Floating point values are rounded to the nearest decimal, and decimals are rounded toward
+/// zero when cast to integers.
+fn c_gen_decimal_cast(
+    value: &str,
+    from: ScalarKind,
+    to: ScalarKind,
+    to_type: &str,
+) -> WeldResult<String> {
+    use crate::ast::ScalarKind::Decimal;
+    let result = match (from, to) {
+        (Decimal(_, from_scale), Decimal(_, to_scale)) if to_scale >= from_scale => format!(
+            "{} * {}L",
+            value,
+            10i64.pow(u32::from(to_scale - from_scale))
+        ),
+        (Decimal(_, from_scale), Decimal(_, to_scale)) => format!(
+            "{} / {}L",
+            value,
+            10i64.pow(u32::from(from_scale - to_scale))
+        ),
+        (_, Decimal(_, _)) if from.is_float() => format!(
+            "({})({} * {}.0 + ({} < 0 ? -0.5 : 0.5))",
+            to_type,
+            value,
+            to.scale_factor(),
+            value
+        ),
+        (_, Decimal(_, _)) if from.is_integer() || from.is_bool() => {
+            format!("({}){} * {}L", to_type, value, to.scale_factor())
+        }
+        (Decimal(_, _), _) if to.is_float() => {
+            format!("({}){} / {}.0", to_type, value, from.scale_factor())
+        }
+        (Decimal(_, _), _) if to.is_integer() => {
+            format!("({})({} / {}L)", to_type, value, from.scale_factor())
+        }
+        _ => return compile_err!("Cannot cast {} to {}", from, to),
+    };
+    Ok(result)
+}
@@ -0,0 +1,365 @@
+//! Splitting a program across several VEs.
+//!
+//! A program can be partitioned if it returns the result of a merger or appender that is built
+//! by a chain of top-level loops over vector parameters. Each VE then runs the whole program on
+//! a contiguous chunk of the vectors, and the results of the chunks are combined on the host:
+//! merger results are merged with the merger's operator, and appender results are concatenated
+//! in the order of the chunks.
+//!
+//! The analysis is conservative. The partitioned vectors may only be used as the data of the
+//! loops, the loops may not use their index, and only builders of scalars are supported.
+
+use fnv::FnvHashMap;
+
+use std::ops::{Add, Mul};
+use std::ptr;
+
+use crate::ast::BinOpKind::{self, *};
+use crate::ast::BuilderKind::*;
+use crate::ast::IterKind::*;
+use crate::ast::Type::*;
+use crate::ast::{ScalarKind, Symbol};
+use crate::sir::StatementKind::*;
+use crate::sir::*;
+
+use super::run::{alloc, scalar_kind_size};
+
+/// How the results of the chunks of a partitioned program are combined.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartitionMerge {
+    /// Merges scalar results with an operator.
+    Merger(ScalarKind, BinOpKind),
+    /// Concatenates vectors of scalars.
+    Appender(ScalarKind),
+}
+
+/// A plan for running a program on chunks of its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    /// Indices of the vector parameters that are split into chunks.
+    pub vectors: Vec<usize>,
+    /// How the results of the chunks are combined.
+    pub merge: PartitionMerge,
+}
+
+/// Returns how to partition `program`, or `None` if it must run on a single VE.
+pub fn find_partition(program: &SirProgram) -> Option<Partition> {
+    let main = &program.funcs[0];
+    if main.blocks.len() != 1 {
+        return None;
+    }
+    let block = &main.blocks[0];
+    let defs: FnvHashMap<&Symbol, &StatementKind> = block
+        .statements
+        .iter()
+        .filter_map(|s| s.output.as_ref().map(|output| (output, &s.kind)))
+        .collect();
+
+    // Find the builder whose result the program returns.
+    let sym = match block.terminator {
+        Terminator::ProgramReturn(ref sym) => sym,
+        _ => return None,
+    };
+    let mut sym = match defs.get(resolve(&defs, sym)) {
+        Some(Res(ref builder)) => builder,
+        _ => return None,
+    };
+
+    // Walk the chain of loops that build it back to the new builder.
+    let mut loops = vec![];
+    let builder_ty = loop {
+        match defs.get(sym) {
+            Some(ParallelFor(ref pf)) => {
+                loops.push(pf);
+                sym = &pf.builder;
+            }
+            Some(NewBuilder { arg: None, ref ty }) => break ty,
+            _ => return None,
+        }
+    };
+    if loops.is_empty() {
+        return None;
+    }
+
+    let merge = match *builder_ty {
+        Builder(Merger(ref elem, op), _) => match **elem {
+            Scalar(kind) if kind.is_numeric() => match op {
+                Add | Multiply | Max | Min => PartitionMerge::Merger(kind, op),
+                _ => return None,
+            },
+            // Decimal products must be rescaled, so only sums and extrema are partitioned.
+            Scalar(kind) if kind.is_decimal() => match op {
+                Add | Max | Min => PartitionMerge::Merger(kind, op),
+                _ => return None,
+            },
+            Scalar(kind) if kind.is_temporal() => match op {
+                Add if kind == ScalarKind::Interval => PartitionMerge::Merger(kind, op),
+                Max | Min => PartitionMerge::Merger(kind, op),
+                _ => return None,
+            },
+            _ => return None,
+        },
+        Builder(Appender(ref elem), _) => match **elem {
+            Scalar(kind) => PartitionMerge::Appender(kind),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    // Each loop must iterate over whole vector parameters of scalars. The vectorizer binds them
+    // to new names first.
+    let mut data = vec![];
+    for pf in loops.iter() {
+        let mut syms = vec![];
+        for iter in pf.data.iter() {
+            let whole = iter.start.is_none() && iter.shape.is_none();
+            let kind = match iter.kind {
+                ScalarIter | SimdIter | FringeIter => true,
+                _ => false,
+            };
+            if !whole || !kind {
+                return None;
+            }
+            let param = resolve(&defs, &iter.data);
+            let index = program.top_params.iter().position(|p| p.name == *param)?;
+            match program.top_params[index].ty {
+                Vector(ref elem) if elem.is_scalar() => (),
+                _ => return None,
+            }
+            syms.push(index);
+        }
+        syms.sort();
+        syms.dedup();
+        data.push(syms);
+    }
+
+    // Appending chunks keeps the order of the elements only if every loop iterates over the
+    // same vectors.
+    if let PartitionMerge::Appender(_) = merge {
+        if data.iter().any(|syms| *syms != data[0]) {
+            return None;
+        }
+    }
+    let mut vectors: Vec<usize> = data.into_iter().flatten().collect();
+    vectors.sort();
+    vectors.dedup();
+
+    // The chunks must not be visible to the program other than through the loops. Besides
+    // the loops, the names of the vectors may only be bound to other names.
+    let is_vector = |sym: &Symbol| {
+        let param = resolve(&defs, sym);
+        vectors
+            .iter()
+            .any(|i| program.top_params[*i].name == *param)
+    };
+    let uses_chunk = |sym: &Symbol| is_vector(sym) || loops.iter().any(|pf| pf.idx_arg == *sym);
+    for func in program.funcs.iter() {
+        for block in func.blocks.iter() {
+            for statement in block.statements.iter() {
+                match statement.kind {
+                    ParallelFor(ref pf) if loops.iter().any(|l| ptr::eq(*l, pf)) => continue,
+                    Assign(_) if func.id == main.id => {
+                        if statement
+                            .output
+                            .as_ref()
+                            .map_or(false, |sym| is_vector(sym))
+                        {
+                            continue;
+                        }
+                    }
+                    _ => (),
+                }
+                if statement.kind.children().any(|sym| uses_chunk(sym)) {
+                    return None;
+                }
+            }
+            if block.terminator.children().any(|sym| uses_chunk(sym)) {
+                return None;
+            }
+        }
+    }
+
+    Some(Partition { vectors, merge })
+}
+
+/// Returns the symbol that `sym` is bound to by assignments in the main function.
+fn resolve<'a>(defs: &FnvHashMap<&'a Symbol, &'a StatementKind>, sym: &'a Symbol) -> &'a Symbol {
+    let mut sym = sym;
+    while let Some(Assign(ref value)) = defs.get(sym).cloned() {
+        sym = value;
+    }
+    sym
+}
+
+/// Returns the range of elements of a vector of `len` elements in chunk `index` of `count`.
+///
+/// The chunks are contiguous and their sizes differ by at most one element.
+pub fn chunk_range(len: usize, index: usize, count: usize) -> (usize, usize) {
+    let base = len / count;
+    let extra = len % count;
+    let start = base * index + index.min(extra);
+    let size = if index < extra { base + 1 } else { base };
+    (start, start + size)
+}
+
+/// Combines the results of the chunks into the first one.
+///
+/// Each output points to the host copy of the result of a chunk, in the order of the chunks.
+pub unsafe fn merge_results(merge: &PartitionMerge, outputs: &[*mut u8]) {
+    match *merge {
+        PartitionMerge::Merger(kind, op) => {
+            for output in outputs[1..].iter() {
+                combine(kind, op, outputs[0], *output);
+            }
+        }
+        PartitionMerge::Appender(kind) => {
+            //      WeldVec
+            //  0:  intptr_t  data
+            //  8:  u64       length
+            let elem_size = scalar_kind_size(kind);
+            let lens: Vec<usize> = outputs
+                .iter()
+                .map(|output| *((*output).offset(8) as *const u64) as usize)
+                .collect();
+            let total: usize = lens.iter().sum();
+            let data = alloc(total * elem_size);
+            let mut offset = 0;
+            for (output, len) in outputs.iter().zip(lens.iter()) {
+                let src = *(*output as *const u64) as *const u8;
+                ptr::copy_nonoverlapping(src, data.add(offset), len * elem_size);
+                offset += len * elem_size;
+            }
+            *(outputs[0] as *mut u64) = data as u64;
+            *((outputs[0]).offset(8) as *mut u64) = total as u64;
+        }
+    }
+}
+
+/// Merges the scalar at `src` into the scalar at `dst` with `op`.
+unsafe fn combine(kind: ScalarKind, op: BinOpKind, dst: *mut u8, src: *const u8) {
+    use crate::ast::ScalarKind::*;
+
+    macro_rules! combine_as {
+        ($t:ty, $add:ident, $mul:ident) => {{
+            let a = ptr::read_unaligned(dst as *const $t);
+            let b = ptr::read_unaligned(src as *const $t);
+            let value = match op {
+                Add => a.$add(b),
+                Multiply => a.$mul(b),
+                Max => {
+                    if b > a {
+                        b
+                    } else {
+                        a
+                    }
+                }
+                Min => {
+                    if b < a {
+                        b
+                    } else {
+                        a
+                    }
+                }
+                _ => unreachable!(),
+            };
+            ptr::write_unaligned(dst as *mut $t, value);
+        }};
+    }
+
+    match kind {
+        I8 => combine_as!(i8, wrapping_add, wrapping_mul),
+        I16 => combine_as!(i16, wrapping_add, wrapping_mul),
+        I32 => combine_as!(i32, wrapping_add, wrapping_mul),
+        I64 => combine_as!(i64, wrapping_add, wrapping_mul),
+        U8 => combine_as!(u8, wrapping_add, wrapping_mul),
+        U16 => combine_as!(u16, wrapping_add, wrapping_mul),
+        U32 => combine_as!(u32, wrapping_add, wrapping_mul),
+        U64 => combine_as!(u64, wrapping_add, wrapping_mul),
+        F32 => combine_as!(f32, add, mul),
+        F64 => combine_as!(f64, add, mul),
+        Decimal(_, _) => combine_as!(i64, wrapping_add, wrapping_mul),
+        Date => combine_as!(i32, wrapping_add, wrapping_mul),
+        Timestamp | Interval => combine_as!(i64, wrapping_add, wrapping_mul),
+        Bool => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+fn partition_of(code: &str) -> Option<Partition> {
+    use crate::tests::typed_expression;
+    find_partition(&ast_to_sir(&typed_expression(code)).unwrap())
+}
+
+#[test]
+fn partition_reductions() {
+    let code = "|x:vec[i64], a:i64| result(for(x, merger[i64,+], |b,i,e| merge(b, e * a)))";
+    let expect = Partition {
+        vectors: vec![0],
+        merge: PartitionMerge::Merger(ScalarKind::I64, Add),
+    };
+    assert_eq!(partition_of(code), Some(expect));
+
+    let code = "|a:i32, x:vec[f64]| result(for(x, appender[f64], |b,i,e| merge(b, e + 1.0)))";
+    let expect = Partition {
+        vectors: vec![1],
+        merge: PartitionMerge::Appender(ScalarKind::F64),
+    };
+    assert_eq!(partition_of(code), Some(expect));
+}
+
+#[test]
+fn partition_rejects_whole_vectors() {
+    // The loop uses its index.
+    let code = "|x:vec[i64]| result(for(x, appender[i64], |b,i,e| merge(b, e + i)))";
+    assert_eq!(partition_of(code), None);
+    // The program uses the length of the vector.
+    let code = "|x:vec[i64]| result(for(x, merger[i64,+], |b,i,e| merge(b, e + len(x))))";
+    assert_eq!(partition_of(code), None);
+    // The result is not a builder.
+    let code = "|x:vec[i64]| len(x)";
+    assert_eq!(partition_of(code), None);
+    // Subtraction is not associative.
+    let code = "|x:vec[i64]| result(for(x, merger[i64,-], |b,i,e| merge(b, e)))";
+    assert_eq!(partition_of(code), None);
+}
+
+#[test]
+fn partition_chunk_ranges() {
+    let ranges: Vec<_> = (0..3).map(|i| chunk_range(10, i, 3)).collect();
+    assert_eq!(ranges, vec![(0, 4), (4, 7), (7, 10)]);
+    let ranges: Vec<_> = (0..4).map(|i| chunk_range(2, i, 4)).collect();
+    assert_eq!(ranges, vec![(0, 1), (1, 2), (2, 2), (2, 2)]);
+}
+
+#[test]
+fn partition_runs_on_every_node() {
+    use crate::util::fakeve::get_fake_ve_ptr;
+    use crate::{Data, WeldConf, WeldContext, WeldModule, WeldValue};
+
+    #[allow(dead_code)]
+    struct Input {
+        data: *const i64,
+        len: i64,
+    }
+
+    // These nodes are not used by other tests, so their calls can be counted.
+    let nodes = [71, 72, 73];
+    let mut conf = WeldConf::new();
+    conf.set("weld.backend", "c-fake-ve");
+    conf.set("weld.ve.nodes", "71,72,73");
+
+    let code = "|x:vec[i64]| result(for(x, merger[i64,+], |b,i,e| merge(b, e)))";
+    let module = WeldModule::compile(code, &conf).unwrap();
+    let context = &mut WeldContext::new(&conf).unwrap();
+    let x: Vec<i64> = (0..100).collect();
+    let input = Input {
+        data: x.as_ptr(),
+        len: x.len() as i64,
+    };
+    let value = WeldValue::new_from_data(&input as *const _ as Data);
+    let result = unsafe { module.run(context, &value).unwrap() };
+    assert_eq!(unsafe { *(result.data() as *const i64) }, 4950);
+    for node in nodes.iter() {
+        assert!(unsafe { (*get_fake_ve_ptr(*node)).calls() } > 0);
+    }
+}
@@ -6,31 +6,29 @@ use libc;
 use time;
 
 use std::ptr;
+use std::rc::Rc;
 use std::sync::{Once, ONCE_INIT};
 
 use libc::{uint64_t, c_void};
 
-use self::time::PreciseTime;
+use self::time::{Duration, PreciseTime};
 
 use crate::WeldError;
 use crate::ast::Type;
 use crate::ast::Type::*;
 use crate::ast::ScalarKind;
-use crate::util::stats::RunStats;
+use crate::conf::SerializePolicy;
+use crate::util::stats::{RunStats, TransferDecision};
 use crate::util::veoffload::*;
 
-use crate::codegen::Runnable;
+use crate::codegen::{DeviceValue, Runnable};
+use crate::codegen::c::marshal::{self, Dest, Layout, Packer};
+use crate::codegen::c::partition::{self, Partition};
+use crate::runtime::{WeldRuntimeContext, WeldRuntimeErrno};
 
 static ONCE: Once = ONCE_INIT;
 static mut INITIALIZE_FAILED: bool = false;
 
-// Several configuration of this run function
-// Whether to check and dump data or not.
-static CHECK_DATA: bool = false;
-static DUMP_DATA: bool = false;
-static USE_CONVERT_TOP_PARAMS: bool = false;
-static SERIALIZE_THRESHOLD: usize = 1024000;
-
 /// The callable function type.
 type I64Func = extern "C" fn(i64) -> i64;
 
@@ -41,19 +39,75 @@ use crate::codegen::c::compile::*;
 // This allows supporting multiple backends via dynamic dispatch.
 impl Runnable for CompiledModule {
     fn run(&self, arg: i64, stats: &mut RunStats) -> Result<i64, WeldError> {
-        use crate::runtime::WeldRuntimeErrno;
+        self.run_offload(arg, None, stats).map(|(ret, _)| ret)
+    }
+
+    fn run_offload(
+        &self,
+        arg: i64,
+        device_arg: Option<&DeviceValue>,
+        stats: &mut RunStats,
+    ) -> Result<(i64, Option<DeviceValue>), WeldError> {
         unsafe {
-            let veo_ptr = get_global_veo_ptr();
+            if device_arg.is_none() {
+                if let Some(ref partition) = self.partition {
+                    if let Some(ret) = self.run_partitioned(partition, arg, stats)? {
+                        return Ok((ret, None));
+                    }
+                }
+            }
+            let launch = self.launch(self.offload, arg, device_arg, stats)?;
             let start = PreciseTime::now();
-            while !(*veo_ptr).ready {}
+            let retval = (*launch.device).call_and_wait(
+                launch.libhdl, "run", &[launch.addrs_ve[0]], self.ve.timeout())?;
+            let end = PreciseTime::now();
+            stats.run_times.push(("call run".to_string(), start.to(end)));
+            self.finish(launch, retval, stats)
+        }
+    }
+}
+
+/// A run whose parameters were sent to a device, and whose entry function can be called.
+struct Launch {
+    /// The device that runs the module.
+    device: *mut dyn OffloadTransport,
+    /// Handle of the module loaded on the device.
+    libhdl: VeoHandle,
+    /// Device memory to free after the run. The first buffer holds the `WeldInputArgs`.
+    addrs_ve: Vec<u64>,
+    /// The host runtime context of the run.
+    context: *mut WeldRuntimeContext,
+    /// The device-side context created for a result that stays on the device.
+    value_run: Option<DeviceRun>,
+}
+
+/// A device pointer that is moved to the thread calling the device.
+struct DevicePtr(*mut dyn OffloadTransport);
+
+// Each device is only called by one thread at a time.
+unsafe impl Send for DevicePtr {}
+
+impl CompiledModule {
+    /// Loads the module on `device` and sends the parameters of a run.
+    fn launch(
+        &self,
+        device: *mut dyn OffloadTransport,
+        arg: i64,
+        device_arg: Option<&DeviceValue>,
+        stats: &mut RunStats,
+    ) -> Result<Launch, WeldError> {
+        unsafe {
+            let veo_ptr = device;
+            let start = PreciseTime::now();
+            ensure_ready(veo_ptr)?;
+            let generation = (*veo_ptr).generation();
             let end = PreciseTime::now();
             stats.run_times.push((
                 "wait initialize veo".to_string(), start.to(end)));
 
             // Load generated shared-object by Weld on VE.
             let start = PreciseTime::now();
-            let libname = format!("./{}", self.filename);
-            let libhdl_run = (*veo_ptr).load_library(libname)?;
+            let libhdl_run = (*veo_ptr).load_library(&self.filename)?;
             let end = PreciseTime::now();
             stats.run_times.push(("veo_load_library".to_string(), start.to(end)));
 
@@ -70,14 +124,12 @@ impl Runnable for CompiledModule {
             let data_ptr = &(*input_ptr).input as *const i64 as u64;
             let nworkers = (*input_ptr).nworkers;
             let mem_limit = (*input_ptr).mem_limit;
-            let mut run = (*input_ptr).run;
-            use crate::runtime::WeldRuntimeContext;
+            let run = (*input_ptr).run;
             let context: *mut WeldRuntimeContext;
             if run == 0 {
                 // Call weld_runst_init to instanciate WeldRuntimeContext.
                 use crate::runtime::ffi::weld_runst_init;
                 context = weld_runst_init(nworkers, mem_limit);
-                run = context as i64;
             } else {
                 // Use existing WeldRuntimeContext.
                 // (Usually, it is created by caller of this function)
@@ -87,20 +139,52 @@ impl Runnable for CompiledModule {
             stats.run_times.push(("from_raw".to_string(), start.to(end)));
 
             // Check parameters.
-            if CHECK_DATA {
+            if self.ve.check_data && device_arg.is_none() {
                 println!("parameters {:?}", self.params);
                 self.check_data(&self.params, data_ptr)?;
             }
 
+            // Runs whose results are copied to the host reuse the context that earlier runs
+            // with this context created on the VE. A result that stays on the VE gets a
+            // context of its own, which is freed with the result.
+            let device = veo_ptr as *mut u8 as u64;
             let mut input_generated = WeldInputArgs {
                 input: 0,
                 nworkers,
                 mem_limit,
-                run: 0, // FIXME: need to recover VE's run if it is avilable.
+                run: if self.ve.device_results {
+                    0
+                } else {
+                    (*context).device_run(device, generation)
+                },
             };
 
-            let (_buffer, addrs_ve, _buffer_size) = if USE_CONVERT_TOP_PARAMS {
+            // Create the context on the VE before sending the parameters, since dictionaries
+            // and builders are copied into its memory.
+            let mut value_run = None;
+            if input_generated.run == 0 {
+                input_generated.run = (*veo_ptr).call_and_wait(
+                    libhdl_run, "run_init", &[nworkers as u64, mem_limit as u64],
+                    self.ve.timeout())? as i64;
+                let run = DeviceRun {
+                    device: veo_ptr,
+                    generation,
+                    libhdl: libhdl_run,
+                    run: input_generated.run,
+                    timeout: self.ve.timeout(),
+                };
+                if self.ve.device_results {
+                    value_run = Some(run);
+                } else {
+                    (*context).set_device_run(device, run);
+                }
+            }
+
+            let (_buffer, addrs_ve, _buffer_size) = if let Some(value) = device_arg {
+                self.send_device_arg(veo_ptr, value, &mut input_generated, stats)?
+            } else if self.ve.convert_top_params {
                 self.send_data_using_convert_top_params(
+                    veo_ptr,
                     &self.params,
                     data_ptr,
                     &mut input_generated,
@@ -108,24 +192,40 @@ impl Runnable for CompiledModule {
                 )?
             } else {
                 self.send_data_using_new_mechanism(
+                    veo_ptr,
                     &self.params,
                     data_ptr,
+                    libhdl_run,
                     &mut input_generated,
                     stats,
                 )?
             };
 
-            let start = PreciseTime::now();
-            let args = VEOffload::args_alloc();
-            VEOffload::args_set(args, 0, addrs_ve[0]);
-            let end = PreciseTime::now();
-            stats.run_times.push(("prepare arguments".to_string(), start.to(end)));
+            Ok(Launch {
+                device: veo_ptr,
+                libhdl: libhdl_run,
+                addrs_ve,
+                context,
+                value_run,
+            })
+        }
+    }
 
-            let start = PreciseTime::now();
-            let retval_ve_ptr: uint64_t = (*veo_ptr).call_and_wait(libhdl_run, "run", args)?;
-            let errno = WeldRuntimeErrno::Success;
-            let end = PreciseTime::now();
-            stats.run_times.push(("call run".to_string(), start.to(end)));
+    /// Reads the result of a run whose entry function returned `retval_ve_ptr`, and frees the
+    /// device memory of its parameters.
+    ///
+    /// Returns the error set by the run on the VE, with the message it left, if the run
+    /// failed.
+    fn finish(
+        &self,
+        launch: Launch,
+        retval_ve_ptr: uint64_t,
+        stats: &mut RunStats,
+    ) -> Result<(i64, Option<DeviceValue>), WeldError> {
+        unsafe {
+            let veo_ptr = launch.device;
+            let context = launch.context;
+            let run = context as i64;
 
             let start = PreciseTime::now();
             // Allocate and read WeldOutputArgs from VE memory.
@@ -134,46 +234,206 @@ impl Runnable for CompiledModule {
             //   8: i64      run
             //  16: i64      errno
             use crate::codegen::WeldOutputArgs;
-            use crate::runtime::ffi::weld_runst_malloc;
+            use crate::runtime::ffi::{weld_runst_free, weld_runst_malloc};
             use std::mem::size_of;
             let output_size = size_of::<WeldOutputArgs>();
             let ret = weld_runst_malloc(context, output_size as i64)
                 as *mut WeldOutputArgs;
-            if errno != WeldRuntimeErrno::Unknown {
-                (*veo_ptr).read_mem(retval_ve_ptr, ret as *mut c_void, output_size)?;
-                // FIXME: need to handle VE's run correctly for latter calls.
-                // Overwrite VE's `run` by HOST's run.
-                (*ret).run = run;
-            } else {
-                // NOTE: never pass below
+            let read_start = PreciseTime::now();
+            (*veo_ptr).read_mem(retval_ve_ptr, ret as *mut c_void, output_size)?;
+            // A small transfer mostly measures the latency of transfers.
+            self.transfer_model.lock().unwrap().record_transfers(
+                1, output_size, seconds(read_start.to(PreciseTime::now())));
+            // The VE's `run` was recorded by `launch`, so overwrite it by HOST's run.
+            (*ret).run = run;
+            // The VE wrote a plain integer, so check it before using it as an errno.
+            let code = ptr::read(&(*ret).errno as *const WeldRuntimeErrno as *const u64);
+            (*ret).errno = errno_from_code(code);
+
+            // Copy VE's output to VH if calculation was succeeded, unless it should stay
+            // on the VE. The output of a failed run points to its error message instead.
+            let mut device_result = None;
+            let mut error = None;
+            if (*ret).errno != WeldRuntimeErrno::Success {
+                let message = read_error_message(&*veo_ptr, (*ret).output as u64)?;
+                let message = if message.is_empty() {
+                    format!("Weld program failed with error {:?}", (*ret).errno)
+                } else {
+                    format!("Weld program failed with error {:?}: {}", (*ret).errno, message)
+                };
+                error = Some(WeldError::new(message, (*ret).errno));
+            } else if self.ve.device_results {
+                device_result = Some(DeviceValue {
+                    addr: (*ret).output as u64,
+                    ty: result_type(&self.ret_ty),
+                    device: veo_ptr,
+                    generation: (*veo_ptr).generation(),
+                    vector_width: self.layout.vector_width as u32,
+                    run: Rc::new(launch.value_run.expect("device results have a run")),
+                });
                 (*ret).output = 0;
-                (*ret).run = run;
-                (*ret).errno = errno;
-            }
-            // Copy VE's output to VH if calculation was succeeded
-            if (*ret).errno == WeldRuntimeErrno::Success {
-                let proc_handle = (*veo_ptr).proc;
-                self.convert_results(&self.ret_ty, (*ret).output as u64,
-                                     &mut (*ret).output as *mut i64 as u64,
-                                     proc_handle)?;
+            } else {
+                convert_results(&self.layout, &self.ret_ty, (*ret).output as u64,
+                                &mut (*ret).output as *mut i64 as u64,
+                                &*veo_ptr)?;
             }
             let end = PreciseTime::now();
             stats.run_times.push(("convert_results".to_string(), start.to(end)));
 
             let start = PreciseTime::now();
-            VEOffload::args_free(args);
-            for addr_ve in addrs_ve {
+            for addr_ve in launch.addrs_ve {
                 (*veo_ptr).free_mem(addr_ve)?;
             }
             let end = PreciseTime::now();
             stats.run_times.push(("free and destroy".to_string(), start.to(end)));
 
-            Ok(ret as i64)
+            if let Some(err) = error {
+                weld_runst_free(context, ret as *mut u8);
+                return Err(err);
+            }
+            Ok((ret as i64, device_result))
+        }
+    }
+
+    /// Runs the module on chunks of the partitioned vectors, one chunk per device of the
+    /// pool, and merges the results of the chunks into the result of the first one.
+    ///
+    /// Returns `None` if the run is not split, e.g. because the partitioned vectors have
+    /// different lengths.
+    fn run_partitioned(
+        &self,
+        partition: &Partition,
+        arg: i64,
+        stats: &mut RunStats,
+    ) -> Result<Option<i64>, WeldError> {
+        use crate::codegen::{WeldInputArgs, WeldOutputArgs};
+        use std::thread;
+        unsafe {
+            let input_ptr = arg as *const WeldInputArgs;
+            let fields_ptr = (*input_ptr).input as u64;
+            let field_tys = match self.params {
+                Struct(ref fields) => fields,
+                _ => unreachable!(),
+            };
+
+            // Find the partitioned vectors in the struct of parameters.
+            //
+            //      WeldVec
+            //  0:  intptr_t  data
+            //  8:  u64       length
+            let mut vectors = vec![];
+            let mut offset = 0;
+            for (i, f) in field_tys.iter().enumerate() {
+                if partition.vectors.contains(&i) {
+                    let elem_size = match *f {
+                        Vector(ref elem) => self.calc_data_size(elem)?,
+                        _ => unreachable!(),
+                    };
+                    vectors.push((offset, elem_size));
+                }
+                offset += self.calc_data_size(f)?;
+            }
+            let params_size = offset;
+            let length_of = |offset: usize| {
+                ptr::read_unaligned((fields_ptr as usize + offset + 8) as *const u64) as usize
+            };
+            let len = length_of(vectors[0].0);
+            if vectors.iter().any(|&(offset, _)| length_of(offset) != len) {
+                return Ok(None);
+            }
+            // Do not create more chunks than there are elements.
+            let count = self.pool.len().min(len);
+            if count < 2 {
+                return Ok(None);
+            }
+
+            // The chunks share the host context, which keeps the contexts of the runs on each
+            // device.
+            let mut run = (*input_ptr).run;
+            if run == 0 {
+                use crate::runtime::ffi::weld_runst_init;
+                run = weld_runst_init((*input_ptr).nworkers, (*input_ptr).mem_limit) as i64;
+            }
+
+            // The vectors in the parameters of a chunk point into the vectors of the run.
+            let start = PreciseTime::now();
+            let mut chunk_params = vec![];
+            for k in 0..count {
+                let mut params = vec![0u8; params_size];
+                copy_nonoverlapping(fields_ptr as *const u8, params.as_mut_ptr(), params_size);
+                let (begin, end) = partition::chunk_range(len, k, count);
+                for &(offset, elem_size) in vectors.iter() {
+                    let vec = params.as_mut_ptr().add(offset) as *mut u64;
+                    let data = ptr::read_unaligned(vec) + (begin * elem_size) as u64;
+                    ptr::write_unaligned(vec, data);
+                    ptr::write_unaligned(vec.add(1), (end - begin) as u64);
+                }
+                chunk_params.push(params);
+            }
+            let chunk_args: Vec<WeldInputArgs> = chunk_params.iter().map(|params| {
+                WeldInputArgs {
+                    input: params.as_ptr() as i64,
+                    nworkers: (*input_ptr).nworkers,
+                    mem_limit: (*input_ptr).mem_limit,
+                    run,
+                }
+            }).collect();
+            let end = PreciseTime::now();
+            stats.run_times.push(("partition".to_string(), start.to(end)));
+
+            // Sending parameters and receiving results use the host context, so they are done
+            // one device at a time. Only the kernels run at the same time.
+            let mut launches = vec![];
+            for (device, args) in self.pool.iter().zip(chunk_args.iter()) {
+                let arg = args as *const WeldInputArgs as i64;
+                launches.push(self.launch(*device, arg, None, stats)?);
+            }
+
+            let start = PreciseTime::now();
+            let timeout = self.ve.timeout();
+            let calls: Vec<_> = launches.iter().map(|launch| {
+                let device = DevicePtr(launch.device);
+                let libhdl = launch.libhdl;
+                let input = launch.addrs_ve[0];
+                thread::spawn(move || {
+                    (*device.0).call_and_wait(libhdl, "run", &[input], timeout)
+                })
+            }).collect();
+            let results: Vec<_> = calls.into_iter().map(|call| call.join()).collect();
+            let end = PreciseTime::now();
+            stats.run_times.push(("call run".to_string(), start.to(end)));
+
+            // Finish every chunk so that the memory of all chunks is freed, and fail with the
+            // error of the first chunk that failed.
+            let mut rets = vec![];
+            let mut error = None;
+            for (launch, result) in launches.into_iter().zip(results) {
+                let retval = match result {
+                    Ok(retval) => retval?,
+                    Err(_) => return weld_err!("Call of the module on a VE panicked"),
+                };
+                match self.finish(launch, retval, stats) {
+                    Ok((ret, _)) => rets.push(ret as *mut WeldOutputArgs),
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                }
+            }
+            if let Some(err) = error {
+                return Err(err);
+            }
+
+            let start = PreciseTime::now();
+            let outputs: Vec<*mut u8> =
+                rets.iter().map(|ret| (**ret).output as *mut u8).collect();
+            partition::merge_results(&partition.merge, &outputs);
+            let end = PreciseTime::now();
+            stats.run_times.push(("merge partitions".to_string(), start.to(end)));
+
+            Ok(Some(rets[0] as i64))
         }
     }
-}
 
-impl CompiledModule {
     fn check_data(&self, ty: &Type, addr: u64) -> Result<u64, WeldError> {
         // Dump information of weld's type
         let view = addr as *const u8;
@@ -222,11 +482,13 @@ impl CompiledModule {
                 */
                 Ok(0)
             }
-            Dict(_, _) => {
-                weld_err!("Unsupported dict type {}", ty)
+            Dict(ref key, ref value) => {
+                println!("  is Dict(key={}, value={})", key, value);
+                Ok(0)
             }
-            Builder(_, _) => {
-                weld_err!("Unsupported builder type {}", ty)
+            Builder(ref kind, _) => {
+                println!("  is Builder({:?})", kind);
+                Ok(0)
             }
             _ => {
                 weld_err!("Unsupported type {}", ty)
@@ -236,6 +498,7 @@ impl CompiledModule {
 
     fn send_data_using_convert_top_params(
         &self,
+        device: *mut dyn OffloadTransport,
         ty: &Type,
         data_ptr: u64,
         input_generated: &mut crate::codegen::WeldInputArgs,
@@ -243,7 +506,7 @@ impl CompiledModule {
     ) -> Result<(Vec<u8>, Vec<u64>, usize), WeldError> {
         use crate::codegen::WeldInputArgs;
         unsafe {
-            let veo_ptr = get_global_veo_ptr();
+            let veo_ptr = device;
             // First, calculate the size of memory needed to be allocated by
             // the size of WeldInputArgs and the given data.
             let start = PreciseTime::now();
@@ -298,14 +561,16 @@ impl CompiledModule {
 
     fn send_data_using_new_mechanism(
         &self,
+        device: *mut dyn OffloadTransport,
         ty: &Type,
         data_ptr: u64,
+        libhdl: VeoHandle,
         input_generated: &mut crate::codegen::WeldInputArgs,
         stats: &mut RunStats,
     ) -> Result<(Vec<u8>, Vec<u64>, usize), WeldError> {
         use crate::codegen::WeldInputArgs;
         unsafe {
-            let veo_ptr = get_global_veo_ptr();
+            let veo_ptr = device;
             // Retrieve files and table data of structure first.
             let field_tys = { match *ty {
                 Struct(ref fields) => fields,
@@ -334,22 +599,53 @@ impl CompiledModule {
                 fields_ptr,
             )?;
             let end = PreciseTime::now();
-            stats.run_times.push(("convert_data".to_string(), start.to(end)));
+            let convert_time = start.to(end);
+            stats.run_times.push(("convert_data".to_string(), convert_time));
 
             let mut addrs = Vec::<*const c_void>::new();
             let mut sizes = Vec::new();
             let mut serialized_flags = Vec::new();
+            let mut marshalled_flags = Vec::new();
+            let mut packer = Packer::new(self.layout);
             // Prepare for the first buffer.
             addrs.push(&buffer[0] as *const u8 as *const c_void);
             sizes.push(buffer_size);
             serialized_flags.push(false);
+            marshalled_flags.push(false);
             // Prepare for the rests.
             let mut field_offset = 0;
             for (i, f) in field_tys.iter().enumerate() {
                 let faddr = fields_ptr + field_offset as u64;
+                if marshal::needs_marshal(f) {
+                    if let Struct(_) = *f {
+                        return weld_err!(
+                            "Unsupported struct type {} with dictionaries or builders", f);
+                    }
+                    // Dictionaries and builders are packed into memory of the run on the VE,
+                    // and their pointers in the first buffer are patched once it is unpacked.
+                    let dest = &mut buffer[input_size + flag_size + field_offset] as *mut u8;
+                    packer.pack(f, faddr as *const u8, Dest::Host(dest))?;
+                    addrs.push(ptr::null());
+                    sizes.push(0);
+                    serialized_flags.push(false);
+                    marshalled_flags.push(true);
+                    field_offset += self.calc_data_size(f)?;
+                    continue;
+                }
+                marshalled_flags.push(false);
                 let addr = *(faddr as *const u64);
+                let size = self.calc_contents_size(f, faddr)?;
                 addrs.push(addr as *const c_void);
-                sizes.push(self.calc_contents_size(f, faddr)?);
+                sizes.push(size);
+                if is_flat_vector(f) {
+                    let (_, measured) = self.serialize_decision(size);
+                    stats.transfer_decisions.push(TransferDecision {
+                        param: i,
+                        bytes: size,
+                        serialized: buffer[input_size + i] == 1,
+                        measured,
+                    });
+                }
                 if buffer[input_size + i] == 1 {
                     // No need to alloc_mem/write_mem for serialized data.
                     // Data is serialized into the first buffer.
@@ -368,7 +664,7 @@ impl CompiledModule {
                                 input_size);
 
             // dump serialized data
-            if DUMP_DATA {
+            if self.ve.dump_data {
                 dump_data(&buffer[0] as *const u8, 160);
             }
 
@@ -376,14 +672,28 @@ impl CompiledModule {
             let start = PreciseTime::now();
             let mut addrs_ve = Vec::new();
             for i in 0..addrs.len() {
-                if !serialized_flags[i] {
+                if !serialized_flags[i] && !marshalled_flags[i] {
                     addrs_ve.push((*veo_ptr).alloc_mem(sizes[i])?);
                 } else {
                     addrs_ve.push(0 as u64);
                 }
             }
             let end = PreciseTime::now();
-            stats.run_times.push(("veo_alloc_mem".to_string(), start.to(end)));
+            let alloc_time = start.to(end);
+            stats.run_times.push(("veo_alloc_mem".to_string(), alloc_time));
+
+            // Start transferring the parameters that are not serialized. They do not depend
+            // on the first buffer, so they transfer while it is deserialized.
+            let start = PreciseTime::now();
+            let mut transfers = TransferQueue::new(&*veo_ptr);
+            for i in 1..addrs.len() {
+                if !serialized_flags[i] && !marshalled_flags[i] {
+                    transfers.write(addrs[i], addrs_ve[i], sizes[i])?;
+                }
+            }
+            let end = PreciseTime::now();
+            let async_write_time = start.to(end);
+            stats.run_times.push(("veo_async_write_mem".to_string(), async_write_time));
 
             // Deserialize data on host.
             let start = PreciseTime::now();
@@ -391,26 +701,93 @@ impl CompiledModule {
                 field_tys,
                 &mut buffer,
                 &serialized_flags,
+                &marshalled_flags,
                 &addrs_ve,
             )?;
             let end = PreciseTime::now();
-            stats.run_times.push(("deserialize_on_host".to_string(), start.to(end)));
+            let deserialize_time = start.to(end);
+            stats.run_times.push(("deserialize_on_host".to_string(), deserialize_time));
+
+            let start = PreciseTime::now();
+            packer.unpack(&mut *veo_ptr, libhdl, "run_unpack", input_generated.run,
+                          self.ve.timeout())?;
+            let end = PreciseTime::now();
+            stats.run_times.push(("unpack".to_string(), start.to(end)));
 
             // dump deserialized data
-            if DUMP_DATA {
+            if self.ve.dump_data {
                 dump_data(&buffer[0] as *const u8, 160);
             }
 
             let start = PreciseTime::now();
-            for i in 0..addrs.len() {
-                if !serialized_flags[i] {
-                    (*veo_ptr).write_mem(addrs[i], addrs_ve[i], sizes[i])?;
+            transfers.write(&buffer[0] as *const u8 as *const c_void, addrs_ve[0], sizes[0])?;
+            transfers.wait_all()?;
+            let end = PreciseTime::now();
+            let write_time = start.to(end);
+            stats.run_times.push(("veo_write_mem".to_string(), write_time));
+
+            // Record the cost of the transfers and of serializing for the adaptive policy.
+            let transferred: Vec<usize> =
+                (0..addrs_ve.len()).filter(|i| addrs_ve[*i] != 0).collect();
+            let bytes = transferred.iter().map(|i| sizes[*i]).sum();
+            let mut model = self.transfer_model.lock().unwrap();
+            model.record_transfers(transferred.len(), bytes,
+                                   seconds(alloc_time + async_write_time + write_time));
+            model.record_copy(buffer_size, seconds(convert_time + deserialize_time));
+
+            Ok((buffer, addrs_ve, buffer_size))
+        }
+    }
+    /// Passes a value that is already in VE memory as the parameters.
+    ///
+    /// The value must have the type of the parameters, or of the only parameter. Only the
+    /// `WeldInputArgs` are written to the VE.
+    fn send_device_arg(
+        &self,
+        device: *mut dyn OffloadTransport,
+        value: &DeviceValue,
+        input_generated: &mut crate::codegen::WeldInputArgs,
+        stats: &mut RunStats,
+    ) -> Result<(Vec<u8>, Vec<u64>, usize), WeldError> {
+        use crate::codegen::WeldInputArgs;
+        unsafe {
+            let veo_ptr = device;
+            if value.device as *mut u8 != veo_ptr as *mut u8 {
+                return weld_err!("Argument of type {} resides on another device", value.ty);
+            }
+            check_generation(value)?;
+            let matches = match self.params {
+                Struct(ref fields) => {
+                    value.ty == self.params || (fields.len() == 1 && fields[0] == value.ty)
                 }
+                _ => false,
+            };
+            if !matches {
+                return weld_err!("Device-resident argument of type {} does not match parameters {}",
+                                 value.ty, self.params);
             }
+
+            use std::mem::size_of;
+            let input_size = size_of::<WeldInputArgs>();
+
+            let start = PreciseTime::now();
+            let addr_ve = (*veo_ptr).alloc_mem(input_size)?;
+            let end = PreciseTime::now();
+            stats.run_times.push(("veo_alloc_mem".to_string(), start.to(end)));
+
+            input_generated.input = value.addr as i64;
+            let mut buffer = Vec::<u8>::with_capacity(input_size);
+            buffer.set_len(input_size);
+            copy_nonoverlapping(input_generated as *const WeldInputArgs
+                                as *const u8, &mut buffer[0],
+                                input_size);
+
+            let start = PreciseTime::now();
+            (*veo_ptr).write_mem(&buffer[0] as *const u8 as *const c_void, addr_ve, input_size)?;
             let end = PreciseTime::now();
             stats.run_times.push(("veo_write_mem".to_string(), start.to(end)));
 
-            Ok((buffer, addrs_ve, buffer_size))
+            Ok((buffer, vec![addr_ve], input_size))
         }
     }
     fn deserialize_on_host(
@@ -418,6 +795,7 @@ impl CompiledModule {
         field_tys: &[Type],             // 0..N
         buffer: &mut Vec<u8>,
         serialized_flags: &[bool],      // 0..N+1
+        marshalled_flags: &[bool],      // 0..N+1
         addrs_ve: &Vec<u64>,            // 0..N+1
     ) -> Result<(), WeldError> {
         // First deserialize the pointer to struct.
@@ -430,7 +808,9 @@ impl CompiledModule {
         let mut field_offset = 0;
         for (i, f) in field_tys.iter().enumerate() {
             // Work on each param.
-            if serialized_flags[i+1] {
+            if marshalled_flags[i+1] {
+                // Pointers of this param are patched by the packer.
+            } else if serialized_flags[i+1] {
                 // Deserialized this param recursively.
                 self._deserialize_on_host(
                     f,
@@ -520,14 +900,17 @@ impl CompiledModule {
 
     fn should_serialize_data(&self, ty: &Type, data: u64)
         -> Result<bool, WeldError> {
+        if marshal::needs_marshal(ty) {
+            // Dictionaries and builders are packed by the packer instead.
+            return Ok(false);
+        }
         match *ty {
             // Should not serialize if the data is a vector of scalar or simd
-            // and its size is more than or equal to SERIALIZE_THRESHOLD.
+            // and the serialize policy sends it separately.
             Vector(ref elem) => {
                 match **elem {
                     Scalar(_) | Simd(_) =>
-                        Ok(self.calc_contents_size(ty, data)? <
-                           SERIALIZE_THRESHOLD),
+                        Ok(self.serialize_decision(self.calc_contents_size(ty, data)?).0),
                     _ => Ok(true),
                 }
             }
@@ -535,6 +918,17 @@ impl CompiledModule {
         }
     }
 
+    /// Returns whether contents of `size` bytes are packed into the buffer of serialized
+    /// parameters, and whether this was decided from measured transfers.
+    fn serialize_decision(&self, size: usize) -> (bool, bool) {
+        if self.ve.serialize_policy == SerializePolicy::Adaptive {
+            if let Some(pack) = self.transfer_model.lock().unwrap().should_pack(size) {
+                return (pack, true);
+            }
+        }
+        (size < self.ve.serialize_threshold, false)
+    }
+
     fn convert_data(&self, field_tys: &[Type], field_data: u64)
         -> Result<(Vec<u8>, usize), WeldError> {
         // We have a list of parameters of top level funtion and
@@ -858,40 +1252,14 @@ impl CompiledModule {
             _ => Ok(0),
         }
     }
-    // Calculate the size of outermost data structure.
-    // In order to calculate the size of whole data, use both
-    // this function and calc_contents_size().
+    // Calculate the size of outermost data structure (see data_size()).
     fn calc_data_size(&self, ty: &Type) -> Result<usize, WeldError> {
-        match *ty {
-            Scalar(kind) => Ok(scalar_kind_size(kind)),
-            Simd(kind) => Ok(simd_size(&Scalar(kind)) * scalar_kind_size(kind)),
-
-            // Struct(fields) is a pointer to an array of each field
-            Struct(_) => Ok(8),
-            // Vector(elem) is following 16 bytes data structure.
-            //  0:  intptr_t  data
-            //  8:  u64       length
-            Vector(_) => Ok(16),
-            Dict(_, _) => {
-                weld_err!("Unsupported dict type {}", ty)
-            }
-            Builder(_, _) => {
-                weld_err!("Unsupported builder type {}", ty)
-            }
-            _ => {
-                weld_err!("Unsupported type {}", ty)
-            }
-        }
+        data_size(&self.layout, ty)
     }
     // Calculate of the size of Struct's field data size.
     fn calc_struct_field_size(&self, field_tys: &[Type])
         -> Result<usize, WeldError> {
-        let mut sz = 0;
-        for f in field_tys.iter() {
-            let data_size = self.calc_data_size(f)?;
-            sz += data_size;
-        }
-        Ok(sz)
+        struct_field_size(&self.layout, field_tys)
     }
     fn convert_top_params(&self, ty: &Type, data: u64,
                           vh_addr: u64, ve_addr: u64) -> Result<(), WeldError> {
@@ -1000,193 +1368,280 @@ impl CompiledModule {
             }
         }
     }
-    fn convert_results(&self, ty: &Type, ve_addr: u64, vh_addr: u64,
-                       proc_handle: VeoProcHandleRef) ->
-                       Result<(), WeldError> {
-        match *ty {
-            Function(_, ref ret_ty) => {
-                self.convert_result(&ret_ty, ve_addr, vh_addr, proc_handle)?;
-                Ok(())
-            }
-            _ => {
-                self.convert_result(&*ty, ve_addr, vh_addr, proc_handle)?;
-                Ok(())
-                // weld_err!("Invalid type {} for the type of return type", ty)
-            }
+}
+
+/// Copies a result kept in VE memory to the host and returns a pointer to the copy.
+pub unsafe fn download(value: &DeviceValue) -> Result<i64, WeldError> {
+    check_generation(value)?;
+    let mut output = 0i64;
+    let layout = Layout::new(value.vector_width);
+    convert_results(&layout, &value.ty, value.addr, &mut output as *mut i64 as u64,
+                    &*value.device)?;
+    Ok(output)
+}
+
+/// Checks that the device process holding `value` was not re-created since it was produced.
+unsafe fn check_generation(value: &DeviceValue) -> Result<(), WeldError> {
+    if (*value.device).generation() != value.generation {
+        return device_err!("Value of type {} was lost when its device process was re-created",
+                           value.ty);
+    }
+    Ok(())
+}
+
+/// Returns the errno with the numeric `code`, or `Unknown` if there is none.
+fn errno_from_code(code: u64) -> WeldRuntimeErrno {
+    if code < WeldRuntimeErrno::ErrnoMax as u64 {
+        // The errno is a `repr(u64)` enum with contiguous values.
+        unsafe { std::mem::transmute::<u64, WeldRuntimeErrno>(code) }
+    } else {
+        WeldRuntimeErrno::Unknown
+    }
+}
+
+/// Reads the message that a failed run left at `addr` on the device.
+unsafe fn read_error_message(offload: &dyn OffloadTransport, addr: u64)
+    -> Result<String, WeldError> {
+    use super::intrinsic::ERROR_MESSAGE_SIZE;
+    if addr == 0 {
+        return Ok(String::new());
+    }
+    let mut buffer = vec![0u8; ERROR_MESSAGE_SIZE];
+    offload.read_mem(addr, buffer.as_mut_ptr() as *mut c_void, ERROR_MESSAGE_SIZE)?;
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(ERROR_MESSAGE_SIZE);
+    Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+/// Returns the type of the value returned by a program with return type `ty`.
+fn result_type(ty: &Type) -> Type {
+    match *ty {
+        Function(_, ref ret_ty) => (**ret_ty).clone(),
+        _ => ty.clone(),
+    }
+}
+
+/// Copies the result at `ve_addr` to the host and stores a pointer to it at `vh_addr`.
+///
+/// Vectors of scalars are read asynchronously, so they transfer while the rest of the
+/// result is traversed. Other values are waited for before their contents are read.
+fn convert_results(layout: &Layout, ty: &Type, ve_addr: u64, vh_addr: u64,
+                   offload: &dyn OffloadTransport) ->
+                   Result<(), WeldError> {
+    let mut transfers = TransferQueue::new(offload);
+    match *ty {
+        Function(_, ref ret_ty) => {
+            convert_result(layout, &ret_ty, ve_addr, vh_addr, &mut transfers)?;
+        }
+        _ => {
+            convert_result(layout, &*ty, ve_addr, vh_addr, &mut transfers)?;
+            // weld_err!("Invalid type {} for the type of return type", ty)
         }
     }
-    fn convert_result_elements(&self, ty: &Type, ve_addr: u64, vh_addr: u64,
-                               proc_handle: VeoProcHandleRef) ->
-                               Result<(), WeldError> {
-        match *ty {
-            Struct(ref fields) => {
-                let mut vh_ptr = vh_addr;
-                for f in fields.iter() {
-                    let field_data_size = self.calc_data_size(f)?;
-                    match f {
-                        Scalar(_) | Simd(_) => {
-                            // Data is already copied, so nothing to do
-                        }
-                        Struct(_) => {
-                            let ve_deref_addr = unsafe {
-                                *(vh_ptr as *const u64)
-                            };
-                            self.convert_result(f, ve_deref_addr, vh_ptr,
-                                                proc_handle)?;
-                        }
-                        Vector(_) => {
-                            let ve_deref_addr = unsafe {
-                                *(vh_ptr as *const u64)
-                            };
-                            self.convert_result_elements(
-                                f, ve_deref_addr, vh_ptr, proc_handle)?;
-                        }
-                        Dict(_, _) => {
-                            return weld_err!("Unsupported dict field type {} in convert_result_elements", ty);
-                        }
-                        Builder(_, _) => {
-                            return weld_err!("Unsupported builder field type {} in convert_result_elements", ty);
-                        }
-                        _ => {
-                            return weld_err!("Unsupported struct field type {} in convert_result_elements", f);
-                        }
-                    }
-                    vh_ptr += field_data_size as u64;
-                }
-                Ok(())
-            }
-            Vector(ref elem) => {
-                //      WeldVec
-                //  0:  intptr_t  data
-                //  8:  u64       length
-                let elem_size = self.calc_data_size(elem)?;
-                let length = unsafe {
-                    *((vh_addr + 8) as *const u64)
-                } as usize;
-                let sz = elem_size * length;
-                let ptr = unsafe { alloc(sz as usize) };
-                unsafe {
-                    *(vh_addr as *mut u64) = ptr as u64;
-                }
-                unsafe {
-                    if veo_read_mem(proc_handle, ptr as *mut c_void,
-                                    ve_addr, sz) != 0 {
-                        return weld_err!("cannot read veo memory");
-                    }
-                };
-                let elem_data = ptr as u64;
-                match **elem {
+    unsafe { transfers.wait_all() }
+}
+fn convert_result_elements(layout: &Layout, ty: &Type, ve_addr: u64, vh_addr: u64,
+                           transfers: &mut TransferQueue<'_>) ->
+                           Result<(), WeldError> {
+    match *ty {
+        Struct(ref fields) => {
+            let mut vh_ptr = vh_addr;
+            for f in fields.iter() {
+                let field_data_size = data_size(layout, f)?;
+                match f {
                     Scalar(_) | Simd(_) => {
                         // Data is already copied, so nothing to do
-                        Ok(())
                     }
                     Struct(_) => {
-                        for i in 0..length {
-                            let ptr = elem_data + (i * elem_size) as u64;
-                            let ve_deref_addr = unsafe {
-                                *(ptr as *const u64)
-                            };
-                            self.convert_result(
-                                &**elem, ve_deref_addr, ptr, proc_handle)?;
-                        }
-                        Ok(())
+                        let ve_deref_addr = unsafe {
+                            *(vh_ptr as *const u64)
+                        };
+                        convert_result(layout, f, ve_deref_addr, vh_ptr, transfers)?;
                     }
                     Vector(_) => {
-                        for i in 0..length {
-                            let ptr = elem_data + (i * elem_size) as u64;
-                            let ve_deref_addr = unsafe {
-                                *(ptr as *const u64)
-                            };
-                            self.convert_result_elements(
-                                &**elem, ve_deref_addr, ptr, proc_handle)?;
-                        }
-                        Ok(())
-                    }
-                    Dict(_, _) => {
-                        weld_err!("Unsupported dict element type {} in convert_result_elements", ty)
+                        let ve_deref_addr = unsafe {
+                            *(vh_ptr as *const u64)
+                        };
+                        convert_result_elements(
+                            layout, f, ve_deref_addr, vh_ptr, transfers)?;
                     }
-                    Builder(_, _) => {
-                        weld_err!("Unsupported builder element type {} in convert_result_elements", ty)
+                    Dict(_, _) | Builder(_, _) => {
+                        unsafe {
+                            marshal::download(transfers, layout, f, vh_ptr as *mut u8)?;
+                        }
                     }
                     _ => {
-                        weld_err!("Unsupported vector element type {} in convert_result_elements", **elem)
+                        return weld_err!("Unsupported struct field type {} in convert_result_elements", f);
                     }
                 }
+                vh_ptr += field_data_size as u64;
             }
-            Dict(_, _) => {
-                weld_err!("Unsupported dict type {} in convert_result_elements", ty)
-            }
-            Builder(_, _) => {
-                weld_err!("Unsupported builder type {} in convert_result_elements", ty)
-            }
-            _ => {
-                weld_err!("Unsupported type {} in convert_result", ty)
-            }
+            Ok(())
         }
-    }
-    fn convert_result(&self, ty: &Type, ve_addr: u64, vh_addr: u64,
-                      proc_handle: VeoProcHandleRef) ->
-                      Result<(), WeldError> {
-        match *ty {
-            Scalar(kind) => {
-                let ptr = unsafe { alloc(scalar_kind_size(kind) as usize) };
-                unsafe {
-                    *(vh_addr as *mut u64) = ptr as u64;
+        Vector(ref elem) => {
+            //      WeldVec
+            //  0:  intptr_t  data
+            //  8:  u64       length
+            let elem_size = data_size(layout, elem)?;
+            let length = unsafe {
+                *((vh_addr + 8) as *const u64)
+            } as usize;
+            let sz = elem_size * length;
+            let ptr = unsafe { alloc(sz as usize) };
+            unsafe {
+                *(vh_addr as *mut u64) = ptr as u64;
+            }
+            unsafe {
+                transfers.read(ve_addr, ptr as *mut c_void, sz)?;
+            };
+            let elem_data = ptr as u64;
+            match **elem {
+                Scalar(_) | Simd(_) => {
+                    // Data is copied by the pending transfer, so nothing to do
+                    Ok(())
                 }
-                unsafe {
-                    if veo_read_mem(proc_handle, ptr as *mut c_void,
-                                    ve_addr, scalar_kind_size(kind)) != 0 {
-                        return weld_err!("cannot read veo memory");
+                Struct(_) => {
+                    unsafe { transfers.wait_all()? };
+                    for i in 0..length {
+                        let ptr = elem_data + (i * elem_size) as u64;
+                        let ve_deref_addr = unsafe {
+                            *(ptr as *const u64)
+                        };
+                        convert_result(
+                            layout, &**elem, ve_deref_addr, ptr, transfers)?;
                     }
-                };
-                Ok(())
-            }
-//            Simd(kind) => simd_size(&Scalar(kind)) * scalar_kind_size(kind),
-            Struct(ref fields) => {
-                let deref_size = self.calc_struct_field_size(&*fields)?;
-                let ptr = unsafe { alloc(deref_size as usize) };
-                unsafe {
-                    *(vh_addr as *mut u64) = ptr as u64;
+                    Ok(())
                 }
-                unsafe {
-                    if veo_read_mem(proc_handle, ptr as *mut c_void,
-                                    ve_addr, deref_size) != 0 {
-                        return weld_err!("cannot read veo memory");
+                Vector(_) => {
+                    unsafe { transfers.wait_all()? };
+                    for i in 0..length {
+                        let ptr = elem_data + (i * elem_size) as u64;
+                        let ve_deref_addr = unsafe {
+                            *(ptr as *const u64)
+                        };
+                        convert_result_elements(
+                            layout, &**elem, ve_deref_addr, ptr, transfers)?;
                     }
-                };
-                self.convert_result_elements(
-                    ty, ve_addr, ptr as u64, proc_handle)
-            }
-            Vector(_) => {
-                let data_size = self.calc_data_size(ty)?;
-                let ptr = unsafe { alloc(data_size as usize) };
-                unsafe {
-                    *(vh_addr as *mut u64) = ptr as u64;
+                    Ok(())
                 }
-                unsafe {
-                    if veo_read_mem(proc_handle, ptr as *mut c_void,
-                                    ve_addr, data_size) != 0 {
-                        return weld_err!("cannot read veo memory");
+                Dict(_, _) | Builder(_, _) => {
+                    unsafe { transfers.wait_all()? };
+                    for i in 0..length {
+                        let ptr = elem_data + (i * elem_size) as u64;
+                        unsafe {
+                            marshal::download(transfers, layout, &**elem, ptr as *mut u8)?;
+                        }
                     }
-                };
-                let new_ve_addr = unsafe { *(ptr as *const u64) };
-                self.convert_result_elements(
-                    ty, new_ve_addr, ptr as u64, proc_handle)
-            }
-            Dict(_, _) => {
-                weld_err!("Unsupported dict type {} in convert_result", ty)
+                    Ok(())
+                }
+                _ => {
+                    weld_err!("Unsupported vector element type {} in convert_result_elements", **elem)
+                }
             }
-            Builder(_, _) => {
-                weld_err!("Unsupported builder type {} in convert_result", ty)
+        }
+        Dict(_, _) => {
+            weld_err!("Unsupported dict type {} in convert_result_elements", ty)
+        }
+        Builder(_, _) => {
+            weld_err!("Unsupported builder type {} in convert_result_elements", ty)
+        }
+        _ => {
+            weld_err!("Unsupported type {} in convert_result", ty)
+        }
+    }
+}
+fn convert_result(layout: &Layout, ty: &Type, ve_addr: u64, vh_addr: u64,
+                  transfers: &mut TransferQueue<'_>) ->
+                  Result<(), WeldError> {
+    match *ty {
+        Scalar(kind) => {
+            let ptr = unsafe { alloc(scalar_kind_size(kind) as usize) };
+            unsafe {
+                *(vh_addr as *mut u64) = ptr as u64;
+            }
+            unsafe {
+                transfers.read(ve_addr, ptr as *mut c_void,
+                               scalar_kind_size(kind))?;
+            };
+            Ok(())
+        }
+//            Simd(kind) => simd_size(&Scalar(kind)) * scalar_kind_size(kind),
+        Struct(ref fields) => {
+            let deref_size = struct_field_size(layout, &*fields)?;
+            let ptr = unsafe { alloc(deref_size as usize) };
+            unsafe {
+                *(vh_addr as *mut u64) = ptr as u64;
+            }
+            unsafe {
+                transfers.read(ve_addr, ptr as *mut c_void, deref_size)?;
+                transfers.wait_all()?;
+            };
+            convert_result_elements(
+                layout, ty, ve_addr, ptr as u64, transfers)
+        }
+        Vector(_) => {
+            let data_size = data_size(layout, ty)?;
+            let ptr = unsafe { alloc(data_size as usize) };
+            unsafe {
+                *(vh_addr as *mut u64) = ptr as u64;
+            }
+            unsafe {
+                transfers.read(ve_addr, ptr as *mut c_void, data_size)?;
+                transfers.wait_all()?;
+            };
+            let new_ve_addr = unsafe { *(ptr as *const u64) };
+            convert_result_elements(
+                layout, ty, new_ve_addr, ptr as u64, transfers)
+        }
+        Dict(_, _) | Builder(_, _) => {
+            let data_size = layout.size_of(ty)?;
+            let ptr = unsafe { alloc(data_size as usize) };
+            unsafe {
+                *(vh_addr as *mut u64) = ptr as u64;
             }
-            _ => {
-                weld_err!("Unsupported type {} in convert_result", ty)
+            unsafe {
+                transfers.read(ve_addr, ptr as *mut c_void, data_size)?;
+                transfers.wait_all()?;
+                marshal::download(transfers, layout, ty, ptr)
             }
         }
+        _ => {
+            weld_err!("Unsupported type {} in convert_result", ty)
+        }
+    }
+}
+
+// Calculate the size of outermost data structure.
+// In order to calculate the size of whole data, use both
+// this function and calc_contents_size().
+fn data_size(layout: &Layout, ty: &Type) -> Result<usize, WeldError> {
+    match *ty {
+        Scalar(kind) => Ok(scalar_kind_size(kind)),
+        Simd(_) => layout.size_of(ty),
+
+        // Struct(fields) is a pointer to an array of each field
+        Struct(_) => Ok(8),
+        // Vector(elem) is following 16 bytes data structure.
+        //  0:  intptr_t  data
+        //  8:  u64       length
+        Vector(_) => Ok(16),
+        // Dictionaries and builders are laid out as in the generated code (see marshal.rs).
+        Dict(_, _) | Builder(_, _) => layout.size_of(ty),
+        _ => {
+            weld_err!("Unsupported type {}", ty)
+        }
+    }
+}
+
+// Calculate of the size of Struct's field data size.
+fn struct_field_size(layout: &Layout, field_tys: &[Type]) -> Result<usize, WeldError> {
+    let mut sz = 0;
+    for f in field_tys.iter() {
+        sz += data_size(layout, f)?;
     }
+    Ok(sz)
 }
 
-fn scalar_kind_size(k: ScalarKind) -> usize {
+pub(super) fn scalar_kind_size(k: ScalarKind) -> usize {
     use crate::ast::ScalarKind::*;
     match k {
         Bool => 1,
@@ -1200,15 +1655,33 @@ fn scalar_kind_size(k: ScalarKind) -> usize {
         U64 => 8,
         F32 => 4,
         F64 => 8,
+        Decimal(_, _) => 8,
+        Date => 4,
+        Timestamp | Interval => 8,
     }
 }
 
-fn simd_size(_: &Type) -> usize {
-    use super::LLVM_VECTOR_WIDTH;
-    LLVM_VECTOR_WIDTH as usize
+/// Returns whether `ty` is a vector of scalars or SIMD values, which the serialize policy may
+/// send with a transfer of its own.
+fn is_flat_vector(ty: &Type) -> bool {
+    match *ty {
+        Vector(ref elem) => match **elem {
+            Scalar(_) | Simd(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns `duration` in seconds.
+fn seconds(duration: Duration) -> f64 {
+    match duration.num_nanoseconds() {
+        Some(ns) => ns as f64 * 1e-9,
+        None => duration.num_milliseconds() as f64 * 1e-3,
+    }
 }
 
-unsafe fn alloc(len: usize) -> *mut u8 {
+pub(super) unsafe fn alloc(len: usize) -> *mut u8 {
     let mut vec = Vec::<u8>::with_capacity(len);
     vec.set_len(len);
     Box::into_raw(vec.into_boxed_slice()) as *mut u8
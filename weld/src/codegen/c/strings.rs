@@ -0,0 +1,421 @@
+//! Code generation for the built-in string operations.
+//!
+//! Each string operation is implemented by a C function over `vec[i8]` values, which is added to
+//! the prelude the first time the operation is used. The functions follow the semantics of the
+//! functions in `weld::runtime::strings`, which the LLVM backend calls instead.
+
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::Scalar;
+use crate::ast::*;
+use crate::error::*;
+use crate::sir::*;
+
+use super::{CGenerator, CodeGenExt, FunctionContext};
+
+/// Generates string operations.
+pub trait StringOpGen {
+    /// Generates code for a `StringOp` statement.
+    unsafe fn gen_string_op(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        statement: &Statement,
+    ) -> WeldResult<()>;
+}
+
+impl StringOpGen for CGenerator {
+    unsafe fn gen_string_op(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        statement: &Statement,
+    ) -> WeldResult<()> {
+        if let StatementKind::StringOp { op, ref args } = statement.kind {
+            let arg_ty = ctx.sir_function.symbol_type(&args[0])?;
+            let name = define_string_fn(self, op, arg_ty)?;
+            let mut c_args = vec![ctx.c_get_run().to_string()];
+            for arg in args.iter() {
+                c_args.push(ctx.c_get_value(arg)?);
+            }
+            let output = ctx.c_get_value(statement.output.as_ref().unwrap())?;
+            ctx.body
+                .add(format!("{} = {}({});", output, name, c_args.join(", ")));
+            Ok(())
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+/// Returns a C string literal with the given contents.
+///
+/// Characters other than printable ASCII are written as octal escapes, since hexadecimal escapes
+/// would also consume any hexadecimal digits following them.
+pub fn c_string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Defines the C function implementing a string operation and returns its name.
+///
+/// `arg_ty` is the type of the operation's first argument.
+unsafe fn define_string_fn(
+    gen: &mut CGenerator,
+    op: StringOpKind,
+    arg_ty: &Type,
+) -> WeldResult<String> {
+    use crate::ast::StringOpKind::*;
+    let name = match op {
+        Concat => "weld_str_concat".to_string(),
+        Substr => "weld_str_substr".to_string(),
+        Find => "weld_str_find".to_string(),
+        StartsWith => "weld_str_startswith".to_string(),
+        EndsWith => "weld_str_endswith".to_string(),
+        ToUpper => "weld_str_toupper".to_string(),
+        ToLower => "weld_str_tolower".to_string(),
+        ParseI64 => "weld_str_strtoi64".to_string(),
+        ParseF64 => "weld_str_strtof64".to_string(),
+        ToString => format!("weld_str_tostring_{}", arg_ty),
+    };
+    if gen.c_string_fns.contains(&name) {
+        return Ok(name);
+    }
+
+    let string = gen.c_type(&Type::string_type())?;
+    let run = gen.c_run_handle_type();
+    let bool_ty = gen.c_bool_type();
+    let i64_ty = gen.c_i64_type();
+    let u64_ty = gen.c_u64_type();
+    let f64_ty = gen.c_f64_type();
+
+    let code = match op {
+        Concat => format!(
+            "\
+static {s} {name}({run} run, {s} left, {s} right)
+{{
+    {s} result;
+    result.size = left.size + right.size;
+    result.data = weld_runst_malloc(run, result.size);
+    memcpy(result.data, left.data, left.size);
+    memcpy(result.data + left.size, right.data, right.size);
+    return result;
+}}",
+            s = string,
+            name = name,
+            run = run,
+        ),
+        // Like `slice`, the substring shares its memory with the original string.
+        Substr => format!(
+            "\
+static {s} {name}({run} run, {s} string, {i64} index, {i64} size)
+{{
+    {s} result;
+    if (index < 0) {{
+        index = 0;
+    }}
+    if (index > ({i64})string.size) {{
+        index = string.size;
+    }}
+    if (size < 0) {{
+        size = 0;
+    }}
+    if (size > ({i64})string.size - index) {{
+        size = string.size - index;
+    }}
+    result.data = string.data + index;
+    result.size = size;
+    return result;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            i64 = i64_ty,
+        ),
+        Find => format!(
+            "\
+static {i64} {name}({run} run, {s} string, {s} pattern)
+{{
+    for ({u64} i = 0; i + pattern.size <= string.size; ++i) {{
+        if (memcmp(string.data + i, pattern.data, pattern.size) == 0) {{
+            return i;
+        }}
+    }}
+    return -1;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            i64 = i64_ty,
+            u64 = u64_ty,
+        ),
+        StartsWith => format!(
+            "\
+static {bool} {name}({run} run, {s} string, {s} prefix)
+{{
+    return prefix.size <= string.size &&
+        memcmp(string.data, prefix.data, prefix.size) == 0;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            bool = bool_ty,
+        ),
+        EndsWith => format!(
+            "\
+static {bool} {name}({run} run, {s} string, {s} suffix)
+{{
+    return suffix.size <= string.size &&
+        memcmp(string.data + string.size - suffix.size, suffix.data, suffix.size) == 0;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            bool = bool_ty,
+        ),
+        ToUpper | ToLower => {
+            let (from, to) = if op == ToUpper {
+                ('a', 'A')
+            } else {
+                ('A', 'a')
+            };
+            format!(
+                "\
+static {s} {name}({run} run, {s} string)
+{{
+    {s} result;
+    result.size = string.size;
+    result.data = weld_runst_malloc(run, result.size);
+    for ({u64} i = 0; i < string.size; ++i) {{
+        char c = string.data[i];
+        result.data[i] = c >= '{from}' && c <= '{last}' ? c - '{from}' + '{to}' : c;
+    }}
+    return result;
+}}",
+                s = string,
+                name = name,
+                run = run,
+                u64 = u64_ty,
+                from = from,
+                last = (from as u8 + 25) as char,
+                to = to,
+            )
+        }
+        // Accepts an optional sign followed by decimal digits, like Rust's `str::parse`.
+        ParseI64 => format!(
+            "\
+static {i64} {name}({run} run, {s} string)
+{{
+    {u64} i = 0;
+    {u64} value = 0;
+    {u64} limit = 9223372036854775807UL;
+    int negative = 0;
+    if (i < string.size && (string.data[i] == '+' || string.data[i] == '-')) {{
+        negative = string.data[i] == '-';
+        limit += negative;
+        ++i;
+    }}
+    if (i == string.size) {{
+        weld_runst_raise(run, ParseError, \"invalid integer\");
+    }}
+    for (; i < string.size; ++i) {{
+        char c = string.data[i];
+        if (c < '0' || c > '9') {{
+            weld_runst_raise(run, ParseError, \"invalid integer\");
+        }}
+        if (value > (limit - (c - '0')) / 10) {{
+            weld_runst_raise(run, ParseError, \"integer out of range\");
+        }}
+        value = value * 10 + (c - '0');
+    }}
+    return negative ? ({i64})(0 - value) : ({i64})value;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            i64 = i64_ty,
+            u64 = u64_ty,
+        ),
+        // `strtod` needs a terminated string, so the string is copied first. Unlike `strtod`,
+        // only decimal syntax is accepted, which rejects whitespace, hexadecimal numbers and
+        // `inf` or `nan`, as the Rust runtime does.
+        ParseF64 => format!(
+            "\
+extern double strtod(const char*, char**);
+static {f64} {name}({run} run, {s} string)
+{{
+    char* buffer;
+    char* end;
+    {f64} value;
+    {i64} i;
+    if (string.size == 0) {{
+        weld_runst_raise(run, ParseError, \"invalid float\");
+    }}
+    for (i = 0; i < string.size; i++) {{
+        char c = string.data[i];
+        if (!((c >= '0' && c <= '9') || c == '+' || c == '-' || c == '.' || c == 'e' ||
+              c == 'E')) {{
+            weld_runst_raise(run, ParseError, \"invalid float\");
+        }}
+    }}
+    buffer = weld_runst_malloc(run, string.size + 1);
+    memcpy(buffer, string.data, string.size);
+    buffer[string.size] = 0;
+    value = strtod(buffer, &end);
+    if (end != buffer + string.size) {{
+        weld_runst_free(run, buffer);
+        weld_runst_raise(run, ParseError, \"invalid float\");
+    }}
+    weld_runst_free(run, buffer);
+    return value;
+}}",
+            s = string,
+            name = name,
+            run = run,
+            i64 = i64_ty,
+            f64 = f64_ty,
+        ),
+        // Floats are formatted like Rust's `Display`: with the fewest significant digits that
+        // still parse to the same value, without an exponent, and as `NaN`, `inf` or `-inf` if
+        // they are not finite.
+        ToString if *arg_ty == Scalar(F32) || *arg_ty == Scalar(F64) => {
+            let f32 = *arg_ty == Scalar(F32);
+            format!(
+                "\
+extern int snprintf(char*, unsigned long, const char*, ...);
+extern double strtod(const char*, char**);
+extern float strtof(const char*, char**);
+static {s} {name}({run} run, {ty} value)
+{{
+    char scientific[32];
+    // Holds all digits of the exact value.
+    char exact[800];
+    char mantissa[32];
+    // Holds the digits of the largest value and of the smallest subnormal value.
+    char buffer[352];
+    {s} result;
+    int size = 0;
+    int ndigits = 0;
+    int exponent;
+    int precision;
+    int i;
+    if (isnan(value)) {{
+        size = snprintf(buffer, sizeof(buffer), \"NaN\");
+    }} else if (isinf(value)) {{
+        size = snprintf(buffer, sizeof(buffer), value > 0 ? \"inf\" : \"-inf\");
+    }} else {{
+        char* p = scientific;
+        for (precision = 0; precision < {digits}; ++precision) {{
+            snprintf(scientific, sizeof(scientific), \"%.*e\", precision, (double)value);
+            if ({parse}(scientific, 0) == value) {{
+                break;
+            }}
+        }}
+        // Split `[-]d.ddde[+-]xx` into its digits and its exponent.
+        if (*p == '-') {{
+            buffer[size++] = '-';
+            ++p;
+        }}
+        for (; *p != 'e'; ++p) {{
+            if (*p != '.') {{
+                mantissa[ndigits++] = *p;
+            }}
+        }}
+        exponent = (int)strtod(p + 1, 0);
+        // `snprintf` rounds halfway cases to even, but Rust rounds them up. A digit rounded down
+        // to even can be incremented without a carry.
+        snprintf(exact, sizeof(exact), \"%.*e\", 770, (double)value);
+        p = exact + (exact[0] == '-') + precision + 2;
+        if (*p == '5' && *(p - 1 - (precision == 0)) == mantissa[ndigits - 1]) {{
+            for (++p; *p == '0'; ++p) {{
+            }}
+            if (*p == 'e') {{
+                ++mantissa[ndigits - 1];
+            }}
+        }}
+        while (ndigits > 1 && mantissa[ndigits - 1] == '0') {{
+            --ndigits;
+        }}
+        if (exponent < 0) {{
+            buffer[size++] = '0';
+            buffer[size++] = '.';
+            for (i = -1; i > exponent; --i) {{
+                buffer[size++] = '0';
+            }}
+            for (i = 0; i < ndigits; ++i) {{
+                buffer[size++] = mantissa[i];
+            }}
+        }} else {{
+            for (i = 0; i <= exponent; ++i) {{
+                buffer[size++] = i < ndigits ? mantissa[i] : '0';
+            }}
+            if (ndigits > exponent + 1) {{
+                buffer[size++] = '.';
+                for (i = exponent + 1; i < ndigits; ++i) {{
+                    buffer[size++] = mantissa[i];
+                }}
+            }}
+        }}
+    }}
+    result.size = size;
+    result.data = weld_runst_malloc(run, result.size);
+    memcpy(result.data, buffer, result.size);
+    return result;
+}}",
+                s = string,
+                name = name,
+                run = run,
+                ty = gen.c_type(arg_ty)?,
+                digits = if f32 { 9 } else { 17 },
+                parse = if f32 { "strtof" } else { "strtod" },
+            )
+        }
+        ToString => {
+            let ty = gen.c_type(arg_ty)?;
+            let format = match *arg_ty {
+                Scalar(Bool) => {
+                    "snprintf(buffer, sizeof(buffer), \"%s\", value ? \"true\" : \"false\")"
+                        .to_string()
+                }
+                Scalar(kind) if kind.is_signed_integer() => {
+                    "snprintf(buffer, sizeof(buffer), \"%lld\", (long long)value)".to_string()
+                }
+                Scalar(kind) if kind.is_unsigned_integer() => {
+                    "snprintf(buffer, sizeof(buffer), \"%llu\", (unsigned long long)value)"
+                        .to_string()
+                }
+                _ => return compile_err!("Unsupported type {} in {}", arg_ty, op),
+            };
+            format!(
+                "\
+extern int snprintf(char*, unsigned long, const char*, ...);
+static {s} {name}({run} run, {ty} value)
+{{
+    char buffer[32];
+    {s} result;
+    int size = {format};
+    result.size = size;
+    result.data = weld_runst_malloc(run, result.size);
+    memcpy(result.data, buffer, result.size);
+    return result;
+}}",
+                s = string,
+                name = name,
+                run = run,
+                ty = ty,
+                format = format,
+            )
+        }
+    };
+    (*gen.ccontext()).prelude_code.add(code);
+    gen.c_string_fns.insert(name.clone());
+    Ok(name)
+}
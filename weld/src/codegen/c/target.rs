@@ -1,7 +1,7 @@
 //! Target-specific information and querying.
 //!
 //! This module provides functionality for querying whether target-specific features are available
-//! on the current platform.
+//! on the current platform, and describes the vector unit that generated C code targets.
 
 use fnv;
 
@@ -10,8 +10,15 @@ use std::str;
 
 use fnv::FnvHashSet;
 
+use crate::conf::{Backend, ParsedConf};
 use crate::error::*;
 
+/// Number of elements in a VE vector register.
+pub const VE_VECTOR_LENGTH: u32 = 256;
+
+/// Default number of SIMD lanes for C code compiled for the host.
+pub const HOST_VECTOR_LENGTH: u32 = 8;
+
 /// X86-specific feature list.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum X86Feature {
@@ -131,3 +138,95 @@ impl Target {
         Ok(result)
     }
 }
+
+/// Describes the vector unit of the machine that runs the generated C code.
+///
+/// SIMD values in generated C code are arrays of `width` lanes, and operations on them are loops
+/// over the lanes that the C compiler vectorizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorTarget {
+    /// Number of lanes in a SIMD value.
+    pub width: u32,
+    /// Whether the code is compiled by the VE compiler, which understands `#pragma _NEC`.
+    pub nec_directives: bool,
+}
+
+impl VectorTarget {
+    /// Returns the vector target of the C backend selected in `conf`.
+    pub fn from_conf(conf: &ParsedConf) -> VectorTarget {
+        let (default_width, nec_directives) = match conf.backend {
+            Backend::CVE => (VE_VECTOR_LENGTH, true),
+            // The fake VE uses the VE's vector length so code behaves as it does on a VE.
+            Backend::CFakeVE => (VE_VECTOR_LENGTH, false),
+            Backend::CHost | Backend::LLVM => (HOST_VECTOR_LENGTH, false),
+        };
+        VectorTarget {
+            width: conf.vector_length.unwrap_or(default_width),
+            nec_directives,
+        }
+    }
+
+    /// Returns the directives placed before a loop over the lanes of SIMD values.
+    ///
+    /// The lanes are independent, and on the VE a loop of at most one vector register's length
+    /// runs as a single vector instruction without strip-mining.
+    pub fn lane_loop_directives(&self) -> String {
+        if !self.nec_directives {
+            return "#pragma GCC ivdep".to_string();
+        }
+        let length = if self.width <= VE_VECTOR_LENGTH {
+            "#pragma _NEC shortloop".to_string()
+        } else {
+            format!("#pragma _NEC loop_count({})", self.width)
+        };
+        format!("{}\n#pragma _NEC ivdep", length)
+    }
+
+    /// Returns the directives placed before a loop whose iterations only merge into mergers and
+    /// non-resizing appenders, or `None` if the target has no such directive.
+    ///
+    /// The builders are local to the loop function, so iterations have no memory dependencies
+    /// that the compiler needs to assume.
+    pub fn independent_loop_directives(&self) -> Option<&'static str> {
+        if self.nec_directives {
+            Some("#pragma _NEC ivdep")
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn vector_target_defaults() {
+    let mut conf = ParsedConf::default();
+    conf.backend = Backend::CVE;
+    assert_eq!(VectorTarget::from_conf(&conf).width, VE_VECTOR_LENGTH);
+    assert!(VectorTarget::from_conf(&conf).nec_directives);
+
+    conf.backend = Backend::CHost;
+    assert_eq!(VectorTarget::from_conf(&conf).width, HOST_VECTOR_LENGTH);
+    assert!(!VectorTarget::from_conf(&conf).nec_directives);
+
+    conf.vector_length = Some(64);
+    assert_eq!(VectorTarget::from_conf(&conf).width, 64);
+}
+
+#[test]
+fn lane_loop_directives() {
+    let target = VectorTarget {
+        width: VE_VECTOR_LENGTH,
+        nec_directives: true,
+    };
+    assert_eq!(
+        target.lane_loop_directives(),
+        "#pragma _NEC shortloop\n#pragma _NEC ivdep"
+    );
+    let target = VectorTarget {
+        width: 512,
+        nec_directives: true,
+    };
+    assert_eq!(
+        target.lane_loop_directives(),
+        "#pragma _NEC loop_count(512)\n#pragma _NEC ivdep"
+    );
+}
@@ -40,6 +40,7 @@ pub struct Appender {
     merge: Option<LLVMValueRef>,
     vmerge: Option<LLVMValueRef>,
     result: Option<LLVMValueRef>,
+    combine: Option<LLVMValueRef>,
 }
 
 impl CodeGenExt for Appender {
@@ -78,6 +79,7 @@ impl Appender {
             merge: None,
             vmerge: None,
             result: None,
+            combine: None,
         }
     }
 
@@ -297,6 +299,96 @@ impl Appender {
         }
     }
 
+    /// Generates code to append the elements of the appender `other` to an appender.
+    ///
+    /// `builder_arg` is a pointer to the appender that is extended, and `other` is an appender
+    /// value.
+    pub unsafe fn gen_combine(
+        &mut self,
+        builder: LLVMBuilderRef,
+        intrinsics: &mut Intrinsics,
+        run_arg: LLVMValueRef,
+        builder_arg: LLVMValueRef,
+        other: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        if self.combine.is_none() {
+            let mut arg_tys = [
+                LLVMPointerType(self.appender_ty, 0),
+                self.appender_ty,
+                self.run_handle_type(),
+            ];
+            let ret_ty = LLVMVoidTypeInContext(self.context);
+            let name = format!("{}.combine", self.name);
+            let (function, builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            let grow_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("grow"));
+            let copy_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("copy"));
+
+            let appender = LLVMGetParam(function, 0);
+            let other = LLVMGetParam(function, 1);
+            let run_handle = LLVMGetParam(function, 2);
+
+            let size_slot = LLVMBuildStructGEP(builder, appender, SIZE_INDEX, c_str!(""));
+            let size = LLVMBuildLoad(builder, size_slot, c_str!("size"));
+            let capacity_slot = LLVMBuildStructGEP(builder, appender, CAPACITY_INDEX, c_str!(""));
+            let capacity = LLVMBuildLoad(builder, capacity_slot, c_str!("capacity"));
+            let other_size = LLVMBuildExtractValue(builder, other, SIZE_INDEX, c_str!("otherSize"));
+            let new_size = LLVMBuildNSWAdd(builder, size, other_size, c_str!("newSize"));
+
+            let full = LLVMBuildICmp(builder, LLVMIntSGT, new_size, capacity, c_str!("full"));
+            LLVMBuildCondBr(builder, full, grow_block, copy_block);
+
+            // Grow the appender to exactly fit both appenders.
+            LLVMPositionBuilderAtEnd(builder, grow_block);
+            let elem_size = self.size_of(self.elem_ty);
+            let alloc_size = LLVMBuildMul(builder, elem_size, new_size, c_str!("allocSize"));
+            let base_pointer = self.gen_index(builder, appender, None)?;
+            let raw_pointer = LLVMBuildBitCast(
+                builder,
+                base_pointer,
+                LLVMPointerType(self.i8_type(), 0),
+                c_str!("rawPtr"),
+            );
+            let bytes = intrinsics.call_weld_run_realloc(
+                builder,
+                run_handle,
+                raw_pointer,
+                alloc_size,
+                Some(c_str!("bytes")),
+            );
+            let typed_bytes =
+                LLVMBuildBitCast(builder, bytes, LLVMTypeOf(base_pointer), c_str!("typed"));
+            let pointer_slot = LLVMBuildStructGEP(builder, appender, POINTER_INDEX, c_str!(""));
+            LLVMBuildStore(builder, typed_bytes, pointer_slot);
+            LLVMBuildStore(builder, new_size, capacity_slot);
+            LLVMBuildBr(builder, copy_block);
+
+            // Copy the elements of the other appender after the existing elements.
+            LLVMPositionBuilderAtEnd(builder, copy_block);
+            let int8p = LLVMPointerType(self.i8_type(), 0);
+            let dst = self.gen_index(builder, appender, Some(size))?;
+            let dst = LLVMBuildBitCast(builder, dst, int8p, c_str!(""));
+            let src = LLVMBuildExtractValue(builder, other, POINTER_INDEX, c_str!(""));
+            let src = LLVMBuildBitCast(builder, src, int8p, c_str!(""));
+            let copy_size = LLVMBuildMul(builder, elem_size, other_size, c_str!(""));
+            intrinsics.call_memcpy(builder, dst, src, copy_size);
+            LLVMBuildStore(builder, new_size, size_slot);
+            LLVMBuildRetVoid(builder);
+
+            self.combine = Some(function);
+            LLVMDisposeBuilder(builder);
+        }
+
+        let mut args = [builder_arg, other, run_arg];
+        Ok(LLVMBuildCall(
+            builder,
+            self.combine.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+
     /// Generates code to get the result from an appender.
     ///
     /// The Appender's result is a vector.
@@ -1,8 +1,11 @@
 //! Code generation for the parallel for loop.
 //!
-//! This backend currently only generates single threaded code, but its structure is amenable to
-//! parallelization (e.g., loops are still divided out into their own function, albeit marked with
-//! `alwaysinline` at the moment).
+//! Each loop is divided out into its own function, which runs the iterations in a range `[lo,
+//! hi)`. When more than one thread is configured and the loop's builders support it, the loop is
+//! split into chunks that run on the runtime's worker threads (see `runtime::parallel`). Every
+//! chunk except the first updates a fresh thread-local builder, and the partial builders are
+//! combined in chunk order after all chunks finish, so appenders retain their iteration order.
+//! Loops with group mergers always run on a single thread.
 //!
 //! The `GenForLoopInternal` is the main workhorse of this module, and provides methods for
 //! building a loop, creating bounds checks, loading elements, and so forth.
@@ -24,10 +27,14 @@ use self::llvm_sys::{LLVMIntPredicate, LLVMLinkage};
 use crate::codegen::llvm2::llvm_exts::LLVMExtAttribute::*;
 use crate::codegen::llvm2::llvm_exts::*;
 use crate::codegen::llvm2::vector::VectorExt;
-use crate::codegen::llvm2::{LLVM_VECTOR_WIDTH, SIR_FUNC_CALL_CONV};
+use crate::codegen::llvm2::{dict, LLVM_VECTOR_WIDTH, SIR_FUNC_CALL_CONV};
 
+use super::{appender, merge_value_pointers, BuilderExpressionGen};
 use super::{CodeGenExt, FunctionContext, LlvmGenerator};
 
+/// Minimum number of iterations each chunk of a parallel loop runs.
+const PARALLEL_GRAIN: i64 = 4096;
+
 /// An internal trait for generating parallel For loops.
 pub trait ForLoopGenInternal {
     /// Entry point to generating a for loop.
@@ -89,6 +96,46 @@ pub trait ForLoopGenInternal {
         e: LLVMValueRef,
         parfor: &ParallelForData,
     ) -> WeldResult<()>;
+    /// Generates a function that runs the loop in chunks across the runtime's worker threads.
+    ///
+    /// The function takes the loop body's parameters, the total number of iterations, the number
+    /// of chunks, and the run handle, and returns the combined builder.
+    unsafe fn gen_parallel_loop_function(
+        &mut self,
+        func: &SirFunction,
+        parfor: &ParallelForData,
+    ) -> WeldResult<LLVMValueRef>;
+    /// Generates a fresh builder of type `ty` for a chunk that runs `iterations` iterations.
+    ///
+    /// `original` is the builder passed into the loop, which provides the sizes of vecmergers.
+    unsafe fn gen_thread_local_builder(
+        &mut self,
+        builder: LLVMBuilderRef,
+        ty: &Type,
+        original: LLVMValueRef,
+        iterations: LLVMValueRef,
+        run: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef>;
+    /// Generates code to merge the builder value `other` into the builder at `builder_pointer`.
+    unsafe fn gen_combine_builders(
+        &mut self,
+        builder: LLVMBuilderRef,
+        ty: &Type,
+        builder_pointer: LLVMValueRef,
+        other: LLVMValueRef,
+        run: LLVMValueRef,
+    ) -> WeldResult<()>;
+}
+
+/// Returns whether partial builders of type `ty` can be combined after a parallel loop.
+fn supports_parallel(ty: &Type) -> bool {
+    use crate::ast::BuilderKind::*;
+    match *ty {
+        Type::Builder(GroupMerger(_, _), _) => false,
+        Type::Builder(_, _) => true,
+        Type::Struct(ref fields) => fields.iter().all(supports_parallel),
+        _ => false,
+    }
 }
 
 impl ForLoopGenInternal for LlvmGenerator {
@@ -114,22 +161,89 @@ impl ForLoopGenInternal for LlvmGenerator {
             let value = self.load(ctx.builder, ctx.get_value(symbol)?)?;
             arguments.push(value);
         }
-        // The body function has an additional arguement representing the number of iterations.
-        arguments.push(iterations);
-        // Last argument is always the run handle.
-        arguments.push(ctx.get_run());
-
-        // Call the body function, which runs the loop and updates the builder. The updated builder
-        // is returned to the current function.
-        let builder = LLVMBuildCall(
-            ctx.builder,
-            body_function,
-            arguments.as_mut_ptr(),
-            arguments.len() as u32,
-            c_str!(""),
-        );
-        LLVMSetInstructionCallConv(builder, SIR_FUNC_CALL_CONV);
-        // XXX what is parfor.builder now...
+
+        let builder_ty = sir_function.symbol_type(&parfor.builder)?;
+        let parallel = self.conf.threads > 1 && supports_parallel(builder_ty);
+
+        // The serial path calls the body function over the full range of iterations. The last
+        // argument is always the run handle.
+        let run = ctx.get_run();
+        let mut serial_arguments = arguments.clone();
+        serial_arguments.extend_from_slice(&[self.i64(0), iterations, run]);
+
+        let builder = if parallel {
+            let parallel_function = self.gen_parallel_loop_function(sir_function, parfor)?;
+
+            let grain = self.i64(PARALLEL_GRAIN);
+            let nchunks = self.intrinsics.call_weld_run_parallel_chunks(
+                ctx.builder,
+                run,
+                iterations,
+                grain,
+                Some(c_str!("nchunks")),
+            );
+
+            let serial_block =
+                LLVMAppendBasicBlockInContext(self.context, ctx.llvm_function, c_str!("serial"));
+            let parallel_block =
+                LLVMAppendBasicBlockInContext(self.context, ctx.llvm_function, c_str!("parallel"));
+            let join_block =
+                LLVMAppendBasicBlockInContext(self.context, ctx.llvm_function, c_str!("join"));
+
+            let one = self.i64(1);
+            let is_parallel = LLVMBuildICmp(
+                ctx.builder,
+                LLVMIntPredicate::LLVMIntSGT,
+                nchunks,
+                one,
+                c_str!(""),
+            );
+            LLVMBuildCondBr(ctx.builder, is_parallel, parallel_block, serial_block);
+
+            LLVMPositionBuilderAtEnd(ctx.builder, serial_block);
+            let mut args = serial_arguments;
+            let serial_builder = LLVMBuildCall(
+                ctx.builder,
+                body_function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMSetInstructionCallConv(serial_builder, SIR_FUNC_CALL_CONV);
+            LLVMBuildBr(ctx.builder, join_block);
+
+            LLVMPositionBuilderAtEnd(ctx.builder, parallel_block);
+            let mut args = arguments;
+            args.extend_from_slice(&[iterations, nchunks, run]);
+            let parallel_builder = LLVMBuildCall(
+                ctx.builder,
+                parallel_function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMBuildBr(ctx.builder, join_block);
+
+            LLVMPositionBuilderAtEnd(ctx.builder, join_block);
+            let builder = LLVMBuildPhi(ctx.builder, LLVMTypeOf(serial_builder), c_str!(""));
+            let mut values = [serial_builder, parallel_builder];
+            let mut blocks = [serial_block, parallel_block];
+            LLVMAddIncoming(builder, values.as_mut_ptr(), blocks.as_mut_ptr(), 2);
+            builder
+        } else {
+            // Call the body function, which runs the loop and updates the builder. The updated
+            // builder is returned to the current function.
+            let mut args = serial_arguments;
+            let builder = LLVMBuildCall(
+                ctx.builder,
+                body_function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMSetInstructionCallConv(builder, SIR_FUNC_CALL_CONV);
+            builder
+        };
         LLVMBuildStore(ctx.builder, builder, ctx.get_value(&parfor.builder)?);
         LLVMBuildStore(ctx.builder, builder, ctx.get_value(&output)?);
 
@@ -215,16 +329,16 @@ impl ForLoopGenInternal for LlvmGenerator {
     ///
     /// A loop body function has the following layout:
     ///
-    /// { builders } FuncName(arg1, arg2, ..., iterations, run):
+    /// { builders } FuncName(arg1, arg2, ..., lo, hi, run):
     /// entry:
     ///     alloca all variables except the local builder.
     ///     alias builder argument with parfor.builder_arg
     ///     br loop.begin
     /// loop.begin:
-    ///     i = <initializer code>
+    ///     i = lo
     ///     br loop.entry
     /// loop.entry:
-    ///     if i >= end:
+    ///     if i >= hi:
     ///         br loop.exit
     ///     else
     ///         br loop.body
@@ -256,10 +370,12 @@ impl ForLoopGenInternal for LlvmGenerator {
         let weld_ty = &builders[0];
 
         let mut arg_tys = self.argument_types(func)?;
-        // The second-to-last argument is the *total* number of iterations across all threads (in a
-        // multi-threaded setting) that this loop will execute for.
+        // The next two arguments are the range of iterations `[lo, hi)` this call executes. This is
+        // the full loop when running serially, or a single chunk when running in parallel.
+        arg_tys.push(self.i64_type());
+        let lo_index = (arg_tys.len() - 1) as u32;
         arg_tys.push(self.i64_type());
-        let num_iterations_index = (arg_tys.len() - 1) as u32;
+        let hi_index = (arg_tys.len() - 1) as u32;
         // Last argument is run handle, as always.
         arg_tys.push(self.run_handle_type());
 
@@ -268,20 +384,20 @@ impl ForLoopGenInternal for LlvmGenerator {
         let name = CString::new(format!("f{}_loop", func.id)).unwrap();
         let function = LLVMAddFunction(self.module, name.as_ptr(), func_ty);
         LLVMSetLinkage(function, LLVMLinkage::LLVMPrivateLinkage);
+        LLVMSetFunctionCallConv(function, SIR_FUNC_CALL_CONV);
 
         LLVMExtAddDefaultAttrs(self.context(), function);
-        // We always inline this since it will appear only once in the program, so there's no code
-        // size cost to doing it.
-        //
-        // XXX this should only be here in the single threaded setting.
+        // We always inline this since it appears at most twice in the program (in the serial path
+        // and the parallel task), so the code size cost is small.
         LLVMExtAddAttrsOnFunction(self.context, function, &[AlwaysInline]);
 
         self.functions.insert(func.id, function);
 
         // Create a context for the function.
         let context = &mut FunctionContext::new(self.context, program, func, function);
-        // Reference to the parameter storing the max number of iterations.
-        let max = LLVMGetParam(context.llvm_function, num_iterations_index);
+        // References to the parameters storing the range of iterations.
+        let lo = LLVMGetParam(context.llvm_function, lo_index);
+        let hi = LLVMGetParam(context.llvm_function, hi_index);
         // Create the entry basic block, where we define alloca'd variables.
         let entry_bb =
             LLVMAppendBasicBlockInContext(self.context, context.llvm_function, c_str!(""));
@@ -296,11 +412,7 @@ impl ForLoopGenInternal for LlvmGenerator {
             self.load(context.builder, context.get_value(&parfor.builder)?)?,
             context.get_value(&parfor.builder_arg)?,
         );
-        LLVMBuildStore(
-            context.builder,
-            self.i64(0),
-            context.get_value(&parfor.idx_arg)?,
-        );
+        LLVMBuildStore(context.builder, lo, context.get_value(&parfor.idx_arg)?);
 
         // Add the SIR function basic blocks.
         self.gen_basic_block_defs(context)?;
@@ -314,9 +426,9 @@ impl ForLoopGenInternal for LlvmGenerator {
         // Check whether we need to loop at all.
         let any_iters_cond = LLVMBuildICmp(
             context.builder,
-            LLVMIntPredicate::LLVMIntNE,
-            max,
-            self.i64(0),
+            LLVMIntPredicate::LLVMIntSLT,
+            lo,
+            hi,
             c_str!(""),
         );
         // First body block of the SIR function.
@@ -367,7 +479,7 @@ impl ForLoopGenInternal for LlvmGenerator {
         let finished_cond = LLVMBuildICmp(
             context.builder,
             LLVMIntPredicate::LLVMIntEQ,
-            max,
+            hi,
             updated,
             c_str!(""),
         );
@@ -610,4 +722,288 @@ impl ForLoopGenInternal for LlvmGenerator {
         LLVMBuildCondBr(ctx.builder, passed, pass_block, fail_block);
         Ok(())
     }
+
+    unsafe fn gen_parallel_loop_function(
+        &mut self,
+        func: &SirFunction,
+        parfor: &ParallelForData,
+    ) -> WeldResult<LLVMValueRef> {
+        let body_function = self.functions[&func.id];
+        let weld_ty = func.symbol_type(&parfor.builder)?.clone();
+        let builder_ty = self.llvm_type(&weld_ty)?;
+        let builder_index = func
+            .params
+            .keys()
+            .position(|symbol| *symbol == parfor.builder)
+            .unwrap();
+
+        // The loop's arguments are passed to the tasks in a struct.
+        let mut param_tys = self.argument_types(func)?;
+        let nparams = param_tys.len() as u32;
+        let args_ty = LLVMStructTypeInContext(self.context, param_tys.as_mut_ptr(), nparams, 0);
+
+        // The task runs a single chunk and stores the resulting builder into the chunk's slot of
+        // the partials array.
+        let mut arg_tys = [
+            self.void_pointer_type(),
+            self.void_pointer_type(),
+            self.i64_type(),
+            self.i64_type(),
+            self.i64_type(),
+            self.run_handle_type(),
+        ];
+        let ret_ty = self.void_type();
+        let name = format!("f{}_task", func.id);
+        let (task, builder, entry_block) = self.define_function(ret_ty, &mut arg_tys, name);
+
+        let args_pointer_ty = LLVMPointerType(args_ty, 0);
+        let args = LLVMBuildBitCast(builder, LLVMGetParam(task, 0), args_pointer_ty, c_str!(""));
+        let partials_ty = LLVMPointerType(builder_ty, 0);
+        let partials = LLVMBuildBitCast(builder, LLVMGetParam(task, 1), partials_ty, c_str!(""));
+        let chunk = LLVMGetParam(task, 2);
+        let lo = LLVMGetParam(task, 3);
+        let hi = LLVMGetParam(task, 4);
+        let run = LLVMGetParam(task, 5);
+
+        let mut arguments = vec![];
+        for i in 0..nparams {
+            let pointer = LLVMBuildStructGEP(builder, args, i, c_str!(""));
+            arguments.push(LLVMBuildLoad(builder, pointer, c_str!("")));
+        }
+
+        // The first chunk updates the builder passed into the loop, and the other chunks update
+        // fresh builders.
+        let original = arguments[builder_index];
+        let fresh_block = LLVMAppendBasicBlockInContext(self.context, task, c_str!("fresh"));
+        let run_block = LLVMAppendBasicBlockInContext(self.context, task, c_str!("run"));
+        let is_first = LLVMBuildICmp(
+            builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            chunk,
+            self.i64(0),
+            c_str!(""),
+        );
+        LLVMBuildCondBr(builder, is_first, run_block, fresh_block);
+
+        LLVMPositionBuilderAtEnd(builder, fresh_block);
+        let chunk_iterations = LLVMBuildNSWSub(builder, hi, lo, c_str!(""));
+        let fresh =
+            self.gen_thread_local_builder(builder, &weld_ty, original, chunk_iterations, run)?;
+        let fresh_end_block = LLVMGetInsertBlock(builder);
+        LLVMBuildBr(builder, run_block);
+
+        LLVMPositionBuilderAtEnd(builder, run_block);
+        let chunk_builder = LLVMBuildPhi(builder, builder_ty, c_str!(""));
+        let mut values = [original, fresh];
+        let mut blocks = [entry_block, fresh_end_block];
+        LLVMAddIncoming(chunk_builder, values.as_mut_ptr(), blocks.as_mut_ptr(), 2);
+        arguments[builder_index] = chunk_builder;
+        arguments.extend_from_slice(&[lo, hi, run]);
+
+        let result = LLVMBuildCall(
+            builder,
+            body_function,
+            arguments.as_mut_ptr(),
+            arguments.len() as u32,
+            c_str!(""),
+        );
+        LLVMSetInstructionCallConv(result, SIR_FUNC_CALL_CONV);
+        let mut indices = [chunk];
+        let slot = LLVMBuildGEP(builder, partials, indices.as_mut_ptr(), 1, c_str!(""));
+        LLVMBuildStore(builder, result, slot);
+        LLVMBuildRetVoid(builder);
+        LLVMDisposeBuilder(builder);
+
+        // The parallel function runs the tasks and combines their builders.
+        let mut arg_tys = param_tys;
+        arg_tys.extend_from_slice(&[self.i64_type(), self.i64_type(), self.run_handle_type()]);
+        let name = format!("f{}_parallel", func.id);
+        let (function, builder, _) = self.define_function(builder_ty, &mut arg_tys, name);
+
+        let iterations = LLVMGetParam(function, nparams);
+        let nchunks = LLVMGetParam(function, nparams + 1);
+        let run = LLVMGetParam(function, nparams + 2);
+
+        let args = LLVMBuildAlloca(builder, args_ty, c_str!("args"));
+        for i in 0..nparams {
+            let pointer = LLVMBuildStructGEP(builder, args, i, c_str!(""));
+            LLVMBuildStore(builder, LLVMGetParam(function, i), pointer);
+        }
+        let combined = LLVMBuildAlloca(builder, builder_ty, c_str!("combined"));
+
+        let slot_size = self.size_of(builder_ty);
+        let size = LLVMBuildNSWMul(builder, slot_size, nchunks, c_str!(""));
+        let bytes =
+            self.intrinsics
+                .call_weld_run_malloc(builder, run, size, Some(c_str!("partials")));
+        let partials = LLVMBuildBitCast(builder, bytes, partials_ty, c_str!(""));
+
+        let task = LLVMBuildBitCast(builder, task, self.void_pointer_type(), c_str!(""));
+        let args = LLVMBuildBitCast(builder, args, self.void_pointer_type(), c_str!(""));
+        self.intrinsics
+            .call_weld_run_parallel_for(builder, run, task, args, bytes, iterations, nchunks);
+
+        // Combine the partial builders in chunk order.
+        let first = LLVMBuildLoad(builder, partials, c_str!(""));
+        LLVMBuildStore(builder, first, combined);
+        let one = self.i64(1);
+        self.gen_counted_loop(builder, one, nchunks, |gen, chunk| {
+            let mut indices = [chunk];
+            let slot = LLVMBuildGEP(builder, partials, indices.as_mut_ptr(), 1, c_str!(""));
+            let other = LLVMBuildLoad(builder, slot, c_str!(""));
+            gen.gen_combine_builders(builder, &weld_ty, combined, other, run)
+        })?;
+
+        self.intrinsics.call_weld_run_free(builder, run, bytes);
+        let combined = LLVMBuildLoad(builder, combined, c_str!(""));
+        LLVMBuildRet(builder, combined);
+        LLVMDisposeBuilder(builder);
+        Ok(function)
+    }
+
+    unsafe fn gen_thread_local_builder(
+        &mut self,
+        builder: LLVMBuilderRef,
+        ty: &Type,
+        original: LLVMValueRef,
+        iterations: LLVMValueRef,
+        run: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        use crate::ast::BuilderKind::*;
+        match *ty {
+            Type::Struct(ref fields) => {
+                let mut result = LLVMGetUndef(self.llvm_type(ty)?);
+                for (i, field) in fields.iter().enumerate() {
+                    let original = LLVMBuildExtractValue(builder, original, i as u32, c_str!(""));
+                    let value =
+                        self.gen_thread_local_builder(builder, field, original, iterations, run)?;
+                    result = LLVMBuildInsertValue(builder, result, value, i as u32, c_str!(""));
+                }
+                Ok(result)
+            }
+            Type::Builder(ref kind, _) => match *kind {
+                Appender(_) => {
+                    // Size the appender for the chunk so it does not resize in the loop.
+                    let default = self.i64(appender::DEFAULT_CAPACITY);
+                    let larger = LLVMBuildICmp(
+                        builder,
+                        LLVMIntPredicate::LLVMIntSGT,
+                        iterations,
+                        default,
+                        c_str!(""),
+                    );
+                    let capacity =
+                        LLVMBuildSelect(builder, larger, iterations, default, c_str!(""));
+                    let methods = self.appenders.get_mut(kind).unwrap();
+                    methods.gen_new(builder, &mut self.intrinsics, run, capacity)
+                }
+                Merger(_, _) => {
+                    let methods = self.mergers.get_mut(kind).unwrap();
+                    let identity = methods.binop_identity(methods.op, methods.scalar_kind)?;
+                    methods.gen_new(builder, identity)
+                }
+                DictMerger(ref key, ref val, _) => {
+                    let dict_type = &Type::Dict(key.clone(), val.clone());
+                    let capacity = self.i64(dict::INITIAL_CAPACITY);
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    methods.gen_new(builder, &mut self.intrinsics, capacity, run)
+                }
+                VecMerger(ref elem, binop) => {
+                    // A vecmerger starts with the identity at each index of the original vector.
+                    let vector_type = &Type::Vector(elem.clone());
+                    let size = self.gen_size(builder, vector_type, original)?;
+                    let vector = self.gen_new(builder, vector_type, size, run)?;
+                    let identity = self.binop_identity_value(binop, elem)?;
+                    let zero = self.i64(0);
+                    self.gen_counted_loop(builder, zero, size, |gen, i| {
+                        let pointer = gen.gen_at(builder, vector_type, vector, i)?;
+                        LLVMBuildStore(builder, identity, pointer);
+                        Ok(())
+                    })?;
+                    Ok(vector)
+                }
+                TopK(_, _) => {
+                    let methods = self.topks.get_mut(kind).unwrap();
+                    methods.gen_new(builder, &mut self.intrinsics, run)
+                }
+                GroupMerger(_, _) | SetMerger(_) | SketchMerger(_, _) => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn gen_combine_builders(
+        &mut self,
+        builder: LLVMBuilderRef,
+        ty: &Type,
+        builder_pointer: LLVMValueRef,
+        other: LLVMValueRef,
+        run: LLVMValueRef,
+    ) -> WeldResult<()> {
+        use crate::ast::BuilderKind::*;
+        match *ty {
+            Type::Struct(ref fields) => {
+                for (i, field) in fields.iter().enumerate() {
+                    let pointer =
+                        LLVMBuildStructGEP(builder, builder_pointer, i as u32, c_str!(""));
+                    let other = LLVMBuildExtractValue(builder, other, i as u32, c_str!(""));
+                    self.gen_combine_builders(builder, field, pointer, other, run)?;
+                }
+                Ok(())
+            }
+            Type::Builder(ref kind, _) => match *kind {
+                Appender(_) => {
+                    let methods = self.appenders.get_mut(kind).unwrap();
+                    methods.gen_combine(
+                        builder,
+                        &mut self.intrinsics,
+                        run,
+                        builder_pointer,
+                        other,
+                    )?;
+                    Ok(())
+                }
+                Merger(_, _) => {
+                    let methods = self.mergers.get_mut(kind).unwrap();
+                    methods.gen_combine(builder, builder_pointer, other)?;
+                    Ok(())
+                }
+                DictMerger(ref key, ref val, binop) => {
+                    let dict_type = &Type::Dict(key.clone(), val.clone());
+                    let default = self.binop_identity_value(binop, val)?;
+                    let dict = LLVMBuildLoad(builder, builder_pointer, c_str!(""));
+                    let methods = self.dictionaries.get_mut(dict_type).unwrap();
+                    methods.gen_combine(
+                        builder,
+                        &mut self.intrinsics,
+                        dict,
+                        other,
+                        default,
+                        run,
+                        |builder, value_pointer, other_pointer| {
+                            merge_value_pointers(builder, val, binop, value_pointer, other_pointer)
+                        },
+                    )
+                }
+                VecMerger(ref elem, binop) => {
+                    let vector_type = &Type::Vector(elem.clone());
+                    let vector = LLVMBuildLoad(builder, builder_pointer, c_str!(""));
+                    let size = self.gen_size(builder, vector_type, vector)?;
+                    let zero = self.i64(0);
+                    self.gen_counted_loop(builder, zero, size, |gen, i| {
+                        let value_pointer = gen.gen_at(builder, vector_type, vector, i)?;
+                        let other_pointer = gen.gen_at(builder, vector_type, other, i)?;
+                        gen.merge_values(builder, elem, binop, value_pointer, other_pointer)
+                    })
+                }
+                TopK(_, _) => {
+                    let methods = self.topks.get_mut(kind).unwrap();
+                    methods.gen_combine(builder, builder_pointer, other)?;
+                    Ok(())
+                }
+                GroupMerger(_, _) | SetMerger(_) | SketchMerger(_, _) => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
 }
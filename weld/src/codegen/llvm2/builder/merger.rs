@@ -34,6 +34,7 @@ pub struct Merger {
     merge: Option<LLVMValueRef>,
     vmerge: Option<LLVMValueRef>,
     result: Option<LLVMValueRef>,
+    combine: Option<LLVMValueRef>,
 }
 
 impl CodeGenExt for Merger {
@@ -71,6 +72,7 @@ impl Merger {
             merge: None,
             vmerge: None,
             result: None,
+            combine: None,
         }
     }
 
@@ -188,6 +190,51 @@ impl Merger {
         }
     }
 
+    /// Generates code to merge the merger `other` into a merger.
+    ///
+    /// `builder` is a pointer to the merger that is updated, and `other` is a merger value. Both
+    /// the scalar and the vector parts of the mergers are combined.
+    pub unsafe fn gen_combine(
+        &mut self,
+        llvm_builder: LLVMBuilderRef,
+        builder: LLVMValueRef,
+        other: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        if self.combine.is_none() {
+            let ret_ty = LLVMVoidTypeInContext(self.context);
+            let mut arg_tys = [LLVMPointerType(self.merger_ty, 0), self.merger_ty];
+            let name = format!("{}.combine", self.name);
+            let (function, fn_builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            let builder_pointer = LLVMGetParam(function, 0);
+            let other = LLVMGetParam(function, 1);
+            let parts = [
+                (SCALAR_INDEX, Scalar(self.scalar_kind)),
+                (VECTOR_INDEX, Simd(self.scalar_kind)),
+            ];
+            for (index, ty) in parts.iter() {
+                let elem_pointer =
+                    LLVMBuildStructGEP(fn_builder, builder_pointer, *index, c_str!(""));
+                let elem = LLVMBuildLoad(fn_builder, elem_pointer, c_str!(""));
+                let other_elem = LLVMBuildExtractValue(fn_builder, other, *index, c_str!(""));
+                let result = gen_binop(fn_builder, self.op, elem, other_elem, ty)?;
+                LLVMBuildStore(fn_builder, result, elem_pointer);
+            }
+            LLVMBuildRetVoid(fn_builder);
+
+            self.combine = Some(function);
+            LLVMDisposeBuilder(fn_builder);
+        }
+        let mut args = [builder, other];
+        Ok(LLVMBuildCall(
+            llvm_builder,
+            self.combine.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+
     pub unsafe fn gen_result(
         &mut self,
         llvm_builder: LLVMBuilderRef,
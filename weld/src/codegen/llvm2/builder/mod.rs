@@ -34,6 +34,7 @@ mod for_loop;
 
 pub mod appender;
 pub mod merger;
+pub mod topk;
 
 /// A trait for generating builder code.
 ///
@@ -51,6 +52,14 @@ pub trait BuilderExpressionGen {
         builder_value_pointer: LLVMValueRef,
         merge_value_pointer: LLVMValueRef,
     ) -> WeldResult<()>;
+    /// Returns a constant holding the identity of `binop` over `ty`.
+    ///
+    /// `ty` is either a scalar or a struct of scalars.
+    unsafe fn binop_identity_value(
+        &mut self,
+        binop: BinOpKind,
+        ty: &Type,
+    ) -> WeldResult<LLVMValueRef>;
     /// Generates code for the `NewBuilder` statement.
     unsafe fn gen_new_builder(
         &mut self,
@@ -165,6 +174,41 @@ impl<'a> ResStatement<'a> {
     }
 }
 
+/// Performs `*builder_value = *builder_value <binop> *merge_value`.
+///
+/// This is the implementation of `BuilderExpressionGen::merge_values`, which is also used by
+/// builder methods that cannot borrow the generator.
+unsafe fn merge_value_pointers(
+    builder: LLVMBuilderRef,
+    merge_ty: &Type,
+    binop: BinOpKind,
+    builder_value_pointer: LLVMValueRef,
+    merge_value_pointer: LLVMValueRef,
+) -> WeldResult<()> {
+    match *merge_ty {
+        Scalar(_) => {
+            let merge_value = LLVMBuildLoad(builder, merge_value_pointer, c_str!(""));
+            let builder_value = LLVMBuildLoad(builder, builder_value_pointer, c_str!(""));
+            let merged = numeric::gen_binop(builder, binop, builder_value, merge_value, merge_ty)?;
+            LLVMBuildStore(builder, merged, builder_value_pointer);
+        }
+        Struct(ref elems) => {
+            for (i, elem) in elems.iter().enumerate() {
+                let builder_elem_pointer =
+                    LLVMBuildStructGEP(builder, builder_value_pointer, i as u32, c_str!(""));
+                let builder_value = LLVMBuildLoad(builder, builder_elem_pointer, c_str!(""));
+                let merge_elem_pointer =
+                    LLVMBuildStructGEP(builder, merge_value_pointer, i as u32, c_str!(""));
+                let merge_value = LLVMBuildLoad(builder, merge_elem_pointer, c_str!(""));
+                let merged = numeric::gen_binop(builder, binop, builder_value, merge_value, elem)?;
+                LLVMBuildStore(builder, merged, builder_elem_pointer);
+            }
+        }
+        _ => unreachable!(),
+    };
+    Ok(())
+}
+
 impl BuilderExpressionGen for LlvmGenerator {
     /// Merges two pointer values using the provided binary operator.
     ///
@@ -177,30 +221,41 @@ impl BuilderExpressionGen for LlvmGenerator {
         builder_value_pointer: LLVMValueRef,
         merge_value_pointer: LLVMValueRef,
     ) -> WeldResult<()> {
-        match *merge_ty {
-            Scalar(_) => {
-                let merge_value = self.load(builder, merge_value_pointer)?;
-                let builder_value = self.load(builder, builder_value_pointer)?;
-                let merged =
-                    numeric::gen_binop(builder, binop, builder_value, merge_value, merge_ty)?;
-                LLVMBuildStore(builder, merged, builder_value_pointer);
-            }
+        merge_value_pointers(
+            builder,
+            merge_ty,
+            binop,
+            builder_value_pointer,
+            merge_value_pointer,
+        )
+    }
+
+    unsafe fn binop_identity_value(
+        &mut self,
+        binop: BinOpKind,
+        ty: &Type,
+    ) -> WeldResult<LLVMValueRef> {
+        match *ty {
+            Scalar(ref kind) => self.binop_identity(binop, *kind),
             Struct(ref elems) => {
+                let mut identity = LLVMGetUndef(self.llvm_type(ty)?);
                 for (i, elem) in elems.iter().enumerate() {
-                    let builder_elem_pointer =
-                        LLVMBuildStructGEP(builder, builder_value_pointer, i as u32, c_str!(""));
-                    let builder_value = self.load(builder, builder_elem_pointer)?;
-                    let merge_elem_pointer =
-                        LLVMBuildStructGEP(builder, merge_value_pointer, i as u32, c_str!(""));
-                    let merge_value = self.load(builder, merge_elem_pointer)?;
-                    let merged =
-                        numeric::gen_binop(builder, binop, builder_value, merge_value, elem)?;
-                    LLVMBuildStore(builder, merged, builder_elem_pointer);
+                    if let Scalar(ref kind) = *elem {
+                        let mut indices = [i as u32];
+                        identity = LLVMConstInsertValue(
+                            identity,
+                            self.binop_identity(binop, *kind)?,
+                            indices.as_mut_ptr(),
+                            indices.len() as u32,
+                        );
+                    } else {
+                        unreachable!()
+                    }
                 }
+                Ok(identity)
             }
             _ => unreachable!(),
-        };
-        Ok(())
+        }
     }
 
     unsafe fn gen_new_builder(
@@ -281,6 +336,16 @@ impl BuilderExpressionGen for LlvmGenerator {
                 LLVMBuildStore(ctx.builder, builder_value, output_pointer);
                 Ok(())
             }
+            TopK(_, _) => {
+                let topk = {
+                    let methods = self.topks.get_mut(nb.kind).unwrap();
+                    let run = ctx.get_run();
+                    methods.gen_new(ctx.builder, &mut self.intrinsics, run)?
+                };
+                LLVMBuildStore(ctx.builder, topk, output_pointer);
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
         }
     }
 
@@ -309,27 +374,7 @@ impl BuilderExpressionGen for LlvmGenerator {
 
                 // Build the default value that we upsert if the key is not present in the
                 // dictionary yet.
-                let default = match *val.as_ref() {
-                    Scalar(ref kind) => self.binop_identity(*binop, *kind)?,
-                    Struct(ref elems) => {
-                        let mut default = LLVMGetUndef(self.llvm_type(val)?);
-                        for (i, elem) in elems.iter().enumerate() {
-                            if let Scalar(ref kind) = *elem {
-                                let mut indices = [i as u32];
-                                default = LLVMConstInsertValue(
-                                    default,
-                                    self.binop_identity(*binop, *kind)?,
-                                    indices.as_mut_ptr(),
-                                    indices.len() as u32,
-                                );
-                            } else {
-                                unreachable!()
-                            }
-                        }
-                        default
-                    }
-                    _ => unreachable!(),
-                };
+                let default = self.binop_identity_value(*binop, val)?;
 
                 // The type of the merge value is {key, value} so use GEP to extract
                 // the key and the key pointer.
@@ -409,6 +454,13 @@ impl BuilderExpressionGen for LlvmGenerator {
                 let _ = methods.gen_merge(ctx.builder, builder_pointer, merge_value)?;
                 Ok(())
             }
+            TopK(_, _) => {
+                let merge_value = self.load(ctx.builder, ctx.get_value(m.value)?)?;
+                let methods = self.topks.get_mut(m.kind).unwrap();
+                let _ = methods.gen_merge(ctx.builder, builder_pointer, merge_value)?;
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
             VecMerger(ref elem, ref binop) => {
                 use super::vector::VectorExt;
                 // The type of the merge value is {index, value} so use GEP to extract
@@ -485,6 +537,17 @@ impl BuilderExpressionGen for LlvmGenerator {
                 LLVMBuildStore(ctx.builder, builder_loaded, output_pointer);
                 Ok(())
             }
+            TopK(ref elem_type, _) => {
+                let vector = &Vector(elem_type.clone());
+                let vector_type = self.llvm_type(vector)?;
+                let result = {
+                    let methods = self.topks.get_mut(m.kind).unwrap();
+                    methods.gen_result(ctx.builder, vector_type, builder_pointer)?
+                };
+                LLVMBuildStore(ctx.builder, result, output_pointer);
+                Ok(())
+            }
+            SetMerger(_) | SketchMerger(_, _) => unreachable!(),
         }
     }
 
@@ -554,6 +617,24 @@ impl BuilderExpressionGen for LlvmGenerator {
                     let vec_type = &Vector(elem.clone());
                     self.llvm_type(vec_type)
                 }
+                TopK(ref elem_type, k) => {
+                    use super::cmp::GenCmp;
+                    if !self.topks.contains_key(kind) {
+                        let llvm_elem_type = self.llvm_type(elem_type)?;
+                        let cmp = self.gen_cmp_fn(elem_type)?;
+                        let topk = topk::TopK::define(
+                            "topk",
+                            llvm_elem_type,
+                            k,
+                            cmp,
+                            self.context,
+                            self.module,
+                        );
+                        self.topks.insert(kind.clone(), topk);
+                    }
+                    Ok(self.topks[kind].topk_ty)
+                }
+                SetMerger(_) | SketchMerger(_, _) => unreachable!(),
             }
         } else {
             unreachable!()
@@ -0,0 +1,471 @@
+//! Code generation for the topk builder type.
+//!
+//! A topk builder keeps the `k` largest values merged into it in a binary min-heap, so the
+//! smallest kept value is always at the root. The heap is stored in a buffer of capacity `k`
+//! that is allocated when the builder is created. Values are ordered using the default
+//! comparator of the element type. The result sorts the heap in place into descending order.
+
+use llvm_sys;
+
+use std::ffi::CString;
+
+use crate::error::*;
+
+use self::llvm_sys::core::*;
+use self::llvm_sys::prelude::*;
+use self::llvm_sys::LLVMIntPredicate::*;
+
+use crate::codegen::llvm2::intrinsic::Intrinsics;
+use crate::codegen::llvm2::llvm_exts::*;
+use crate::codegen::llvm2::CodeGenExt;
+
+pub const POINTER_INDEX: u32 = 0;
+pub const SIZE_INDEX: u32 = 1;
+
+/// The topk type.
+pub struct TopK {
+    pub topk_ty: LLVMTypeRef,
+    pub elem_ty: LLVMTypeRef,
+    pub name: String,
+    /// The maximum number of values kept.
+    pub k: i64,
+    /// The comparator function over pointers to elements.
+    cmp: LLVMValueRef,
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    new: Option<LLVMValueRef>,
+    merge: Option<LLVMValueRef>,
+    sift_down: Option<LLVMValueRef>,
+    result: Option<LLVMValueRef>,
+    combine: Option<LLVMValueRef>,
+}
+
+impl CodeGenExt for TopK {
+    fn module(&self) -> LLVMModuleRef {
+        self.module
+    }
+
+    fn context(&self) -> LLVMContextRef {
+        self.context
+    }
+}
+
+impl TopK {
+    pub unsafe fn define<T: AsRef<str>>(
+        name: T,
+        elem_ty: LLVMTypeRef,
+        k: i64,
+        cmp: LLVMValueRef,
+        context: LLVMContextRef,
+        module: LLVMModuleRef,
+    ) -> TopK {
+        let c_name = CString::new(name.as_ref()).unwrap();
+        // A topk is a struct with a pointer to the heap and its size.
+        let mut layout = [LLVMPointerType(elem_ty, 0), LLVMInt64TypeInContext(context)];
+        let topk = LLVMStructCreateNamed(context, c_name.as_ptr());
+        LLVMStructSetBody(topk, layout.as_mut_ptr(), layout.len() as u32, 0);
+        TopK {
+            topk_ty: topk,
+            elem_ty,
+            name: c_name.into_string().unwrap(),
+            k,
+            cmp,
+            context,
+            module,
+            new: None,
+            merge: None,
+            sift_down: None,
+            result: None,
+            combine: None,
+        }
+    }
+
+    /// Returns a pointer to the `index`th element of the heap `data`.
+    unsafe fn gen_index(
+        &mut self,
+        builder: LLVMBuilderRef,
+        data: LLVMValueRef,
+        index: LLVMValueRef,
+    ) -> LLVMValueRef {
+        LLVMBuildGEP(builder, data, [index].as_mut_ptr(), 1, c_str!(""))
+    }
+
+    /// Returns whether the element at `left` compares less than the element at `right`.
+    unsafe fn gen_less_than(
+        &mut self,
+        builder: LLVMBuilderRef,
+        left: LLVMValueRef,
+        right: LLVMValueRef,
+    ) -> LLVMValueRef {
+        let mut args = [left, right];
+        let cmp = LLVMBuildCall(
+            builder,
+            self.cmp,
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        );
+        LLVMBuildICmp(builder, LLVMIntSLT, cmp, self.i32(0), c_str!(""))
+    }
+
+    /// Generates code for a new topk builder.
+    pub unsafe fn gen_new(
+        &mut self,
+        builder: LLVMBuilderRef,
+        intrinsics: &mut Intrinsics,
+        run: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        if self.new.is_none() {
+            let mut arg_tys = [self.run_handle_type()];
+            let ret_ty = self.topk_ty;
+
+            let name = format!("{}.new", self.name);
+            let (function, builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            let run = LLVMGetParam(function, 0);
+            let elem_size = self.size_of(self.elem_ty);
+            let alloc_size = LLVMBuildMul(builder, elem_size, self.i64(self.k), c_str!("capacity"));
+            let bytes =
+                intrinsics.call_weld_run_malloc(builder, run, alloc_size, Some(c_str!("bytes")));
+            let elements = LLVMBuildBitCast(
+                builder,
+                bytes,
+                LLVMPointerType(self.elem_ty, 0),
+                c_str!("elements"),
+            );
+
+            let mut result = LLVMGetUndef(self.topk_ty);
+            result = LLVMBuildInsertValue(builder, result, elements, POINTER_INDEX, c_str!(""));
+            result = LLVMBuildInsertValue(builder, result, self.i64(0), SIZE_INDEX, c_str!(""));
+            LLVMBuildRet(builder, result);
+
+            self.new = Some(function);
+            LLVMDisposeBuilder(builder);
+        }
+        let mut args = [run];
+        Ok(LLVMBuildCall(
+            builder,
+            self.new.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+
+    /// Generates a function that places the value at a pointer into the heap, starting at the root.
+    ///
+    /// The function has the signature `void (T* data, i64 size, T* value)`. The root of the heap
+    /// is treated as a hole, so its previous value is overwritten.
+    unsafe fn gen_sift_down(&mut self) -> WeldResult<LLVMValueRef> {
+        if let Some(function) = self.sift_down {
+            return Ok(function);
+        }
+
+        let elem_pointer_ty = LLVMPointerType(self.elem_ty, 0);
+        let mut arg_tys = [elem_pointer_ty, self.i64_type(), elem_pointer_ty];
+        let ret_ty = LLVMVoidTypeInContext(self.context);
+        let name = format!("{}.siftDown", self.name);
+        let (function, builder, entry_block) = self.define_function(ret_ty, &mut arg_tys, name);
+
+        let loop_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("loop"));
+        let children_block =
+            LLVMAppendBasicBlockInContext(self.context, function, c_str!("children"));
+        let right_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("right"));
+        let pick_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("pick"));
+        let move_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("move"));
+        let done_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("done"));
+
+        let data = LLVMGetParam(function, 0);
+        let size = LLVMGetParam(function, 1);
+        let value = LLVMGetParam(function, 2);
+        LLVMBuildBr(builder, loop_block);
+
+        // Find the children of the hole.
+        LLVMPositionBuilderAtEnd(builder, loop_block);
+        let hole = LLVMBuildPhi(builder, self.i64_type(), c_str!("hole"));
+        let twice = LLVMBuildNSWMul(builder, hole, self.i64(2), c_str!(""));
+        let left = LLVMBuildNSWAdd(builder, twice, self.i64(1), c_str!("left"));
+        let has_left = LLVMBuildICmp(builder, LLVMIntSLT, left, size, c_str!(""));
+        LLVMBuildCondBr(builder, has_left, children_block, done_block);
+
+        LLVMPositionBuilderAtEnd(builder, children_block);
+        let right = LLVMBuildNSWAdd(builder, left, self.i64(1), c_str!("right"));
+        let has_right = LLVMBuildICmp(builder, LLVMIntSLT, right, size, c_str!(""));
+        LLVMBuildCondBr(builder, has_right, right_block, pick_block);
+
+        // Pick the smaller of the two children.
+        LLVMPositionBuilderAtEnd(builder, right_block);
+        let left_pointer = self.gen_index(builder, data, left);
+        let right_pointer = self.gen_index(builder, data, right);
+        let right_smaller = self.gen_less_than(builder, right_pointer, left_pointer);
+        let smaller = LLVMBuildSelect(builder, right_smaller, right, left, c_str!(""));
+        LLVMBuildBr(builder, pick_block);
+
+        // Move the child into the hole if it is smaller than the value.
+        LLVMPositionBuilderAtEnd(builder, pick_block);
+        let child = LLVMBuildPhi(builder, self.i64_type(), c_str!("child"));
+        let mut blocks = [children_block, right_block];
+        let mut values = [left, smaller];
+        LLVMAddIncoming(
+            child,
+            values.as_mut_ptr(),
+            blocks.as_mut_ptr(),
+            values.len() as u32,
+        );
+        let child_pointer = self.gen_index(builder, data, child);
+        let child_smaller = self.gen_less_than(builder, child_pointer, value);
+        LLVMBuildCondBr(builder, child_smaller, move_block, done_block);
+
+        LLVMPositionBuilderAtEnd(builder, move_block);
+        let child_value = self.load(builder, child_pointer)?;
+        let hole_pointer = self.gen_index(builder, data, hole);
+        LLVMBuildStore(builder, child_value, hole_pointer);
+        LLVMBuildBr(builder, loop_block);
+
+        let mut blocks = [entry_block, move_block];
+        let mut values = [self.i64(0), child];
+        LLVMAddIncoming(
+            hole,
+            values.as_mut_ptr(),
+            blocks.as_mut_ptr(),
+            values.len() as u32,
+        );
+
+        // Fill the hole with the value.
+        LLVMPositionBuilderAtEnd(builder, done_block);
+        let value = self.load(builder, value)?;
+        let hole_pointer = self.gen_index(builder, data, hole);
+        LLVMBuildStore(builder, value, hole_pointer);
+        LLVMBuildRetVoid(builder);
+
+        self.sift_down = Some(function);
+        LLVMDisposeBuilder(builder);
+        Ok(function)
+    }
+
+    /// Generates code to merge a value into a topk builder.
+    ///
+    /// While the heap holds fewer than `k` values, the value is added to it. Afterwards, the value
+    /// replaces the root of the heap if it is larger than the root.
+    pub unsafe fn gen_merge(
+        &mut self,
+        builder: LLVMBuilderRef,
+        builder_arg: LLVMValueRef,
+        value_arg: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        if self.merge.is_none() {
+            let sift_down = self.gen_sift_down()?;
+
+            let mut arg_tys = [LLVMPointerType(self.topk_ty, 0), self.elem_ty];
+            let ret_ty = LLVMVoidTypeInContext(self.context);
+            let name = format!("{}.merge", self.name);
+            let (function, builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            LLVMExtAddAttrsOnFunction(self.context, function, &[LLVMExtAttribute::AlwaysInline]);
+
+            let push_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("push"));
+            let up_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("up"));
+            let up_check_block =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("up.check"));
+            let up_move_block =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("up.move"));
+            let up_done_block =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("up.done"));
+            let full_block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("full"));
+            let replace_block =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("replace"));
+            let finish_block =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("finish"));
+
+            let topk = LLVMGetParam(function, 0);
+            let merge_value = LLVMGetParam(function, 1);
+
+            // The comparator takes pointers, so keep the merged value on the stack.
+            let value = LLVMBuildAlloca(builder, self.elem_ty, c_str!("value"));
+            LLVMBuildStore(builder, merge_value, value);
+
+            let data_slot = LLVMBuildStructGEP(builder, topk, POINTER_INDEX, c_str!(""));
+            let data = LLVMBuildLoad(builder, data_slot, c_str!("data"));
+            let size_slot = LLVMBuildStructGEP(builder, topk, SIZE_INDEX, c_str!(""));
+            let size = LLVMBuildLoad(builder, size_slot, c_str!("size"));
+            let full = LLVMBuildICmp(builder, LLVMIntSGE, size, self.i64(self.k), c_str!("full"));
+            LLVMBuildCondBr(builder, full, full_block, push_block);
+
+            // The heap is not full: add the value as a leaf and sift it up.
+            LLVMPositionBuilderAtEnd(builder, push_block);
+            let new_size = LLVMBuildNSWAdd(builder, size, self.i64(1), c_str!("newSize"));
+            LLVMBuildStore(builder, new_size, size_slot);
+            LLVMBuildBr(builder, up_block);
+
+            LLVMPositionBuilderAtEnd(builder, up_block);
+            let hole = LLVMBuildPhi(builder, self.i64_type(), c_str!("hole"));
+            let at_root = LLVMBuildICmp(builder, LLVMIntEQ, hole, self.i64(0), c_str!(""));
+            LLVMBuildCondBr(builder, at_root, up_done_block, up_check_block);
+
+            LLVMPositionBuilderAtEnd(builder, up_check_block);
+            let parent = LLVMBuildNSWSub(builder, hole, self.i64(1), c_str!(""));
+            let parent = LLVMBuildLShr(builder, parent, self.i64(1), c_str!("parent"));
+            let parent_pointer = self.gen_index(builder, data, parent);
+            let value_smaller = self.gen_less_than(builder, value, parent_pointer);
+            LLVMBuildCondBr(builder, value_smaller, up_move_block, up_done_block);
+
+            LLVMPositionBuilderAtEnd(builder, up_move_block);
+            let parent_value = self.load(builder, parent_pointer)?;
+            let hole_pointer = self.gen_index(builder, data, hole);
+            LLVMBuildStore(builder, parent_value, hole_pointer);
+            LLVMBuildBr(builder, up_block);
+
+            let mut blocks = [push_block, up_move_block];
+            let mut values = [size, parent];
+            LLVMAddIncoming(
+                hole,
+                values.as_mut_ptr(),
+                blocks.as_mut_ptr(),
+                values.len() as u32,
+            );
+
+            LLVMPositionBuilderAtEnd(builder, up_done_block);
+            let hole_pointer = self.gen_index(builder, data, hole);
+            LLVMBuildStore(builder, merge_value, hole_pointer);
+            LLVMBuildRetVoid(builder);
+
+            // The heap is full: replace the root if the value is larger than it.
+            LLVMPositionBuilderAtEnd(builder, full_block);
+            let root_smaller = self.gen_less_than(builder, data, value);
+            LLVMBuildCondBr(builder, root_smaller, replace_block, finish_block);
+
+            LLVMPositionBuilderAtEnd(builder, replace_block);
+            let mut args = [data, size, value];
+            let _ = LLVMBuildCall(
+                builder,
+                sift_down,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMBuildBr(builder, finish_block);
+
+            LLVMPositionBuilderAtEnd(builder, finish_block);
+            LLVMBuildRetVoid(builder);
+
+            self.merge = Some(function);
+            LLVMDisposeBuilder(builder);
+        }
+
+        let mut args = [builder_arg, value_arg];
+        Ok(LLVMBuildCall(
+            builder,
+            self.merge.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+
+    /// Generates code to merge the values of the topk builder `other` into a topk builder.
+    ///
+    /// `builder_arg` is a pointer to the builder that is updated, and `other` is a topk value.
+    pub unsafe fn gen_combine(
+        &mut self,
+        builder: LLVMBuilderRef,
+        builder_arg: LLVMValueRef,
+        other: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        if self.combine.is_none() {
+            let mut arg_tys = [LLVMPointerType(self.topk_ty, 0), self.topk_ty];
+            let ret_ty = LLVMVoidTypeInContext(self.context);
+            let name = format!("{}.combine", self.name);
+            let (function, fn_builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            let topk = LLVMGetParam(function, 0);
+            let other = LLVMGetParam(function, 1);
+            let data = LLVMBuildExtractValue(fn_builder, other, POINTER_INDEX, c_str!("data"));
+            let size = LLVMBuildExtractValue(fn_builder, other, SIZE_INDEX, c_str!("size"));
+            self.gen_counted_loop(fn_builder, self.i64(0), size, |topk_gen, i| {
+                let pointer = topk_gen.gen_index(fn_builder, data, i);
+                let value = topk_gen.load(fn_builder, pointer)?;
+                let _ = topk_gen.gen_merge(fn_builder, topk, value)?;
+                Ok(())
+            })?;
+            LLVMBuildRetVoid(fn_builder);
+
+            self.combine = Some(function);
+            LLVMDisposeBuilder(fn_builder);
+        }
+
+        let mut args = [builder_arg, other];
+        Ok(LLVMBuildCall(
+            builder,
+            self.combine.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+
+    /// Generates code to get the result from a topk builder.
+    ///
+    /// The result is a vector of the kept values in descending order. It reuses the heap's
+    /// buffer, which is sorted in place by repeatedly moving the root to the end of the heap.
+    pub unsafe fn gen_result(
+        &mut self,
+        builder: LLVMBuilderRef,
+        vector_ty: LLVMTypeRef,
+        builder_arg: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        use crate::codegen::llvm2::vector;
+        if self.result.is_none() {
+            let sift_down = self.gen_sift_down()?;
+
+            let mut arg_tys = [LLVMPointerType(self.topk_ty, 0)];
+            let ret_ty = vector_ty;
+            let name = format!("{}.result", self.name);
+            let (function, fn_builder, _) = self.define_function(ret_ty, &mut arg_tys, name);
+
+            let topk = LLVMGetParam(function, 0);
+            let last = LLVMBuildAlloca(fn_builder, self.elem_ty, c_str!("last"));
+
+            let data_slot = LLVMBuildStructGEP(fn_builder, topk, POINTER_INDEX, c_str!(""));
+            let data = LLVMBuildLoad(fn_builder, data_slot, c_str!("data"));
+            let size_slot = LLVMBuildStructGEP(fn_builder, topk, SIZE_INDEX, c_str!(""));
+            let size = LLVMBuildLoad(fn_builder, size_slot, c_str!("size"));
+
+            // Swap the root with the last value of the heap and restore the heap on the rest.
+            self.gen_counted_loop(fn_builder, self.i64(1), size, |topk_gen, i| {
+                let end = LLVMBuildNSWSub(fn_builder, size, i, c_str!("end"));
+                let end_pointer = topk_gen.gen_index(fn_builder, data, end);
+                let end_value = topk_gen.load(fn_builder, end_pointer)?;
+                LLVMBuildStore(fn_builder, end_value, last);
+                let root_value = topk_gen.load(fn_builder, data)?;
+                LLVMBuildStore(fn_builder, root_value, end_pointer);
+                let mut args = [data, end, last];
+                let _ = LLVMBuildCall(
+                    fn_builder,
+                    sift_down,
+                    args.as_mut_ptr(),
+                    args.len() as u32,
+                    c_str!(""),
+                );
+                Ok(())
+            })?;
+
+            let mut result = LLVMGetUndef(vector_ty);
+            result =
+                LLVMBuildInsertValue(fn_builder, result, data, vector::POINTER_INDEX, c_str!(""));
+            result = LLVMBuildInsertValue(fn_builder, result, size, vector::SIZE_INDEX, c_str!(""));
+            LLVMBuildRet(fn_builder, result);
+
+            self.result = Some(function);
+            LLVMDisposeBuilder(fn_builder);
+        }
+
+        let mut args = [builder_arg];
+        Ok(LLVMBuildCall(
+            builder,
+            self.result.unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        ))
+    }
+}
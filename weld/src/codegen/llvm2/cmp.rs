@@ -347,7 +347,9 @@ impl GenCmp for LlvmGenerator {
 
                 LLVMBuildRet(builder, result);
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
 
         LLVMDisposeBuilder(builder);
@@ -1186,6 +1186,53 @@ impl Dict {
         Ok(self.size(builder, dict))
     }
 
+    /// Merges each key/value pair of the dictionary `other` into `dict`.
+    ///
+    /// Keys that are missing from `dict` are inserted with the `default` value first. The
+    /// `merge` closure generates code that merges the value pointed to by its third argument
+    /// into the value pointed to by its second argument.
+    pub unsafe fn gen_combine<F>(
+        &mut self,
+        builder: LLVMBuilderRef,
+        intrinsics: &mut Intrinsics,
+        dict: LLVMValueRef,
+        other: LLVMValueRef,
+        default: LLVMValueRef,
+        run: LLVMValueRef,
+        mut merge: F,
+    ) -> WeldResult<()>
+    where
+        F: FnMut(LLVMBuilderRef, LLVMValueRef, LLVMValueRef) -> WeldResult<()>,
+    {
+        let slot_array = self.slot_array(builder, other);
+        let capacity = self.capacity(builder, other);
+        let zero = self.i64(0);
+        self.gen_counted_loop(builder, zero, capacity, |methods, i| {
+            let function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(builder));
+            let merge_block =
+                LLVMAppendBasicBlockInContext(methods.context, function, c_str!("combine.merge"));
+            let next_block =
+                LLVMAppendBasicBlockInContext(methods.context, function, c_str!("combine.next"));
+
+            let slot = methods.slot_at_index(builder, slot_array, i);
+            let filled = methods.slot_ty.filled(builder, slot);
+            LLVMBuildCondBr(builder, filled, merge_block, next_block);
+
+            LLVMPositionBuilderAtEnd(builder, merge_block);
+            let key = methods.slot_ty.key(builder, slot);
+            let hash_pointer = methods.slot_ty.hash(builder, slot);
+            let hash = methods.load(builder, hash_pointer)?;
+            let target = methods.gen_upsert(builder, intrinsics, dict, key, hash, default, run)?;
+            let target_value = methods.slot_ty.value(builder, target);
+            let other_value = methods.slot_ty.value(builder, slot);
+            merge(builder, target_value, other_value)?;
+            LLVMBuildBr(builder, next_block);
+
+            LLVMPositionBuilderAtEnd(builder, next_block);
+            Ok(())
+        })
+    }
+
     /// Converts this dictionary to a vector of key/value pairs.
     pub unsafe fn gen_to_vec(
         &mut self,
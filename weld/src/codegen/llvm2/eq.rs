@@ -35,7 +35,7 @@ impl SupportsMemCmpEq for Type {
         use crate::ast::Type::*;
         // Structs do not support memcmp because they may be padded.
         match *self {
-            Scalar(ref kind) => kind.is_integer(),
+            Scalar(ref kind) => kind.is_integer() || kind.is_temporal(),
             _ => false,
         }
     }
@@ -253,7 +253,9 @@ impl GenEq for LlvmGenerator {
                 );
                 result
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
 
         LLVMBuildRet(builder, result);
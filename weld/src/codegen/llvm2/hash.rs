@@ -237,7 +237,7 @@ impl Hash for LlvmGenerator {
         }
 
         match kind {
-            I64 | U64 | F64 => {
+            I64 | U64 | F64 | Decimal(_, _) | Timestamp | Interval => {
                 // Extend the 32-bit hash so we can pass it to the hashing function.
                 let mut hash = LLVMBuildZExt(builder, hash, self.u64_type(), c_str!(""));
                 let value = LLVMBuildBitCast(builder, value, self.u64_type(), c_str!(""));
@@ -251,7 +251,7 @@ impl Hash for LlvmGenerator {
                 );
                 LLVMBuildTrunc(builder, hash, self.hash_type(), c_str!(""))
             }
-            I32 | U32 | F32 => {
+            I32 | U32 | F32 | Date => {
                 let value = LLVMBuildBitCast(builder, value, self.u32_type(), c_str!(""));
                 let mut args = [hash, value];
                 LLVMBuildCall(
@@ -614,7 +614,14 @@ impl Hash for LlvmGenerator {
                 );
                 result
             }
-            Dict(_, _) | Builder(_, _) | Function(_, _) | Unknown | Alias(_, _) => {
+            Dict(_, _)
+            | Builder(_, _)
+            | Function(_, _)
+            | Optional(_)
+            | Set(_)
+            | Sketch(_, _)
+            | Unknown
+            | Alias(_, _) => {
                 return compile_err!("Unhashable type {}", ty);
             }
         };
@@ -104,16 +104,19 @@ impl Intrinsics {
 
         result.push_str(match kind {
             Bool => "i1",
-            I8 => "i32",
+            I8 => "i8",
             I16 => "i16",
             I32 => "i32",
             I64 => "i64",
-            U8 => "i32",
+            U8 => "i8",
             U16 => "i16",
             U32 => "i32",
             U64 => "i64",
             F32 => "f32",
             F64 => "f64",
+            Decimal(_, _) => "i64",
+            Date => "i32",
+            Timestamp | Interval => "i64",
         });
         result
     }
@@ -145,6 +148,31 @@ impl Intrinsics {
         }
     }
 
+    /// Add a new intrinsic function that is linked to the given function pointer.
+    ///
+    /// Returns true if the function was added or false if it was already registered. This is used
+    /// for functions defined by the Weld runtime.
+    pub unsafe fn add_function_pointer<T: AsRef<str>>(
+        &mut self,
+        name: T,
+        ret_ty: LLVMTypeRef,
+        arg_tys: &mut [LLVMTypeRef],
+        pointer: *mut c_void,
+    ) -> bool {
+        if !self.intrinsics.contains_key(name.as_ref()) {
+            let name = CString::new(name.as_ref()).unwrap();
+            let fn_type = LLVMFunctionType(ret_ty, arg_tys.as_mut_ptr(), arg_tys.len() as u32, 0);
+            let function = LLVMAddFunction(self.module, name.as_ptr(), fn_type);
+            self.intrinsics.insert(
+                name.into_string().unwrap(),
+                Intrinsic::FunctionPointer(function, pointer),
+            );
+            true
+        } else {
+            false
+        }
+    }
+
     /// Generate code to call an intrinsic function with the given name and arguments.
     ///
     /// If the intrinsic is not defined, this function throws an error.
@@ -348,6 +376,48 @@ impl Intrinsics {
         )
     }
 
+    /// Convinience wrapper for calling the `weld_runst_parallel_chunks` intrinsic.
+    pub unsafe fn call_weld_run_parallel_chunks(
+        &mut self,
+        builder: LLVMBuilderRef,
+        run: LLVMValueRef,
+        iterations: LLVMValueRef,
+        grain: LLVMValueRef,
+        name: Option<*const c_char>,
+    ) -> LLVMValueRef {
+        let mut args = [run, iterations, grain];
+        LLVMBuildCall(
+            builder,
+            self.get("weld_runst_parallel_chunks").unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            name.unwrap_or(c_str!("")),
+        )
+    }
+
+    /// Convinience wrapper for calling the `weld_runst_parallel_for` intrinsic.
+    ///
+    /// `task`, `args`, and `partials` must be `i8*` values.
+    pub unsafe fn call_weld_run_parallel_for(
+        &mut self,
+        builder: LLVMBuilderRef,
+        run: LLVMValueRef,
+        task: LLVMValueRef,
+        args: LLVMValueRef,
+        partials: LLVMValueRef,
+        iterations: LLVMValueRef,
+        nchunks: LLVMValueRef,
+    ) -> LLVMValueRef {
+        let mut args = [run, task, args, partials, iterations, nchunks];
+        LLVMBuildCall(
+            builder,
+            self.get("weld_runst_parallel_for").unwrap(),
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        )
+    }
+
     /// Convinience wrapper for calling `memcpy`.
     ///
     /// This assumes the `memcpy` is non-volatile and uses an default alignment value of 8.
@@ -557,6 +627,46 @@ impl Intrinsics {
             Intrinsic::FunctionPointer(function, ffi::weld_runst_print as *mut c_void),
         );
 
+        let mut params = vec![self.run_handle_type(), self.i64_type(), self.i64_type()];
+        let name = CString::new("weld_runst_parallel_chunks").unwrap();
+        let fn_type =
+            LLVMFunctionType(self.i64_type(), params.as_mut_ptr(), params.len() as u32, 0);
+        let function = LLVMAddFunction(self.module, name.as_ptr(), fn_type);
+        LLVMExtAddAttrsOnFunction(self.context, function, &[NoUnwind]);
+        LLVMExtAddAttrsOnParameter(
+            self.context,
+            function,
+            &[NoCapture, NoAlias, NonNull, ReadOnly],
+            0,
+        );
+        self.intrinsics.insert(
+            name.into_string().unwrap(),
+            Intrinsic::FunctionPointer(function, ffi::weld_runst_parallel_chunks as *mut c_void),
+        );
+
+        // The task is passed as an opaque pointer.
+        let mut params = vec![
+            self.run_handle_type(),
+            int8p,
+            int8p,
+            int8p,
+            self.i64_type(),
+            self.i64_type(),
+        ];
+        let name = CString::new("weld_runst_parallel_for").unwrap();
+        let fn_type = LLVMFunctionType(
+            self.void_type(),
+            params.as_mut_ptr(),
+            params.len() as u32,
+            0,
+        );
+        let function = LLVMAddFunction(self.module, name.as_ptr(), fn_type);
+        LLVMExtAddAttrsOnParameter(self.context, function, &[NoCapture, NoAlias, NonNull], 0);
+        self.intrinsics.insert(
+            name.into_string().unwrap(),
+            Intrinsic::FunctionPointer(function, ffi::weld_runst_parallel_for as *mut c_void),
+        );
+
         let mut params = vec![
             int8p,
             int8p,
@@ -100,11 +100,13 @@ mod jit;
 mod llvm_exts;
 mod numeric;
 mod serde;
+mod strings;
 mod target;
 mod vector;
 
 use self::builder::appender;
 use self::builder::merger;
+use self::builder::topk;
 
 /// Loads a dynamic library from a file using LLVMLoadLibraryPermanently.
 ///
@@ -266,7 +268,9 @@ impl HasPointer for Type {
             Dict(_, _) => true,
             Builder(_, _) => true,
             Struct(ref tys) => tys.iter().any(|ref t| t.has_pointer()),
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         }
     }
 }
@@ -295,6 +299,10 @@ pub struct LlvmGenerator {
     ///
     /// The key maps the appender type to the appender's type reference and methods on it.
     appenders: FnvHashMap<BuilderKind, appender::Appender>,
+    /// A map tracking generated topk builders.
+    ///
+    /// The key maps the topk type to the topk's type reference and methods on it.
+    topks: FnvHashMap<BuilderKind, topk::TopK>,
     /// A map tracking generated dictionaries.
     ///
     /// The key maps the dictionary's `Dict` type to the type reference and methods on it.
@@ -444,6 +452,57 @@ pub trait CodeGenExt {
         (function, builder, block)
     }
 
+    /// Generates a loop that runs `body` for each index in `[start, end)`.
+    ///
+    /// The loop is generated at the position of `builder`, and `builder` is positioned after the
+    /// loop when this method returns. `body` receives the `i64` index and may add basic blocks.
+    unsafe fn gen_counted_loop<F>(
+        &mut self,
+        builder: LLVMBuilderRef,
+        start: LLVMValueRef,
+        end: LLVMValueRef,
+        mut body: F,
+    ) -> WeldResult<()>
+    where
+        Self: Sized,
+        F: FnMut(&mut Self, LLVMValueRef) -> WeldResult<()>,
+    {
+        use self::llvm_sys::LLVMIntPredicate::LLVMIntSLT;
+        let entry_block = LLVMGetInsertBlock(builder);
+        let function = LLVMGetBasicBlockParent(entry_block);
+        let header_block =
+            LLVMAppendBasicBlockInContext(self.context(), function, c_str!("loop.header"));
+        let body_block =
+            LLVMAppendBasicBlockInContext(self.context(), function, c_str!("loop.body"));
+        let exit_block =
+            LLVMAppendBasicBlockInContext(self.context(), function, c_str!("loop.exit"));
+        LLVMBuildBr(builder, header_block);
+
+        LLVMPositionBuilderAtEnd(builder, header_block);
+        let index = LLVMBuildPhi(builder, self.i64_type(), c_str!("i"));
+        let cond = LLVMBuildICmp(builder, LLVMIntSLT, index, end, c_str!(""));
+        LLVMBuildCondBr(builder, cond, body_block, exit_block);
+
+        LLVMPositionBuilderAtEnd(builder, body_block);
+        body(self, index)?;
+        // The body may have moved the builder to a new block.
+        let latch_block = LLVMGetInsertBlock(builder);
+        let next = LLVMBuildNSWAdd(builder, index, self.i64(1), c_str!(""));
+        LLVMBuildBr(builder, header_block);
+
+        let mut blocks = [entry_block, latch_block];
+        let mut values = [start, next];
+        LLVMAddIncoming(
+            index,
+            values.as_mut_ptr(),
+            blocks.as_mut_ptr(),
+            values.len() as u32,
+        );
+
+        LLVMPositionBuilderAtEnd(builder, exit_block);
+        Ok(())
+    }
+
     /// Converts a `LiteralKind` into a constant LLVM scalar literal value.
     ///
     /// This method does not generate any code.
@@ -504,6 +563,18 @@ pub trait CodeGenExt {
                     _ => unreachable!(),
                 }
             }
+            Decimal(_, _) => {
+                let ty = self.i64_type();
+                match op {
+                    Add => Ok(LLVMConstInt(ty, 0, 1)),
+                    // The stored value of a decimal one is scaled.
+                    Multiply => Ok(LLVMConstInt(ty, kind.scale_factor() as u64, 1)),
+                    Max => Ok(LLVMConstInt(ty, ::std::i64::MIN as u64, 1)),
+                    Min => Ok(LLVMConstInt(ty, ::std::i64::MAX as u64, 1)),
+                    _ => unreachable!(),
+                }
+            }
+            _ if kind.is_temporal() => self.binop_identity(op, kind.storage_kind()),
             _ => unreachable!(),
         }
     }
@@ -750,6 +821,7 @@ impl LlvmGenerator {
             vectors: FnvHashMap::default(),
             mergers: FnvHashMap::default(),
             appenders: FnvHashMap::default(),
+            topks: FnvHashMap::default(),
             dictionaries: FnvHashMap::default(),
             strings: FnvHashMap::default(),
             eq_fns: FnvHashMap::default(),
@@ -1238,6 +1310,15 @@ impl LlvmGenerator {
                 LLVMBuildStore(context.builder, result, output_pointer);
                 Ok(())
             }
+            Hash(ref child) => {
+                use self::hash::GenHash;
+                let output_pointer = context.get_value(output)?;
+                let child_pointer = context.get_value(child)?;
+                let child_type = context.sir_function.symbol_type(child)?;
+                let hash = self.gen_hash(child_type, context.builder, child_pointer, None)?;
+                LLVMBuildStore(context.builder, hash, output_pointer);
+                Ok(())
+            }
             Length(ref child) => {
                 let output_pointer = context.get_value(output)?;
                 let child_value = self.load(context.builder, context.get_value(child)?)?;
@@ -1523,6 +1604,10 @@ impl LlvmGenerator {
                     unreachable!()
                 }
             }
+            StringOp { .. } => {
+                use self::strings::StringOpGen;
+                self.gen_string_op(context, statement)
+            }
             ToVec(ref child) => {
                 let output_pointer = context.get_value(output)?;
                 let child_value = self.load(context.builder, context.get_value(child)?)?;
@@ -1680,8 +1765,8 @@ impl LlvmGenerator {
                 Bool => self.bool_type(),
                 I8 | U8 => self.i8_type(),
                 I16 | U16 => self.i16_type(),
-                I32 | U32 => self.i32_type(),
-                I64 | U64 => self.i64_type(),
+                I32 | U32 | Date => self.i32_type(),
+                I64 | U64 | Decimal(_, _) | Timestamp | Interval => self.i64_type(),
                 F32 => self.f32_type(),
                 F64 => self.f64_type(),
             },
@@ -1721,7 +1806,9 @@ impl LlvmGenerator {
                 }
                 self.vectors[elem_type].vector_ty
             }
-            Function(_, _) | Unknown | Alias(_, _) => unreachable!(),
+            Function(_, _) | Optional(_) | Set(_) | Sketch(_, _) | Unknown | Alias(_, _) => {
+                unreachable!()
+            }
         };
         Ok(result)
     }
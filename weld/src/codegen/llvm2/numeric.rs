@@ -80,6 +80,15 @@ trait NumericExpressionGenInternal {
         right: LLVMValueRef,
         ty: &Type,
     ) -> WeldResult<LLVMValueRef>;
+    /// Generates a unary operator over an integer scalar or SIMD value.
+    unsafe fn gen_integer_unaryop(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        op: UnaryOpKind,
+        kind: ScalarKind,
+        simd: bool,
+        value: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef>;
 }
 
 impl NumericExpressionGenInternal for LlvmGenerator {
@@ -127,6 +136,50 @@ impl NumericExpressionGenInternal for LlvmGenerator {
             _ => unreachable!(),
         }
     }
+
+    unsafe fn gen_integer_unaryop(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        op: UnaryOpKind,
+        kind: ScalarKind,
+        simd: bool,
+        value: LLVMValueRef,
+    ) -> WeldResult<LLVMValueRef> {
+        use crate::ast::UnaryOpKind::*;
+        let ty = LLVMTypeOf(value);
+        match op {
+            Abs if kind.is_signed_integer() => {
+                let zero = LLVMConstNull(ty);
+                let negative = LLVMBuildICmp(ctx.builder, LLVMIntSLT, value, zero, c_str!(""));
+                let negated = LLVMBuildSub(ctx.builder, zero, value, c_str!(""));
+                Ok(LLVMBuildSelect(
+                    ctx.builder,
+                    negative,
+                    negated,
+                    value,
+                    c_str!(""),
+                ))
+            }
+            // Unsigned values are their own absolute value.
+            Abs => Ok(value),
+            Popcount => {
+                let name = Intrinsics::llvm_numeric("ctpop", kind, simd);
+                let mut arg_tys = [ty];
+                self.intrinsics.add(&name, ty, &mut arg_tys);
+                self.intrinsics.call(ctx.builder, name, &mut [value])
+            }
+            Clz | Ctz => {
+                let name = if op == Clz { "ctlz" } else { "cttz" };
+                let name = Intrinsics::llvm_numeric(name, kind, simd);
+                // The flag makes the count of a zero value the bit width instead of undefined.
+                let mut arg_tys = [ty, self.i1_type()];
+                self.intrinsics.add(&name, ty, &mut arg_tys);
+                self.intrinsics
+                    .call(ctx.builder, name, &mut [value, self.i1(false)])
+            }
+            _ => compile_err!("Unsupported unary op: {} on {}", op, kind),
+        }
+    }
 }
 
 trait UnaryOpSupport {
@@ -143,6 +196,10 @@ impl UnaryOpSupport for UnaryOpKind {
             Sqrt => Some("sqrt"),
             Sin => Some("sin"),
             Cos => Some("cos"),
+            Floor => Some("floor"),
+            Ceil => Some("ceil"),
+            Round => Some("round"),
+            Abs => Some("fabs"),
             _ => None,
         }
     }
@@ -167,7 +224,9 @@ impl NumericExpressionGen for LlvmGenerator {
 
             // Use the LLVM intrinsic if one is available, since LLVM may be able to vectorize it.
             // Otherwise, fall back to the libc math variant and unroll SIMD values manually.
-            let result = if let Some(name) = op.llvm_intrinsic() {
+            let result = if kind.is_integer() {
+                self.gen_integer_unaryop(ctx, op, kind, simd, child)?
+            } else if let Some(name) = op.llvm_intrinsic() {
                 let name = Intrinsics::llvm_numeric(name, kind, simd);
                 let ret_ty = LLVMTypeOf(child);
                 let mut arg_tys = [ret_ty];
@@ -185,6 +244,7 @@ impl NumericExpressionGen for LlvmGenerator {
                     (Cosh, F32) => "coshf",
                     (Tanh, F32) => "tanhf",
                     (Erf, F32) => "erff",
+                    (Cbrt, F32) => "cbrtf",
                     (Tan, F64) => "tan",
                     (ASin, F64) => "asin",
                     (ACos, F64) => "acos",
@@ -193,6 +253,7 @@ impl NumericExpressionGen for LlvmGenerator {
                     (Cosh, F64) => "cosh",
                     (Tanh, F64) => "tanh",
                     (Erf, F64) => "erf",
+                    (Cbrt, F64) => "cbrt",
                     _ => unreachable!(),
                 };
                 let ret_ty = self.llvm_type(&Scalar(kind))?;
@@ -475,11 +536,93 @@ pub unsafe fn gen_cast(
     to: &Type,
     to_ll: LLVMTypeRef,
 ) -> WeldResult<LLVMValueRef> {
+    use self::llvm_sys::LLVMRealPredicate::LLVMRealOLT;
     use crate::ast::ScalarKind::*;
     use crate::ast::Type::Scalar;
     let result = match (from, to) {
         (&Scalar(s1), &Scalar(s2)) => {
             match (s1, s2) {
+                // Dates, timestamps and intervals are cast as their stored values.
+                (_, _) if s1.is_temporal() || s2.is_temporal() => {
+                    let from = &Scalar(s1.storage_kind());
+                    let to = &Scalar(s2.storage_kind());
+                    if from == to {
+                        value
+                    } else {
+                        gen_cast(builder, value, from, to, to_ll)?
+                    }
+                }
+
+                // Rescaling between decimals.
+                (Decimal(_, from_scale), Decimal(_, to_scale)) => {
+                    let ty = LLVMTypeOf(value);
+                    if to_scale >= from_scale {
+                        let factor = 10i64.pow(u32::from(to_scale - from_scale));
+                        LLVMBuildMul(
+                            builder,
+                            value,
+                            LLVMConstInt(ty, factor as u64, 1),
+                            c_str!(""),
+                        )
+                    } else {
+                        let factor = 10i64.pow(u32::from(from_scale - to_scale));
+                        LLVMBuildSDiv(
+                            builder,
+                            value,
+                            LLVMConstInt(ty, factor as u64, 1),
+                            c_str!(""),
+                        )
+                    }
+                }
+
+                // Floating point to decimal, rounding to the nearest decimal.
+                (_, Decimal(_, _)) if s1.is_float() => {
+                    let ty = LLVMTypeOf(value);
+                    let factor = LLVMConstReal(ty, s2.scale_factor() as f64);
+                    let scaled = LLVMBuildFMul(builder, value, factor, c_str!(""));
+                    let negative = LLVMBuildFCmp(
+                        builder,
+                        LLVMRealOLT,
+                        scaled,
+                        LLVMConstReal(ty, 0.0),
+                        c_str!(""),
+                    );
+                    let half = LLVMBuildSelect(
+                        builder,
+                        negative,
+                        LLVMConstReal(ty, -0.5),
+                        LLVMConstReal(ty, 0.5),
+                        c_str!(""),
+                    );
+                    let rounded = LLVMBuildFAdd(builder, scaled, half, c_str!(""));
+                    LLVMBuildFPToSI(builder, rounded, to_ll, c_str!(""))
+                }
+
+                // Integer to decimal. Decimals have the same LLVM type as `i64`.
+                (_, Decimal(_, _)) if s1.is_integer() || s1.is_bool() => {
+                    let value = gen_cast(builder, value, from, &Scalar(I64), to_ll)?;
+                    let factor = LLVMConstInt(to_ll, s2.scale_factor() as u64, 1);
+                    LLVMBuildMul(builder, value, factor, c_str!(""))
+                }
+
+                // Decimal to floating point.
+                (Decimal(_, _), _) if s2.is_float() => {
+                    let value = LLVMBuildSIToFP(builder, value, to_ll, c_str!(""));
+                    let factor = LLVMConstReal(to_ll, s1.scale_factor() as f64);
+                    LLVMBuildFDiv(builder, value, factor, c_str!(""))
+                }
+
+                // Decimal to integer, rounding toward zero.
+                (Decimal(_, _), _) if s2.is_integer() => {
+                    let factor = LLVMConstInt(LLVMTypeOf(value), s1.scale_factor() as u64, 1);
+                    let value = LLVMBuildSDiv(builder, value, factor, c_str!(""));
+                    gen_cast(builder, value, &Scalar(I64), to, to_ll)?
+                }
+
+                (Decimal(_, _), _) | (_, Decimal(_, _)) => {
+                    return compile_err!("Cannot cast {} to {}", from, to)
+                }
+
                 // Floating point extension and truncation.
                 (F32, F64) => LLVMBuildFPExt(builder, value, to_ll, c_str!("")),
                 (F64, F32) => LLVMBuildFPTrunc(builder, value, to_ll, c_str!("")),
@@ -557,9 +700,34 @@ pub unsafe fn gen_binop(
     use self::llvm_sys::LLVMIntPredicate::*;
     use self::llvm_sys::LLVMRealPredicate::*;
     use crate::ast::BinOpKind::*;
+    use crate::ast::ScalarKind::{Decimal, Interval, I64};
     use crate::ast::Type::*;
     let name = c_str!("");
     let result = match *ty {
+        // Dates, timestamps and intervals are stored as integers. Other arithmetic on them is
+        // lowered before code generation.
+        Scalar(s) | Simd(s) if s.is_temporal() => {
+            let storage = match *ty {
+                Simd(_) => Simd(s.storage_kind()),
+                _ => Scalar(s.storage_kind()),
+            };
+            match op {
+                Add | Subtract if s == Interval => gen_binop(builder, op, left, right, &storage)?,
+                Max | Min => gen_binop(builder, op, left, right, &storage)?,
+                _ if op.is_comparison() => gen_binop(builder, op, left, right, &storage)?,
+                _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+            }
+        }
+        // Decimals are stored as `i64` values with the same scale, so only multiplication and
+        // division need to rescale their results.
+        Scalar(Decimal(_, _)) => match op {
+            Multiply | Divide => gen_decimal_binop(builder, op, left, right, ty)?,
+            Add | Subtract | Modulo | Max | Min => {
+                gen_binop(builder, op, left, right, &Scalar(I64))?
+            }
+            _ if op.is_comparison() => gen_binop(builder, op, left, right, &Scalar(I64))?,
+            _ => return compile_err!("Unsupported binary op: {} on {}", op, ty),
+        },
         Scalar(s) | Simd(s) => match op {
             Add if s.is_integer() => LLVMBuildAdd(builder, left, right, name),
             Add if s.is_float() => LLVMBuildFAdd(builder, left, right, name),
@@ -632,6 +800,19 @@ pub unsafe fn gen_binop(
 
             Xor if s.is_integer() || s.is_bool() => LLVMBuildXor(builder, left, right, name),
 
+            ShiftLeft if s.is_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildShl(builder, left, right, name)
+            }
+            ShiftRight if s.is_signed_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildAShr(builder, left, right, name)
+            }
+            ShiftRight if s.is_unsigned_integer() => {
+                let right = gen_shift_amount(builder, right, ty);
+                LLVMBuildLShr(builder, left, right, name)
+            }
+
             Max => {
                 let compare = gen_binop(builder, GreaterThanOrEqual, left, right, ty)?;
                 LLVMBuildSelect(builder, compare, left, right, c_str!(""))
@@ -648,3 +829,65 @@ pub unsafe fn gen_binop(
     };
     Ok(result)
 }
+
+/// Masks a shift amount of a value with type `ty` to the bit width of the type.
+///
+/// Shifting by a negative amount or by at least the bit width is otherwise undefined.
+unsafe fn gen_shift_amount(
+    builder: LLVMBuilderRef,
+    amount: LLVMValueRef,
+    ty: &Type,
+) -> LLVMValueRef {
+    let (kind, simd) = match *ty {
+        Type::Scalar(kind) => (kind, false),
+        Type::Simd(kind) => (kind, true),
+        _ => unreachable!(),
+    };
+    let elem_ty = if simd {
+        LLVMGetElementType(LLVMTypeOf(amount))
+    } else {
+        LLVMTypeOf(amount)
+    };
+    let mut mask = LLVMConstInt(elem_ty, u64::from(kind.bits() - 1), 0);
+    if simd {
+        mask = LLVMConstVector(
+            [mask; LLVM_VECTOR_WIDTH as usize].as_mut_ptr(),
+            LLVM_VECTOR_WIDTH,
+        );
+    }
+    LLVMBuildAnd(builder, amount, mask, c_str!(""))
+}
+
+/// Generates a multiplication or division of decimals with type `ty`.
+///
+/// The operation uses 128-bit intermediate values, and the result is rounded toward zero.
+unsafe fn gen_decimal_binop(
+    builder: LLVMBuilderRef,
+    op: BinOpKind,
+    left: LLVMValueRef,
+    right: LLVMValueRef,
+    ty: &Type,
+) -> WeldResult<LLVMValueRef> {
+    use crate::ast::BinOpKind::*;
+    let factor = match *ty {
+        Type::Scalar(kind) => kind.scale_factor(),
+        _ => unreachable!(),
+    };
+    let i64_type = LLVMTypeOf(left);
+    let i128_type = LLVMIntTypeInContext(LLVMGetTypeContext(i64_type), 128);
+    let factor = LLVMConstInt(i128_type, factor as u64, 1);
+    let left = LLVMBuildSExt(builder, left, i128_type, c_str!(""));
+    let right = LLVMBuildSExt(builder, right, i128_type, c_str!(""));
+    let result = match op {
+        Multiply => {
+            let product = LLVMBuildMul(builder, left, right, c_str!(""));
+            LLVMBuildSDiv(builder, product, factor, c_str!(""))
+        }
+        Divide => {
+            let dividend = LLVMBuildMul(builder, left, factor, c_str!(""));
+            LLVMBuildSDiv(builder, dividend, right, c_str!(""))
+        }
+        _ => unreachable!(),
+    };
+    Ok(LLVMBuildTrunc(builder, result, i64_type, c_str!("")))
+}
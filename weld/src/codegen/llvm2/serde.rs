@@ -256,6 +256,10 @@ impl SerHelper for LlvmGenerator {
                     let value = self.load(builder, value)?;
                     self.gen_put_value(builder, value, buffer, run, position)?
                 }
+                Struct(ref tys) if tys.is_empty() => {
+                    // Zero-size values, such as the values of a lowered set, take no space.
+                    (buffer, position)
+                }
                 Struct(_) if !ty.has_pointer() => {
                     // Use a memcpy intrinsic instead of a load and store for structs.
                     let count = self.i64(1);
@@ -440,7 +444,16 @@ impl SerHelper for LlvmGenerator {
                         val_ser_fn,
                     )?
                 }
-                Unknown | Alias(_, _) | Simd(_) | Function(_, _) | Builder(_, _) => unreachable!(),
+                Unknown
+                | Alias(_, _)
+                | Optional(_)
+                | Set(_)
+                | Sketch(_, _)
+                | Simd(_)
+                | Function(_, _)
+                | Builder(_, _) => {
+                    unreachable!()
+                }
             };
 
             let ret =
@@ -616,6 +629,7 @@ impl DeHelper for LlvmGenerator {
                     LLVMBuildStore(builder, value, output);
                     position
                 }
+                Struct(ref tys) if tys.is_empty() => position,
                 Struct(_) if !ty.has_pointer() => {
                     // Copy a single value of the given type.
                     let one = self.i64(1);
@@ -865,7 +879,16 @@ impl DeHelper for LlvmGenerator {
                     LLVMBuildStore(builder, dictionary, output);
                     phi_position
                 }
-                Unknown | Alias(_, _) | Simd(_) | Function(_, _) | Builder(_, _) => unreachable!(),
+                Unknown
+                | Alias(_, _)
+                | Optional(_)
+                | Set(_)
+                | Sketch(_, _)
+                | Simd(_)
+                | Function(_, _)
+                | Builder(_, _) => {
+                    unreachable!()
+                }
             };
 
             LLVMBuildRet(builder, updated_position);
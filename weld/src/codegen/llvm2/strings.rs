@@ -0,0 +1,141 @@
+//! Code generation for the built-in string operations.
+//!
+//! String operations call functions defined in `weld::runtime::strings`. Like CUDFs, these take
+//! pointers to their arguments and a pointer to the result, and additionally the run handle to
+//! allocate new strings.
+
+use llvm_sys;
+
+use libc::c_void;
+
+use crate::ast::ScalarKind::*;
+use crate::ast::Type::Scalar;
+use crate::ast::*;
+use crate::error::*;
+use crate::runtime::strings;
+use crate::sir::*;
+
+use self::llvm_sys::core::*;
+
+use super::{CodeGenExt, FunctionContext, LlvmGenerator};
+
+/// Generates string operations.
+pub trait StringOpGen {
+    /// Generates code for a `StringOp` statement.
+    unsafe fn gen_string_op(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        statement: &Statement,
+    ) -> WeldResult<()>;
+}
+
+/// Returns the name and address of the runtime function implementing a string operation.
+///
+/// `arg_ty` is the type of the operation's first argument.
+fn runtime_function(op: StringOpKind, arg_ty: &Type) -> WeldResult<(&'static str, *mut c_void)> {
+    use crate::ast::StringOpKind::*;
+    let function = match (op, arg_ty) {
+        (Concat, _) => ("weld_str_concat", strings::weld_str_concat as *mut c_void),
+        (Substr, _) => ("weld_str_substr", strings::weld_str_substr as *mut c_void),
+        (Find, _) => ("weld_str_find", strings::weld_str_find as *mut c_void),
+        (StartsWith, _) => (
+            "weld_str_startswith",
+            strings::weld_str_startswith as *mut c_void,
+        ),
+        (EndsWith, _) => (
+            "weld_str_endswith",
+            strings::weld_str_endswith as *mut c_void,
+        ),
+        (ToUpper, _) => ("weld_str_toupper", strings::weld_str_toupper as *mut c_void),
+        (ToLower, _) => ("weld_str_tolower", strings::weld_str_tolower as *mut c_void),
+        (ParseI64, _) => (
+            "weld_str_strtoi64",
+            strings::weld_str_strtoi64 as *mut c_void,
+        ),
+        (ParseF64, _) => (
+            "weld_str_strtof64",
+            strings::weld_str_strtof64 as *mut c_void,
+        ),
+        (ToString, Scalar(Bool)) => (
+            "weld_str_tostring_bool",
+            strings::weld_str_tostring_bool as *mut c_void,
+        ),
+        (ToString, Scalar(I8)) => (
+            "weld_str_tostring_i8",
+            strings::weld_str_tostring_i8 as *mut c_void,
+        ),
+        (ToString, Scalar(I16)) => (
+            "weld_str_tostring_i16",
+            strings::weld_str_tostring_i16 as *mut c_void,
+        ),
+        (ToString, Scalar(I32)) => (
+            "weld_str_tostring_i32",
+            strings::weld_str_tostring_i32 as *mut c_void,
+        ),
+        (ToString, Scalar(I64)) => (
+            "weld_str_tostring_i64",
+            strings::weld_str_tostring_i64 as *mut c_void,
+        ),
+        (ToString, Scalar(U8)) => (
+            "weld_str_tostring_u8",
+            strings::weld_str_tostring_u8 as *mut c_void,
+        ),
+        (ToString, Scalar(U16)) => (
+            "weld_str_tostring_u16",
+            strings::weld_str_tostring_u16 as *mut c_void,
+        ),
+        (ToString, Scalar(U32)) => (
+            "weld_str_tostring_u32",
+            strings::weld_str_tostring_u32 as *mut c_void,
+        ),
+        (ToString, Scalar(U64)) => (
+            "weld_str_tostring_u64",
+            strings::weld_str_tostring_u64 as *mut c_void,
+        ),
+        (ToString, Scalar(F32)) => (
+            "weld_str_tostring_f32",
+            strings::weld_str_tostring_f32 as *mut c_void,
+        ),
+        (ToString, Scalar(F64)) => (
+            "weld_str_tostring_f64",
+            strings::weld_str_tostring_f64 as *mut c_void,
+        ),
+        (ToString, _) => return compile_err!("Unsupported type {} in {}", arg_ty, op),
+    };
+    Ok(function)
+}
+
+impl StringOpGen for LlvmGenerator {
+    unsafe fn gen_string_op(
+        &mut self,
+        ctx: &mut FunctionContext<'_>,
+        statement: &Statement,
+    ) -> WeldResult<()> {
+        if let StatementKind::StringOp { op, ref args } = statement.kind {
+            let output = statement.output.as_ref().unwrap();
+            let (name, pointer) = runtime_function(op, ctx.sir_function.symbol_type(&args[0])?)?;
+
+            let mut arg_tys = vec![self.run_handle_type()];
+            for arg in args.iter() {
+                let ty = self.llvm_type(ctx.sir_function.symbol_type(arg)?)?;
+                arg_tys.push(LLVMPointerType(ty, 0));
+            }
+            let ret_ty = self.llvm_type(ctx.sir_function.symbol_type(output)?)?;
+            arg_tys.push(LLVMPointerType(ret_ty, 0));
+
+            let void_type = self.void_type();
+            self.intrinsics
+                .add_function_pointer(name, void_type, &mut arg_tys, pointer);
+
+            let mut arg_values = vec![ctx.get_run()];
+            for arg in args.iter() {
+                arg_values.push(ctx.get_value(arg)?);
+            }
+            arg_values.push(ctx.get_value(output)?);
+            let _ = self.intrinsics.call(ctx.builder, name, &mut arg_values)?;
+            Ok(())
+        } else {
+            unreachable!()
+        }
+    }
+}
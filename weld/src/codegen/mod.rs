@@ -17,15 +17,17 @@ use crate::error::*;
 use crate::runtime::WeldRuntimeErrno;
 use crate::sir::*;
 use crate::util::stats::{CompilationStats, RunStats};
-use crate::util::offload_ve::offload_ve;
+use crate::util::veoffload::{DeviceRun, OffloadTransport};
 use crate::WeldError;
 
 use std::fmt;
+use std::rc::Rc;
 
 mod llvm2;
 mod c;
 
 pub use self::llvm2::load_library;
+pub(crate) use self::c::{HostRun, ERROR_MESSAGE_SIZE};
 
 /// A wrapper for a struct passed as input to Weld.
 #[derive(Clone, Debug)]
@@ -57,9 +59,60 @@ pub struct WeldOutputArgs {
     pub errno: WeldRuntimeErrno,
 }
 
+/// A value that resides in the memory of an offload device.
+///
+/// The value owns the device-side context of the run that produced it, so its memory is freed
+/// on the device when the value and all its clones are dropped.
+#[derive(Clone, Debug)]
+pub struct DeviceValue {
+    /// The address of the value in device memory.
+    pub addr: u64,
+    /// The Weld type of the value.
+    pub ty: Type,
+    /// The device that holds the value.
+    pub device: *mut dyn OffloadTransport,
+    /// The generation of the device process that holds the value.
+    ///
+    /// The value is lost if the process is re-created after a failure.
+    pub generation: u64,
+    /// The vector length of the module that produced the value, which determines its layout.
+    pub vector_width: u32,
+    /// The device-side context that holds the memory of the value.
+    pub run: Rc<DeviceRun>,
+}
+
+impl DeviceValue {
+    /// Copies the value to the host and returns a pointer to the copy.
+    pub unsafe fn to_host(&self) -> WeldResult<i64> {
+        c::download(self)
+    }
+}
+
 /// A trait implemented by trait objects for running a Weld program.
 pub trait Runnable {
     fn run(&self, arg: i64, stats: &mut RunStats) -> Result<i64, WeldError>;
+
+    /// Runs the program on an offload device.
+    ///
+    /// If `device_arg` is set, it is passed to the program instead of the input data of `arg`.
+    /// Returns a pointer to the `WeldOutputArgs` and, if the backend keeps results on the device,
+    /// the result in device memory.
+    ///
+    /// Backends that do not offload reject device-resident arguments and run with `run`.
+    fn run_offload(
+        &self,
+        arg: i64,
+        device_arg: Option<&DeviceValue>,
+        stats: &mut RunStats,
+    ) -> Result<(i64, Option<DeviceValue>), WeldError> {
+        if let Some(value) = device_arg {
+            return weld_err!(
+                "Backend cannot run with a device-resident argument of type {}",
+                value.ty
+            );
+        }
+        Ok((self.run(arg, stats)?, None))
+    }
 }
 
 /// A compiled, runnable module.
@@ -78,6 +131,18 @@ impl CompiledModule {
     ) -> Result<i64, WeldError> {
         self.runnable.run(arg, stats)
     }
+
+    /// Run the compiled module, possibly with an argument or result on an offload device.
+    ///
+    /// This calls the `run_offload` function on the internal `Runnable`.
+    pub fn run_offload(
+        &self,
+        arg: i64,
+        device_arg: Option<&DeviceValue>,
+        stats: &mut RunStats
+    ) -> Result<(i64, Option<DeviceValue>), WeldError> {
+        self.runnable.run_offload(arg, device_arg, stats)
+    }
 }
 
 impl fmt::Debug for CompiledModule {
@@ -91,14 +156,18 @@ impl fmt::Debug for CompiledModule {
 /// This function dispatches to the backend specified in `conf` to generate a compiled, runnable
 /// module. Statistics about compilation (e.g., time to generate code) are written into `stats`. If
 /// the `dumpCode` option is enabled, code is dumped to a filename `code-<timestamp>.[ll|S].
+///
+/// `program_hash` identifies the optimized program independently of its symbol names. Backends
+/// may use it to reuse previously compiled artifacts.
 pub fn compile_program(
     program: &SirProgram,
+    program_hash: u64,
     conf: &mut ParsedConf,
     stats: &mut CompilationStats,
 ) -> WeldResult<CompiledModule> {
     let runnable =
-        if offload_ve() {
-            c::compile(&program, conf, stats)?
+        if conf.backend.is_c() {
+            c::compile(&program, program_hash, conf, stats)?
         } else {
             llvm2::compile(&program, conf, stats)?
         };
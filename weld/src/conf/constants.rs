@@ -112,16 +112,125 @@ pub const CONF_DUMP_CODE_FILENAME_KEY: &str = "weld.compile.dumpCodeFilename";
 /// This key does not have an explicit default value: if it is not specified, all formats
 /// are dumped. The possible formats are:
 ///
-/// weld,weldopt,llvm,llvmopt,sir,assembly.
+/// weld,weldopt,llvm,llvmopt,sir,assembly,c.
 ///
 /// This parameter should be set for compilation.
 pub const CONF_DUMP_CODE_FORMATS_KEY: &str = "weld.compile.dumpCodeFormats";
 
+/// Enables the compiled-artifact cache of the C backend.
+///
+/// When enabled, shared objects built by the external C compiler are kept in the directory
+/// specified by `weld.compile.cacheDir`, keyed by the optimized program and the compiler
+/// settings. Compiling an equivalent program again reuses the cached shared object.
+///
+/// This parameter should be set for compilation.
+pub const CONF_COMPILE_CACHE_KEY: &str = "weld.compile.cache";
+
+/// Specifies the directory of the compiled-artifact cache.
+///
+/// This parameter should be set for compilation.
+pub const CONF_COMPILE_CACHE_DIR_KEY: &str = "weld.compile.cacheDir";
+
 /// Enables runtime bounds checking for loops before executing them.
 ///
 /// This parameter should be set for compilation.
 pub const CONF_ENABLE_BOUNDS_CHECKS_KEY: &str = "weld.compile.enableBoundsChecks";
 
+/// Selects the backend used to generate and run code.
+///
+/// The possible backends are:
+///
+/// * `llvm`: JIT-compile LLVM for the host.
+/// * `c-ve`: generate C, compile it with the VE compiler and offload it to a VE.
+/// * `c-host`: generate C, compile it with the host compiler and run it in-process.
+/// * `c-fake-ve`: like `c-ve`, but offload to an in-process fake VE on the host.
+///
+/// This parameter should be set for compilation.
+pub const CONF_BACKEND_KEY: &str = "weld.backend";
+
+/// Specifies the number of lanes in SIMD values generated by the C backends.
+///
+/// Vectorized loops process this many elements per iteration. If this key is not set, the
+/// vector length of the target is used: 256 (the length of a VE vector register) for the `c-ve`
+/// and `c-fake-ve` backends, and 8 for the `c-host` backend.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VECTOR_LENGTH_KEY: &str = "weld.compile.vectorLength";
+
+/// Specifies the VE node to offload to with the `c-ve` backend.
+///
+/// If this is not set, the deprecated `VE_NODE_NUMBER` environment variable is used if it holds
+/// a non-negative node number. A negative `VE_NODE_NUMBER` no longer disables offloading: select
+/// the `llvm` or `c-host` backend with `weld.backend` instead.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_NODE_KEY: &str = "weld.ve.node";
+
+/// Specifies a comma-separated list of VE nodes to offload to with the `c-ve` or `c-fake-ve`
+/// backend.
+///
+/// If more than one node is given, a program whose result is built by top-level loops over its
+/// vector parameters is split into chunks that run on the nodes at the same time, and the
+/// results of the chunks are merged on the host. Other programs run on the first node. If this
+/// is empty, `weld.ve.node` is used.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_NODES_KEY: &str = "weld.ve.nodes";
+
+/// Specifies whether the results of a module compiled for the `c-ve` or `c-fake-ve` backend stay
+/// in VE memory.
+///
+/// If enabled, `WeldModule::run` returns a device-resident `WeldValue` instead of copying the
+/// result to the host. The value can be passed to a later run on the same VE without a copy, or
+/// copied to the host with `WeldValue::to_host`. Its VE memory is freed once the value and all
+/// its clones are dropped.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_DEVICE_RESULTS_KEY: &str = "weld.ve.deviceResults";
+
+/// Specifies how parameters of modules compiled for the `c-ve` or `c-fake-ve` backend are sent.
+///
+/// Vectors of scalars are either packed into the single buffer that carries all parameters, or
+/// sent with a transfer of their own. The possible policies are:
+///
+/// * `threshold`: pack vectors smaller than `weld.ve.serializeThreshold` bytes.
+/// * `adaptive`: pack vectors when copying them costs less than the latency of a transfer, as
+///   measured by earlier runs of the module. The threshold is used until enough runs were
+///   measured.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_SERIALIZE_POLICY_KEY: &str = "weld.ve.serializePolicy";
+
+/// Specifies the size in bytes from which vectors are sent with a transfer of their own.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_SERIALIZE_THRESHOLD_KEY: &str = "weld.ve.serializeThreshold";
+
+/// Specifies whether parameters are sent to the VE in a single buffer converted in one pass.
+///
+/// This is the conversion used before parameters could be transferred separately.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_CONVERT_TOP_PARAMS_KEY: &str = "weld.ve.convertTopParams";
+
+/// Specifies whether the parameters of each run are printed before they are sent to the VE.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_CHECK_DATA_KEY: &str = "weld.ve.checkData";
+
+/// Specifies whether the buffer of serialized parameters is dumped before it is sent to the VE.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_DUMP_DATA_KEY: &str = "weld.ve.dumpData";
+
+/// Specifies how many seconds a call of a function on the VE may take, or 0 for no limit.
+///
+/// A call that does not finish in time fails with a `DeviceError`. The VE process is then
+/// considered hung, and the next run re-creates it.
+///
+/// This parameter should be set for compilation.
+pub const CONF_VE_CALL_TIMEOUT_KEY: &str = "weld.ve.callTimeout";
+
 /// Default memory limit.
 pub const CONF_MEMORY_LIMIT_DEFAULT: i64 = 1_000_000_000;
 
@@ -164,6 +273,47 @@ pub const CONF_EXPERIMENTAL_PASSES_DEFAULT: bool = false;
 /// Default setting for whether to enable bounds checking.
 pub const CONF_ENABLE_BOUNDS_CHECKS_DEFAULT: bool = false;
 
+/// Default backend.
+#[cfg(feature = "offload_ve")]
+pub const CONF_BACKEND_DEFAULT: &str = "c-ve";
+
+/// Default backend.
+#[cfg(not(feature = "offload_ve"))]
+pub const CONF_BACKEND_DEFAULT: &str = "llvm";
+
+/// Default VE node.
+pub const CONF_VE_NODE_DEFAULT: i32 = 0;
+
+/// Default list of VE nodes.
+pub const CONF_VE_NODES_DEFAULT: &str = "";
+
+/// Default setting for whether results stay in VE memory.
+pub const CONF_VE_DEVICE_RESULTS_DEFAULT: bool = false;
+
+/// Default policy for sending parameters to the VE.
+pub const CONF_VE_SERIALIZE_POLICY_DEFAULT: &str = "threshold";
+
+/// Default size from which vectors are sent with a transfer of their own.
+pub const CONF_VE_SERIALIZE_THRESHOLD_DEFAULT: usize = 1024000;
+
+/// Default setting for whether parameters are converted in one pass.
+pub const CONF_VE_CONVERT_TOP_PARAMS_DEFAULT: bool = false;
+
+/// Default setting for whether parameters are printed.
+pub const CONF_VE_CHECK_DATA_DEFAULT: bool = false;
+
+/// Default setting for whether serialized parameters are dumped.
+pub const CONF_VE_DUMP_DATA_DEFAULT: bool = false;
+
+/// Default number of seconds a call on the VE may take.
+pub const CONF_VE_CALL_TIMEOUT_DEFAULT: u64 = 0;
+
+/// Default setting for whether to cache compiled artifacts.
+pub const CONF_COMPILE_CACHE_DEFAULT: bool = false;
+
+/// Default directory for cached compiled artifacts.
+pub const CONF_COMPILE_CACHE_DIR_DEFAULT: &str = ".weld-cache";
+
 /// Default directory for dumping code.
 pub const CONF_DUMP_CODE_DIR_DEFAULT: &str = ".";
 
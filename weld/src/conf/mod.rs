@@ -6,9 +6,11 @@ use crate::optimizer::Pass;
 use crate::optimizer::OPTIMIZATION_PASSES;
 
 use crate::util::dump::{unique_filename, DumpCodeFormat};
+use crate::util::env::get_ve_node_number;
 
 use std::collections::HashSet;
 use std::str::FromStr;
+use std::time::Duration;
 
 pub mod constants;
 
@@ -48,6 +50,24 @@ impl Default for DumpCodeConfig {
     }
 }
 
+/// Configuration for caching compiled artifacts.
+#[derive(Clone, Debug)]
+pub struct CompileCacheConfig {
+    /// Toggles the cache.
+    pub enabled: bool,
+    /// Directory of cached artifacts.
+    pub directory: String,
+}
+
+impl Default for CompileCacheConfig {
+    fn default() -> Self {
+        CompileCacheConfig {
+            enabled: CONF_COMPILE_CACHE_DEFAULT,
+            directory: CONF_COMPILE_CACHE_DIR_DEFAULT.to_string(),
+        }
+    }
+}
+
 /// LLVM configuration.
 #[derive(Clone, Debug)]
 pub struct LLVMConfig {
@@ -92,6 +112,100 @@ impl Default for LLVMConfig {
     }
 }
 
+/// Backends that generate and run code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// LLVM JIT-compiled for the host.
+    LLVM,
+    /// C compiled for and offloaded to a VE.
+    CVE,
+    /// C compiled for and run on the host.
+    CHost,
+    /// C compiled for the host and offloaded to an in-process fake VE.
+    CFakeVE,
+}
+
+impl Backend {
+    /// Returns whether this backend generates C code.
+    pub fn is_c(self) -> bool {
+        self != Backend::LLVM
+    }
+
+    /// Returns whether this backend offloads execution through an offload transport.
+    pub fn is_offload(self) -> bool {
+        self == Backend::CVE || self == Backend::CFakeVE
+    }
+}
+
+/// Policies for sending parameters to a VE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SerializePolicy {
+    /// Packs vectors below a fixed size into the buffer of serialized parameters.
+    Threshold,
+    /// Packs vectors based on the measured cost of transfers.
+    Adaptive,
+}
+
+/// VE configuration.
+#[derive(Clone, Debug)]
+pub struct VEConfig {
+    /// VE node to offload to.
+    pub node: i32,
+    /// VE nodes to split programs across, or empty to only use `node`.
+    pub nodes: Vec<i32>,
+    /// Keeps results in VE memory instead of copying them to the host.
+    pub device_results: bool,
+    /// Decides which parameters are packed into the buffer of serialized parameters.
+    pub serialize_policy: SerializePolicy,
+    /// Size in bytes from which vectors are transferred separately.
+    pub serialize_threshold: usize,
+    /// Converts all parameters into a single buffer in one pass.
+    pub convert_top_params: bool,
+    /// Prints parameters before sending them.
+    pub check_data: bool,
+    /// Dumps the buffer of serialized parameters before sending it.
+    pub dump_data: bool,
+    /// Seconds a call on the VE may take, or 0 for no limit.
+    pub call_timeout: u64,
+}
+
+impl Default for VEConfig {
+    fn default() -> Self {
+        VEConfig {
+            node: CONF_VE_NODE_DEFAULT,
+            nodes: parse_ve_nodes(CONF_VE_NODES_DEFAULT.to_string()).unwrap(),
+            device_results: CONF_VE_DEVICE_RESULTS_DEFAULT,
+            serialize_policy: parse_serialize_policy(CONF_VE_SERIALIZE_POLICY_DEFAULT.to_string())
+                .unwrap(),
+            serialize_threshold: CONF_VE_SERIALIZE_THRESHOLD_DEFAULT,
+            convert_top_params: CONF_VE_CONVERT_TOP_PARAMS_DEFAULT,
+            check_data: CONF_VE_CHECK_DATA_DEFAULT,
+            dump_data: CONF_VE_DUMP_DATA_DEFAULT,
+            call_timeout: CONF_VE_CALL_TIMEOUT_DEFAULT,
+        }
+    }
+}
+
+impl VEConfig {
+    /// Returns the VE nodes to run on. The first node runs programs that are not split.
+    pub fn pool(&self) -> Vec<i32> {
+        if self.nodes.is_empty() {
+            vec![self.node]
+        } else {
+            self.nodes.clone()
+        }
+    }
+
+    /// Returns how long a call on the VE may take, or `None` if there is no limit.
+    pub fn timeout(&self) -> Option<Duration> {
+        if self.call_timeout == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.call_timeout))
+        }
+    }
+}
+
 /// A parsed Weld configuration.
 #[derive(Clone, Debug)]
 pub struct ParsedConf {
@@ -109,10 +223,18 @@ pub struct ParsedConf {
     pub optimization_passes: Vec<Pass>,
     /// Enables bounds checking in generated code.
     pub enable_bounds_checks: bool,
+    /// Backend to generate code for.
+    pub backend: Backend,
+    /// Number of lanes in SIMD values of the C backends, or `None` to use the target's default.
+    pub vector_length: Option<u32>,
+    /// VE options.
+    pub ve: VEConfig,
     /// LLVM options.
     pub llvm: LLVMConfig,
     /// Options for writing code to a file.
     pub dump_code: DumpCodeConfig,
+    /// Options for caching compiled artifacts.
+    pub compile_cache: CompileCacheConfig,
 }
 
 impl Default for ParsedConf {
@@ -125,8 +247,12 @@ impl Default for ParsedConf {
             enable_experimental_passes: CONF_EXPERIMENTAL_PASSES_DEFAULT,
             optimization_passes: CONF_OPTIMIZATION_PASSES.clone(),
             enable_bounds_checks: CONF_ENABLE_BOUNDS_CHECKS_DEFAULT,
+            backend: default_backend(),
+            vector_length: None,
+            ve: VEConfig::default(),
             llvm: LLVMConfig::default(),
             dump_code: DumpCodeConfig::default(),
+            compile_cache: CompileCacheConfig::default(),
         }
     }
 }
@@ -186,6 +312,40 @@ impl ParsedConf {
                 CONF_ENABLE_BOUNDS_CHECKS_KEY,
                 CONF_ENABLE_BOUNDS_CHECKS_DEFAULT,
             )?,
+            backend: conf.parse_map(CONF_BACKEND_KEY, default_backend(), parse_backend)?,
+            vector_length: conf.parse_map(CONF_VECTOR_LENGTH_KEY, None, parse_vector_length)?,
+            ve: VEConfig {
+                node: conf.parse_str(
+                    CONF_VE_NODE_KEY,
+                    get_ve_node_number()
+                        .filter(|node| *node >= 0)
+                        .unwrap_or(CONF_VE_NODE_DEFAULT),
+                )?,
+                nodes: conf.parse_map(
+                    CONF_VE_NODES_KEY,
+                    parse_ve_nodes(CONF_VE_NODES_DEFAULT.to_string())?,
+                    parse_ve_nodes,
+                )?,
+                device_results: conf
+                    .parse_str(CONF_VE_DEVICE_RESULTS_KEY, CONF_VE_DEVICE_RESULTS_DEFAULT)?,
+                serialize_policy: conf.parse_map(
+                    CONF_VE_SERIALIZE_POLICY_KEY,
+                    parse_serialize_policy(CONF_VE_SERIALIZE_POLICY_DEFAULT.to_string())?,
+                    parse_serialize_policy,
+                )?,
+                serialize_threshold: conf.parse_str(
+                    CONF_VE_SERIALIZE_THRESHOLD_KEY,
+                    CONF_VE_SERIALIZE_THRESHOLD_DEFAULT,
+                )?,
+                convert_top_params: conf.parse_str(
+                    CONF_VE_CONVERT_TOP_PARAMS_KEY,
+                    CONF_VE_CONVERT_TOP_PARAMS_DEFAULT,
+                )?,
+                check_data: conf.parse_str(CONF_VE_CHECK_DATA_KEY, CONF_VE_CHECK_DATA_DEFAULT)?,
+                dump_data: conf.parse_str(CONF_VE_DUMP_DATA_KEY, CONF_VE_DUMP_DATA_DEFAULT)?,
+                call_timeout: conf
+                    .parse_str(CONF_VE_CALL_TIMEOUT_KEY, CONF_VE_CALL_TIMEOUT_DEFAULT)?,
+            },
             llvm: LLVMConfig {
                 opt_level: conf.parse_str(
                     CONF_LLVM_OPTIMIZATION_LEVEL_KEY,
@@ -219,6 +379,13 @@ impl ParsedConf {
                     parse_dump_code_formats,
                 )?,
             },
+            compile_cache: CompileCacheConfig {
+                enabled: conf.parse_str(CONF_COMPILE_CACHE_KEY, CONF_COMPILE_CACHE_DEFAULT)?,
+                directory: conf.parse_str(
+                    CONF_COMPILE_CACHE_DIR_KEY,
+                    CONF_COMPILE_CACHE_DIR_DEFAULT.to_string(),
+                )?,
+            },
         };
         Ok(conf)
     }
@@ -235,11 +402,64 @@ fn parse_dump_code_formats(s: String) -> WeldResult<HashSet<DumpCodeFormat>> {
             "llvmopt" => Ok(LLVMOpt),
             "assembly" => Ok(Assembly),
             "sir" => Ok(SIR),
+            "c" => Ok(C),
             other => compile_err!("Unknown dumpCode format '{}'", other),
         })
         .collect::<WeldResult<HashSet<DumpCodeFormat>>>()
 }
 
+/// Returns the backend used if `weld.backend` is not set.
+///
+/// A negative `VE_NODE_NUMBER` selects the LLVM backend, as it disabled offloading before the
+/// backend could be configured.
+fn default_backend() -> Backend {
+    match get_ve_node_number() {
+        Some(node) if node < 0 => Backend::LLVM,
+        _ => parse_backend(CONF_BACKEND_DEFAULT.to_string()).unwrap(),
+    }
+}
+
+/// Parses the name of a backend.
+fn parse_backend(s: String) -> WeldResult<Backend> {
+    match s.to_lowercase().as_ref() {
+        "llvm" => Ok(Backend::LLVM),
+        "c-ve" => Ok(Backend::CVE),
+        "c-host" => Ok(Backend::CHost),
+        "c-fake-ve" => Ok(Backend::CFakeVE),
+        other => compile_err!("Unknown backend '{}'", other),
+    }
+}
+
+/// Parses a comma separated list of VE nodes.
+fn parse_ve_nodes(s: String) -> WeldResult<Vec<i32>> {
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    s.split(',')
+        .map(|piece| match piece.trim().parse::<i32>() {
+            Ok(node) if node >= 0 => Ok(node),
+            _ => compile_err!("Invalid VE node '{}'", piece),
+        })
+        .collect()
+}
+
+/// Parses the policy for sending parameters to a VE.
+fn parse_serialize_policy(s: String) -> WeldResult<SerializePolicy> {
+    match s.to_lowercase().as_ref() {
+        "threshold" => Ok(SerializePolicy::Threshold),
+        "adaptive" => Ok(SerializePolicy::Adaptive),
+        other => compile_err!("Unknown serialize policy '{}'", other),
+    }
+}
+
+/// Parses the number of lanes in a SIMD value.
+fn parse_vector_length(length: u32) -> WeldResult<Option<u32>> {
+    if length == 0 {
+        return compile_err!("Vector length must be positive");
+    }
+    Ok(Some(length))
+}
+
 /// Parse a list of optimization passes.
 fn parse_passes(s: String) -> WeldResult<Vec<Pass>> {
     if s.is_empty() {
@@ -9,10 +9,26 @@
 //! guaranteed to be one byte in size, but are defined as `_Bool` from `stdbool.h` in Rust when
 //! defined in a struct with `repr(C)`.
 //!
+//! # Decimals
+//!
+//! A `decimal[p,s]` is passed as an `i64` holding the value multiplied by `10^s`. For example,
+//! `12.34` is passed as `1234` when its type is `decimal[10,2]`.
+//!
+//! # Dates and Times
+//!
+//! A `date` is passed as an `i32` holding the number of days since 1970-01-01. Timestamps and
+//! intervals are passed as an `i64` holding a number of microseconds, since 1970-01-01 00:00:00
+//! UTC for timestamps.
+//!
 //! # Vectors
 //!
 //! Vectors will always have the same layout: a pointer followed by a 64-bit length.
 //!
+//! # Optional Values
+//!
+//! An `optional[T]` has the layout of a `{bool, T}` struct: a boolean indicating whether the value
+//! is non-null, followed by the value itself. Null values returned by Weld hold a zero value.
+//!
 //! # Builders
 //!
 //! Builders are backend-specific and have layouts that may change at any time. Therefore, the
@@ -96,6 +112,57 @@ where
     }
 }
 
+/// An optional value, which may be null.
+///
+/// Null values returned by Weld always hold a zeroed `value`, so two null values compare equal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct WeldOption<T> {
+    pub valid: bool,
+    pub value: T,
+}
+
+impl<T> WeldOption<T> {
+    /// Return a new non-null optional value.
+    pub fn new(value: T) -> WeldOption<T> {
+        WeldOption { valid: true, value }
+    }
+
+    /// Return whether this value is null.
+    pub fn is_null(&self) -> bool {
+        !self.valid
+    }
+}
+
+impl<T: Default> WeldOption<T> {
+    /// Return a new null value.
+    pub fn null() -> WeldOption<T> {
+        WeldOption {
+            valid: false,
+            value: T::default(),
+        }
+    }
+}
+
+impl<T: Default> From<Option<T>> for WeldOption<T> {
+    fn from(value: Option<T>) -> WeldOption<T> {
+        match value {
+            Some(value) => WeldOption::new(value),
+            None => WeldOption::null(),
+        }
+    }
+}
+
+impl<T> From<WeldOption<T>> for Option<T> {
+    fn from(value: WeldOption<T>) -> Option<T> {
+        if value.valid {
+            Some(value.value)
+        } else {
+            None
+        }
+    }
+}
+
 /// The `appender` builder type.
 #[derive(Clone, Debug)]
 #[repr(C)]
@@ -119,6 +186,16 @@ pub struct Dict<K, V> {
     phantom_val: PhantomData<V>, // 0-sized
 }
 
+/// The set type.
+///
+/// Sets are dictionaries whose values are zero-size, so they share the opaque format of
+/// dictionaries.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct Set<K> {
+    d: Dict<K, ()>,
+}
+
 /// The `dictmerger` builder type.
 #[derive(Clone, Debug)]
 #[repr(C)]
@@ -133,6 +210,59 @@ pub struct GroupMerger<K, V> {
     d: Dict<K, WeldVec<V>>,
 }
 
+/// The `setmerger` builder type.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct SetMerger<K> {
+    d: Dict<K, ()>,
+}
+
+/// The HyperLogLog sketch type.
+///
+/// HyperLogLog sketches are vectors of `u8` registers.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct Hll<K> {
+    registers: WeldVec<u8>,
+    phantom: PhantomData<K>, // 0-sized
+}
+
+/// The quantiles sketch type.
+///
+/// Quantile sketches are dictionaries from bucket keys to counts.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct Quantiles<T> {
+    d: Dict<i32, i64>,
+    phantom: PhantomData<T>, // 0-sized
+}
+
+/// The `hllmerger` builder type.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct HllMerger<K> {
+    registers: WeldVec<u8>,
+    phantom: PhantomData<K>, // 0-sized
+}
+
+/// The `quantilemerger` builder type.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct QuantileMerger<T> {
+    d: Dict<i32, i64>,
+    phantom: PhantomData<T>, // 0-sized
+}
+
+/// The `topk` builder type.
+///
+/// The values are kept in a heap with room for `k` elements.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct TopK<T> {
+    pointer: *mut T,
+    size: i64,
+}
+
 // Ensures that the sizes of the types defined here match the sizes of the types in the backend.
 #[test]
 fn size_check() {
@@ -147,6 +277,10 @@ fn size_check() {
     let vector = &Type::Vector(i32_ty.clone());
     assert_eq!(size_of(vector), mem::size_of::<WeldVec<i32>>());
 
+    // Optional values are lowered to structs before code generation.
+    let optional = &Type::Struct(vec![Type::Scalar(ScalarKind::Bool), *i32_ty.clone()]);
+    assert_eq!(size_of(optional), mem::size_of::<WeldOption<i32>>());
+
     let dict = &Type::Dict(i32_ty.clone(), i32_ty.clone());
     assert_eq!(size_of(dict), mem::size_of::<Dict<i32, i32>>());
 
@@ -167,4 +301,47 @@ fn size_check() {
         size_of(groupmerger),
         mem::size_of::<GroupMerger<i32, i32>>()
     );
+
+    let topk = &Type::Builder(BuilderKind::TopK(i32_ty.clone(), 10), Annotations::new());
+    assert_eq!(size_of(topk), mem::size_of::<TopK<i32>>());
+
+    // Sets are lowered to dictionaries with empty struct values before code generation.
+    let set = &Type::Dict(i32_ty.clone(), Box::new(Type::Struct(vec![])));
+    assert_eq!(size_of(set), mem::size_of::<Set<i32>>());
+
+    let setmerger = &Type::Builder(
+        BuilderKind::DictMerger(i32_ty.clone(), Box::new(Type::Struct(vec![])), Add),
+        Annotations::new(),
+    );
+    assert_eq!(size_of(setmerger), mem::size_of::<SetMerger<i32>>());
+
+    // Sketches are lowered to vectors of registers and dictionaries of bucket counts before code
+    // generation.
+    let u8_vector = &Type::Vector(Box::new(Type::Scalar(ScalarKind::U8)));
+    assert_eq!(size_of(u8_vector), mem::size_of::<Hll<i32>>());
+
+    let buckets = &Type::Dict(
+        Box::new(Type::Scalar(ScalarKind::I32)),
+        Box::new(Type::Scalar(ScalarKind::I64)),
+    );
+    assert_eq!(size_of(buckets), mem::size_of::<Quantiles<f64>>());
+
+    let hllmerger = &Type::Builder(
+        BuilderKind::VecMerger(Box::new(Type::Scalar(ScalarKind::U8)), BinOpKind::Max),
+        Annotations::new(),
+    );
+    assert_eq!(size_of(hllmerger), mem::size_of::<HllMerger<i32>>());
+
+    let quantilemerger = &Type::Builder(
+        BuilderKind::DictMerger(
+            Box::new(Type::Scalar(ScalarKind::I32)),
+            Box::new(Type::Scalar(ScalarKind::I64)),
+            Add,
+        ),
+        Annotations::new(),
+    );
+    assert_eq!(
+        size_of(quantilemerger),
+        mem::size_of::<QuantileMerger<f64>>()
+    );
 }
@@ -150,6 +150,17 @@ macro_rules! weld_err {
     })
 }
 
+/// A macro for creating a `WeldError` with a message and the `DeviceError` error code.
+#[macro_export]
+macro_rules! device_err {
+    ( $($arg:tt)* ) => ({
+        ::std::result::Result::Err($crate::WeldError::new(
+            format!($($arg)*),
+            $crate::WeldRuntimeErrno::DeviceError,
+        ))
+    })
+}
+
 /// A build ID.
 ///
 /// If Weld was compiled in a non-standard manner (i.e., without Cargo), this will be unknown.
@@ -189,6 +200,10 @@ use crate::util::stats::{CompilationStats, RunStats};
 // Error codes are exposed publicly.
 pub use crate::runtime::WeldRuntimeErrno;
 
+// The fake VE is exposed for tests of the offload path.
+#[doc(hidden)]
+pub use crate::util::fakeve;
+
 /// A wrapper for a C pointer.
 pub type Data = *const libc::c_void;
 
@@ -393,11 +408,16 @@ impl From<error::WeldCompileError> for WeldError {
 ///
 /// Values produced by Weld (i.e., as a return value from `WeldModule::run`) hold a reference to
 /// the context they are allocated in.
+///
+/// Modules compiled for a VE with `weld.ve.deviceResults` enabled return _device-resident_
+/// values, which stay in VE memory. Passing such a value to `WeldModule::run` hands it to the
+/// program without copying it, and `WeldValue::to_host` copies it to the host.
 #[derive(Debug, Clone)]
 pub struct WeldValue {
     data: Data,
     run: Option<RunId>,
     context: Option<WeldContext>,
+    device: Option<codegen::DeviceValue>,
 }
 
 impl WeldValue {
@@ -420,11 +440,14 @@ impl WeldValue {
             data,
             run: None,
             context: None,
+            device: None,
         }
     }
 
     /// Returns the data pointer of this `WeldValue`.
     ///
+    /// Device-resident values have a null data pointer: use `to_host` to access their data.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -491,6 +514,42 @@ impl WeldValue {
     pub fn run_id(&self) -> Option<RunId> {
         Some(0)
     }
+
+    /// Returns whether this value resides in the memory of an offload device.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use weld::{Data, WeldValue};
+    ///
+    /// let vec = vec![1, 2, 3];
+    /// let value = WeldValue::new_from_data(vec.as_ptr() as Data);
+    ///
+    /// assert!(!value.is_on_device());
+    /// ```
+    pub fn is_on_device(&self) -> bool {
+        self.device.is_some()
+    }
+
+    /// Returns a copy of this value in host memory.
+    ///
+    /// Device-resident values are copied from the device, and the copy holds a reference to the
+    /// same context as this value. Values on the host are returned as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if copying from the device fails.
+    pub unsafe fn to_host(&self) -> WeldResult<WeldValue> {
+        match self.device {
+            Some(ref device) => Ok(WeldValue {
+                data: device.to_host()? as Data,
+                run: self.run,
+                context: self.context.clone(),
+                device: None,
+            }),
+            None => Ok(self.clone()),
+        }
+    }
 }
 
 /// A struct used to configure compilation and the Weld runtime.
@@ -679,12 +738,61 @@ impl WeldModule {
             .push(("Type Inference".to_string(), start.to(end)));
         debug!("After type inference:\n{}\n", expr.pretty_print());
 
+        // Remember the signature before optional values, sets and sketches are lowered.
+        let signature = expr.ty.clone();
+
+        // Lower window operators first, so the other lowering passes only see loops.
+        let start = PreciseTime::now();
+        expr.lower_windows()?;
+        let end = PreciseTime::now();
+        stats
+            .weld_times
+            .push(("Lower Windows".to_string(), start.to(end)));
+        debug!("After lowering windows:\n{}\n", expr.pretty_print());
+
+        // Lower optional values.
+        let start = PreciseTime::now();
+        expr.lower_optionals()?;
+        let end = PreciseTime::now();
+        stats
+            .weld_times
+            .push(("Lower Optionals".to_string(), start.to(end)));
+        debug!("After lowering optionals:\n{}\n", expr.pretty_print());
+
+        // Lower calendar functions and date arithmetic.
+        let start = PreciseTime::now();
+        expr.lower_temporals()?;
+        let end = PreciseTime::now();
+        stats
+            .weld_times
+            .push(("Lower Temporals".to_string(), start.to(end)));
+        debug!("After lowering temporals:\n{}\n", expr.pretty_print());
+
+        // Lower sets to dictionaries.
+        let start = PreciseTime::now();
+        expr.lower_sets()?;
+        let end = PreciseTime::now();
+        stats
+            .weld_times
+            .push(("Lower Sets".to_string(), start.to(end)));
+        debug!("After lowering sets:\n{}\n", expr.pretty_print());
+
+        // Lower sketches to vectors and dictionaries.
+        let start = PreciseTime::now();
+        expr.lower_sketches()?;
+        let end = PreciseTime::now();
+        stats
+            .weld_times
+            .push(("Lower Sketches".to_string(), start.to(end)));
+        debug!("After lowering sketches:\n{}\n", expr.pretty_print());
+
         // Apply optimization passes.
         optimizer::apply_passes(
             &mut expr,
             &conf.optimization_passes,
             &mut stats,
             conf.enable_experimental_passes,
+            conf.backend.is_c(),
         )?;
 
         // Uniquify again.
@@ -731,7 +839,9 @@ impl WeldModule {
         ));
 
         // Generate code.
-        let compiled_module = codegen::compile_program(&sir_prog, conf, &mut stats)?;
+        let program_hash = expr.hash_ignoring_symbols()?;
+        let compiled_module =
+            codegen::compile_program(&sir_prog, program_hash, conf, &mut stats)?;
         use crate::util::env::get_weld_compilation_stats;
         if get_weld_compilation_stats() {
             println!("\n{}\n", stats.pretty_print());
@@ -739,7 +849,7 @@ impl WeldModule {
         debug!("\n{}\n", stats.pretty_print());
 
         let (param_types, return_type) =
-            if let Type::Function(ref param_tys, ref return_ty) = expr.ty {
+            if let Type::Function(ref param_tys, ref return_ty) = signature {
                 (param_tys.clone(), *return_ty.clone())
             } else {
                 unreachable!()
@@ -792,6 +902,10 @@ impl WeldModule {
     /// Note that most Rust values cannot be passed into Weld directly. For example, it is *not*
     /// safe to simply pass a raw pointer to a `Vec<T>` into Weld directly.
     ///
+    /// A device-resident value returned by an earlier run is passed to the program without
+    /// copying it. It must reside on the device this module runs on and have the type of the
+    /// parameters, or of the only parameter.
+    ///
     /// # Errors
     ///
     /// This method may return any of the errors specified in `WeldRuntimeErrno`, if a runtime
@@ -843,7 +957,7 @@ impl WeldModule {
         // module. This enforces the single-mutable-borrow rule manually for contexts.
         let mut context_borrowed = context.context.borrow_mut();
 
-        let (raw, result) = {
+        let (raw, result, device) = {
             // This is the required input format of data passed into a compiled module.
             let input = Box::new(codegen::WeldInputArgs {
                 input: arg.data as i64,
@@ -855,8 +969,13 @@ impl WeldModule {
 
             // Runs the Weld program.
             let mut stats = RunStats::new();
-            let raw = self.llvm_module.run(ptr, &mut stats)?
-                as *const codegen::WeldOutputArgs;
+            let output = self.llvm_module.run_offload(ptr, arg.device.as_ref(), &mut stats);
+
+            // Free the boxed input.
+            let _ = Box::from_raw(ptr as *mut codegen::WeldInputArgs);
+
+            let (raw, device) = output?;
+            let raw = raw as *const codegen::WeldOutputArgs;
             let result = (*raw).clone();
 
             // Dump stat
@@ -866,16 +985,14 @@ impl WeldModule {
             }
             debug!("\n{}\n", stats.pretty_print());
 
-            // Free the boxed input.
-            let _ = Box::from_raw(ptr as *mut codegen::WeldInputArgs);
-
-            (raw, result)
+            (raw, result, device)
         };
 
         let value = WeldValue {
             data: result.output as Data,
             run: None,
             context: Some(context.clone()),
+            device,
         };
 
         let end = PreciseTime::now();
@@ -20,14 +20,18 @@ mod passes;
 pub mod transforms;
 
 /// Apply passes from a list until fix point.
+///
+/// The `vectorize` pass only runs if `enable_vectorizer` is set. Weld sets it for the C backends,
+/// whose SIMD width is configured with `weld.compile.vectorLength`.
 pub fn apply_passes(
     expr: &mut Expr,
     passes: &[Pass],
     stats: &mut CompilationStats,
     use_experimental: bool,
+    enable_vectorizer: bool,
 ) -> WeldResult<()> {
     for pass in passes {
-        if pass.pass_name() == "vectorize" {
+        if pass.pass_name() == "vectorize" && !enable_vectorizer {
             continue;
         }
         let start = PreciseTime::now();
@@ -260,6 +260,29 @@ pub fn fuse_loops_vertical(expr: &mut Expr) {
         {
             if all_iters.len() == 1 {
                 let iter1 = &all_iters[0];
+                // Hoist values bound around the input loop, such as the dictionary built by a
+                // hash join, so the input loop can be fused. Symbols are unique, so the values
+                // cannot be captured by the outer loop.
+                if let Let {
+                    ref name,
+                    ref value,
+                    ref body,
+                } = iter1.data.kind
+                {
+                    if produces_appender(body) {
+                        let mut iter = iter1.clone();
+                        iter.data = body.clone();
+                        let mut e = constructors::for_expr(
+                            vec![iter],
+                            *bldr1.clone(),
+                            *nested.clone(),
+                            false,
+                        )?;
+                        e.annotations = expr.annotations.clone();
+                        let e = constructors::let_expr(name.clone(), *value.clone(), e)?;
+                        return Ok((Some(e), true));
+                    }
+                }
                 if let Res {
                     builder: ref res_bldr,
                 } = iter1.data.kind
@@ -294,6 +317,30 @@ pub fn fuse_loops_vertical(expr: &mut Expr) {
     });
 }
 
+/// Returns whether `expr` is a loop that appends to a new appender and consumes all of its inputs,
+/// possibly nested in `Let` expressions.
+fn produces_appender(expr: &Expr) -> bool {
+    match expr.kind {
+        Let { ref body, .. } => produces_appender(body),
+        Res {
+            builder: ref res_bldr,
+        } => {
+            if let For {
+                ref iters,
+                ref builder,
+                ..
+            } = res_bldr.kind
+            {
+                if let (&NewBuilder(_), &Builder(Appender(_), _)) = (&builder.kind, &builder.ty) {
+                    return iters.iter().all(|i| consumes_all(i));
+                }
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
 /// Given an iterator, returns whether the iterator consumes every element of its data vector.
 fn consumes_all(iter: &Iter) -> bool {
     if let Iter {
@@ -607,3 +654,28 @@ fn simple_vertical_loop_fusion() {
     );
     assert!(e1.compare_ignoring_symbols(&e2).unwrap());
 }
+
+#[test]
+fn vertical_loop_fusion_through_let() {
+    // Values bound around the input loop are hoisted out of the outer loop.
+    let mut e1 = typed_expression(
+        "for((let d = result(for([1,2,3], dictmerger[i32,i32,+], |b,i,e| merge(b, {e,1}))); \
+         result(for([1,2,3], appender, |b,i,e| if(optlookup(d, e).$0, merge(b, e), b)))), \
+         appender, |b,h,f| merge(b, f+1))",
+    );
+    fuse_loops_vertical(&mut e1);
+    let e2 = typed_expression(
+        "let d = result(for([1,2,3], dictmerger[i32,i32,+], |b,i,e| merge(b, {e,1}))); \
+         for([1,2,3], appender, |b,i,e| if(optlookup(d, e).$0, merge(b, e+1), b))",
+    );
+    assert!(e1.compare_ignoring_symbols(&e2).unwrap());
+
+    // Values are not hoisted if the input loop cannot be fused.
+    let mut e1 = typed_expression(
+        "for((let a = [1,2,3]; result(for(iter(a, 0L, 1L, 1L), appender, |b,i,e| \
+         merge(b,e+2)))), appender, |b,h,f| merge(b, f+1))",
+    );
+    let e2 = e1.clone();
+    fuse_loops_vertical(&mut e1);
+    assert!(e1.compare_ignoring_symbols(&e2).unwrap());
+}
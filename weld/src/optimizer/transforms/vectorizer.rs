@@ -381,6 +381,12 @@ fn vectorizable(for_loop: &Expr) -> Option<HashSet<Symbol>> {
 
                     // Check if subexpressions in the body are all vectorizable.
                     body.traverse(&mut |f| {
+                        // Decimal and temporal arithmetic is not supported on SIMD values.
+                        if let Scalar(ref kind) = f.ty {
+                            if kind.is_decimal() || kind.is_temporal() {
+                                passed = false;
+                            }
+                        }
                         if passed {
                             match f.kind {
                                 Literal(_) => {}
@@ -603,6 +609,15 @@ fn zipped_input() {
     assert!(has_vectorized_merge(&e));
 }
 
+#[test]
+fn integer_ops() {
+    let mut e = typed_expression(
+        "|v:vec[i64]| result(for(v, merger[i64,+], |b,i,e| merge(b,popcount(abs(e))<<2L)))",
+    );
+    vectorize(&mut e);
+    assert!(has_vectorized_merge(&e));
+}
+
 // Pointless test as dictmerger cannot be vectorized anyway.
 // #[test]
 // fn zips_in_body() {
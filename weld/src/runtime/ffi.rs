@@ -31,7 +31,7 @@ pub unsafe extern "C" fn weld_runst_release(run: WeldRuntimeContextRef) {
 #[no_mangle]
 /// Allocate memory within the provided context.
 pub unsafe extern "C" fn weld_runst_malloc(run: WeldRuntimeContextRef, size: i64) -> Ptr {
-    let run = &mut *run;
+    let run = &*run;
     run.malloc(size)
 }
 
@@ -44,14 +44,14 @@ pub unsafe extern "C" fn weld_runst_realloc(
     ptr: Ptr,
     newsize: i64,
 ) -> Ptr {
-    let run = &mut *run;
+    let run = &*run;
     run.realloc(ptr, newsize)
 }
 
 #[no_mangle]
 /// Free memory allocated in this context.
 pub unsafe extern "C" fn weld_runst_free(run: WeldRuntimeContextRef, ptr: Ptr) {
-    let run = &mut *run;
+    let run = &*run;
     run.free(ptr)
 }
 
@@ -65,7 +65,7 @@ pub unsafe extern "C" fn weld_runst_set_result(run: WeldRuntimeContextRef, ptr:
 #[no_mangle]
 /// Set the errno value.
 pub unsafe extern "C" fn weld_runst_set_errno(run: WeldRuntimeContextRef, errno: WeldRuntimeErrno) {
-    let run = &mut *run;
+    let run = &*run;
     run.set_errno(errno)
 }
 
@@ -86,7 +86,7 @@ pub unsafe extern "C" fn weld_runst_get_result(run: WeldRuntimeContextRef) -> Pt
 #[no_mangle]
 /// Check whether cond is 0 (assertion fails).
 pub unsafe extern "C" fn weld_runst_assert(run: WeldRuntimeContextRef, cond: u8) -> u8 {
-    let run = &mut *run;
+    let run = &*run;
     if cond == 0 {
         // Doesn't return.
         run.set_errno(WeldRuntimeErrno::AssertionError);
@@ -102,3 +102,31 @@ pub unsafe extern "C" fn weld_runst_print(_run: WeldRuntimeContextRef, string: *
     let string = CStr::from_ptr(string).to_str().unwrap();
     println!("{} ", string);
 }
+
+#[no_mangle]
+/// Returns the number of chunks to split a loop with `iterations` iterations into.
+///
+/// Returns 1 if the loop should run serially.
+pub unsafe extern "C" fn weld_runst_parallel_chunks(
+    run: WeldRuntimeContextRef,
+    iterations: i64,
+    grain: i64,
+) -> i64 {
+    let run = &*run;
+    parallel::chunks(run.threads(), iterations, grain)
+}
+
+#[no_mangle]
+/// Run `task` for each of the `nchunks` chunks of a loop on the worker threads of the run.
+///
+/// See `weld::runtime::parallel` for the calling convention of the task.
+pub unsafe extern "C" fn weld_runst_parallel_for(
+    run: WeldRuntimeContextRef,
+    task: parallel::WeldTaskFn,
+    args: Ptr,
+    partials: Ptr,
+    iterations: i64,
+    nchunks: i64,
+) {
+    parallel::parallel_for(run, task, args, partials, iterations, nchunks)
+}
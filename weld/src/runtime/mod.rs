@@ -6,8 +6,13 @@ use fnv;
 use libc;
 
 pub mod ffi;
+pub mod parallel;
+pub mod strings;
 
 use self::ffi::*;
+use self::parallel::WorkerPool;
+use crate::codegen::HostRun;
+use crate::util::veoffload::DeviceRun;
 
 use libc::c_char;
 use std::alloc::System as Allocator;
@@ -16,8 +21,10 @@ use fnv::FnvHashMap;
 
 use std::ffi::CStr;
 use std::fmt;
+use std::mem;
 use std::ptr;
-use std::sync::{Once, ONCE_INIT};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
 
 use std::alloc::{GlobalAlloc, Layout};
 
@@ -67,6 +74,13 @@ pub enum WeldRuntimeErrno {
     KeyNotFoundError,
     /// An assertion evaluated to `false`.
     AssertionError,
+    /// An offload device failed.
+    ///
+    /// This error is returned if a VE cannot be initialized, does not become ready in time, or
+    /// fails while running a program.
+    DeviceError,
+    /// A string could not be parsed as a number.
+    ParseError,
     /// Maximum errno value.
     ///
     /// All errors will have a value less than this value and greater than 0.
@@ -80,28 +94,74 @@ impl fmt::Display for WeldRuntimeErrno {
     }
 }
 
+/// Memory allocated by a single Weld run.
+#[derive(Debug, Default, PartialEq)]
+struct Allocations {
+    /// Maps pointers to allocation size in bytes.
+    layouts: FnvHashMap<Ptr, Layout>,
+    /// Number of allocated bytes so far.
+    ///
+    /// This will always be equal to `layouts.values().sum()`.
+    allocated: usize,
+}
+
 /// Maintains information about a single Weld run.
-#[derive(Debug, PartialEq)]
+///
+/// Allocations are guarded by a lock and the error code is atomic, since the workers of a
+/// parallel loop allocate memory and raise errors concurrently.
+#[derive(Debug)]
 pub struct WeldRuntimeContext {
-    /// Maps pointers to allocation size in bytes.
-    allocations: FnvHashMap<Ptr, Layout>,
-    /// An error code set for the context.
-    errno: WeldRuntimeErrno,
+    /// Memory allocated by this run.
+    allocations: Mutex<Allocations>,
+    /// An error code set for the context, stored as its `u64` value.
+    ///
+    /// Only the first error raised is kept.
+    errno: AtomicU64,
     /// A result pointer set by the runtime.
     result: Ptr,
     /// The number of worker threads.
     nworkers: i32,
     /// A memory limit.
     memlimit: usize,
-    /// Number of allocated bytes so far.
+    /// Run handles of the contexts created on offload devices, keyed by device.
     ///
-    /// This will always be equal to `allocations.values().sum()`.
-    allocated: usize,
+    /// Runs offloaded with this context reuse the device's context, so memory allocated on the
+    /// device by one run stays valid in later runs. The device-side contexts are freed when
+    /// this context is dropped.
+    device_runs: Mutex<FnvHashMap<u64, DeviceRun>>,
+    /// Runs of C code compiled for the host whose results were returned with this context.
+    ///
+    /// The runs are freed with all their memory when this context is dropped.
+    host_runs: Mutex<Vec<HostRun>>,
+    /// The threads that run parallel loops alongside the calling thread.
+    ///
+    /// The pool is started by the first parallel loop and reused by later loops and runs.
+    pool: Mutex<Option<Arc<WorkerPool>>>,
+}
+
+// The workers of a parallel loop share the context. Its allocations and error code are
+// synchronized, and its result is only set by the thread that runs the program.
+unsafe impl Sync for WeldRuntimeContext {}
+
+impl PartialEq for WeldRuntimeContext {
+    fn eq(&self, other: &WeldRuntimeContext) -> bool {
+        if ptr::eq(self, other) {
+            return true;
+        }
+        self.errno() == other.errno()
+            && self.result == other.result
+            && self.nworkers == other.nworkers
+            && self.memlimit == other.memlimit
+            && *self.allocations.lock().unwrap() == *other.allocations.lock().unwrap()
+    }
 }
 
 /// Private API used by the FFI.
+///
+/// The memory functions take `&self` so that the workers of a parallel loop can call them
+/// concurrently.
 impl WeldRuntimeContext {
-    unsafe fn malloc(&mut self, size: i64) -> Ptr {
+    unsafe fn malloc(&self, size: i64) -> Ptr {
         if size == 0 {
             trace!("Alloc'd 0-size pointer (null)");
             return ptr::null_mut();
@@ -109,54 +169,70 @@ impl WeldRuntimeContext {
 
         let size = size as usize;
 
-        if self.allocated + size > self.memlimit {
-            self.errno = WeldRuntimeErrno::OutOfMemory;
-            panic!(
-                "Weld run ran out of memory (limit={}, attempted to allocate {}",
-                self.memlimit,
-                self.allocated + size
-            );
+        let mut allocations = self.allocations.lock().unwrap();
+        if allocations.allocated + size > self.memlimit {
+            let attempted = allocations.allocated + size;
+            drop(allocations);
+            self.out_of_memory(attempted);
         }
         let layout = Layout::from_size_align_unchecked(size as usize, DEFAULT_ALIGN);
         let mem = Allocator.alloc(layout);
 
-        self.allocated += layout.size();
+        allocations.allocated += layout.size();
         trace!("Alloc'd pointer {:?} ({} bytes)", mem, layout.size());
 
-        self.allocations.insert(mem, layout);
+        allocations.layouts.insert(mem, layout);
         mem
     }
 
-    unsafe fn realloc(&mut self, pointer: Ptr, size: i64) -> Ptr {
+    unsafe fn realloc(&self, pointer: Ptr, size: i64) -> Ptr {
         if pointer.is_null() {
             return self.malloc(size);
         }
 
         let size = size as usize;
-        let old_layout = self.allocations.remove(&pointer).unwrap();
-        if self.allocated - old_layout.size() + size > self.memlimit {
-            self.errno = WeldRuntimeErrno::OutOfMemory;
-            panic!(
-                "Weld run ran out of memory (limit={}, attempted to allocate {}",
-                self.memlimit,
-                self.allocated - old_layout.size() + size
-            );
+        let mut allocations = self.allocations.lock().unwrap();
+        let old_layout = allocations.layouts.remove(&pointer).unwrap();
+        if allocations.allocated - old_layout.size() + size > self.memlimit {
+            let attempted = allocations.allocated - old_layout.size() + size;
+            allocations.layouts.insert(pointer, old_layout);
+            drop(allocations);
+            self.out_of_memory(attempted);
         }
 
         // Must pass *old* layout to realloc!
         let mem = Allocator.realloc(pointer, old_layout, size);
         let new_layout = Layout::from_size_align_unchecked(size, DEFAULT_ALIGN);
 
-        self.allocated -= old_layout.size();
-        self.allocated += new_layout.size();
+        allocations.allocated -= old_layout.size();
+        allocations.allocated += new_layout.size();
 
-        self.allocations.insert(mem, new_layout);
+        allocations.layouts.insert(mem, new_layout);
         mem
     }
 
-    fn set_errno(&mut self, errno: WeldRuntimeErrno) {
-        self.errno = errno;
-        panic!("Weld runtime threw error: {}", self.errno)
+    /// Raises an out of memory error for an attempted allocation of `attempted` total bytes.
+    fn out_of_memory(&self, attempted: usize) -> ! {
+        self.record_errno(WeldRuntimeErrno::OutOfMemory);
+        panic!(
+            "Weld run ran out of memory (limit={}, attempted to allocate {}",
+            self.memlimit, attempted
+        );
+    }
+
+    fn set_errno(&self, errno: WeldRuntimeErrno) {
+        self.record_errno(errno);
+        panic!("Weld runtime threw error: {}", errno)
+    }
+
+    /// Sets the error code of the context unless an error was already raised.
+    fn record_errno(&self, errno: WeldRuntimeErrno) {
+        let _ = self.errno.compare_exchange(
+            WeldRuntimeErrno::Success as u64,
+            errno as u64,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
     }
 
     fn set_result(&mut self, result: Ptr) {
@@ -166,6 +242,35 @@ impl WeldRuntimeContext {
     fn result(&self) -> Ptr {
         self.result
     }
+
+    /// Returns the run handle of the context on `device`, or 0 if none was created by the
+    /// process of `generation` yet.
+    pub(crate) fn device_run(&self, device: u64, generation: u64) -> i64 {
+        match self.device_runs.lock().unwrap().get(&device) {
+            Some(run) if run.generation == generation => run.run,
+            _ => 0,
+        }
+    }
+
+    /// Records the context on `device`, replacing the context recorded earlier.
+    pub(crate) fn set_device_run(&self, device: u64, run: DeviceRun) {
+        self.device_runs.lock().unwrap().insert(device, run);
+    }
+
+    /// Records a run of C code compiled for the host, which is freed with this context.
+    pub(crate) fn add_host_run(&self, run: HostRun) {
+        self.host_runs.lock().unwrap().push(run);
+    }
+
+    /// Returns the pool of threads that run parallel loops, starting it if necessary.
+    ///
+    /// The pool has one thread less than the number of workers, since the thread that starts a
+    /// loop also works on it.
+    pub(crate) fn worker_pool(&self) -> Arc<WorkerPool> {
+        let mut pool = self.pool.lock().unwrap();
+        pool.get_or_insert_with(|| Arc::new(WorkerPool::new(self.nworkers - 1)))
+            .clone()
+    }
 }
 
 // Public API.
@@ -173,25 +278,28 @@ impl WeldRuntimeContext {
     /// Construct a new `WeldRuntimeContext`.
     pub fn new(nworkers: i32, memlimit: i64) -> WeldRuntimeContext {
         WeldRuntimeContext {
-            allocations: FnvHashMap::default(),
-            errno: WeldRuntimeErrno::Success,
+            allocations: Mutex::new(Allocations::default()),
+            errno: AtomicU64::new(WeldRuntimeErrno::Success as u64),
             result: ptr::null_mut(),
             nworkers,
             memlimit: memlimit as usize,
-            allocated: 0,
+            device_runs: Mutex::new(FnvHashMap::default()),
+            host_runs: Mutex::new(Vec::new()),
+            pool: Mutex::new(None),
         }
     }
 
     /// Free an allocated data value.
     ///
     /// Panics if the passed value was not allocated by the Weld runtime.
-    pub unsafe fn free(&mut self, pointer: Ptr) {
+    pub unsafe fn free(&self, pointer: Ptr) {
         if pointer.is_null() {
             trace!("Freed null pointer (no-op) in runst_free()");
             return;
         }
 
-        let layout = self.allocations.remove(&pointer).unwrap();
+        let mut allocations = self.allocations.lock().unwrap();
+        let layout = allocations.layouts.remove(&pointer).unwrap();
 
         trace!(
             "Freeing pointer {:?} ({} bytes) in runst_free()",
@@ -200,12 +308,12 @@ impl WeldRuntimeContext {
         );
 
         Allocator.dealloc(pointer, layout);
-        self.allocated -= layout.size();
+        allocations.allocated -= layout.size();
     }
 
     /// Returns the number of bytes allocated by this Weld run.
     pub fn memory_usage(&self) -> i64 {
-        self.allocated as i64
+        self.allocations.lock().unwrap().allocated as i64
     }
 
     /// Returns a 64-bit ID identifying this run.
@@ -215,7 +323,9 @@ impl WeldRuntimeContext {
 
     /// Returns the error code of this run.
     fn errno(&self) -> WeldRuntimeErrno {
-        self.errno
+        let code = self.errno.load(Ordering::SeqCst);
+        // The errno is a `repr(u64)` enum, and only its values are stored.
+        unsafe { mem::transmute::<u64, WeldRuntimeErrno>(code) }
     }
 
     /// Returns the number of worker threads set for this run.
@@ -232,9 +342,14 @@ impl WeldRuntimeContext {
 impl Drop for WeldRuntimeContext {
     fn drop(&mut self) {
         // Free memory allocated by the run.
-        trace!("Allocations: {}", self.allocations.len());
+        // A worker may have panicked while allocating.
+        let allocations = match self.allocations.get_mut() {
+            Ok(allocations) => allocations,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        trace!("Allocations: {}", allocations.layouts.len());
         unsafe {
-            for (pointer, layout) in self.allocations.iter() {
+            for (pointer, layout) in allocations.layouts.iter() {
                 trace!(
                     "Freeing pointer {:?} ({} bytes) in drop()",
                     *pointer,
@@ -259,7 +374,24 @@ unsafe fn initialize() {
         x += weld_runst_set_errno as usize;
         x += weld_runst_assert as usize;
         x += weld_runst_print as usize;
+        x += weld_runst_parallel_chunks as usize;
+        x += weld_runst_parallel_for as usize;
 
         trace!("Runtime initialized with hashed values {}", x);
     });
 }
+
+#[test]
+fn first_error_is_kept() {
+    use std::thread;
+
+    let run = WeldRuntimeContext::new(4, 1 << 20);
+    assert_eq!(run.errno(), WeldRuntimeErrno::Success);
+    run.record_errno(WeldRuntimeErrno::ArrayOutOfBounds);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| run.record_errno(WeldRuntimeErrno::KeyNotFoundError));
+        }
+    });
+    assert_eq!(run.errno(), WeldRuntimeErrno::ArrayOutOfBounds);
+}
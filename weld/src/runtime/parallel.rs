@@ -0,0 +1,348 @@
+//! A work-stealing runtime for running loops across worker threads.
+//!
+//! Generated code splits a loop into chunks of consecutive iterations and calls
+//! `weld_runst_parallel_for`, which runs a task once per chunk. Each worker owns a deque of
+//! chunks: it takes chunks from the front of its own deque, and once its deque is empty, it
+//! steals chunks from the back of the other workers' deques. Each task writes the builder it
+//! produced into a slot reserved for its chunk, so the generated code can merge the partial
+//! builders in iteration order afterwards.
+//!
+//! The workers other than the thread that starts a loop belong to a `WorkerPool`, which each
+//! runtime context starts once and reuses for all of its loops.
+//!
+//! Loops nested within a task run serially on the worker that executes the task.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use super::ffi::WeldRuntimeContextRef;
+use super::Ptr;
+
+/// A task that runs a single chunk of a loop.
+///
+/// The arguments are the loop arguments, the array of partial builders, the chunk index, the
+/// range of iterations `[lo, hi)` of the chunk, and the run handle.
+pub type WeldTaskFn = unsafe extern "C" fn(Ptr, Ptr, i64, i64, i64, WeldRuntimeContextRef);
+
+/// Number of chunks created per worker.
+///
+/// Creating more chunks than workers lets idle workers steal work when iterations have uneven
+/// costs, at the price of merging more partial builders.
+const CHUNKS_PER_WORKER: i64 = 4;
+
+thread_local! {
+    /// Whether the current thread is running a task.
+    static IN_TASK: Cell<bool> = Cell::new(false);
+}
+
+/// Returns the number of chunks to split a loop with `iterations` iterations into.
+///
+/// Each chunk has at least `grain` iterations. Returns 1 if the loop should run serially, i.e.,
+/// if there is only one worker, if the loop is too small, or if the caller is already a task.
+pub fn chunks(nworkers: i32, iterations: i64, grain: i64) -> i64 {
+    let grain = grain.max(1);
+    if nworkers <= 1 || iterations < grain * 2 || IN_TASK.with(|t| t.get()) {
+        return 1;
+    }
+    let max_chunks = i64::from(nworkers) * CHUNKS_PER_WORKER;
+    (iterations / grain).min(max_chunks).max(1)
+}
+
+/// Returns the range of iterations `[lo, hi)` of chunk `chunk`.
+fn chunk_bounds(iterations: i64, nchunks: i64, chunk: i64) -> (i64, i64) {
+    let bound = |c: i64| (i128::from(iterations) * i128::from(c) / i128::from(nchunks)) as i64;
+    (bound(chunk), bound(chunk + 1))
+}
+
+/// A job run by a thread of a `WorkerPool`.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed set of threads that run jobs in the order they are submitted.
+pub struct WorkerPool {
+    /// Sends jobs to the threads. Dropping it stops the threads once they finish their jobs.
+    sender: Option<Mutex<Sender<Job>>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Starts a pool with `size` threads.
+    pub fn new(size: i32) -> WorkerPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = (0..size.max(0))
+            .map(|i| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("weld-worker-{}", i))
+                    .spawn(move || loop {
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("Could not start Weld worker thread")
+            })
+            .collect();
+        WorkerPool {
+            sender: Some(Mutex::new(sender)),
+            threads,
+        }
+    }
+
+    /// Returns the number of threads in the pool.
+    pub fn size(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Runs `job` on one of the threads of the pool.
+    fn execute(&self, job: Job) {
+        let sender = self.sender.as_ref().unwrap().lock().unwrap();
+        sender.send(job).expect("Weld worker threads stopped");
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WorkerPool(size={})", self.size())
+    }
+}
+
+/// Counts down the workers of a loop that run on a `WorkerPool`.
+struct Latch {
+    pending: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Latch {
+    fn new(count: usize) -> Latch {
+        Latch {
+            pending: Mutex::new(count),
+            done: Condvar::new(),
+        }
+    }
+
+    fn count_down(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    /// Blocks until the count reaches zero.
+    fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.done.wait(pending).unwrap();
+        }
+    }
+}
+
+/// State shared by the workers of a single loop.
+struct ParallelLoop {
+    task: WeldTaskFn,
+    args: Ptr,
+    partials: Ptr,
+    iterations: i64,
+    nchunks: i64,
+    run: WeldRuntimeContextRef,
+    /// The chunks not yet claimed by each worker.
+    deques: Vec<Mutex<VecDeque<i64>>>,
+    /// Set when a task panics, so the remaining chunks are skipped.
+    failed: AtomicBool,
+    /// The payload of the first panic of a task.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+// The pointers in the loop are only dereferenced by the generated tasks, which write to disjoint
+// slots of `partials`.
+unsafe impl Sync for ParallelLoop {}
+
+impl ParallelLoop {
+    /// Claims the next chunk for `worker`, stealing from another worker if necessary.
+    fn next_chunk(&self, worker: usize) -> Option<i64> {
+        if let Some(chunk) = self.deques[worker].lock().unwrap().pop_front() {
+            return Some(chunk);
+        }
+        let n = self.deques.len();
+        (1..n)
+            .map(|offset| (worker + offset) % n)
+            .filter_map(|victim| self.deques[victim].lock().unwrap().pop_back())
+            .next()
+    }
+
+    /// Runs chunks on the current thread until no chunks remain.
+    fn work(&self, worker: usize) {
+        let was_in_task = IN_TASK.with(|t| t.replace(true));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            while !self.failed.load(Ordering::Relaxed) {
+                let chunk = match self.next_chunk(worker) {
+                    Some(chunk) => chunk,
+                    None => break,
+                };
+                let (lo, hi) = chunk_bounds(self.iterations, self.nchunks, chunk);
+                trace!("Worker {} running chunk {} [{}, {})", worker, chunk, lo, hi);
+                unsafe { (self.task)(self.args, self.partials, chunk, lo, hi, self.run) };
+            }
+        }));
+        IN_TASK.with(|t| t.set(was_in_task));
+        if let Err(payload) = result {
+            self.failed.store(true, Ordering::Relaxed);
+            let mut panic = self.panic.lock().unwrap();
+            if panic.is_none() {
+                *panic = Some(payload);
+            }
+        }
+    }
+}
+
+/// The share of a loop that a worker of a `WorkerPool` runs.
+struct LoopShare {
+    parallel_loop: *const ParallelLoop,
+    worker: usize,
+    latch: Arc<Latch>,
+}
+
+// The loop outlives the share, since `parallel_for` waits for the latch before returning.
+unsafe impl Send for LoopShare {}
+
+impl LoopShare {
+    fn run(self) {
+        unsafe { (*self.parallel_loop).work(self.worker) };
+        self.latch.count_down();
+    }
+}
+
+/// Runs `task` for each of the `nchunks` chunks of a loop with `iterations` iterations.
+///
+/// The calling thread participates as a worker alongside the worker pool of `run`, and returns
+/// once all chunks have finished. If a task panics, the panic is resumed on the calling thread.
+pub unsafe fn parallel_for(
+    run: WeldRuntimeContextRef,
+    task: WeldTaskFn,
+    args: Ptr,
+    partials: Ptr,
+    iterations: i64,
+    nchunks: i64,
+) {
+    let pool = (*run).worker_pool();
+    let nworkers = (pool.size() as i64 + 1).min(nchunks).max(1) as usize;
+
+    // Give each worker a contiguous run of chunks to start with.
+    let deques = (0..nworkers as i64)
+        .map(|w| {
+            let first = nchunks * w / nworkers as i64;
+            let last = nchunks * (w + 1) / nworkers as i64;
+            Mutex::new((first..last).collect())
+        })
+        .collect();
+
+    let parallel_loop = ParallelLoop {
+        task,
+        args,
+        partials,
+        iterations,
+        nchunks,
+        run,
+        deques,
+        failed: AtomicBool::new(false),
+        panic: Mutex::new(None),
+    };
+
+    let latch = Arc::new(Latch::new(nworkers - 1));
+    for worker in 1..nworkers {
+        let share = LoopShare {
+            parallel_loop: &parallel_loop,
+            worker,
+            latch: latch.clone(),
+        };
+        pool.execute(Box::new(move || share.run()));
+    }
+    parallel_loop.work(0);
+    latch.wait();
+
+    if let Some(payload) = parallel_loop.panic.into_inner().unwrap() {
+        panic::resume_unwind(payload);
+    }
+}
+
+#[test]
+fn chunk_counts() {
+    assert_eq!(chunks(1, 1 << 20, 1024), 1);
+    assert_eq!(chunks(4, 1000, 1024), 1);
+    assert_eq!(chunks(4, 4096, 1024), 4);
+    assert_eq!(chunks(4, 1 << 20, 1024), 16);
+}
+
+#[test]
+fn chunk_bounds_cover_range() {
+    let iterations = 1003;
+    let nchunks = 7;
+    let mut expected_lo = 0;
+    for chunk in 0..nchunks {
+        let (lo, hi) = chunk_bounds(iterations, nchunks, chunk);
+        assert_eq!(lo, expected_lo);
+        assert!(hi > lo);
+        expected_lo = hi;
+    }
+    assert_eq!(expected_lo, iterations);
+}
+
+#[test]
+fn parallel_for_runs_each_chunk_once() {
+    unsafe extern "C" fn task(
+        _args: Ptr,
+        partials: Ptr,
+        chunk: i64,
+        lo: i64,
+        hi: i64,
+        _run: WeldRuntimeContextRef,
+    ) {
+        let partials = partials as *mut i64;
+        *partials.offset(chunk as isize) = (lo..hi).sum();
+    }
+
+    let iterations = 100_000;
+    let nchunks = chunks(4, iterations, 1024);
+    let mut partials = vec![0i64; nchunks as usize];
+    let mut run = super::WeldRuntimeContext::new(4, 1 << 20);
+    unsafe {
+        parallel_for(
+            &mut run,
+            task,
+            std::ptr::null_mut(),
+            partials.as_mut_ptr() as Ptr,
+            iterations,
+            nchunks,
+        );
+    }
+    assert_eq!(partials.iter().sum::<i64>(), (0..iterations).sum());
+}
+
+#[test]
+fn worker_pool_is_reused() {
+    let run = super::WeldRuntimeContext::new(4, 1 << 20);
+    let pool = run.worker_pool();
+    assert_eq!(pool.size(), 3);
+    assert!(Arc::ptr_eq(&pool, &run.worker_pool()));
+
+    let single = super::WeldRuntimeContext::new(1, 1 << 20);
+    assert_eq!(single.worker_pool().size(), 0);
+}
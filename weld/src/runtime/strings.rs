@@ -0,0 +1,294 @@
+//! String operations called from generated code.
+//!
+//! Strings are `vec[i8]` values. Each function takes its arguments by pointer and writes its
+//! result into the last argument, which is the calling convention used for CUDFs. New strings are
+//! allocated within the run, so they are freed together with the run's other memory.
+
+use std::ptr;
+use std::slice;
+use std::str;
+
+use crate::data::WeldVec;
+
+use super::ffi::WeldRuntimeContextRef;
+use super::WeldRuntimeErrno;
+
+/// A Weld string.
+pub type WeldString = WeldVec<i8>;
+
+/// Returns the bytes of a string.
+unsafe fn bytes<'a>(string: *const WeldString) -> &'a [u8] {
+    let string = &*string;
+    if string.len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(string.data as *const u8, string.len as usize)
+    }
+}
+
+/// Copies `bytes` into a new string allocated within the run.
+unsafe fn new_string(run: WeldRuntimeContextRef, bytes: &[u8]) -> WeldString {
+    if bytes.is_empty() {
+        return WeldString {
+            data: ptr::null(),
+            len: 0,
+        };
+    }
+    let run = &*run;
+    let data = run.malloc(bytes.len() as i64);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+    WeldString {
+        data: data as *const i8,
+        len: bytes.len() as i64,
+    }
+}
+
+/// Parses a string as a number, raising a `ParseError` if it is not a valid number.
+unsafe fn parse<T: str::FromStr>(run: WeldRuntimeContextRef, string: *const WeldString) -> T {
+    let parsed = str::from_utf8(bytes(string))
+        .ok()
+        .and_then(|s| s.parse().ok());
+    match parsed {
+        Some(value) => value,
+        None => {
+            let run = &*run;
+            run.set_errno(WeldRuntimeErrno::ParseError);
+            unreachable!()
+        }
+    }
+}
+
+#[no_mangle]
+/// Concatenates two strings.
+pub unsafe extern "C" fn weld_str_concat(
+    run: WeldRuntimeContextRef,
+    left: *const WeldString,
+    right: *const WeldString,
+    out: *mut WeldString,
+) {
+    let mut result = bytes(left).to_vec();
+    result.extend_from_slice(bytes(right));
+    *out = new_string(run, &result);
+}
+
+#[no_mangle]
+/// Returns the substring of `size` bytes starting at `index`.
+///
+/// The range is clamped to the bounds of the string. Like `slice`, the result shares its memory
+/// with the original string.
+pub unsafe extern "C" fn weld_str_substr(
+    _run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    index: *const i64,
+    size: *const i64,
+    out: *mut WeldString,
+) {
+    let string = &*string;
+    let index = (*index).max(0).min(string.len);
+    let size = (*size).max(0).min(string.len - index);
+    *out = WeldString {
+        data: if size == 0 {
+            ptr::null()
+        } else {
+            string.data.offset(index as isize)
+        },
+        len: size,
+    };
+}
+
+#[no_mangle]
+/// Returns the index of the first occurrence of `pattern` in a string, or -1 if there is none.
+pub unsafe extern "C" fn weld_str_find(
+    _run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    pattern: *const WeldString,
+    out: *mut i64,
+) {
+    let string = bytes(string);
+    let pattern = bytes(pattern);
+    *out = if pattern.is_empty() {
+        0
+    } else {
+        string
+            .windows(pattern.len())
+            .position(|window| window == pattern)
+            .map(|index| index as i64)
+            .unwrap_or(-1)
+    };
+}
+
+#[no_mangle]
+/// Returns whether a string starts with `prefix`.
+pub unsafe extern "C" fn weld_str_startswith(
+    _run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    prefix: *const WeldString,
+    out: *mut bool,
+) {
+    *out = bytes(string).starts_with(bytes(prefix));
+}
+
+#[no_mangle]
+/// Returns whether a string ends with `suffix`.
+pub unsafe extern "C" fn weld_str_endswith(
+    _run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    suffix: *const WeldString,
+    out: *mut bool,
+) {
+    *out = bytes(string).ends_with(bytes(suffix));
+}
+
+#[no_mangle]
+/// Converts the ASCII letters of a string to upper case.
+pub unsafe extern "C" fn weld_str_toupper(
+    run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    out: *mut WeldString,
+) {
+    *out = new_string(run, &bytes(string).to_ascii_uppercase());
+}
+
+#[no_mangle]
+/// Converts the ASCII letters of a string to lower case.
+pub unsafe extern "C" fn weld_str_tolower(
+    run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    out: *mut WeldString,
+) {
+    *out = new_string(run, &bytes(string).to_ascii_lowercase());
+}
+
+#[no_mangle]
+/// Parses a string as an `i64`.
+pub unsafe extern "C" fn weld_str_strtoi64(
+    run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    out: *mut i64,
+) {
+    *out = parse(run, string);
+}
+
+#[no_mangle]
+/// Parses a string as an `f64`.
+pub unsafe extern "C" fn weld_str_strtof64(
+    run: WeldRuntimeContextRef,
+    string: *const WeldString,
+    out: *mut f64,
+) {
+    // Only decimal syntax is accepted, so that the C backend, which parses with `strtod`, agrees.
+    let decimal = bytes(string)
+        .iter()
+        .all(|c| c.is_ascii_digit() || b"+-.eE".contains(c));
+    if !decimal {
+        (*run).set_errno(WeldRuntimeErrno::ParseError);
+    }
+    *out = parse(run, string);
+}
+
+/// Defines a function that formats a scalar of the given type as a string.
+macro_rules! to_string_fn {
+    ($name:ident, $ty:ty) => {
+        #[no_mangle]
+        /// Formats a scalar as a string.
+        pub unsafe extern "C" fn $name(
+            run: WeldRuntimeContextRef,
+            value: *const $ty,
+            out: *mut WeldString,
+        ) {
+            *out = new_string(run, (*value).to_string().as_bytes());
+        }
+    };
+}
+
+to_string_fn!(weld_str_tostring_bool, bool);
+to_string_fn!(weld_str_tostring_i8, i8);
+to_string_fn!(weld_str_tostring_i16, i16);
+to_string_fn!(weld_str_tostring_i32, i32);
+to_string_fn!(weld_str_tostring_i64, i64);
+to_string_fn!(weld_str_tostring_u8, u8);
+to_string_fn!(weld_str_tostring_u16, u16);
+to_string_fn!(weld_str_tostring_u32, u32);
+to_string_fn!(weld_str_tostring_u64, u64);
+to_string_fn!(weld_str_tostring_f32, f32);
+to_string_fn!(weld_str_tostring_f64, f64);
+
+#[cfg(test)]
+fn string_of(value: &'static str) -> WeldString {
+    WeldString {
+        data: value.as_ptr() as *const i8,
+        len: value.len() as i64,
+    }
+}
+
+#[test]
+fn concat_and_case() {
+    use super::WeldRuntimeContext;
+    let mut run = WeldRuntimeContext::new(1, 1 << 20);
+    let run: WeldRuntimeContextRef = &mut run;
+    let mut string = string_of("");
+    let mut out = string_of("");
+    unsafe {
+        weld_str_concat(run, &string_of("Hello, "), &string_of("Weld"), &mut string);
+        assert_eq!(bytes(&string), b"Hello, Weld");
+        weld_str_toupper(run, &string, &mut out);
+        assert_eq!(bytes(&out), b"HELLO, WELD");
+        weld_str_tolower(run, &string, &mut out);
+        assert_eq!(bytes(&out), b"hello, weld");
+    }
+}
+
+#[test]
+fn substr_is_clamped() {
+    let run: WeldRuntimeContextRef = ptr::null_mut();
+    let string = string_of("weld");
+    let mut out = string_of("");
+    unsafe {
+        weld_str_substr(run, &string, &1, &2, &mut out);
+        assert_eq!(bytes(&out), b"el");
+        weld_str_substr(run, &string, &2, &10, &mut out);
+        assert_eq!(bytes(&out), b"ld");
+        weld_str_substr(run, &string, &-1, &1, &mut out);
+        assert_eq!(bytes(&out), b"w");
+        weld_str_substr(run, &string, &5, &1, &mut out);
+        assert_eq!(bytes(&out), b"");
+    }
+}
+
+#[test]
+fn find_and_affixes() {
+    let run: WeldRuntimeContextRef = ptr::null_mut();
+    let string = string_of("abcabc");
+    let mut index = 0;
+    let mut result = false;
+    unsafe {
+        weld_str_find(run, &string, &string_of("ca"), &mut index);
+        assert_eq!(index, 2);
+        weld_str_find(run, &string, &string_of("cb"), &mut index);
+        assert_eq!(index, -1);
+        weld_str_startswith(run, &string, &string_of("abc"), &mut result);
+        assert!(result);
+        weld_str_endswith(run, &string, &string_of("ab"), &mut result);
+        assert!(!result);
+    }
+}
+
+#[test]
+fn numbers_round_trip() {
+    use super::WeldRuntimeContext;
+    let mut run = WeldRuntimeContext::new(1, 1 << 20);
+    let run: WeldRuntimeContextRef = &mut run;
+    let mut string = string_of("");
+    let mut int = 0;
+    let mut float = 0.0;
+    unsafe {
+        weld_str_tostring_i64(run, &-42, &mut string);
+        assert_eq!(bytes(&string), b"-42");
+        weld_str_strtoi64(run, &string, &mut int);
+        assert_eq!(int, -42);
+        weld_str_tostring_f64(run, &1.5, &mut string);
+        assert_eq!(bytes(&string), b"1.5");
+        weld_str_strtof64(run, &string, &mut float);
+        assert_eq!(float, 1.5);
+    }
+}
@@ -40,6 +40,7 @@ pub enum StatementKind {
         value: Symbol,
         index: u32,
     },
+    Hash(Symbol),
     KeyExists {
         child: Symbol,
         key: Symbol,
@@ -83,6 +84,10 @@ pub enum StatementKind {
     },
     Serialize(Symbol),
     Deserialize(Symbol),
+    StringOp {
+        op: StringOpKind,
+        args: Vec<Symbol>,
+    },
     ToVec(Symbol),
     UnaryOp {
         op: UnaryOpKind,
@@ -189,6 +194,9 @@ impl StatementKind {
                 vars.push(child);
                 vars.push(key);
             }
+            Hash(ref child) => {
+                vars.push(child);
+            }
             Slice {
                 ref child,
                 ref index,
@@ -249,6 +257,11 @@ impl StatementKind {
                     vars.push(arg);
                 }
             }
+            StringOp { ref args, .. } => {
+                for arg in args {
+                    vars.push(arg);
+                }
+            }
         }
         vars.into_iter()
     }
@@ -522,6 +535,7 @@ impl fmt::Display for StatementKind {
                 join("(", ", ", ")", args.iter().map(|e| format!("{}", e)))
             ),
             GetField { ref value, index } => write!(f, "{}.${}", value, index),
+            Hash(ref child) => write!(f, "hash({})", child),
             KeyExists { ref child, ref key } => write!(f, "keyexists({}, {})", child, key),
             Length(ref child) => write!(f, "len({})", child),
             MakeStruct(ref elems) => write!(
@@ -582,6 +596,12 @@ impl fmt::Display for StatementKind {
                 ref size,
             } => write!(f, "slice({}, {}, {})", child, index, size),
             Sort { ref child, .. } => write!(f, "sort({})", child),
+            StringOp { ref op, ref args } => write!(
+                f,
+                "{}{}",
+                op,
+                join("(", ", ", ")", args.iter().map(|e| format!("{}", e)))
+            ),
             ToVec(ref child) => write!(f, "toVec({})", child),
             UnaryOp { ref op, ref child } => write!(f, "{}({})", op, child),
         }
@@ -958,6 +978,24 @@ fn gen_expr(
             Ok((cur_func, cur_block, res_sym))
         }
 
+        ExprKind::StringOp { kind, ref args } => {
+            let mut syms = vec![];
+            let mut cur_func = cur_func;
+            let mut cur_block = cur_block;
+            for arg in args.iter() {
+                let r = gen_expr(arg, prog, cur_func, cur_block, tracker)?;
+                cur_func = r.0;
+                cur_block = r.1;
+                syms.push(r.2);
+            }
+            let kind = StringOp {
+                op: kind,
+                args: syms,
+            };
+            let res_sym = tracker.symbol_for_statement(prog, cur_func, cur_block, &expr.ty, kind);
+            Ok((cur_func, cur_block, res_sym))
+        }
+
         ExprKind::Negate(ref child_expr) => {
             let (cur_func, cur_block, child_sym) =
                 gen_expr(child_expr, prog, cur_func, cur_block, tracker)?;
@@ -1155,6 +1193,14 @@ fn gen_expr(
             Ok((cur_func, cur_block, res_sym))
         }
 
+        ExprKind::Hash(ref child_expr) => {
+            let (cur_func, cur_block, child_sym) =
+                gen_expr(child_expr, prog, cur_func, cur_block, tracker)?;
+            let kind = Hash(child_sym);
+            let res_sym = tracker.symbol_for_statement(prog, cur_func, cur_block, &expr.ty, kind);
+            Ok((cur_func, cur_block, res_sym))
+        }
+
         ExprKind::If {
             ref cond,
             ref on_true,
@@ -419,7 +419,7 @@ impl<'t> Parser<'t> {
 
     /// Parse a <, >, <= or >= expression (for operator precedence).
     fn comparison_expr(&mut self) -> WeldResult<Box<Expr>> {
-        let mut res = self.sum_expr()?;
+        let mut res = self.shift_expr()?;
         // Unlike other expressions, we only allow one operator here; prevents stuff like a>b>c
         if *self.peek() == TLessThan
             || *self.peek() == TLessThanOrEqual
@@ -432,7 +432,7 @@ impl<'t> Parser<'t> {
                 TLessThanOrEqual => LessThanOrEqual,
                 _ => GreaterThanOrEqual,
             };
-            let right = self.sum_expr()?;
+            let right = self.shift_expr()?;
             res = expr_box(
                 BinOp {
                     kind: op,
@@ -445,6 +445,28 @@ impl<'t> Parser<'t> {
         Ok(res)
     }
 
+    /// Parse a << or >> expression (for operator precedence).
+    fn shift_expr(&mut self) -> WeldResult<Box<Expr>> {
+        let mut res = self.sum_expr()?;
+        while *self.peek() == TShiftLeft || *self.peek() == TShiftRight {
+            let kind = if *self.next() == TShiftLeft {
+                ShiftLeft
+            } else {
+                ShiftRight
+            };
+            let right = self.sum_expr()?;
+            res = expr_box(
+                BinOp {
+                    kind,
+                    left: res,
+                    right,
+                },
+                Annotations::new(),
+            )
+        }
+        Ok(res)
+    }
+
     /// Parse a sum expression with terms separated by + and - (for operator precedence).
     fn sum_expr(&mut self) -> WeldResult<Box<Expr>> {
         let mut res = self.product_expr()?;
@@ -650,6 +672,80 @@ impl<'t> Parser<'t> {
         Ok(cast_expr)
     }
 
+    /// Parses the precision and scale of a decimal in the format "[<precision>,<scale>]".
+    fn decimal_kind(&mut self) -> WeldResult<ScalarKind> {
+        self.consume(TOpenBracket)?;
+        let precision = self.decimal_digits()?;
+        self.consume(TComma)?;
+        let scale = self.decimal_digits()?;
+        self.consume(TCloseBracket)?;
+        if precision == 0 || precision > MAX_DECIMAL_PRECISION {
+            return compile_err!(
+                "Decimal precision must be between 1 and {}",
+                MAX_DECIMAL_PRECISION
+            );
+        }
+        if scale > precision {
+            return compile_err!("Decimal scale must not exceed its precision");
+        }
+        Ok(ScalarKind::Decimal(precision, scale))
+    }
+
+    /// Parses a cast to a date, timestamp or interval.
+    ///
+    /// If the argument is a string literal, it is parsed as a literal of the type.
+    fn temporal_cast(&mut self, kind: ScalarKind) -> WeldResult<Box<Expr>> {
+        self.consume(TOpenParen)?;
+        let mut child_expr = self.expr()?;
+        self.consume(TCloseParen)?;
+        if let Literal(StringLiteral(ref text)) = child_expr.kind {
+            child_expr = expr_box(Literal(temporal_literal(kind, text)?), Annotations::new());
+        }
+        Ok(expr_box(Cast { kind, child_expr }, Annotations::new()))
+    }
+
+    /// Parses a calendar function, which takes a date or timestamp argument.
+    fn date_op_leaf_expr(&mut self, kind: DateOpKind) -> WeldResult<Box<Expr>> {
+        self.consume(TOpenParen)?;
+        let value = self.expr()?;
+        self.consume(TCloseParen)?;
+        Ok(expr_box(DateOp { kind, value }, Annotations::new()))
+    }
+
+    /// Parses a `date_trunc` call in the format "date_trunc("<unit>", <expr>)".
+    fn date_trunc_leaf_expr(&mut self) -> WeldResult<Box<Expr>> {
+        self.consume(TOpenParen)?;
+        let unit = match *self.next() {
+            TStringLiteral(ref name) => match DateUnit::from_name(name) {
+                Some(unit) => unit,
+                None => return compile_err!("Unknown date_trunc unit '{}'", name),
+            },
+            ref t => return compile_err!("Expected date_trunc unit but got '{}'", t),
+        };
+        self.consume(TComma)?;
+        let value = self.expr()?;
+        self.consume(TCloseParen)?;
+        let kind = DateOpKind::Trunc(unit);
+        Ok(expr_box(DateOp { kind, value }, Annotations::new()))
+    }
+
+    /// Parses a number of digits in a decimal type.
+    fn decimal_digits(&mut self) -> WeldResult<u8> {
+        match *self.next() {
+            TI32Literal(v) if v >= 0 && v <= i32::from(u8::max_value()) => Ok(v as u8),
+            ref t => compile_err!("Expected number of decimal digits but got '{}'", t),
+        }
+    }
+
+    /// Parses the number of items kept by a `topk` builder.
+    fn topk_size(&mut self) -> WeldResult<i64> {
+        match *self.next() {
+            TI32Literal(v) if v > 0 => Ok(i64::from(v)),
+            TI64Literal(v) if v > 0 => Ok(v),
+            ref t => compile_err!("Expected positive topk size but got '{}'", t),
+        }
+    }
+
     /// Parses annotations in the format "@(<annotation name>: <annotation value>,...)".
     fn parse_annotations(&mut self, annotations: &mut Annotations) -> WeldResult<()> {
         if *self.peek() == TAtMark {
@@ -708,6 +804,14 @@ impl<'t> Parser<'t> {
             TSinh => Sinh,
             TCosh => Cosh,
             TTanh => Tanh,
+            TCbrt => Cbrt,
+            TFloor => Floor,
+            TCeil => Ceil,
+            TRound => Round,
+            TAbs => Abs,
+            TPopcount => Popcount,
+            TClz => Clz,
+            TCtz => Ctz,
             _ => {
                 return compile_err!("Invalid token for UnaryOp");
             }
@@ -724,6 +828,59 @@ impl<'t> Parser<'t> {
         Ok(expr_box(UnaryOp { kind, value }, Annotations::new()))
     }
 
+    /// Parses the arguments of `lag` and `lead`.
+    fn shift_expr(&mut self, kind: ShiftKind) -> WeldResult<Box<Expr>> {
+        self.consume(TOpenParen)?;
+        let data = self.expr()?;
+        self.consume(TComma)?;
+        let offset = self.expr()?;
+        self.consume(TComma)?;
+        let default = self.expr()?;
+        self.consume(TCloseParen)?;
+        Ok(expr_box(
+            Shift {
+                kind,
+                data,
+                offset,
+                default,
+            },
+            Annotations::new(),
+        ))
+    }
+
+    /// Helper function which returns the `StringOpKind` for a token.
+    fn string_op_kind_for_token(&self, token: Token) -> WeldResult<StringOpKind> {
+        let kind = match token {
+            TStrConcat => StringOpKind::Concat,
+            TSubstr => StringOpKind::Substr,
+            TStrFind => StringOpKind::Find,
+            TStartsWith => StringOpKind::StartsWith,
+            TEndsWith => StringOpKind::EndsWith,
+            TToUpper => StringOpKind::ToUpper,
+            TToLower => StringOpKind::ToLower,
+            TStrToI64 => StringOpKind::ParseI64,
+            TStrToF64 => StringOpKind::ParseF64,
+            TToString => StringOpKind::ToString,
+            _ => {
+                return compile_err!("Invalid token for StringOp");
+            }
+        };
+        Ok(kind)
+    }
+
+    /// Helper function for leaf_expr which parses the arguments of a string operator.
+    fn string_op_leaf_expr(&mut self, token: Token) -> WeldResult<Box<Expr>> {
+        let kind = self.string_op_kind_for_token(token)?;
+        self.consume(TOpenParen)?;
+        let mut args = vec![*self.expr()?];
+        while *self.peek() == TComma {
+            self.consume(TComma)?;
+            args.push(*self.expr()?);
+        }
+        self.consume(TCloseParen)?;
+        Ok(expr_box(StringOp { kind, args }, Annotations::new()))
+    }
+
     /// Parse a terminal expression at the bottom of the precedence chain.
     fn leaf_expr(&mut self) -> WeldResult<Box<Expr>> {
         let mut annotations = Annotations::new();
@@ -758,6 +915,13 @@ impl<'t> Parser<'t> {
             TU64 => Ok(self.parse_cast(ScalarKind::U64)?),
             TF32 => Ok(self.parse_cast(ScalarKind::F32)?),
             TF64 => Ok(self.parse_cast(ScalarKind::F64)?),
+            TDecimal => {
+                let kind = self.decimal_kind()?;
+                Ok(self.parse_cast(kind)?)
+            }
+            TDate => self.temporal_cast(ScalarKind::Date),
+            TTimestamp => self.temporal_cast(ScalarKind::Timestamp),
+            TInterval => self.temporal_cast(ScalarKind::Interval),
             TBool => Ok(self.parse_cast(ScalarKind::Bool)?),
 
             TToVec => {
@@ -994,7 +1158,8 @@ impl<'t> Parser<'t> {
                 Ok(expr_box(OptLookup { data, index }, Annotations::new()))
             }
 
-            TKeyExists => {
+            // Sets use the same expression as dictionaries to check for keys.
+            TKeyExists | TContains => {
                 self.consume(TOpenParen)?;
                 let data = self.expr()?;
                 self.consume(TComma)?;
@@ -1003,6 +1168,13 @@ impl<'t> Parser<'t> {
                 Ok(expr_box(KeyExists { data, key }, Annotations::new()))
             }
 
+            THash => {
+                self.consume(TOpenParen)?;
+                let value = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(Hash(value), Annotations::new()))
+            }
+
             TSlice => {
                 self.consume(TOpenParen)?;
                 let data = self.expr()?;
@@ -1022,6 +1194,92 @@ impl<'t> Parser<'t> {
                 self.consume(TCloseParen)?;
                 Ok(expr_box(Sort { data, cmpfunc }, Annotations::new()))
             }
+
+            TScan => {
+                self.consume(TOpenBracket)?;
+                let elem_type = self.type_()?;
+                self.consume(TComma)?;
+                let op = self.commutative_binop_()?;
+                self.consume(TCloseBracket)?;
+                self.consume(TOpenParen)?;
+                let data = self.expr()?;
+                self.consume(TCloseParen)?;
+
+                let mut expr = expr_box(Scan { data, op }, Annotations::new());
+                expr.ty = Vector(Box::new(elem_type));
+                Ok(expr)
+            }
+
+            TWindow => {
+                self.consume(TOpenBracket)?;
+                let elem_type = self.type_()?;
+                self.consume(TComma)?;
+                let op = self.commutative_binop_()?;
+                self.consume(TCloseBracket)?;
+                self.consume(TOpenParen)?;
+                let data = self.expr()?;
+                self.consume(TComma)?;
+                let size = self.expr()?;
+                self.consume(TCloseParen)?;
+
+                let mut expr = expr_box(Window { data, size, op }, Annotations::new());
+                expr.ty = Vector(Box::new(elem_type));
+                Ok(expr)
+            }
+
+            TLag => self.shift_expr(ShiftKind::Lag),
+            TLead => self.shift_expr(ShiftKind::Lead),
+
+            TNull => {
+                let mut value_type = Unknown;
+                if *self.peek() == TOpenBracket {
+                    self.consume(TOpenBracket)?;
+                    value_type = self.type_()?;
+                    self.consume(TCloseBracket)?;
+                }
+                let mut expr = expr_box(Null, Annotations::new());
+                expr.ty = Optional(Box::new(value_type));
+                Ok(expr)
+            }
+
+            TOptional => {
+                self.consume(TOpenParen)?;
+                let value = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(MakeOptional(value), Annotations::new()))
+            }
+
+            TIsNull => {
+                self.consume(TOpenParen)?;
+                let value = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(IsNull(value), Annotations::new()))
+            }
+
+            TCoalesce => {
+                self.consume(TOpenParen)?;
+                let value = self.expr()?;
+                self.consume(TComma)?;
+                let default = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(Coalesce { value, default }, Annotations::new()))
+            }
+
+            TDistinctCount => {
+                self.consume(TOpenParen)?;
+                let sketch = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(DistinctCount(sketch), Annotations::new()))
+            }
+
+            TQuantile => {
+                self.consume(TOpenParen)?;
+                let sketch = self.expr()?;
+                self.consume(TComma)?;
+                let quantile = self.expr()?;
+                self.consume(TCloseParen)?;
+                Ok(expr_box(Quantile { sketch, quantile }, Annotations::new()))
+            }
             TExp => self.unary_leaf_expr(TExp),
             TLog => self.unary_leaf_expr(TLog),
             TErf => self.unary_leaf_expr(TErf),
@@ -1035,6 +1293,29 @@ impl<'t> Parser<'t> {
             TSinh => self.unary_leaf_expr(TSinh),
             TCosh => self.unary_leaf_expr(TCosh),
             TTanh => self.unary_leaf_expr(TTanh),
+            TCbrt => self.unary_leaf_expr(TCbrt),
+            TFloor => self.unary_leaf_expr(TFloor),
+            TCeil => self.unary_leaf_expr(TCeil),
+            TRound => self.unary_leaf_expr(TRound),
+            TAbs => self.unary_leaf_expr(TAbs),
+            TPopcount => self.unary_leaf_expr(TPopcount),
+            TClz => self.unary_leaf_expr(TClz),
+            TCtz => self.unary_leaf_expr(TCtz),
+            TStrConcat => self.string_op_leaf_expr(TStrConcat),
+            TSubstr => self.string_op_leaf_expr(TSubstr),
+            TStrFind => self.string_op_leaf_expr(TStrFind),
+            TStartsWith => self.string_op_leaf_expr(TStartsWith),
+            TEndsWith => self.string_op_leaf_expr(TEndsWith),
+            TToUpper => self.string_op_leaf_expr(TToUpper),
+            TToLower => self.string_op_leaf_expr(TToLower),
+            TStrToI64 => self.string_op_leaf_expr(TStrToI64),
+            TStrToF64 => self.string_op_leaf_expr(TStrToF64),
+            TToString => self.string_op_leaf_expr(TToString),
+            TYear => self.date_op_leaf_expr(DateOpKind::Year),
+            TMonth => self.date_op_leaf_expr(DateOpKind::Month),
+            TDay => self.date_op_leaf_expr(DateOpKind::Day),
+            THour => self.date_op_leaf_expr(DateOpKind::Hour),
+            TDateTrunc => self.date_trunc_leaf_expr(),
 
             TMerge => {
                 self.consume(TOpenParen)?;
@@ -1158,6 +1439,50 @@ impl<'t> Parser<'t> {
                 Ok(expr)
             }
 
+            TTopK => {
+                self.consume(TOpenBracket)?;
+                let elem_type = self.type_()?;
+                self.consume(TComma)?;
+                let k = self.topk_size()?;
+                self.consume(TCloseBracket)?;
+
+                let mut expr = expr_box(NewBuilder(None), Annotations::new());
+                expr.ty = Builder(TopK(Box::new(elem_type), k), annotations);
+                Ok(expr)
+            }
+
+            TSetMerger => {
+                self.consume(TOpenBracket)?;
+                let key_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+
+                let mut expr = expr_box(NewBuilder(None), Annotations::new());
+                expr.ty = Builder(SetMerger(Box::new(key_type)), annotations);
+                Ok(expr)
+            }
+
+            THllMerger => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+
+                let kind = SketchMerger(SketchKind::HyperLogLog, Box::new(item_type));
+                let mut expr = expr_box(NewBuilder(None), Annotations::new());
+                expr.ty = Builder(kind, annotations);
+                Ok(expr)
+            }
+
+            TQuantileMerger => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+
+                let kind = SketchMerger(SketchKind::Quantiles, Box::new(item_type));
+                let mut expr = expr_box(NewBuilder(None), Annotations::new());
+                expr.ty = Builder(kind, annotations);
+                Ok(expr)
+            }
+
             TMinus => Ok(expr_box(Negate(self.leaf_expr()?), Annotations::new())),
             TBang => Ok(expr_box(Not(self.leaf_expr()?), Annotations::new())),
 
@@ -1293,6 +1618,10 @@ impl<'t> Parser<'t> {
             TU64 => Ok(Scalar(ScalarKind::U64)),
             TF32 => Ok(Scalar(ScalarKind::F32)),
             TF64 => Ok(Scalar(ScalarKind::F64)),
+            TDecimal => Ok(Scalar(self.decimal_kind()?)),
+            TDate => Ok(Scalar(ScalarKind::Date)),
+            TTimestamp => Ok(Scalar(ScalarKind::Timestamp)),
+            TInterval => Ok(Scalar(ScalarKind::Interval)),
             TBool => Ok(Scalar(ScalarKind::Bool)),
 
             TVec => {
@@ -1302,6 +1631,13 @@ impl<'t> Parser<'t> {
                 Ok(Vector(Box::new(elem_type)))
             }
 
+            TOptional => {
+                self.consume(TOpenBracket)?;
+                let value_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                Ok(Optional(Box::new(value_type)))
+            }
+
             TSimd => {
                 self.consume(TOpenBracket)?;
                 let elem_type = self.type_()?;
@@ -1343,6 +1679,27 @@ impl<'t> Parser<'t> {
                 Ok(Dict(Box::new(key_type), Box::new(value_type)))
             }
 
+            TSet => {
+                self.consume(TOpenBracket)?;
+                let key_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                Ok(Set(Box::new(key_type)))
+            }
+
+            THll => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                Ok(Sketch(SketchKind::HyperLogLog, Box::new(item_type)))
+            }
+
+            TQuantiles => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                Ok(Sketch(SketchKind::Quantiles, Box::new(item_type)))
+            }
+
             TDictMerger => {
                 let key_type: Type;
                 let value_type: Type;
@@ -1387,6 +1744,38 @@ impl<'t> Parser<'t> {
                 ))
             }
 
+            TTopK => {
+                self.consume(TOpenBracket)?;
+                let elem_type = self.type_()?;
+                self.consume(TComma)?;
+                let k = self.topk_size()?;
+                self.consume(TCloseBracket)?;
+                Ok(Builder(TopK(Box::new(elem_type), k), annotations))
+            }
+
+            TSetMerger => {
+                self.consume(TOpenBracket)?;
+                let key_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                Ok(Builder(SetMerger(Box::new(key_type)), annotations))
+            }
+
+            THllMerger => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                let kind = SketchMerger(SketchKind::HyperLogLog, Box::new(item_type));
+                Ok(Builder(kind, annotations))
+            }
+
+            TQuantileMerger => {
+                self.consume(TOpenBracket)?;
+                let item_type = self.type_()?;
+                self.consume(TCloseBracket)?;
+                let kind = SketchMerger(SketchKind::Quantiles, Box::new(item_type));
+                Ok(Builder(kind, annotations))
+            }
+
             TOpenBrace => {
                 let mut types: Vec<Type> = Vec::new();
                 while *self.peek() != TCloseBrace {
@@ -1409,6 +1798,138 @@ impl<'t> Parser<'t> {
     }
 }
 
+/// Microseconds per second.
+const MICROS_PER_SECOND: i64 = 1_000_000;
+/// Microseconds per day.
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// Parses the text of a date, timestamp or interval literal.
+///
+/// Dates are written as "YYYY-MM-DD", timestamps as "YYYY-MM-DD HH:MM:SS" with optional
+/// fractional seconds, and intervals as "<count> <unit>", where the unit is one of the units
+/// accepted by `interval_unit`.
+fn temporal_literal(kind: ScalarKind, text: &str) -> WeldResult<LiteralKind> {
+    let literal = match kind {
+        ScalarKind::Date => parse_date(text).map(|days| I32Literal(days as i32)),
+        ScalarKind::Timestamp => parse_timestamp(text).map(I64Literal),
+        ScalarKind::Interval => parse_interval(text).map(I64Literal),
+        _ => None,
+    };
+    match literal {
+        Some(literal) => Ok(literal),
+        None => compile_err!("Invalid {} literal \"{}\"", kind, text),
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the number of days in a month.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a date in the format "YYYY-MM-DD" into days since 1970-01-01.
+fn parse_date(text: &str) -> Option<i64> {
+    let fields: Vec<_> = text.split('-').collect();
+    if fields.len() != 3 || fields.iter().any(|f| f.is_empty()) {
+        return None;
+    }
+    let year: i64 = fields[0].parse().ok()?;
+    let month: i64 = fields[1].parse().ok()?;
+    let day: i64 = fields[2].parse().ok()?;
+    if year < 0 || month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if days < i64::from(i32::min_value()) || days > i64::from(i32::max_value()) {
+        return None;
+    }
+    Some(days)
+}
+
+/// Parses a timestamp in the format "YYYY-MM-DD HH:MM:SS[.ffffff]" into microseconds since the
+/// epoch. The time is optional, and may be separated from the date by a "T".
+fn parse_timestamp(text: &str) -> Option<i64> {
+    let mut parts = text.splitn(2, |c: char| c == ' ' || c == 'T');
+    let days = parse_date(parts.next()?)?;
+    let time = match parts.next() {
+        Some(time) => parse_time(time)?,
+        None => 0,
+    };
+    days.checked_mul(MICROS_PER_DAY)?.checked_add(time)
+}
+
+/// Parses a time of day in the format "HH:MM[:SS[.ffffff]]" into microseconds since midnight.
+fn parse_time(text: &str) -> Option<i64> {
+    let fields: Vec<_> = text.split(':').collect();
+    if fields.len() < 2 || fields.len() > 3 {
+        return None;
+    }
+    let hours: i64 = fields[0].parse().ok()?;
+    let minutes: i64 = fields[1].parse().ok()?;
+    let (seconds, micros) = match fields.get(2) {
+        Some(seconds) => {
+            let mut parts = seconds.splitn(2, '.');
+            let whole: i64 = parts.next()?.parse().ok()?;
+            let micros = match parts.next() {
+                Some(fraction) if fraction.len() <= 6 => {
+                    let padded = format!("{:0<6}", fraction);
+                    padded.parse::<u32>().ok()?
+                }
+                Some(_) => return None,
+                None => 0,
+            };
+            (whole, i64::from(micros))
+        }
+        None => (0, 0),
+    };
+    if hours < 0 || hours > 23 || minutes < 0 || minutes > 59 || seconds < 0 || seconds > 59 {
+        return None;
+    }
+    Some(((hours * 60 + minutes) * 60 + seconds) * MICROS_PER_SECOND + micros)
+}
+
+/// Returns the number of microseconds in an interval unit.
+///
+/// Units can be written in singular or plural form. Months and years are not supported, since
+/// they do not have a fixed length.
+fn interval_unit(name: &str) -> Option<i64> {
+    let micros = match name.trim_end_matches('s') {
+        "microsecond" => 1,
+        "millisecond" => 1000,
+        "second" => MICROS_PER_SECOND,
+        "minute" => 60 * MICROS_PER_SECOND,
+        "hour" => 3600 * MICROS_PER_SECOND,
+        "day" => MICROS_PER_DAY,
+        "week" => 7 * MICROS_PER_DAY,
+        _ => return None,
+    };
+    Some(micros)
+}
+
+/// Parses an interval in the format "<count> <unit>" into microseconds.
+fn parse_interval(text: &str) -> Option<i64> {
+    let fields: Vec<_> = text.split_whitespace().collect();
+    if fields.len() != 2 {
+        return None;
+    }
+    let count: i64 = fields[0].parse().ok()?;
+    count.checked_mul(interval_unit(fields[1])?)
+}
+
 #[test]
 fn basic_parsing() {
     let e = parse_expr("10 - 2 - 3 + 1").unwrap();
@@ -1535,6 +2056,18 @@ fn operator_precedence() {
         print_expr_without_indent(&e),
         "(((((((((a%b)-c)>=d)!=e)&f)^g)|h)&&i)||j)"
     );
+
+    let e = parse_expr("a << b + c > d >> e - f << g").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e),
+        "((a<<(b+c))>((d>>(e-f))<<g))"
+    );
+
+    let e = parse_expr("abs(a) >> 1 & popcount(b)").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e),
+        "(((abs(a))>>1)&(popcount(b)))"
+    );
 }
 
 #[test]
@@ -1618,6 +2151,51 @@ fn parse_and_print_simple_expressions() {
         print_expr_without_indent(&e).as_str(),
         "for(d,appender[?],|e|(e+1))"
     );
+
+    let e = parse_expr("substr(strconcat(a, b), 1L, tolower(c))").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "substr(strconcat(a,b),1L,tolower(c))"
+    );
+
+    let e = parse_expr("|a: optional[i32]| coalesce(a + optional(1), 0)").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "|a:optional[i32]|coalesce((a+optional(1)),0)"
+    );
+
+    let e = parse_expr("{null, null[vec[i8]], isnull(a)}").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "{null[?],null[vec[i8]],isnull(a)}"
+    );
+
+    let e = parse_expr("|a: decimal[10,2]| decimal[12,4](a) * decimal[12,4](1.5)").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "|a:decimal[10,2]|((decimal[12,4](a))*(decimal[12,4](1.5)))"
+    );
+    assert!(parse_expr("decimal[19,2](a)").is_err());
+    assert!(parse_expr("decimal[4,5](a)").is_err());
+
+    let e = parse_expr(
+        r#"|a: date| {date("2020-02-29") - a, timestamp("1969-12-31 23:59:59.5"), interval("-2 days")}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "|a:date|{((date(18321))-a),(timestamp(-500000L)),(interval(-172800000000L))}"
+    );
+    assert!(parse_expr(r#"date("2019-02-29")"#).is_err());
+    assert!(parse_expr(r#"timestamp("2020-01-01 24:00:00")"#).is_err());
+    assert!(parse_expr(r#"interval("1 month")"#).is_err());
+
+    let e = parse_expr(r#"|t: timestamp| {year(t), hour(t), date_trunc("month", t)}"#).unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "|t:timestamp|{year(t),hour(t),date_trunc(\"month\",t)}"
+    );
+    assert!(parse_expr(r#"date_trunc("week", t)"#).is_err());
 }
 
 #[test]
@@ -1657,4 +2235,41 @@ fn parse_and_print_for_expressions() {
         print_expr_without_indent(&e).as_str(),
         "for(zip(a,b,iter(c,0L,4L,1L),d),appender[?],|e|(e+1))"
     );
+    let e = parse_expr("for(d, topk[f64, 100], |b, i, e| merge(b, e))").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "for(d,topk[f64,100],|b,i,e|merge(b,e))"
+    );
+
+    assert!(parse_expr("topk[f64, 0]").is_err());
+    assert!(parse_expr("topk[f64, 1.0]").is_err());
+
+    let e =
+        parse_expr("let s = result(for(d, setmerger[i32], |b, i, e| merge(b, e))); contains(s, 1)")
+            .unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "(let s=(result(for(d,setmerger[i32],|b,i,e|merge(b,e))));keyexists(s,1))"
+    );
+
+    let e =
+        parse_expr("distinctcount(result(for(v, hllmerger[u32], |b, i, e| merge(b, hash(e)))))")
+            .unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "distinctcount(result(for(v,hllmerger[u32],|b,i,e|merge(b,hash(e)))))"
+    );
+
+    let e = parse_expr("|q: quantiles[f64]| quantile(q, 0.5)").unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "|q:quantiles[f64]|quantile(q,0.5)"
+    );
+
+    let e = parse_expr("{scan[?,+](v), window[i64,max](v, 3L), lag(v, 1L, 0L), lead(v, 2L, -1L)}")
+        .unwrap();
+    assert_eq!(
+        print_expr_without_indent(&e).as_str(),
+        "{scan[?,+](v),window[i64,max](v,3L),lag(v,1L,0L),lead(v,2L,(-1L))}"
+    );
 }
@@ -43,9 +43,16 @@ pub enum Token {
     TU64,
     TF32,
     TF64,
+    TDecimal,
+    TDate,
+    TTimestamp,
+    TInterval,
     TBool,
     TVec,
     TDict,
+    TSet,
+    THll,
+    TQuantiles,
     TZip,
     TScalarIter,
     TSimdIter,
@@ -56,8 +63,14 @@ pub enum Token {
     TLookup,
     TOptLookup,
     TKeyExists,
+    TContains,
+    THash,
     TSlice,
     TSort,
+    TScan,
+    TWindow,
+    TLag,
+    TLead,
     TExp,
     TSin,
     TCos,
@@ -77,12 +90,45 @@ pub enum Token {
     TLog,
     TErf,
     TSqrt,
+    TCbrt,
+    TFloor,
+    TCeil,
+    TRound,
+    TAbs,
+    TPopcount,
+    TClz,
+    TCtz,
+    TStrConcat,
+    TSubstr,
+    TStrFind,
+    TStartsWith,
+    TEndsWith,
+    TToUpper,
+    TToLower,
+    TStrToI64,
+    TStrToF64,
+    TToString,
+    TOptional,
+    TNull,
+    TIsNull,
+    TCoalesce,
+    TDistinctCount,
+    TQuantile,
+    TYear,
+    TMonth,
+    TDay,
+    THour,
+    TDateTrunc,
     TCUDF,
     TAppender,
     TMerger,
     TDictMerger,
     TGroupMerger,
     TVecMerger,
+    TTopK,
+    TSetMerger,
+    THllMerger,
+    TQuantileMerger,
     TToVec,
     TOpenParen,    // (
     TCloseParen,   // )
@@ -114,6 +160,8 @@ pub enum Token {
     TLogicalOr,
     TBitwiseAnd,
     TXor,
+    TShiftLeft,
+    TShiftRight,
     TMax,
     TMin,
     TPow,
@@ -130,7 +178,8 @@ impl Token {
             | TComma | TPlus | TMinus | TTimes | TDivide | TModulo | TEqual | TBar | TAtMark
             | TDot | TColon | TSemicolon | TQuestion | TBang | TEqualEqual | TNotEqual
             | TLessThanOrEqual | TGreaterThanOrEqual | TLessThan | TGreaterThan | TLogicalAnd
-            | TLogicalOr | TBitwiseAnd | TXor | TMax | TMin | TPow | TEndOfInput => false,
+            | TLogicalOr | TBitwiseAnd | TXor | TShiftLeft | TShiftRight | TMax | TMin | TPow
+            | TEndOfInput => false,
             _ => true,
         }
     }
@@ -141,9 +190,11 @@ impl Token {
     pub fn signals_type(&self) -> bool {
         use self::Token::*;
         match *self {
-            TI8 | TI16 | TI32 | TI64 | TU8 | TU16 | TU32 | TU64 | TF32 | TF64 | TBool | TVec
-            | TSimd | TAppender | TMerger | TDict | TDictMerger | TGroupMerger | TVecMerger
-            | TOpenBrace | TQuestion => true,
+            TI8 | TI16 | TI32 | TI64 | TU8 | TU16 | TU32 | TU64 | TF32 | TF64 | TDecimal
+            | TDate | TTimestamp | TInterval | TBool | TVec | TOptional | TSimd | TAppender
+            | TMerger | TDict | TSet | TDictMerger | TGroupMerger | TVecMerger | TTopK
+            | TSetMerger | THll | TQuantiles | THllMerger | TQuantileMerger | TOpenBrace
+            | TQuestion => true,
             _ => false,
         }
     }
@@ -157,16 +208,18 @@ pub fn tokenize(input: &str) -> WeldResult<Vec<Token>> {
         static ref TOKEN_RE: Regex = Regex::new(concat!(
             "(?m)#.*$|",
             r#"[0-9]+\.[0-9]+([eE]-?[0-9]+)?[fF]?|[0-9]+[eE]-?[0-9]+[fF]?|"[^"]*"|"#,
-            r#"[A-Za-z0-9$_]+|==|!=|>=|<=|&&|\|\||[-+/*%,=()[\]{}|@&\.:;?!&\|^<>]|\S+"#
+            r#"[A-Za-z0-9$_]+|==|!=|>=|<=|<<|>>|&&|\|\||[-+/*%,=()[\]{}|@&\.:;?!&\|^<>]|\S+"#
         )).unwrap();
 
         // Regular expressions for various types of tokens.
         static ref KEYWORD_RE: Regex = Regex::new(
-            "^(if|for|zip|len|lookup|optlookup|keyexists|slice|sort|exp|sin|cos|tan|asin|acos|atan|sinh|cosh|tanh|\
-             log|erf|sqrt|simd|select|assert|broadcast|serialize|deserialize|\
+            "^(if|for|zip|len|lookup|optlookup|keyexists|contains|hash|slice|sort|scan|window|lag|lead|exp|sin|cos|tan|asin|acos|atan|sinh|cosh|tanh|\
+             log|erf|sqrt|cbrt|floor|ceil|round|abs|popcount|clz|ctz|simd|select|assert|broadcast|serialize|deserialize|\
+             strconcat|substr|strfind|startswith|endswith|toupper|tolower|strtoi64|strtof64|tostring|\
+             optional|null|isnull|coalesce|distinctcount|quantile|year|month|day|hour|date_trunc|\
              iterate|cudf|simditer|fringeiter|rangeiter|nditer|iter|merge|result|let|true|false|macro|type|\
-             i8|i16|i32|i64|u8|u16|u32|u64|f32|f64|bool|vec|dict|appender|merger|vecmerger|\
-             dictmerger|groupmerger|tovec|min|max|pow)$").unwrap();
+             i8|i16|i32|i64|u8|u16|u32|u64|f32|f64|decimal|date|timestamp|interval|bool|vec|dict|set|appender|merger|vecmerger|\
+             dictmerger|groupmerger|topk|setmerger|hll|quantiles|hllmerger|quantilemerger|tovec|min|max|pow)$").unwrap();
 
         static ref COMMENT_RE: Regex = Regex::new("#.*$").unwrap();
         static ref STRLIT_RE: Regex = Regex::new(r#""[^"]*""#).unwrap();
@@ -223,14 +276,25 @@ pub fn tokenize(input: &str) -> WeldResult<Vec<Token>> {
                 "u64" => TU64,
                 "f32" => TF32,
                 "f64" => TF64,
+                "decimal" => TDecimal,
+                "date" => TDate,
+                "timestamp" => TTimestamp,
+                "interval" => TInterval,
                 "bool" => TBool,
                 "vec" => TVec,
                 "dict" => TDict,
+                "set" => TSet,
+                "hll" => THll,
+                "quantiles" => TQuantiles,
                 "appender" => TAppender,
                 "merger" => TMerger,
                 "dictmerger" => TDictMerger,
                 "groupmerger" => TGroupMerger,
                 "vecmerger" => TVecMerger,
+                "topk" => TTopK,
+                "setmerger" => TSetMerger,
+                "hllmerger" => THllMerger,
+                "quantilemerger" => TQuantileMerger,
                 "tovec" => TToVec,
                 "zip" => TZip,
                 "iter" => TScalarIter,
@@ -242,8 +306,14 @@ pub fn tokenize(input: &str) -> WeldResult<Vec<Token>> {
                 "lookup" => TLookup,
                 "optlookup" => TOptLookup,
                 "keyexists" => TKeyExists,
+                "contains" => TContains,
+                "hash" => THash,
                 "slice" => TSlice,
                 "sort" => TSort,
+                "scan" => TScan,
+                "window" => TWindow,
+                "lag" => TLag,
+                "lead" => TLead,
                 "exp" => TExp,
                 "sin" => TSin,
                 "cos" => TCos,
@@ -257,6 +327,35 @@ pub fn tokenize(input: &str) -> WeldResult<Vec<Token>> {
                 "log" => TLog,
                 "erf" => TErf,
                 "sqrt" => TSqrt,
+                "cbrt" => TCbrt,
+                "floor" => TFloor,
+                "ceil" => TCeil,
+                "round" => TRound,
+                "abs" => TAbs,
+                "popcount" => TPopcount,
+                "clz" => TClz,
+                "ctz" => TCtz,
+                "strconcat" => TStrConcat,
+                "substr" => TSubstr,
+                "strfind" => TStrFind,
+                "startswith" => TStartsWith,
+                "endswith" => TEndsWith,
+                "toupper" => TToUpper,
+                "tolower" => TToLower,
+                "strtoi64" => TStrToI64,
+                "strtof64" => TStrToF64,
+                "tostring" => TToString,
+                "optional" => TOptional,
+                "null" => TNull,
+                "isnull" => TIsNull,
+                "coalesce" => TCoalesce,
+                "distinctcount" => TDistinctCount,
+                "quantile" => TQuantile,
+                "year" => TYear,
+                "month" => TMonth,
+                "day" => TDay,
+                "hour" => THour,
+                "date_trunc" => TDateTrunc,
                 "cudf" => TCUDF,
                 "simd" => TSimd,
                 "select" => TSelect,
@@ -341,6 +440,8 @@ pub fn tokenize(input: &str) -> WeldResult<Vec<Token>> {
                 ">" => TGreaterThan,
                 "<=" => TLessThanOrEqual,
                 ">=" => TGreaterThanOrEqual,
+                "<<" => TShiftLeft,
+                ">>" => TShiftRight,
                 "&&" => TLogicalAnd,
                 "||" => TLogicalOr,
                 "&" => TBitwiseAnd,
@@ -403,14 +504,25 @@ impl fmt::Display for Token {
                         TU64 => "u64",
                         TF32 => "f32",
                         TF64 => "f64",
+                        TDecimal => "decimal",
+                        TDate => "date",
+                        TTimestamp => "timestamp",
+                        TInterval => "interval",
                         TBool => "bool",
                         TVec => "vec",
                         TDict => "dict",
+                        TSet => "set",
+                        THll => "hll",
+                        TQuantiles => "quantiles",
                         TAppender => "appender",
                         TMerger => "merger",
                         TDictMerger => "dictmerger",
                         TGroupMerger => "groupmerger",
                         TVecMerger => "vecmerger",
+                        TTopK => "topk",
+                        TSetMerger => "setmerger",
+                        THllMerger => "hllmerger",
+                        TQuantileMerger => "quantilemerger",
                         TToVec => "tovec",
                         TZip => "zip",
                         TScalarIter => "iter",
@@ -422,8 +534,14 @@ impl fmt::Display for Token {
                         TLookup => "lookup",
                         TOptLookup => "optlookup",
                         TKeyExists => "keyexists",
+                        TContains => "contains",
+                        THash => "hash",
                         TSlice => "slice",
                         TSort => "sort",
+                        TScan => "scan",
+                        TWindow => "window",
+                        TLag => "lag",
+                        TLead => "lead",
                         TExp => "exp",
                         TSin => "sin",
                         TCos => "cos",
@@ -437,6 +555,35 @@ impl fmt::Display for Token {
                         TLog => "log",
                         TErf => "erf",
                         TSqrt => "sqrt",
+                        TCbrt => "cbrt",
+                        TFloor => "floor",
+                        TCeil => "ceil",
+                        TRound => "round",
+                        TAbs => "abs",
+                        TPopcount => "popcount",
+                        TClz => "clz",
+                        TCtz => "ctz",
+                        TStrConcat => "strconcat",
+                        TSubstr => "substr",
+                        TStrFind => "strfind",
+                        TStartsWith => "startswith",
+                        TEndsWith => "endswith",
+                        TToUpper => "toupper",
+                        TToLower => "tolower",
+                        TStrToI64 => "strtoi64",
+                        TStrToF64 => "strtof64",
+                        TToString => "tostring",
+                        TOptional => "optional",
+                        TNull => "null",
+                        TIsNull => "isnull",
+                        TCoalesce => "coalesce",
+                        TDistinctCount => "distinctcount",
+                        TQuantile => "quantile",
+                        TYear => "year",
+                        TMonth => "month",
+                        TDay => "day",
+                        THour => "hour",
+                        TDateTrunc => "date_trunc",
                         TCUDF => "cudf",
                         TSimd => "simd",
                         TSelect => "select",
@@ -474,6 +621,8 @@ impl fmt::Display for Token {
                         TLogicalOr => "||",
                         TBitwiseAnd => "&",
                         TXor => "^",
+                        TShiftLeft => "<<",
+                        TShiftRight => ">>",
                         TMin => "min",
                         TMax => "max",
                         TPow => "pow",
@@ -568,6 +717,62 @@ fn basic_tokenize() {
             TEndOfInput
         ]
     );
+    assert_eq!(
+        tokenize("topk[f64, 100]").unwrap(),
+        vec![
+            TTopK,
+            TOpenBracket,
+            TF64,
+            TComma,
+            TI32Literal(100),
+            TCloseBracket,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize("contains(s, 1) setmerger[i32] set[i32]").unwrap(),
+        vec![
+            TContains,
+            TOpenParen,
+            TIdent("s".into()),
+            TComma,
+            TI32Literal(1),
+            TCloseParen,
+            TSetMerger,
+            TOpenBracket,
+            TI32,
+            TCloseBracket,
+            TSet,
+            TOpenBracket,
+            TI32,
+            TCloseBracket,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize("hllmerger[i64] quantiles[f64] distinctcount(h) quantile(q, 0.5)").unwrap(),
+        vec![
+            THllMerger,
+            TOpenBracket,
+            TI64,
+            TCloseBracket,
+            TQuantiles,
+            TOpenBracket,
+            TF64,
+            TCloseBracket,
+            TDistinctCount,
+            TOpenParen,
+            TIdent("h".into()),
+            TCloseParen,
+            TQuantile,
+            TOpenParen,
+            TIdent("q".into()),
+            TComma,
+            TF64Literal(0.5),
+            TCloseParen,
+            TEndOfInput
+        ]
+    );
 
     assert_eq!(
         tokenize("= == | || & &&").unwrap(),
@@ -675,6 +880,91 @@ fn basic_tokenize() {
             TEndOfInput
         ]
     );
+    assert_eq!(
+        tokenize("substr(a, 1L, 2L) tostring").unwrap(),
+        vec![
+            TSubstr,
+            TOpenParen,
+            TIdent("a".into()),
+            TComma,
+            TI64Literal(1),
+            TComma,
+            TI64Literal(2),
+            TCloseParen,
+            TToString,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize("optional[i32] null isnull coalesce").unwrap(),
+        vec![
+            TOptional,
+            TOpenBracket,
+            TI32,
+            TCloseBracket,
+            TNull,
+            TIsNull,
+            TCoalesce,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize("decimal[10,2](x)").unwrap(),
+        vec![
+            TDecimal,
+            TOpenBracket,
+            TI32Literal(10),
+            TComma,
+            TI32Literal(2),
+            TCloseBracket,
+            TOpenParen,
+            TIdent("x".into()),
+            TCloseParen,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize(r#"date_trunc("month", date("2020-01-31")) year"#).unwrap(),
+        vec![
+            TDateTrunc,
+            TOpenParen,
+            TStringLiteral("month".into()),
+            TComma,
+            TDate,
+            TOpenParen,
+            TStringLiteral("2020-01-31".into()),
+            TCloseParen,
+            TCloseParen,
+            TYear,
+            TEndOfInput
+        ]
+    );
+    assert_eq!(
+        tokenize("a<<2>>b<c abs(popcount(a)) clz ctz floor ceil round cbrt").unwrap(),
+        vec![
+            TIdent("a".into()),
+            TShiftLeft,
+            TI32Literal(2),
+            TShiftRight,
+            TIdent("b".into()),
+            TLessThan,
+            TIdent("c".into()),
+            TAbs,
+            TOpenParen,
+            TPopcount,
+            TOpenParen,
+            TIdent("a".into()),
+            TCloseParen,
+            TCloseParen,
+            TClz,
+            TCtz,
+            TFloor,
+            TCeil,
+            TRound,
+            TCbrt,
+            TEndOfInput
+        ]
+    );
     assert_eq!(
         tokenize("iffy if").unwrap(),
         vec![TIdent("iffy".into()), TIf, TEndOfInput]
@@ -746,4 +1036,32 @@ fn basic_tokenize() {
             TEndOfInput
         ]
     );
+    assert_eq!(
+        tokenize("scan[?,+](v) window[i64,max](v, 3L) lag lead").unwrap(),
+        vec![
+            TScan,
+            TOpenBracket,
+            TQuestion,
+            TComma,
+            TPlus,
+            TCloseBracket,
+            TOpenParen,
+            TIdent("v".into()),
+            TCloseParen,
+            TWindow,
+            TOpenBracket,
+            TI64,
+            TComma,
+            TMax,
+            TCloseBracket,
+            TOpenParen,
+            TIdent("v".into()),
+            TComma,
+            TI64Literal(3),
+            TCloseParen,
+            TLag,
+            TLead,
+            TEndOfInput
+        ]
+    );
 }
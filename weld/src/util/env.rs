@@ -61,3 +61,28 @@ pub fn get_home() -> String {
       Err(_) => "".to_string(),
   }
 }
+
+pub fn get_host_cc() -> String {
+  match env::var("WELD_HOST_CC") {
+      Ok(val) => val,
+      Err(_) => "cc".to_string(),
+  }
+}
+
+pub fn get_host_cflags() -> String {
+  match env::var("WELD_HOST_CFLAGS") {
+      Ok(val) => val,
+      Err(_) => "-O2".to_string(),
+  }
+}
+
+// Old environment options
+/// Returns the VE node set with `VE_NODE_NUMBER`, which `weld.ve.node` replaces.
+///
+/// A negative node number disables offloading if no backend is set.
+pub fn get_ve_node_number() -> Option<i32> {
+  match env::var("VE_NODE_NUMBER") {
+      Ok(val) => val.parse().ok(),
+      Err(_) => None,
+  }
+}
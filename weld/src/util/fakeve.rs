@@ -0,0 +1,518 @@
+//! An in-process stand-in for a VE used to test the offload path without hardware.
+//!
+//! `FakeVEOffload` implements `OffloadTransport` on the host. Device memory is allocated
+//! separately from host data, so parameters only reach a kernel if they were explicitly
+//! written with `write_mem`. Kernels are shared objects built for the host and loaded with
+//! `dlopen`, and calls run synchronously on the calling thread. Asynchronous transfers only
+//! copy data once they are waited for, so a missing wait shows up as stale data. Each VE node
+//! number gets a fake VE of its own, so several nodes can be used on a host-only machine.
+//! `crash` puts a fake VE into the failed state to test recovery from a lost VE process, and
+//! `hang` makes its calls time out to test recovery from a VE process that stops responding.
+//! The runs created by the `run_init` entry of a module and not yet freed by its `run_free`
+//! entry are counted, so tests can check that runs on the device are not leaked.
+
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use fnv::{FnvHashMap, FnvHashSet};
+use libc::c_void;
+
+use crate::WeldResult;
+use crate::codegen::ERROR_MESSAGE_SIZE;
+use crate::util::veoffload::{DeviceState, OffloadTransport, TransferHandle, VeoHandle};
+
+lazy_static! {
+    /// Fake VEs, one per VE node.
+    ///
+    /// Fake VEs are never freed, so pointers to them stay valid for the lifetime of the
+    /// process.
+    static ref FAKE_VE_NODES: Mutex<FnvHashMap<i32, u64>> = Mutex::new(FnvHashMap::default());
+}
+
+/// Returns the fake VE of `node`, creating it if necessary.
+pub unsafe fn get_fake_ve_ptr(node: i32) -> *mut FakeVEOffload {
+    let mut nodes = FAKE_VE_NODES.lock().unwrap();
+    let ptr = nodes.entry(node).or_insert_with(|| {
+        let ve = Box::new(FakeVEOffload::default());
+        Box::into_raw(ve) as u64
+    });
+    *ptr as *mut FakeVEOffload
+}
+
+/// Returns the fake VE of `node`, ready to accept requests.
+///
+/// A crashed fake VE is re-created.
+pub unsafe fn initialize_fake_ve(node: i32) -> WeldResult<*mut FakeVEOffload> {
+    let ve = get_fake_ve_ptr(node);
+    (*ve).recover()?;
+    Ok(ve)
+}
+
+/// Finalizes the fake VEs of all nodes.
+pub extern "C" fn finalize_fake_ve_global() {
+    unsafe {
+        let nodes = FAKE_VE_NODES.lock().unwrap();
+        for ptr in nodes.values() {
+            (*(*ptr as *mut FakeVEOffload)).finalize();
+        }
+    }
+}
+
+type Kernel0 = extern "C" fn() -> u64;
+type Kernel1 = extern "C" fn(u64) -> u64;
+type Kernel2 = extern "C" fn(u64, u64) -> u64;
+type Kernel3 = extern "C" fn(u64, u64, u64) -> u64;
+type Kernel4 = extern "C" fn(u64, u64, u64, u64) -> u64;
+
+/// A copy started by an asynchronous transfer, as `(src, dst, len)`.
+type PendingCopy = (u64, u64, usize);
+
+/// The header of a block allocated by a run, as laid out by the generated code.
+#[allow(dead_code)]
+#[repr(C)]
+struct KernelAllocation {
+    prev: *const KernelAllocation,
+    next: *const KernelAllocation,
+    size: u64,
+    pad: u64,
+}
+
+/// The runtime state of a run, as laid out by the generated code.
+#[allow(dead_code)]
+#[repr(C)]
+struct KernelRun {
+    allocations: *const KernelAllocation,
+    errno: i64,
+    result: u64,
+    nworkers: i32,
+    memlimit: u64,
+    allocated: u64,
+    handler: u64,
+    message: [u8; ERROR_MESSAGE_SIZE],
+}
+
+pub struct FakeVEOffload {
+    /// Live device allocations, mapped from address to size.
+    allocations: Mutex<FnvHashMap<u64, usize>>,
+    /// Asynchronous transfers that were not waited for yet.
+    transfers: Mutex<FnvHashMap<TransferHandle, PendingCopy>>,
+    /// Loaded libraries, mapped from path to `dlopen` handle.
+    libs: Mutex<FnvHashMap<String, VeoHandle>>,
+    /// Number of functions called on this fake VE.
+    calls: AtomicUsize,
+    /// Runs created on this fake VE that were not freed yet.
+    runs: Mutex<FnvHashSet<u64>>,
+    /// Whether calls never finish, as on a hung process.
+    hung: bool,
+    pub state: DeviceState,
+    /// Number of times the fake VE was (re-)created.
+    pub generation: u64,
+}
+
+impl Default for FakeVEOffload {
+    fn default() -> Self {
+        Self {
+            allocations: Mutex::new(FnvHashMap::default()),
+            transfers: Mutex::new(FnvHashMap::default()),
+            libs: Mutex::new(FnvHashMap::default()),
+            calls: AtomicUsize::new(0),
+            runs: Mutex::new(FnvHashSet::default()),
+            hung: false,
+            state: DeviceState::Uninitialized,
+            generation: 0,
+        }
+    }
+}
+
+unsafe impl Sync for FakeVEOffload {}  // needed to have the fake VE static
+
+impl FakeVEOffload {
+    /// Returns the number of live device allocations.
+    pub fn live_allocations(&self) -> usize {
+        self.allocations.lock().unwrap().len()
+    }
+
+    /// Returns the number of runs created on this fake VE that were not freed yet.
+    pub fn live_runs(&self) -> usize {
+        self.runs.lock().unwrap().len()
+    }
+
+    /// Returns the number of functions called on this fake VE.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Releases all device memory and unloads all libraries for good.
+    pub unsafe fn finalize(&mut self) {
+        match self.state {
+            DeviceState::Ready | DeviceState::Failed => {
+                self.release_all();
+                self.state = DeviceState::Finalized;
+            }
+            _ => (),
+        }
+    }
+
+    /// Simulates a crash of the VE process.
+    ///
+    /// Requests fail until the fake VE is recovered, which drops all device memory.
+    pub fn crash(&mut self) {
+        if self.state == DeviceState::Ready {
+            self.state = DeviceState::Failed;
+        }
+    }
+
+    /// Simulates a VE process that stops responding.
+    ///
+    /// Calls do not finish until the fake VE is recovered, so they fail once their timeout
+    /// expires.
+    pub fn hang(&mut self) {
+        self.hung = true;
+    }
+
+    /// Releases all device memory and unloads all libraries.
+    unsafe fn release_all(&mut self) {
+        for (addr, size) in self.allocations.lock().unwrap().drain() {
+            release(addr, size);
+        }
+        self.transfers.lock().unwrap().clear();
+        self.runs.lock().unwrap().clear();
+        self.hung = false;
+        for (_, handle) in self.libs.lock().unwrap().drain() {
+            libc::dlclose(handle as *mut c_void);
+        }
+    }
+
+    /// Checks that the fake VE accepts requests.
+    fn check_ready(&self) -> WeldResult<()> {
+        if self.state == DeviceState::Ready {
+            Ok(())
+        } else {
+            device_err!("fake ve is not ready ({:?})", self.state)
+        }
+    }
+
+    /// Records a copy that runs when its transfer is waited for.
+    fn start_transfer(&self, copy: PendingCopy) -> TransferHandle {
+        let mut transfers = self.transfers.lock().unwrap();
+        let handle = (0..).find(|h| !transfers.contains_key(h)).unwrap();
+        transfers.insert(handle, copy);
+        handle
+    }
+
+    /// Checks that `[addr, addr + len)` lies within a single device allocation.
+    fn check_range(&self, addr: u64, len: usize) -> WeldResult<()> {
+        let allocations = self.allocations.lock().unwrap();
+        let found = allocations.iter().any(|(base, size)| {
+            addr >= *base && addr + len as u64 <= *base + *size as u64
+        });
+        if found {
+            Ok(())
+        } else {
+            weld_err!("fake ve: address {:#x} (len {}) is not device memory", addr, len)
+        }
+    }
+
+    /// Returns the base address of the loaded library that contains `addr`, if any.
+    unsafe fn library_base(&self, addr: u64) -> Option<u64> {
+        let mut info: libc::Dl_info = mem::zeroed();
+        if libc::dladdr(addr as *const c_void, &mut info) == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+        let name = CStr::from_ptr(info.dli_fname).to_string_lossy();
+        if self.libs.lock().unwrap().contains_key(name.as_ref()) {
+            Some(info.dli_fbase as u64)
+        } else {
+            None
+        }
+    }
+
+    /// Checks that `[addr, addr + len)` can be read from the device.
+    ///
+    /// Besides allocations from `alloc_mem`, kernels return memory allocated by their runs and
+    /// constants of their libraries.
+    unsafe fn check_read_range(&self, addr: u64, len: usize) -> WeldResult<()> {
+        let end = addr + len as u64;
+        let within = |base: u64, size: u64| addr >= base && end <= base + size;
+        if self.check_range(addr, len).is_ok() {
+            return Ok(());
+        }
+        for run in self.runs.lock().unwrap().iter() {
+            if within(*run, mem::size_of::<KernelRun>() as u64) {
+                return Ok(());
+            }
+            let mut block = (*(*run as *const KernelRun)).allocations;
+            while !block.is_null() {
+                if within(block.offset(1) as u64, (*block).size) {
+                    return Ok(());
+                }
+                block = (*block).next;
+            }
+        }
+        if len > 0 {
+            let base = self.library_base(addr);
+            if base.is_some() && base == self.library_base(end - 1) {
+                return Ok(());
+            }
+        }
+        weld_err!("fake ve: address {:#x} (len {}) is not device memory", addr, len)
+    }
+}
+
+/// Frees a buffer created by `alloc_mem`.
+unsafe fn release(addr: u64, size: usize) {
+    drop(Box::from_raw(slice::from_raw_parts_mut(addr as *mut u8, size)));
+}
+
+unsafe fn dlerror_string() -> String {
+    let err = libc::dlerror();
+    if err.is_null() {
+        "unknown error".to_string()
+    } else {
+        CStr::from_ptr(err).to_string_lossy().into_owned()
+    }
+}
+
+impl OffloadTransport for FakeVEOffload {
+    fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    unsafe fn recover(&mut self) -> WeldResult<()> {
+        match self.state {
+            DeviceState::Ready => Ok(()),
+            DeviceState::Finalized => device_err!("fake ve was finalized"),
+            state => {
+                if state == DeviceState::Failed {
+                    // The memory of a crashed process is gone.
+                    self.release_all();
+                }
+                self.generation += 1;
+                self.state = DeviceState::Ready;
+                Ok(())
+            }
+        }
+    }
+
+    unsafe fn load_library(&mut self, libname: &str) -> WeldResult<VeoHandle> {
+        self.check_ready()?;
+        let c_libname = CString::new(libname).unwrap();
+        let handle = libc::dlopen(c_libname.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            return weld_err!("cannot load library (name:{}): {}", libname, dlerror_string());
+        }
+
+        if let Some(old) = self.libs.lock().unwrap().insert(libname.to_string(), handle as VeoHandle) {
+            // dlopen is reference counted, so drop the extra reference to a reloaded library.
+            libc::dlclose(old as *mut c_void);
+        }
+        Ok(handle as VeoHandle)
+    }
+
+    unsafe fn call_and_wait(&mut self, libhdl: VeoHandle, symname: &str, args: &[u64],
+                            timeout: Option<Duration>) -> WeldResult<u64> {
+        self.check_ready()?;
+        if self.hung {
+            match timeout {
+                Some(timeout) => {
+                    thread::sleep(timeout);
+                    self.state = DeviceState::Failed;
+                    return device_err!("fake ve: call of {} did not finish in {:?}",
+                                       symname, timeout);
+                }
+                None => panic!("fake ve: call of {} on a hung fake ve never finishes", symname),
+            }
+        }
+        let c_symname = CString::new(symname).unwrap();
+        let sym = libc::dlsym(libhdl as *mut c_void, c_symname.as_ptr());
+        if sym.is_null() {
+            return weld_err!("cannot find function (name:{})", symname);
+        }
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let a = args;
+        let result = match args.len() {
+            0 => mem::transmute::<*mut c_void, Kernel0>(sym)(),
+            1 => mem::transmute::<*mut c_void, Kernel1>(sym)(a[0]),
+            2 => mem::transmute::<*mut c_void, Kernel2>(sym)(a[0], a[1]),
+            3 => mem::transmute::<*mut c_void, Kernel3>(sym)(a[0], a[1], a[2]),
+            4 => mem::transmute::<*mut c_void, Kernel4>(sym)(a[0], a[1], a[2], a[3]),
+            n => return weld_err!("fake ve: too many arguments ({})", n),
+        };
+        match symname {
+            "run_init" => {
+                self.runs.lock().unwrap().insert(result);
+            }
+            "run_free" => {
+                self.runs.lock().unwrap().remove(&a[0]);
+            }
+            _ => (),
+        }
+        Ok(result)
+    }
+
+    unsafe fn alloc_mem(&mut self, size: usize) -> WeldResult<u64> {
+        self.check_ready()?;
+        // Keep every allocation non-empty so that each has a distinct address.
+        let size = size.max(1);
+        let buffer = vec![0u8; size].into_boxed_slice();
+        let addr = Box::into_raw(buffer) as *mut u8 as u64;
+        self.allocations.lock().unwrap().insert(addr, size);
+        Ok(addr)
+    }
+
+    unsafe fn free_mem(&mut self, addr: u64) -> WeldResult<()> {
+        match self.allocations.lock().unwrap().remove(&addr) {
+            Some(size) => {
+                release(addr, size);
+                Ok(())
+            }
+            None => weld_err!("free memory"),
+        }
+    }
+
+    unsafe fn read_mem(&self, src: u64, dst: *mut c_void, len: usize) -> WeldResult<()> {
+        self.check_ready()?;
+        self.check_read_range(src, len)?;
+        ptr::copy_nonoverlapping(src as *const u8, dst as *mut u8, len);
+        Ok(())
+    }
+
+    unsafe fn write_mem(&self, src: *const c_void, dst: u64, len: usize) -> WeldResult<()> {
+        self.check_ready()?;
+        self.check_range(dst, len)?;
+        ptr::copy_nonoverlapping(src as *const u8, dst as *mut u8, len);
+        Ok(())
+    }
+
+    unsafe fn async_read_mem(&self, src: u64, dst: *mut c_void, len: usize)
+        -> WeldResult<TransferHandle> {
+        self.check_ready()?;
+        self.check_read_range(src, len)?;
+        Ok(self.start_transfer((src, dst as u64, len)))
+    }
+
+    unsafe fn async_write_mem(&self, src: *const c_void, dst: u64, len: usize)
+        -> WeldResult<TransferHandle> {
+        self.check_ready()?;
+        self.check_range(dst, len)?;
+        Ok(self.start_transfer((src as u64, dst, len)))
+    }
+
+    unsafe fn wait_transfer(&self, handle: TransferHandle) -> WeldResult<()> {
+        match self.transfers.lock().unwrap().remove(&handle) {
+            Some((src, dst, len)) => {
+                ptr::copy_nonoverlapping(src as *const u8, dst as *mut u8, len);
+                Ok(())
+            }
+            None => weld_err!("fake ve: unknown transfer {}", handle),
+        }
+    }
+}
+
+#[test]
+fn fake_ve_memory_roundtrip() {
+    let mut ve = FakeVEOffload::default();
+    let input: Vec<i64> = (0..16).collect();
+    let mut output = vec![0i64; 16];
+    let size = input.len() * mem::size_of::<i64>();
+    unsafe {
+        ve.recover().unwrap();
+        let addr = ve.alloc_mem(size).unwrap();
+        assert_ne!(addr, input.as_ptr() as u64);
+        ve.write_mem(input.as_ptr() as *const c_void, addr, size).unwrap();
+        ve.read_mem(addr, output.as_mut_ptr() as *mut c_void, size).unwrap();
+        ve.free_mem(addr).unwrap();
+    }
+    assert_eq!(input, output);
+    assert_eq!(ve.live_allocations(), 0);
+}
+
+#[test]
+fn fake_ve_rejects_host_addresses() {
+    let mut ve = FakeVEOffload::default();
+    let input = vec![1u8; 8];
+    unsafe {
+        ve.recover().unwrap();
+        let addr = ve.alloc_mem(4).unwrap();
+        // Writing past the end of an allocation is an error.
+        assert!(ve.write_mem(input.as_ptr() as *const c_void, addr, 8).is_err());
+        // Host memory is not device memory.
+        assert!(ve.write_mem(input.as_ptr() as *const c_void, input.as_ptr() as u64, 8).is_err());
+        // The same holds for reads.
+        let mut output = vec![0u8; 8];
+        assert!(ve.read_mem(addr, output.as_mut_ptr() as *mut c_void, 8).is_err());
+        assert!(ve.read_mem(input.as_ptr() as u64, output.as_mut_ptr() as *mut c_void, 8).is_err());
+        assert!(ve.async_read_mem(addr, output.as_mut_ptr() as *mut c_void, 8).is_err());
+        assert!(ve.free_mem(input.as_ptr() as u64).is_err());
+        ve.free_mem(addr).unwrap();
+        assert!(ve.free_mem(addr).is_err());
+    }
+}
+
+#[test]
+fn fake_ve_transfer_queue_roundtrip() {
+    use crate::util::veoffload::{TransferQueue, TRANSFER_CHUNK_SIZE};
+
+    let mut ve = FakeVEOffload::default();
+    // Spans several chunks and ends with a partial one.
+    let size = TRANSFER_CHUNK_SIZE * 2 + 3;
+    let input: Vec<u8> = (0..size).map(|i| i as u8).collect();
+    let mut output = vec![0u8; size];
+    unsafe {
+        ve.recover().unwrap();
+        let addr = ve.alloc_mem(size).unwrap();
+        {
+            let mut queue = TransferQueue::new(&ve);
+            queue.write(input.as_ptr() as *const c_void, addr, size).unwrap();
+            queue.wait_all().unwrap();
+            queue.read(addr, output.as_mut_ptr() as *mut c_void, size).unwrap();
+            // The data only arrives once the transfers are waited for.
+            assert_ne!(input, output);
+            queue.wait_all().unwrap();
+        }
+        assert!(ve.transfers.lock().unwrap().is_empty());
+        ve.free_mem(addr).unwrap();
+    }
+    assert_eq!(input, output);
+}
+
+#[test]
+fn fake_ve_recovers_after_crash() {
+    let mut ve = FakeVEOffload::default();
+    let input = vec![1u8; 8];
+    unsafe {
+        assert_eq!(ve.state(), DeviceState::Uninitialized);
+        ve.recover().unwrap();
+        assert_eq!(ve.state(), DeviceState::Ready);
+        assert_eq!(ve.generation(), 1);
+        ve.alloc_mem(8).unwrap();
+
+        // A crashed fake VE rejects requests until it is recovered.
+        ve.crash();
+        assert_eq!(ve.state(), DeviceState::Failed);
+        let err = ve.alloc_mem(8).unwrap_err();
+        assert_eq!(err.code(), crate::WeldRuntimeErrno::DeviceError);
+
+        // Recovering drops the memory of the crashed process.
+        ve.recover().unwrap();
+        assert_eq!(ve.generation(), 2);
+        assert_eq!(ve.live_allocations(), 0);
+        let addr = ve.alloc_mem(8).unwrap();
+        ve.write_mem(input.as_ptr() as *const c_void, addr, 8).unwrap();
+
+        // A finalized fake VE cannot be recovered.
+        ve.finalize();
+        assert_eq!(ve.state(), DeviceState::Finalized);
+        assert!(ve.recover().is_err());
+    }
+}
@@ -88,16 +88,32 @@ impl CompilationStats {
     }
 }
 
+/// How a parameter of an offloaded run was sent to the device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferDecision {
+    /// Index of the parameter.
+    pub param: usize,
+    /// Size of the contents of the parameter in bytes.
+    pub bytes: usize,
+    /// Whether the parameter was packed into the buffer of serialized parameters.
+    pub serialized: bool,
+    /// Whether the decision was based on measured transfers instead of the size threshold.
+    pub measured: bool,
+}
+
 /// Tracks various run-time statistics throughout the executor.
 pub struct RunStats {
     /// Running times for various Weld run-time components.
     pub run_times: Vec<(String, Duration)>,
+    /// How parameters were sent to an offload device.
+    pub transfer_decisions: Vec<TransferDecision>,
 }
 
 impl RunStats {
     pub fn new() -> RunStats {
         RunStats {
             run_times: Vec::new(),
+            transfer_decisions: Vec::new(),
         }
     }
 
@@ -112,6 +128,19 @@ impl RunStats {
         }
         result.push_str(&format!("\t\x1b[0;32mWeld Run Total\x1b[0m {} ms\n", CompilationStats::format_time(&total)));
 
+        if !self.transfer_decisions.is_empty() {
+            result.push_str("Weld Transfers:\n");
+            for decision in self.transfer_decisions.iter() {
+                result.push_str(&format!(
+                    "\tparam {}: {} bytes {} ({})\n",
+                    decision.param,
+                    decision.bytes,
+                    if decision.serialized { "serialized" } else { "transferred separately" },
+                    if decision.measured { "measured" } else { "threshold" },
+                ));
+            }
+        }
+
         result
     }
 }
@@ -1,12 +1,19 @@
 //! Utility functions for veoffload
 
+use std::collections::VecDeque;
 use std::ptr;
 use std::os::raw::c_char;
 use std::ffi::CString;
 use libc::{c_int, c_void};
 
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnv::FnvHashMap;
+
 use crate::WeldResult;
-use crate::util::offload_ve::*;
+use crate::conf::Backend;
 
 pub enum VeoProcHandle {}
 pub enum VeoThrContext {}
@@ -17,6 +24,20 @@ pub type VeoThrContextRef = *mut VeoThrContext;
 pub type VeoArgsRef = *mut VeoArgs;
 pub type SymHandle = u64;
 pub type CallHandle = u64;
+/// Handle of an asynchronous memory transfer.
+pub type TransferHandle = u64;
+
+/// Request ID VEO returns when it cannot submit a request.
+pub const VEO_REQUEST_ID_INVALID: u64 = !0;
+
+/// Maximum number of transfers a `TransferQueue` keeps in flight.
+///
+/// With two transfers in flight, the next chunk is already queued on the device while the
+/// current one is copied.
+pub const TRANSFER_QUEUE_DEPTH: usize = 2;
+
+/// Size of the chunks a `TransferQueue` splits large transfers into.
+pub const TRANSFER_CHUNK_SIZE: usize = 8 << 20;
 
 #[derive(Debug)]
 #[repr(C)]
@@ -76,6 +97,10 @@ extern "C" {
                                 Id: CallHandle,
                                 RetVal: *mut u64)
                                 -> VeoCommandState;
+    pub fn veo_call_peek_result(Ctx: VeoThrContextRef,
+                                Id: CallHandle,
+                                RetVal: *mut u64)
+                                -> VeoCommandState;
     pub fn veo_alloc_mem(PH: VeoProcHandleRef, Addr: *mut u64,
                          Size: usize) -> c_int;
     pub fn veo_free_mem(PH: VeoProcHandleRef, Addr: u64) -> c_int;
@@ -91,30 +116,335 @@ extern "C" {
 
 
 lazy_static! {
-    /// global context of VE offload
-    pub static ref VEO_GLOBAL: VEOffload = VEOffload::default();
-    /// pointer to global context
-    pub static ref VEO_GLOBAL_PTR: u64 = &(*VEO_GLOBAL) as *const VEOffload as u64 ;
+    /// VE offload contexts, one per VE node.
+    ///
+    /// Contexts are never freed, so pointers to them stay valid for the lifetime of
+    /// the process.
+    static ref VEO_NODES: Mutex<FnvHashMap<i32, u64>> = Mutex::new(FnvHashMap::default());
+}
+
+/// Returns the VE offload context of `node`, creating it if necessary.
+///
+/// The returned context is not initialized yet.
+pub unsafe fn get_veo_ptr(node: i32) -> *mut VEOffload {
+    let mut nodes = VEO_NODES.lock().unwrap();
+    let ptr = nodes.entry(node).or_insert_with(|| {
+        let veo = Box::new(VEOffload::new(node));
+        Box::into_raw(veo) as u64
+    });
+    *ptr as *mut VEOffload
+}
+
+/// Returns the offload transport used by `backend`.
+///
+/// The transport is initialized if it is not ready yet.
+pub unsafe fn get_offload(backend: Backend, node: i32) -> WeldResult<*mut dyn OffloadTransport> {
+    use crate::util::fakeve::*;
+    match backend {
+        Backend::CVE => {
+            let veo = get_veo_ptr(node);
+            initialize_veo(veo as u64, None, None)?;
+            Ok(veo)
+        }
+        Backend::CFakeVE => Ok(initialize_fake_ve(node)?),
+        _ => weld_err!("backend {:?} does not offload", backend),
+    }
+}
+
+/// Returns the offload transports of `nodes`, in the same order.
+pub unsafe fn get_offload_pool(backend: Backend, nodes: &[i32])
+    -> WeldResult<Vec<*mut dyn OffloadTransport>> {
+    nodes.iter().map(|node| get_offload(backend, *node)).collect()
+}
+
+/// Lifecycle of the process on an offload device.
+///
+/// ```text
+/// Uninitialized -> Initializing -> Ready -> Finalized
+///                       ^   |        |
+///                       |   v        v
+///                       +-- Failed <-+
+/// ```
+///
+/// A failed device is re-created by `OffloadTransport::recover`. Memory of the failed process
+/// is lost. A device whose process does not finish a call in time is marked failed as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceState {
+    /// No process was created yet.
+    Uninitialized,
+    /// The process is being created.
+    Initializing,
+    /// The process accepts requests.
+    Ready,
+    /// Creating the process failed, or the process crashed.
+    Failed,
+    /// The process was destroyed for good.
+    Finalized,
+}
+
+/// Operations the C backend needs to run a module on an offload device.
+///
+/// Addresses returned by `alloc_mem` and passed to `read_mem`/`write_mem` live in the
+/// device's address space and must not be dereferenced on the host.
+pub trait OffloadTransport {
+    /// Returns the lifecycle state of the device.
+    fn state(&self) -> DeviceState;
+    /// Returns how many times the process on the device was created.
+    ///
+    /// Device memory does not survive the re-creation of the process, so values in device
+    /// memory are only valid while the generation that created them lasts.
+    fn generation(&self) -> u64;
+    /// Creates the process on the device unless it is ready, replacing a failed one.
+    unsafe fn recover(&mut self) -> WeldResult<()>;
+    /// Loads a shared object onto the device and returns a handle to it.
+    unsafe fn load_library(&mut self, libname: &str) -> WeldResult<VeoHandle>;
+    /// Calls `symname` in a loaded library with `args` and waits for its return value.
+    ///
+    /// If the call does not finish within `timeout`, the process is considered hung: the device
+    /// is marked failed and a `DeviceError` is returned.
+    unsafe fn call_and_wait(&mut self, libhdl: VeoHandle, symname: &str, args: &[u64],
+                            timeout: Option<Duration>) -> WeldResult<u64>;
+    /// Allocates `size` bytes of device memory.
+    unsafe fn alloc_mem(&mut self, size: usize) -> WeldResult<u64>;
+    /// Frees device memory allocated with `alloc_mem`.
+    unsafe fn free_mem(&mut self, addr: u64) -> WeldResult<()>;
+    /// Copies `len` bytes from device address `src` to host pointer `dst`.
+    unsafe fn read_mem(&self, src: u64, dst: *mut c_void, len: usize) -> WeldResult<()>;
+    /// Copies `len` bytes from host pointer `src` to device address `dst`.
+    unsafe fn write_mem(&self, src: *const c_void, dst: u64, len: usize) -> WeldResult<()>;
+    /// Starts copying `len` bytes from device address `src` to host pointer `dst`.
+    ///
+    /// `dst` must stay valid until the transfer is waited for with `wait_transfer`.
+    unsafe fn async_read_mem(&self, src: u64, dst: *mut c_void, len: usize)
+        -> WeldResult<TransferHandle>;
+    /// Starts copying `len` bytes from host pointer `src` to device address `dst`.
+    ///
+    /// `src` must stay valid until the transfer is waited for with `wait_transfer`.
+    unsafe fn async_write_mem(&self, src: *const c_void, dst: u64, len: usize)
+        -> WeldResult<TransferHandle>;
+    /// Waits until the transfer `handle` has finished.
+    unsafe fn wait_transfer(&self, handle: TransferHandle) -> WeldResult<()>;
+}
+
+/// Makes sure that `offload` is ready to accept requests.
+///
+/// A failed device is re-created. Returns a `DeviceError` if it cannot be re-created.
+pub unsafe fn ensure_ready(offload: *mut dyn OffloadTransport) -> WeldResult<()> {
+    match (*offload).state() {
+        DeviceState::Ready => Ok(()),
+        DeviceState::Finalized => device_err!("offload device was finalized"),
+        _ => (*offload).recover(),
+    }
+}
+
+/// A run created on a device by the `run_init` entry of a loaded module.
+///
+/// Dropping the run frees it and all memory it allocated on the device with the `run_free`
+/// entry of the same module. Nothing is freed if the process that created the run is gone,
+/// since its memory went with it.
+#[derive(Debug)]
+pub struct DeviceRun {
+    /// The device the run lives on.
+    pub device: *mut dyn OffloadTransport,
+    /// The generation of the device process that created the run.
+    pub generation: u64,
+    /// The module whose entries created the run.
+    pub libhdl: VeoHandle,
+    /// The handle of the run on the device.
+    pub run: i64,
+    /// How long freeing the run may take.
+    pub timeout: Option<Duration>,
+}
+
+impl DeviceRun {
+    /// Returns whether the run is still alive, i.e. its process was not re-created.
+    pub unsafe fn is_alive(&self) -> bool {
+        (*self.device).state() == DeviceState::Ready &&
+            (*self.device).generation() == self.generation
+    }
 }
-use std::sync::{Once, ONCE_INIT};
-static ONCE: Once = ONCE_INIT;
 
-pub unsafe fn get_global_veo_ptr() -> *mut VEOffload {
-    return *VEO_GLOBAL_PTR as *mut VEOffload;
+impl Drop for DeviceRun {
+    fn drop(&mut self) {
+        unsafe {
+            if self.run == 0 || !self.is_alive() {
+                return;
+            }
+            let freed = (*self.device).call_and_wait(
+                self.libhdl, "run_free", &[self.run as u64], self.timeout);
+            if let Err(err) = freed {
+                warn!("Could not free run {:#x} on the device: {:?}", self.run, err.message());
+            }
+        }
+    }
+}
+
+/// Pipelines memory transfers between the host and a device.
+///
+/// Transfers are split into chunks of `TRANSFER_CHUNK_SIZE` bytes, and up to
+/// `TRANSFER_QUEUE_DEPTH` chunks are in flight at once, so the host can prepare the next
+/// transfer while earlier ones are still running. Host buffers passed to the queue must stay
+/// valid until `wait_all` returns; dropping the queue waits for the remaining transfers.
+pub struct TransferQueue<'a> {
+    offload: &'a dyn OffloadTransport,
+    pending: VecDeque<TransferHandle>,
+}
+
+impl<'a> TransferQueue<'a> {
+    pub fn new(offload: &'a dyn OffloadTransport) -> TransferQueue<'a> {
+        TransferQueue {
+            offload,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Starts copying `len` bytes from device address `src` to host pointer `dst`.
+    pub unsafe fn read(&mut self, src: u64, dst: *mut c_void, len: usize) -> WeldResult<()> {
+        let mut offset = 0;
+        while offset < len {
+            let chunk = TRANSFER_CHUNK_SIZE.min(len - offset);
+            self.reserve()?;
+            let handle = self.offload.async_read_mem(
+                src + offset as u64, (dst as *mut u8).add(offset) as *mut c_void, chunk)?;
+            self.pending.push_back(handle);
+            offset += chunk;
+        }
+        Ok(())
+    }
+
+    /// Starts copying `len` bytes from host pointer `src` to device address `dst`.
+    pub unsafe fn write(&mut self, src: *const c_void, dst: u64, len: usize) -> WeldResult<()> {
+        let mut offset = 0;
+        while offset < len {
+            let chunk = TRANSFER_CHUNK_SIZE.min(len - offset);
+            self.reserve()?;
+            let handle = self.offload.async_write_mem(
+                (src as *const u8).add(offset) as *const c_void, dst + offset as u64, chunk)?;
+            self.pending.push_back(handle);
+            offset += chunk;
+        }
+        Ok(())
+    }
+
+    /// Waits until all transfers have finished.
+    ///
+    /// Returns the first error, after waiting for all transfers.
+    pub unsafe fn wait_all(&mut self) -> WeldResult<()> {
+        let mut result = Ok(());
+        while let Some(handle) = self.pending.pop_front() {
+            let waited = self.offload.wait_transfer(handle);
+            if result.is_ok() {
+                result = waited;
+            }
+        }
+        result
+    }
+
+    /// Waits for the oldest transfers until another transfer can be started.
+    unsafe fn reserve(&mut self) -> WeldResult<()> {
+        while self.pending.len() >= TRANSFER_QUEUE_DEPTH {
+            let handle = self.pending.pop_front().unwrap();
+            self.offload.wait_transfer(handle)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for TransferQueue<'a> {
+    fn drop(&mut self) {
+        // Host buffers may be freed after the queue, so never leave transfers running.
+        let _ = unsafe { self.wait_all() };
+    }
+}
+
+/// Estimates the cost of transfers to a device from measurements.
+///
+/// Transfers are modelled to take `latency` each plus `bytes / bandwidth`, fit with least squares
+/// to the measured groups of transfers. Packing data into the buffer of another transfer instead
+/// costs a copy on the host, and saves the latency of a transfer of its own; the time to move the
+/// data itself is the same either way.
+#[derive(Clone, Debug, Default)]
+pub struct TransferModel {
+    // Sums over measurements of `transfers` (n), `bytes` (b) and `seconds` (t) for the normal
+    // equations of the fit.
+    nn: f64,
+    nb: f64,
+    bb: f64,
+    nt: f64,
+    bt: f64,
+    /// Bytes copied on the host.
+    copied: f64,
+    /// Time spent copying on the host, in seconds.
+    copy_seconds: f64,
+}
+
+impl TransferModel {
+    /// Records that `transfers` transfers of `bytes` bytes in total took `seconds`.
+    pub fn record_transfers(&mut self, transfers: usize, bytes: usize, seconds: f64) {
+        let (n, b) = (transfers as f64, bytes as f64);
+        self.nn += n * n;
+        self.nb += n * b;
+        self.bb += b * b;
+        self.nt += n * seconds;
+        self.bt += b * seconds;
+    }
+
+    /// Records that copying `bytes` bytes on the host took `seconds`.
+    pub fn record_copy(&mut self, bytes: usize, seconds: f64) {
+        self.copied += bytes as f64;
+        self.copy_seconds += seconds;
+    }
+
+    /// Returns the latency of a transfer in seconds and the bandwidth in bytes per second, or
+    /// `None` if the measurements do not determine them.
+    pub fn transfer_cost(&self) -> Option<(f64, f64)> {
+        let det = self.nn * self.bb - self.nb * self.nb;
+        if det <= 1e-9 * self.nn * self.bb {
+            return None;
+        }
+        let latency = (self.nt * self.bb - self.bt * self.nb) / det;
+        let seconds_per_byte = (self.nn * self.bt - self.nb * self.nt) / det;
+        if latency <= 0.0 || seconds_per_byte <= 0.0 {
+            return None;
+        }
+        Some((latency, 1.0 / seconds_per_byte))
+    }
+
+    /// Returns the bandwidth of copies on the host in bytes per second.
+    pub fn copy_bandwidth(&self) -> Option<f64> {
+        if self.copied > 0.0 && self.copy_seconds > 0.0 {
+            Some(self.copied / self.copy_seconds)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether packing `bytes` bytes into the buffer of another transfer is cheaper than
+    /// sending them with a transfer of their own, or `None` if too little was measured.
+    pub fn should_pack(&self, bytes: usize) -> Option<bool> {
+        let (latency, _) = self.transfer_cost()?;
+        let copy_bandwidth = self.copy_bandwidth()?;
+        Some((bytes as f64) / copy_bandwidth < latency)
+    }
 }
 
 /// Initialize VEO
+///
+/// A failed process is re-created.
 pub unsafe fn initialize_veo(veo_ptr: u64, veorun: Option<String>,
-                             libs: Option<Vec<String>>) {
+                             libs: Option<Vec<String>>) -> WeldResult<()> {
     let veo = veo_ptr as *mut VEOffload;
-    if !(*veo).ready {
-        (*veo).initialize(veorun).unwrap();
+    // Serialize initialization of the same node from several threads.
+    let _guard = VEO_NODES.lock().unwrap();
+    if (*veo).state != DeviceState::Ready {
+        (*veo).restart(veorun)?;
         if let Some(libnames) = libs {
             for libname in libnames {
-                (*veo).load_library(libname.as_str()).unwrap();
+                (*veo).load_library(libname.as_str())?;
             }
         }
     }
+    Ok(())
 }
 
 /*
@@ -123,7 +453,7 @@ pub unsafe fn initialize_veo_bg(veo_ptr: u64, veorun: Option<String>,
                                 libs: Option<Vec<String>>)
                                 -> Option<thread::JoinHandle<()>> {
     let veo = veo_ptr as *mut VEOffload;
-    if (*veo).ready { return None }
+    if (*veo).state == DeviceState::Ready { return None }
 
     let handle = thread::spawn(move || {
         let veo = veo_ptr as *mut VEOffload;
@@ -138,40 +468,47 @@ pub unsafe fn initialize_veo_bg(veo_ptr: u64, veorun: Option<String>,
 }
 */
 
-pub extern "C" fn initialize_veo_global(
-    veorun: Option<String>, libs: Option<Vec<String>>) {
-    unsafe {
-        ONCE.call_once(
-            || initialize_veo(*VEO_GLOBAL_PTR, veorun, libs)
-        );
-    }
-}
-
+/// Finalizes the VE offload contexts of all nodes.
 pub extern "C" fn finalize_veo_global() {
     unsafe {
-        let veo = *VEO_GLOBAL_PTR as *mut VEOffload;
-        (*veo).finalize();
+        let nodes = VEO_NODES.lock().unwrap();
+        for ptr in nodes.values() {
+            let veo = *ptr as *mut VEOffload;
+            if let Err(err) = (*veo).finalize() {
+                warn!("Could not finalize VE node {}: {:?}", (*veo).node, err.message());
+            }
+        }
     }
 }
 
 
 pub struct VEOffload {
+    pub node: i32,
     pub proc: VeoProcHandleRef,
     pub ctx: VeoThrContextRef,
     pub libs: fnv::FnvHashMap<String, VeoHandle>,
-    pub ready: bool,
+    pub state: DeviceState,
+    /// Number of times the process was created.
+    pub generation: u64,
 //    pub udma_peer: Option<c_int>
 }
 
 impl VEOffload {
 
+    pub fn new(node: i32) -> Self {
+        Self {
+            node,
+            ..Self::default()
+        }
+    }
+
     pub unsafe fn load_library<T: AsRef<str>>(
         &mut self, libname: T
     ) -> WeldResult<VeoHandle> {
         let c_libname = CString::new(libname.as_ref()).unwrap();
         let handle = veo_load_library(self.proc, c_libname.as_ptr());
         if handle == 0 {
-            return weld_err!("cannot load library (name:{})", libname.as_ref());
+            return device_err!("cannot load library (name:{})", libname.as_ref());
         }
 
         use std::path::Path;
@@ -186,7 +523,7 @@ impl VEOffload {
         let c_symname = CString::new(symname.as_ref()).unwrap();
         let sym = veo_get_sym(self.proc, libhdl, c_symname.as_ptr());
         if sym == 0 {
-            return weld_err!("cannot find function (name:{})", symname.as_ref());
+            return device_err!("cannot find function (name:{})", symname.as_ref());
         }
         Ok(sym)
     }
@@ -204,25 +541,55 @@ impl VEOffload {
     ) -> WeldResult<CallHandle> {
         let c_symname = CString::new(symname.as_ref()).unwrap();
         let call = veo_call_async_by_name(self.ctx, libhdl, c_symname.as_ptr(), args);
-        // let VeoRequestIdInvalid = !(0_u64) as CallHandle;
+        if call == VEO_REQUEST_ID_INVALID {
+            return device_err!("cannot call function (name:{})", symname.as_ref());
+        }
         Ok(call)
     }
 
-    pub unsafe fn call_wait_result(&mut self, call: CallHandle) -> WeldResult<u64> {
+    /// Waits for the result of `call`, for at most `timeout`.
+    pub unsafe fn call_wait_result(&mut self, call: CallHandle, timeout: Option<Duration>)
+        -> WeldResult<u64> {
         let mut retp: u64 = 0;
-        let state = veo_call_wait_result(self.ctx, call, &mut retp);
-        match state {
-            VeoCommandState::VeoCommandOk => {}
-            _ => { return weld_err!("wait result (call handle:{}) failed", call); }
+        let state = match timeout {
+            None => veo_call_wait_result(self.ctx, call, &mut retp),
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                loop {
+                    match veo_call_peek_result(self.ctx, call, &mut retp) {
+                        VeoCommandState::VeoCommandUnfinished => (),
+                        state => break state,
+                    }
+                    if Instant::now() >= deadline {
+                        // The process is hung, so it must be re-created.
+                        self.state = DeviceState::Failed;
+                        return device_err!(
+                            "call (call handle:{}) on VE node {} did not finish in {:?}",
+                            call, self.node, timeout);
+                    }
+                    thread::sleep(Duration::from_micros(100));
+                }
+            }
         };
-        Ok(retp)
+        match state {
+            VeoCommandState::VeoCommandOk => Ok(retp),
+            VeoCommandState::VeoCommandUnfinished => {
+                device_err!("wait result (call handle:{}) did not finish", call)
+            }
+            _ => {
+                // The process raised an exception or died, so it must be re-created.
+                self.state = DeviceState::Failed;
+                device_err!("call (call handle:{}) failed on VE node {}: {:?}",
+                            call, self.node, state)
+            }
+        }
     }
 
     pub unsafe fn alloc_mem(&mut self, size: usize) -> WeldResult<u64> {
         let mut addr: u64 = 0;
         let err = veo_alloc_mem(self.proc, &mut addr as *mut u64, size);
         if err != 0 {
-            return weld_err!("alloc memory");
+            return device_err!("alloc memory");
         } else {
             Ok(addr)
         }
@@ -231,7 +598,7 @@ impl VEOffload {
     pub unsafe fn free_mem(&mut self, addr: u64) -> WeldResult<()> {
         let err = veo_free_mem(self.proc, addr);
         if err != 0 {
-            return weld_err!("free memory");
+            return device_err!("free memory");
         } else {
             Ok(())
         }
@@ -240,7 +607,7 @@ impl VEOffload {
     pub unsafe fn read_mem(&self, src: u64, dst: *mut c_void, len: usize) -> WeldResult<()> {
         let err = veo_read_mem(self.proc, dst, src as u64, len);
         if err != 0 {
-            return weld_err!("veo read mem: failed");
+            return device_err!("veo read mem: failed");
         }
         Ok(())
     }
@@ -248,11 +615,39 @@ impl VEOffload {
     pub unsafe fn write_mem(&self, src: *const c_void, dst: u64, len: usize) -> WeldResult<()> {
         let err = veo_write_mem(self.proc, dst as u64, src, len);
         if err != 0 {
-            return weld_err!("veo write mem: failed");
+            return device_err!("veo write mem: failed");
         }
         Ok(())
     }
 
+    pub unsafe fn async_read_mem(&self, src: u64, dst: *mut c_void, len: usize)
+        -> WeldResult<TransferHandle> {
+        let request = veo_async_read_mem(self.ctx, dst, src, len);
+        if request == VEO_REQUEST_ID_INVALID {
+            return device_err!("veo async read mem: failed");
+        }
+        Ok(request)
+    }
+
+    pub unsafe fn async_write_mem(&self, src: *const c_void, dst: u64, len: usize)
+        -> WeldResult<TransferHandle> {
+        let request = veo_async_write_mem(self.ctx, dst, src, len);
+        if request == VEO_REQUEST_ID_INVALID {
+            return device_err!("veo async write mem: failed");
+        }
+        Ok(request)
+    }
+
+    pub unsafe fn wait_transfer(&self, handle: TransferHandle) -> WeldResult<()> {
+        // Memory transfers return 0 on success.
+        let mut retp: u64 = 0;
+        let state = veo_call_wait_result(self.ctx, handle, &mut retp);
+        match state {
+            VeoCommandState::VeoCommandOk if retp == 0 => Ok(()),
+            _ => device_err!("wait transfer (request:{}) failed", handle),
+        }
+    }
+
     pub unsafe fn args_alloc() -> VeoArgsRef {
         veo_args_alloc()
     }
@@ -264,16 +659,54 @@ impl VEOffload {
     pub unsafe fn args_free(args: VeoArgsRef) {
         veo_args_free(args);
     }
+
+    /// Creates the process, destroying the previous one if it failed.
+    unsafe fn restart(&mut self, veorun: Option<String>) -> WeldResult<()> {
+        match self.state {
+            DeviceState::Ready => Ok(()),
+            DeviceState::Finalized => device_err!("VE node {} was finalized", self.node),
+            DeviceState::Failed => {
+                // The failed process may be gone already, so errors are only logged.
+                if let Err(err) = self.release() {
+                    warn!("Could not destroy the failed process on VE node {}: {:?}",
+                          self.node, err.message());
+                }
+                self.initialize(veorun)
+            }
+            DeviceState::Uninitialized | DeviceState::Initializing => self.initialize(veorun),
+        }
+    }
+
+    /// Closes the context and destroys the process, if they exist.
+    unsafe fn release(&mut self) -> WeldResult<()> {
+        let mut result = Ok(());
+        if !self.ctx.is_null() {
+            if veo_context_close(self.ctx) != 0 {
+                result = device_err!("cannot close veo context on VE node {}", self.node);
+            }
+            self.ctx = ptr::null_mut();
+        }
+        if !self.proc.is_null() {
+            if veo_proc_destroy(self.proc) != 0 && result.is_ok() {
+                result = device_err!("cannot destroy veo proc on VE node {}", self.node);
+            }
+            self.proc = ptr::null_mut();
+        }
+        self.libs.clear();
+        result
+    }
 }
 
 
 impl Default for VEOffload {
     fn default() -> Self {
         Self {
+            node: 0,
             proc: ptr::null::<VeoProcHandle>() as VeoProcHandleRef,
             ctx: ptr::null::<VeoThrContext>() as VeoThrContextRef,
             libs: fnv::FnvHashMap::default(),
-            ready: false,
+            state: DeviceState::Uninitialized,
+            generation: 0,
 //            udma_peer: None,
         }
     }
@@ -281,18 +714,86 @@ impl Default for VEOffload {
 
 unsafe impl Sync for VEOffload {}  // needed to have VEO static
 
+impl OffloadTransport for VEOffload {
+    fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    unsafe fn recover(&mut self) -> WeldResult<()> {
+        // Serialize initialization of the same node from several threads.
+        let _guard = VEO_NODES.lock().unwrap();
+        self.restart(None)
+    }
+
+    unsafe fn load_library(&mut self, libname: &str) -> WeldResult<VeoHandle> {
+        VEOffload::load_library(self, libname)
+    }
+
+    unsafe fn call_and_wait(&mut self, libhdl: VeoHandle, symname: &str, args: &[u64],
+                            timeout: Option<Duration>) -> WeldResult<u64> {
+        let veo_args = VEOffload::args_alloc();
+        if veo_args.is_null() {
+            return device_err!("cannot allocate veo args");
+        }
+        let result = args
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, arg)| VEOffload::args_set(veo_args, i as isize, *arg))
+            .and_then(|_| {
+                VEOffloadHelper::call_and_wait(self, libhdl, symname, veo_args, timeout)
+            });
+        VEOffload::args_free(veo_args);
+        result
+    }
+
+    unsafe fn alloc_mem(&mut self, size: usize) -> WeldResult<u64> {
+        VEOffload::alloc_mem(self, size)
+    }
+
+    unsafe fn free_mem(&mut self, addr: u64) -> WeldResult<()> {
+        VEOffload::free_mem(self, addr)
+    }
+
+    unsafe fn read_mem(&self, src: u64, dst: *mut c_void, len: usize) -> WeldResult<()> {
+        VEOffload::read_mem(self, src, dst, len)
+    }
+
+    unsafe fn write_mem(&self, src: *const c_void, dst: u64, len: usize) -> WeldResult<()> {
+        VEOffload::write_mem(self, src, dst, len)
+    }
+
+    unsafe fn async_read_mem(&self, src: u64, dst: *mut c_void, len: usize)
+        -> WeldResult<TransferHandle> {
+        VEOffload::async_read_mem(self, src, dst, len)
+    }
+
+    unsafe fn async_write_mem(&self, src: *const c_void, dst: u64, len: usize)
+        -> WeldResult<TransferHandle> {
+        VEOffload::async_write_mem(self, src, dst, len)
+    }
+
+    unsafe fn wait_transfer(&self, handle: TransferHandle) -> WeldResult<()> {
+        VEOffload::wait_transfer(self, handle)
+    }
+}
+
 
 pub trait VEOffloadHelper {
     unsafe fn initialize<T: AsRef<str>>(&mut self, veorun_path: Option<T>) -> WeldResult<()>;
-    unsafe fn finalize(&mut self);
-    unsafe fn call_and_wait<T: AsRef<str>>(&mut self, libhdl: VeoHandle, symname: T, args: VeoArgsRef) -> WeldResult<u64>;
+    unsafe fn finalize(&mut self) -> WeldResult<()>;
+    unsafe fn call_and_wait<T: AsRef<str>>(&mut self, libhdl: VeoHandle, symname: T, args: VeoArgsRef, timeout: Option<Duration>) -> WeldResult<u64>;
     fn get_libhdl(&self, filename: &str) -> Option<VeoHandle>;
     fn unwrap_ready(&self) -> WeldResult<()>;
 }
 
 impl VEOffloadHelper for VEOffload {
     unsafe fn initialize<T: AsRef<str>>(&mut self, veorun_path: Option<T>) -> WeldResult<()> {
-        let node = get_ve_node_number();
+        let node = self.node as i64;
+        self.state = DeviceState::Initializing;
 
         if let Some(vp) = veorun_path {
             let c_vp = CString::new(vp.as_ref()).unwrap();
@@ -301,38 +802,38 @@ impl VEOffloadHelper for VEOffload {
             self.proc = veo_proc_create(node);
         }
         if self.proc.is_null() {
-            return weld_err!("cannot create proc");
+            self.state = DeviceState::Failed;
+            return device_err!("cannot create proc on VE node {}", node);
         }
 
         self.ctx = veo_context_open(self.proc);
         if self.ctx.is_null() {
             let _err = veo_proc_destroy(self.proc);
-            return weld_err!("cannot create veo context");
+            self.proc = ptr::null_mut();
+            self.state = DeviceState::Failed;
+            return device_err!("cannot create veo context on VE node {}", node);
         }
 
-        self.ready = true;
+        self.generation += 1;
+        self.state = DeviceState::Ready;
         Ok(())
     }
 
-    unsafe fn finalize(&mut self) {
-        if !self.ready { return }
-
-        let err = veo_context_close(self.ctx);
-        if err != 0 {
-            panic!("cannot close veo context");
-        }
-
-        let err = veo_proc_destroy(self.proc);
-        if err != 0 {
-            panic!("cannot destrocy veo proc");
+    unsafe fn finalize(&mut self) -> WeldResult<()> {
+        match self.state {
+            DeviceState::Ready | DeviceState::Failed => {
+                self.state = DeviceState::Finalized;
+                self.release()
+            }
+            _ => Ok(()),
         }
     }
 
     unsafe fn call_and_wait<T: AsRef<str>>(
-        &mut self, libhdl: VeoHandle, symname: T, args: VeoArgsRef
+        &mut self, libhdl: VeoHandle, symname: T, args: VeoArgsRef, timeout: Option<Duration>
     ) -> WeldResult<u64> {
         let call = self.call_async_by_name(libhdl, symname, args)?;
-        self.call_wait_result(call)
+        self.call_wait_result(call, timeout)
     }
 
     fn get_libhdl(&self, filename: &str) -> Option<VeoHandle> {
@@ -344,53 +845,77 @@ impl VEOffloadHelper for VEOffload {
     }
 
     fn unwrap_ready(&self) -> WeldResult<()> {
-        if self.ready {
+        if self.state == DeviceState::Ready {
             Ok(())
         } else {
-            return weld_err!("veo is not initialized");
+            device_err!("veo is not ready ({:?})", self.state)
         }
     }
 }
 
 /// argument for VEO call
 pub trait VEOffloadArgument<V> {
-    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: V);
+    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: V) -> WeldResult<()>;
 }
 
 impl VEOffloadArgument<i64> for VEOffload {
-    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: i64) {
+    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: i64) -> WeldResult<()> {
         let ret = veo_args_set_i64(args, argnum as c_int, val as i64);
-        if ret < -1 {
-            panic!("cannot set veo args");
+        if ret < 0 {
+            return device_err!("cannot set veo args (argnum:{})", argnum);
         }
+        Ok(())
     }
 }
 
 impl VEOffloadArgument<u64> for VEOffload {
-    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: u64) {
+    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: u64) -> WeldResult<()> {
         let ret = veo_args_set_u64(args, argnum as c_int, val as u64);
-        if ret < -1 {
-            panic!("cannot set veo args");
+        if ret < 0 {
+            return device_err!("cannot set veo args (argnum:{})", argnum);
         }
+        Ok(())
     }
 }
 
 impl VEOffloadArgument<i32> for VEOffload {
-    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: i32) {
+    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: i32) -> WeldResult<()> {
         let ret = veo_args_set_i32(args, argnum as c_int, val as i32);
-        if ret < -1 {
-            panic!("cannot set veo args");
+        if ret < 0 {
+            return device_err!("cannot set veo args (argnum:{})", argnum);
         }
+        Ok(())
     }
 }
 
 impl VEOffloadArgument<u32> for VEOffload {
-    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: u32) {
+    unsafe fn args_set(args: VeoArgsRef, argnum: isize, val: u32) -> WeldResult<()> {
         let ret = veo_args_set_u32(args, argnum as c_int, val as u32);
-        if ret < -1 {
-            panic!("cannot set veo args");
+        if ret < 0 {
+            return device_err!("cannot set veo args (argnum:{})", argnum);
         }
+        Ok(())
     }
 }
 
 
+
+#[test]
+fn transfer_model_fit() {
+    let mut model = TransferModel::default();
+    assert_eq!(model.should_pack(1), None);
+
+    // 10us per transfer and 1 GB/s.
+    let cost = |transfers: usize, bytes: usize| transfers as f64 * 10e-6 + bytes as f64 * 1e-9;
+    model.record_transfers(1, 24, cost(1, 24));
+    assert_eq!(model.transfer_cost(), None);
+    model.record_transfers(3, 1 << 20, cost(3, 1 << 20));
+    let (latency, bandwidth) = model.transfer_cost().unwrap();
+    assert!((latency - 10e-6).abs() < 1e-9);
+    assert!((bandwidth - 1e9).abs() < 1e3);
+
+    // Copying 4 KB on the host takes 1us, copying 1 MB takes 250us.
+    model.record_copy(1 << 20, 250e-6);
+    assert_eq!(model.should_pack(4 << 10), Some(true));
+    assert_eq!(model.should_pack(1 << 20), Some(false));
+}
@@ -0,0 +1,190 @@
+//! Tests for running the C backend on the host.
+
+use weld::data::*;
+use weld::*;
+
+mod common;
+use crate::common::*;
+
+fn host_conf() -> WeldConf {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "c-host");
+    conf
+}
+
+#[test]
+fn host_scalar_add() {
+    #[allow(dead_code)]
+    struct Args {
+        a: i32,
+        b: i64,
+    }
+
+    let code = "|a:i32, b:i64| i64(a) + b";
+    let ref conf = host_conf();
+
+    let ref input_data = Args { a: 40, b: 2 };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i64;
+    let result = unsafe { *data };
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn host_for_merger_loop() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        a: i32,
+    }
+
+    let code = "|x:vec[i32], a:i32| result(for(x, merger[i32,+], |b,i,e| merge(b, e+a)))";
+    let ref conf = host_conf();
+
+    let input_vec = vec![1, 2, 3, 4, 5];
+    let ref input_data = Args {
+        x: WeldVec::from(&input_vec),
+        a: 1,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i32;
+    let result = unsafe { *data };
+    assert_eq!(result, 20);
+}
+
+#[test]
+fn host_for_appender_loop() {
+    let code = "|x:vec[i32]| result(for(x, appender[i32], |b,i,e| merge(b, e*2)))";
+    let ref conf = host_conf();
+
+    let input_vec = vec![1, 2, 3, 4, 5];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i32>;
+    let result = unsafe { (*data).clone() };
+    assert_eq!(result.len, input_vec.len() as i64);
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, input_vec[i as usize] * 2);
+    }
+}
+
+#[test]
+fn host_runs_release_memory() {
+    use std::fs;
+
+    /// Returns the resident set size of this process in kB.
+    fn resident_kb() -> u64 {
+        let status = fs::read_to_string("/proc/self/status").unwrap();
+        let line = status.lines().find(|l| l.starts_with("VmRSS:")).unwrap();
+        line.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    let code = "|x:vec[i64]| result(for(x, appender[i64], |b,i,e| merge(b, e + 1L)))";
+    let ref conf = host_conf();
+    let module = WeldModule::compile(code, conf).unwrap();
+
+    // Each run allocates about 8MB for its result.
+    let input_vec: Vec<i64> = (0..1_000_000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let input_value = WeldValue::new_from_data(input_data as *const _ as Data);
+
+    let mut run = || {
+        let ref mut context = WeldContext::new(conf).unwrap();
+        let ret_value = unsafe { module.run(context, &input_value).unwrap() };
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<i64>)).clone() };
+        assert_eq!(result.len, input_vec.len() as i64);
+    };
+
+    run();
+    let before = resident_kb();
+    for _ in 0..50 {
+        run();
+    }
+    let growth = resident_kb().saturating_sub(before);
+    assert!(growth < 64 * 1024, "memory grew by {} kB", growth);
+}
+
+#[test]
+fn host_error_message() {
+    let code = "|x:vec[i64]|
+                let d = result(for(x, dictmerger[i64,i64,+], |b,i,e| b));
+                lookup(d, 1L)";
+    let ref conf = host_conf();
+    let input_vec: Vec<i64> = vec![1, 2, 3];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let err_value = compile_and_run_error(code, conf, input_data);
+    assert_eq!(err_value.code(), WeldRuntimeErrno::KeyNotFoundError);
+    assert_eq!(
+        err_value.message().to_str().unwrap(),
+        "Weld program failed with error KeyNotFoundError: key not found"
+    );
+}
+
+#[test]
+fn host_and_llvm_modules_in_one_process() {
+    let code = "|x:i64| x + 1L";
+    let ref input_data: i64 = 41;
+
+    let ref host = host_conf();
+    let ret_value = compile_and_run(code, host, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, 42);
+
+    let mut llvm = default_conf();
+    llvm.set("weld.backend", "llvm");
+    let ret_value = compile_and_run(code, &llvm, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn unknown_backend() {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "gpu");
+    assert!(WeldModule::compile("|x:i64| x", &conf).is_err());
+}
+
+#[test]
+fn host_compile_cache_reuses_artifact() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("weld-cache-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut conf = host_conf();
+    conf.set("weld.compile.cache", "true");
+    conf.set("weld.compile.cacheDir", dir.to_str().unwrap());
+
+    // Compiling the same program again reuses its cache entry.
+    let ref input_data: i64 = 21;
+    for _ in 0..2 {
+        let ret_value = compile_and_run("|x:i64| x * 2L", &conf, input_data);
+        let result = unsafe { *(ret_value.data() as *const i64) };
+        assert_eq!(result, 42);
+    }
+
+    let entries = fs::read_dir(&dir).unwrap().count();
+    assert_eq!(entries, 1);
+
+    // The same program compiled with different flags gets its own entry.
+    let mut threaded_conf = conf.clone();
+    threaded_conf.set("weld.threads", "2");
+    let ret_value = compile_and_run("|x:i64| x * 2L", &threaded_conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, 42);
+    let entries = fs::read_dir(&dir).unwrap().count();
+    assert_eq!(entries, 2);
+
+    // A different program gets its own entry.
+    let ret_value = compile_and_run("|x:i64| x * 3L", &conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, 63);
+    let entries = fs::read_dir(&dir).unwrap().count();
+    assert_eq!(entries, 3);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,194 @@
+//! Tests for running loops in the C backend across multiple threads.
+//!
+//! Each test runs a loop that is large enough to be split across threads and checks that the
+//! result matches the single-threaded result.
+
+use weld::runtime::WeldRuntimeErrno;
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Number of loop iterations, which is above the threshold for splitting a loop.
+const SIZE: i32 = 100000;
+
+fn host_conf(threads: i32) -> WeldConf {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "c-host");
+    conf.set("weld.threads", format!("{}", threads));
+    conf
+}
+
+#[repr(C)]
+struct I32KeyValArgs {
+    x: WeldVec<i32>,
+    y: WeldVec<i32>,
+}
+
+fn key_val_args(keys: &Vec<i32>, vals: &Vec<i32>) -> I32KeyValArgs {
+    I32KeyValArgs {
+        x: WeldVec::from(keys),
+        y: WeldVec::from(vals),
+    }
+}
+
+#[test]
+fn parallel_merger_loop() {
+    let code = "|x:vec[i64]| result(for(x, merger[i64,+], |b,i,e| merge(b, e)))";
+    let input_vec: Vec<i64> = (0..SIZE as i64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { *(ret_value.data() as *const i64) };
+        assert_eq!(result, input_vec.iter().sum::<i64>());
+    }
+}
+
+#[test]
+fn parallel_appender_keeps_order() {
+    let code = "|x:vec[i32]| result(for(x, appender[i32], |b,i,e| if(e % 3 == 0, b, merge(b, e))))";
+    let input_vec: Vec<i32> = (0..SIZE).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let expected: Vec<i32> = input_vec.iter().cloned().filter(|e| e % 3 != 0).collect();
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<i32>)).clone() };
+        assert_eq!(result.len, expected.len() as i64);
+        for i in 0..(result.len as isize) {
+            assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize]);
+        }
+    }
+}
+
+#[test]
+fn parallel_dictmerger_loop() {
+    let code = "|x:vec[i32], y:vec[i32]| tovec(result(for(zip(x,y), dictmerger[i32,i32,+],
+                |b,i,e| merge(b, e))))";
+    const UNIQUE_KEYS: i32 = 1000;
+    let keys: Vec<i32> = (0..SIZE).map(|i| i % UNIQUE_KEYS).collect();
+    let vals: Vec<i32> = vec![1; SIZE as usize];
+    let ref input_data = key_val_args(&keys, &vals);
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<Pair<i32, i32>>)).clone() };
+        let mut res: Vec<(i32, i32)> = (0..result.len as isize)
+            .map(|i| unsafe { ((*result.data.offset(i)).ele1, (*result.data.offset(i)).ele2) })
+            .collect();
+        res.sort();
+        let expected: Vec<(i32, i32)> =
+            (0..UNIQUE_KEYS).map(|k| (k, SIZE / UNIQUE_KEYS)).collect();
+        assert_eq!(res, expected);
+    }
+}
+
+#[test]
+fn parallel_groupmerger_keeps_order() {
+    let code = "|x:vec[i32], y:vec[i32]| tovec(result(for(zip(x,y), groupmerger[i32,i32],
+                |b,i,e| merge(b, e))))";
+    const UNIQUE_KEYS: i32 = 256;
+    let keys: Vec<i32> = (0..SIZE).map(|i| i % UNIQUE_KEYS).collect();
+    let vals: Vec<i32> = (0..SIZE).collect();
+    let ref input_data = key_val_args(&keys, &vals);
+
+    let mut expected: Vec<(i32, Vec<i32>)> = (0..UNIQUE_KEYS).map(|k| (k, vec![])).collect();
+    for i in 0..SIZE as usize {
+        expected[keys[i] as usize].1.push(vals[i]);
+    }
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result =
+            unsafe { (*(ret_value.data() as *const WeldVec<Pair<i32, WeldVec<i32>>>)).clone() };
+        let mut res: Vec<(i32, Vec<i32>)> = (0..result.len as isize)
+            .map(|i| {
+                let key = unsafe { (*result.data.offset(i)).ele1 };
+                let val = unsafe { (*result.data.offset(i)).ele2.clone() };
+                let vec: Vec<i32> = (0..val.len as isize)
+                    .map(|j| unsafe { *val.data.offset(j) })
+                    .collect();
+                (key, vec)
+            })
+            .collect();
+        res.sort();
+        assert_eq!(res, expected);
+    }
+}
+
+#[test]
+fn parallel_vecmerger_loop() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        y: WeldVec<i32>,
+    }
+
+    let code = "|x:vec[i32], y:vec[i32]| result(for(y, vecmerger[i32,+](x),
+                |b,i,e| merge(b, {i64(e % 10), 1})))";
+    let x = vec![0; 10];
+    let y: Vec<i32> = (0..SIZE).collect();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<i32>)).clone() };
+        assert_eq!(result.len, x.len() as i64);
+        for i in 0..(result.len as isize) {
+            assert_eq!(unsafe { *result.data.offset(i) }, SIZE / 10);
+        }
+    }
+}
+
+#[test]
+fn parallel_struct_of_builders() {
+    #[allow(dead_code)]
+    struct Output {
+        sum: i64,
+        vals: WeldVec<i64>,
+    }
+
+    let code = "|x:vec[i64]| let r = for(x, {merger[i64,+], appender[i64]},
+                |b,i,e| {merge(b.$0, e), merge(b.$1, e * 2L)}); {result(r.$0), result(r.$1)}";
+    let input_vec: Vec<i64> = (0..SIZE as i64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { &*(ret_value.data() as *const Output) };
+        assert_eq!(result.sum, input_vec.iter().sum::<i64>());
+        assert_eq!(result.vals.len, input_vec.len() as i64);
+        for i in 0..(result.vals.len as isize) {
+            assert_eq!(unsafe { *result.vals.data.offset(i) }, input_vec[i as usize] * 2);
+        }
+    }
+}
+
+#[test]
+fn parallel_error_on_worker_thread() {
+    // The last key is missing from the dictionary, so the lookup fails on the last thread.
+    let code = "|x:vec[i64]|
+                let d = result(for(x, dictmerger[i64,i64,+],
+                    |b,i,e| if(e < len(x) - 1L, merge(b, {e, e}), b)));
+                result(for(x, merger[i64,+], |b,i,e| merge(b, lookup(d, e))))";
+    let input_vec: Vec<i64> = (0..SIZE as i64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for threads in [1, 4].iter() {
+        let ref conf = host_conf(*threads);
+        let err_value = compile_and_run_error(code, conf, input_data);
+        assert_eq!(err_value.code(), WeldRuntimeErrno::KeyNotFoundError);
+        let message = err_value.message().to_str().unwrap();
+        assert!(message.ends_with("key not found"), "{}", message);
+    }
+}
@@ -0,0 +1,143 @@
+//! Tests for vectorized loops in the C backend.
+//!
+//! Each test runs a loop that the vectorizer transforms into a loop over SIMD values followed by
+//! a loop over the fringe, using vector lengths that do and do not divide the input size.
+
+use weld::runtime::WeldRuntimeErrno;
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Number of loop iterations, which leaves a fringe for each tested vector length above one.
+const SIZE: i32 = 1003;
+
+/// Vector lengths to test, including the VE's vector register length.
+const VECTOR_LENGTHS: [u32; 3] = [1, 8, 256];
+
+fn vector_conf(length: u32) -> WeldConf {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "c-host");
+    conf.set("weld.compile.vectorLength", format!("{}", length));
+    conf
+}
+
+#[test]
+fn vectorized_merger_loop() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        a: i32,
+    }
+
+    let code = "|x:vec[i32], a:i32| result(for(x, merger[i32,+], |b,i,e| merge(b, e * a + 1)))";
+    let input_vec: Vec<i32> = (0..SIZE).collect();
+    let ref input_data = Args {
+        x: WeldVec::from(&input_vec),
+        a: 3,
+    };
+    let expected: i32 = input_vec.iter().map(|e| e * 3 + 1).sum();
+
+    for length in VECTOR_LENGTHS.iter() {
+        let ref conf = vector_conf(*length);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { *(ret_value.data() as *const i32) };
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn vectorized_appender_loop() {
+    let code = "|x:vec[f64]| result(for(x, appender[f64], |b,i,e| merge(b, sqrt(e) + 1.0)))";
+    let input_vec: Vec<f64> = (0..SIZE).map(|e| e as f64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for length in VECTOR_LENGTHS.iter() {
+        let ref conf = vector_conf(*length);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<f64>)).clone() };
+        assert_eq!(result.len, input_vec.len() as i64);
+        for i in 0..(result.len as isize) {
+            let expected = input_vec[i as usize].sqrt() + 1.0;
+            assert_eq!(unsafe { *result.data.offset(i) }, expected);
+        }
+    }
+}
+
+#[test]
+fn vectorized_predicated_merger_loop() {
+    let code = "|x:vec[i64]| result(for(x, merger[i64,max],
+                |b,i,e| @(predicate:true)if(e % 7L == 3L, merge(b, 0L - e), b)))";
+    let input_vec: Vec<i64> = (0..SIZE as i64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let expected = input_vec
+        .iter()
+        .filter(|e| *e % 7 == 3)
+        .map(|e| -e)
+        .max()
+        .unwrap();
+
+    for length in VECTOR_LENGTHS.iter() {
+        let ref conf = vector_conf(*length);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { *(ret_value.data() as *const i64) };
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn vectorized_zipped_loop() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        y: WeldVec<i32>,
+    }
+
+    let code = "|x:vec[i32], y:vec[i32]| result(for(zip(x, y), appender[i32],
+                |b,i,e| merge(b, e.$0 * e.$1)))";
+    let x: Vec<i32> = (0..SIZE).collect();
+    let y: Vec<i32> = (0..SIZE).map(|e| SIZE - e).collect();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    for length in VECTOR_LENGTHS.iter() {
+        let ref conf = vector_conf(*length);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<i32>)).clone() };
+        assert_eq!(result.len, x.len() as i64);
+        for i in 0..(result.len as isize) {
+            let i = i as usize;
+            assert_eq!(unsafe { *result.data.offset(i as isize) }, x[i] * y[i]);
+        }
+    }
+}
+
+#[test]
+fn vectorized_struct_select_loop() {
+    let code = "|x:vec[i64]| result(for(x, appender[i64],
+                |b,i,e| let s = select(e % 3L == 0L, {e, 1L}, {0L, e}); merge(b, s.$0 * 2L + s.$1)))";
+    let input_vec: Vec<i64> = (0..SIZE as i64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for length in VECTOR_LENGTHS.iter() {
+        let ref conf = vector_conf(*length);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let result = unsafe { (*(ret_value.data() as *const WeldVec<i64>)).clone() };
+        assert_eq!(result.len, input_vec.len() as i64);
+        for i in 0..(result.len as isize) {
+            let e = input_vec[i as usize];
+            let expected = if e % 3 == 0 { e * 2 + 1 } else { e };
+            assert_eq!(unsafe { *result.data.offset(i) }, expected);
+        }
+    }
+}
+
+#[test]
+fn invalid_vector_length() {
+    let code = "|x:i32| x";
+    let ref conf = vector_conf(0);
+    let err_value = compile_and_run_error(code, conf, &0i32);
+    assert_eq!(err_value.code(), WeldRuntimeErrno::CompileError);
+}
@@ -42,6 +42,8 @@ fn conf(threads: i32) -> WeldConf {
     let threads = format!("{}", threads);
     let mut conf = WeldConf::new();
     conf.set("weld.threads", threads);
+    // The default backend depends on the enabled features.
+    conf.set("weld.backend", "llvm");
     conf
 }
 
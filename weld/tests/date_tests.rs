@@ -0,0 +1,153 @@
+//! Tests for dates, timestamps and intervals.
+
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Returns configurations for the LLVM and the C backend.
+fn backend_confs() -> Vec<WeldConf> {
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+    vec![default_conf(), c_conf]
+}
+
+/// 2020-02-29 as days since 1970-01-01.
+const LEAP_DAY: i32 = 18321;
+/// Microseconds per day.
+const DAY: i64 = 86_400_000_000;
+
+#[test]
+fn calendar_functions() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        d: i32,
+        t: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        year: i32,
+        month: i32,
+        day: i32,
+        hour: i32,
+        month_start: i32,
+        hour_start: i64,
+        year_start: i64,
+    }
+
+    let code = r#"|d: date, t: timestamp|
+        {year(d), month(d), day(d), hour(t), date_trunc("month", d), date_trunc("hour", t),
+         date_trunc("year", t)}"#;
+
+    // t is 1969-12-31 23:59:59.5.
+    let ref input_data = Args {
+        d: LEAP_DAY,
+        t: -500_000,
+    };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { &*data };
+        assert_eq!(result.year, 2020);
+        assert_eq!(result.month, 2);
+        assert_eq!(result.day, 29);
+        assert_eq!(result.hour, 23);
+        assert_eq!(result.month_start, LEAP_DAY - 28);
+        assert_eq!(result.hour_start, -3_600_000_000);
+        assert_eq!(result.year_start, -365 * DAY);
+    }
+}
+
+#[test]
+fn interval_arithmetic() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        d: i32,
+        t: i64,
+        i: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        earlier: i32,
+        later: i64,
+        since_epoch: i64,
+        days_into_year: i64,
+        less: bool,
+    }
+
+    let code = r#"|d: date, t: timestamp, i: interval|
+        {d - i, t + i, t - timestamp("1970-01-01"), d - date("2020-01-01"),
+         d < date("2021-01-01")}"#;
+
+    // t is 1970-01-02 01:01:01 and i is 36 hours.
+    let ref input_data = Args {
+        d: LEAP_DAY,
+        t: 90_061_000_000,
+        i: 36 * 3_600_000_000,
+    };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { &*data };
+        // Subtracting a day and a half from midnight rounds down to the start of the day.
+        assert_eq!(result.earlier, LEAP_DAY - 2);
+        assert_eq!(result.later, 219_661_000_000);
+        assert_eq!(result.since_epoch, 90_061_000_000);
+        assert_eq!(result.days_into_year, 59 * DAY);
+        assert!(result.less);
+    }
+}
+
+#[test]
+fn dictmerger_with_date_keys() {
+    let code = r#"|d: vec[date], v: vec[i64]|
+        let r = result(for(zip(d, v), dictmerger[date,i64,+],
+            |b, i, e| merge(b, {date_trunc("month", e.$0), e.$1})));
+        {lookup(r, date("2020-01-01")), lookup(r, date("2020-02-01"))}"#;
+
+    #[allow(dead_code)]
+    struct Args {
+        d: WeldVec<i32>,
+        v: WeldVec<i64>,
+    }
+    // 2020-01-15, 2020-02-29, 2020-01-31 and 2020-02-01.
+    let d = vec![18276, LEAP_DAY, 18292, 18293];
+    let v = vec![1, 10, 100, 1000];
+    let ref input_data = Args {
+        d: WeldVec::from(&d),
+        v: WeldVec::from(&v),
+    };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Pair<i64, i64>;
+        let result = unsafe { (*data).clone() };
+        assert_eq!(result.ele1, 101);
+        assert_eq!(result.ele2, 1010);
+    }
+}
+
+#[test]
+fn groupmerger_with_timestamp_keys() {
+    let code = r#"|t: vec[timestamp]|
+        let g = result(for(t, groupmerger[timestamp,timestamp],
+            |b, i, e| merge(b, {date_trunc("day", e), e})));
+        {len(lookup(g, timestamp("1970-01-01"))), len(lookup(g, timestamp("1970-01-02")))}"#;
+
+    let t: Vec<i64> = vec![1, DAY + 1, DAY - 1, 2 * DAY - 1, DAY];
+    let ref input_data = WeldVec::from(&t);
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Pair<i64, i64>;
+        let result = unsafe { (*data).clone() };
+        assert_eq!(result.ele1, 2);
+        assert_eq!(result.ele2, 3);
+    }
+}
@@ -0,0 +1,141 @@
+//! Tests for decimal values.
+
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Returns configurations for the LLVM backend, the C backend and the fake VE.
+fn backend_confs() -> Vec<WeldConf> {
+    let mut host_conf = default_conf();
+    host_conf.set("weld.backend", "c-host");
+    let mut fake_ve_conf = default_conf();
+    fake_ve_conf.set("weld.backend", "c-fake-ve");
+    vec![default_conf(), host_conf, fake_ve_conf]
+}
+
+#[test]
+fn decimal_sum() {
+    let code =
+        "|x: vec[decimal[12,2]]| result(for(x, merger[decimal[12,2],+], |b, i, e| merge(b, e)))";
+    let ref conf = default_conf();
+
+    // Decimals are passed as their scaled values: 19.99, 0.01 and 2.50.
+    let x: Vec<i64> = vec![1999, 1, 250];
+    let ref input_data = WeldVec::from(&x);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i64;
+    let result = unsafe { *data };
+    assert_eq!(result, 2250);
+}
+
+#[test]
+fn decimal_arithmetic() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        a: i64,
+        b: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        product: i64,
+        quotient: i64,
+        sum: i64,
+        less: bool,
+    }
+
+    let code = "|a: decimal[10,2], b: decimal[10,2]| {a * b, a / b, a + b, a < b}";
+    let ref conf = default_conf();
+
+    // 12.34 and -2.50.
+    let ref input_data = Args { a: 1234, b: -250 };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    // Products and quotients are rounded toward zero.
+    assert_eq!(result.product, -3085);
+    assert_eq!(result.quotient, -493);
+    assert_eq!(result.sum, 984);
+    assert!(!result.less);
+}
+
+#[test]
+fn decimal_casts() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: f64,
+        y: i32,
+        z: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        from_float: i64,
+        from_int: i64,
+        rescaled: i64,
+        to_float: f64,
+        to_int: i64,
+    }
+
+    let code = "|x: f64, y: i32, z: decimal[10,4]|
+        {decimal[10,2](x), decimal[10,2](y), decimal[10,2](z), f64(z), i64(z)}";
+    let ref conf = default_conf();
+
+    // z is 12.3456.
+    let ref input_data = Args {
+        x: -0.125,
+        y: 7,
+        z: 123456,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.from_float, -13);
+    assert_eq!(result.from_int, 700);
+    assert_eq!(result.rescaled, 1234);
+    assert_eq!(result.to_float, 12.3456);
+    assert_eq!(result.to_int, 12);
+}
+
+#[test]
+fn decimal_wide_products() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        a: i64,
+        b: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        product: i64,
+        quotient: i64,
+    }
+
+    let code = "|a: decimal[18,6], b: decimal[18,6]| {a * b, a / b}";
+    let scale = 1_000_000i128;
+    let inputs = [
+        // Rounds toward zero.
+        (1, -3),
+        // The product and the rescaled dividend need more than 64 bits, but the results fit.
+        (123_456_789_012_345, -1_000_500_001),
+        // The results do not fit in 64 bits either, so they wrap around.
+        (i64::max_value() / 2, i64::max_value() / 3),
+    ];
+
+    for conf in backend_confs().iter() {
+        for &(a, b) in inputs.iter() {
+            let ref input_data = Args { a, b };
+            let ret_value = compile_and_run(code, conf, input_data);
+            let result = unsafe { &*(ret_value.data() as *const Output) };
+            assert_eq!(result.product, (a as i128 * b as i128 / scale) as i64);
+            assert_eq!(result.quotient, (a as i128 * scale / b as i128) as i64);
+        }
+    }
+}
@@ -0,0 +1,647 @@
+//! Tests for the VE offload path using the in-process fake VE.
+
+use weld::data::{Appender, GroupMerger};
+use weld::runtime::WeldRuntimeErrno;
+use weld::{fakeve, Data, WeldConf, WeldContext, WeldModule, WeldValue};
+
+mod common;
+use crate::common::*;
+
+fn fake_ve_conf() -> WeldConf {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "c-fake-ve");
+    conf
+}
+
+#[test]
+fn fake_ve_scalar_result() {
+    #[allow(dead_code)]
+    struct Args {
+        a: i64,
+        b: i64,
+    }
+
+    let code = "|a:i64, b:i64| a * b";
+    let ref conf = fake_ve_conf();
+
+    let ref input_data = Args { a: 6, b: 7 };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i64;
+    let result = unsafe { *data };
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn fake_ve_serialized_vector() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        a: i32,
+    }
+
+    // Small vectors are packed into the single serialized buffer.
+    let code = "|x:vec[i32], a:i32| result(for(x, merger[i32,+], |b,i,e| merge(b, e+a)))";
+    let ref conf = fake_ve_conf();
+
+    let input_vec = vec![1, 2, 3, 4, 5];
+    let ref input_data = Args {
+        x: WeldVec::from(&input_vec),
+        a: 1,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i32;
+    let result = unsafe { *data };
+    assert_eq!(result, 20);
+}
+
+#[test]
+fn fake_ve_large_vector() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i64>,
+        y: WeldVec<i64>,
+    }
+
+    // Vectors above the serialize threshold are transferred separately.
+    let code = "|x:vec[i64], y:vec[i64]| map(zip(x, y), |e| e.$0 + e.$1)";
+    let ref conf = fake_ve_conf();
+
+    let size = 200000;
+    let x: Vec<i64> = (0..size).collect();
+    let y: Vec<i64> = vec![3; size as usize];
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+    assert_eq!(result.len, size);
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, x[i as usize] + 3);
+    }
+}
+
+#[test]
+fn fake_ve_nested_vector() {
+    let code = "|x:vec[vec[i32]]| map(x, |v| result(for(v, merger[i32,+], |b,i,e| merge(b, e))))";
+    let ref conf = fake_ve_conf();
+
+    let inner = vec![vec![1, 2, 3], vec![4, 5], vec![6]];
+    let inner_vecs: Vec<WeldVec<i32>> = inner.iter().map(|v| WeldVec::from(v)).collect();
+    let ref input_data = WeldVec::from(&inner_vecs);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i32>;
+    let result = unsafe { (*data).clone() };
+    let expected = vec![6, 9, 6];
+    assert_eq!(result.len as usize, expected.len());
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize]);
+    }
+}
+
+#[test]
+fn fake_ve_pipelined_transfers() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i64>,
+        y: WeldVec<i64>,
+        a: i64,
+    }
+
+    #[allow(dead_code)]
+    struct Output {
+        x: WeldVec<i64>,
+        y: WeldVec<i64>,
+        a: i64,
+    }
+
+    // Each vector spans more than one transfer chunk in both directions.
+    let code = "|x:vec[i64], y:vec[i64], a:i64| {map(x, |e| e + a), map(y, |e| e * a), a}";
+    let ref conf = fake_ve_conf();
+
+    let size = 1100000;
+    let x: Vec<i64> = (0..size).collect();
+    let y: Vec<i64> = (0..size).rev().collect();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+        a: 3,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { &*(ret_value.data() as *const Output) };
+    assert_eq!(result.a, 3);
+    assert_eq!(result.x.len, size);
+    assert_eq!(result.y.len, size);
+    for i in 0..(size as isize) {
+        assert_eq!(unsafe { *result.x.data.offset(i) }, x[i as usize] + 3);
+        assert_eq!(unsafe { *result.y.data.offset(i) }, y[i as usize] * 3);
+    }
+}
+
+#[test]
+fn fake_ve_device_resident_values() {
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.deviceResults", "true");
+
+    let ref mut context = WeldContext::new(conf).unwrap();
+    let double = WeldModule::compile("|x:vec[i64]| map(x, |e| e * 2L)", conf).unwrap();
+    let increment = WeldModule::compile("|x:vec[i64]| map(x, |e| e + 1L)", conf).unwrap();
+
+    let size = 1000;
+    let x: Vec<i64> = (0..size).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+
+    // The intermediate result is passed between runs without leaving the device.
+    let doubled = unsafe { double.run(context, input_value).unwrap() };
+    assert!(doubled.is_on_device());
+    assert!(doubled.data().is_null());
+    let incremented = unsafe { increment.run(context, &doubled).unwrap() };
+    assert!(incremented.is_on_device());
+
+    let ret_value = unsafe { incremented.to_host().unwrap() };
+    assert!(!ret_value.is_on_device());
+    let result = unsafe { (*(ret_value.data() as *const WeldVec<i64>)).clone() };
+    assert_eq!(result.len, size);
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, x[i as usize] * 2 + 1);
+    }
+
+    // Device-resident values must match the parameters of the module.
+    let code = "|x:vec[i32]| result(for(x, merger[i32,+], |b,i,e| merge(b, e)))";
+    let sum = WeldModule::compile(code, conf).unwrap();
+    assert!(unsafe { sum.run(context, &doubled) }.is_err());
+}
+
+#[test]
+fn fake_ve_frees_device_runs() {
+    // Only this test uses the node, so no other runs are created on it.
+    let node = 80;
+    let mut conf = fake_ve_conf();
+    conf.set("weld.ve.node", format!("{}", node));
+    let fake_ve = unsafe { fakeve::get_fake_ve_ptr(node) };
+    let live_runs = || unsafe { (*fake_ve).live_runs() };
+
+    let x: Vec<i64> = (0..1000).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let code = "|x:vec[i64]| map(x, |e| e * 2L)";
+
+    // Runs with the same context share its run on the device, which is freed with the context.
+    {
+        let ref mut context = WeldContext::new(&conf).unwrap();
+        let module = WeldModule::compile(code, &conf).unwrap();
+        unsafe { module.run(context, input_value).unwrap() };
+        unsafe { module.run(context, input_value).unwrap() };
+        assert_eq!(live_runs(), 1);
+    }
+    assert_eq!(live_runs(), 0);
+    assert_eq!(unsafe { (*fake_ve).live_allocations() }, 0);
+
+    // A device-resident value owns its run, which is freed with the last clone of the value.
+    conf.set("weld.ve.deviceResults", "true");
+    let ref mut context = WeldContext::new(&conf).unwrap();
+    let module = WeldModule::compile(code, &conf).unwrap();
+    let doubled = unsafe { module.run(context, input_value).unwrap() };
+    let copy = doubled.clone();
+    let quadrupled = unsafe { module.run(context, &doubled).unwrap() };
+    assert_eq!(live_runs(), 2);
+    drop(doubled);
+    assert_eq!(live_runs(), 2);
+    drop(copy);
+    assert_eq!(live_runs(), 1);
+    drop(quadrupled);
+    assert_eq!(live_runs(), 0);
+    assert_eq!(unsafe { (*fake_ve).live_allocations() }, 0);
+}
+
+#[test]
+fn fake_ve_crash_loses_device_values() {
+    // Only this test uses the node, so it can be crashed.
+    let node = 74;
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.node", format!("{}", node));
+    conf.set("weld.ve.deviceResults", "true");
+    let fake_ve = unsafe { fakeve::get_fake_ve_ptr(node) };
+
+    let module = WeldModule::compile("|x:vec[i64]| map(x, |e| e * 2L)", conf).unwrap();
+    let ref mut context = WeldContext::new(conf).unwrap();
+    let x: Vec<i64> = (0..100).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let doubled = unsafe { module.run(context, input_value).unwrap() };
+    assert!(doubled.is_on_device());
+    let generation = unsafe { (*fake_ve).generation };
+
+    // Values in the memory of the crashed process are lost.
+    unsafe { (*fake_ve).crash() };
+    let err = unsafe { module.run(context, &doubled) }.unwrap_err();
+    assert_eq!(err.code(), WeldRuntimeErrno::DeviceError);
+    let err = unsafe { doubled.to_host() }.unwrap_err();
+    assert_eq!(err.code(), WeldRuntimeErrno::DeviceError);
+
+    // The failed run re-created the process, so later runs succeed.
+    let doubled = unsafe { module.run(context, input_value).unwrap() };
+    let ret_value = unsafe { doubled.to_host().unwrap() };
+    let result = unsafe { (*(ret_value.data() as *const WeldVec<i64>)).clone() };
+    assert_eq!(result.len, 100);
+    assert_eq!(unsafe { *result.data.offset(99) }, 198);
+    assert_eq!(unsafe { (*fake_ve).generation }, generation + 1);
+}
+
+#[test]
+fn fake_ve_call_timeout() {
+    // Only this test uses the node, so it can hang.
+    let node = 75;
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.node", format!("{}", node));
+    conf.set("weld.ve.callTimeout", "1");
+    let fake_ve = unsafe { fakeve::get_fake_ve_ptr(node) };
+
+    let module = WeldModule::compile("|x:i64| x + 1L", conf).unwrap();
+    let ref mut context = WeldContext::new(conf).unwrap();
+    let ref input_data = 41i64;
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { module.run(context, input_value).unwrap() };
+    assert_eq!(unsafe { *(ret_value.data() as *const i64) }, 42);
+    let generation = unsafe { (*fake_ve).generation };
+
+    // A call on a hung process fails once the timeout expires.
+    unsafe { (*fake_ve).hang() };
+    let err = unsafe { module.run(context, input_value) }.unwrap_err();
+    assert_eq!(err.code(), WeldRuntimeErrno::DeviceError);
+
+    // The next run re-creates the process.
+    let ret_value = unsafe { module.run(context, input_value).unwrap() };
+    assert_eq!(unsafe { *(ret_value.data() as *const i64) }, 42);
+    assert_eq!(unsafe { (*fake_ve).generation }, generation + 1);
+}
+
+#[test]
+fn fake_ve_dictionary_roundtrip() {
+    let ref conf = fake_ve_conf();
+    let ref mut context = WeldContext::new(conf).unwrap();
+
+    let code = "|x:vec[i64]| result(for(x, dictmerger[i64,i64,+], |b,i,e| merge(b, {e % 10L, e})))";
+    let build = WeldModule::compile(code, conf).unwrap();
+    let probe =
+        WeldModule::compile("|d:dict[i64,i64]| {lookup(d, 3L), len(tovec(d))}", conf).unwrap();
+
+    let x: Vec<i64> = (0..1000).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+
+    // The dictionary is copied to the host and then back to the VE.
+    let dict = unsafe { build.run(context, input_value).unwrap() };
+    let ret_value = unsafe { probe.run(context, &dict).unwrap() };
+    let result = unsafe { &*(ret_value.data() as *const Pair<i64, i64>) };
+    let expected: i64 = x.iter().filter(|e| *e % 10 == 3).sum();
+    assert_eq!(result.ele1, expected);
+    assert_eq!(result.ele2, 10);
+}
+
+#[test]
+fn fake_ve_runtime_errors() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i64>,
+        k: i64,
+    }
+
+    let ref conf = fake_ve_conf();
+    let ref mut context = WeldContext::new(conf).unwrap();
+    let code = "|x:vec[i64], k:i64|
+                lookup(result(for(x, dictmerger[i64,i64,+], |b,i,e| merge(b, {e, e * 10L}))), k)";
+    let module = WeldModule::compile(code, conf).unwrap();
+
+    let x: Vec<i64> = vec![1, 2, 3];
+    let ref missing = Args {
+        x: WeldVec::from(&x),
+        k: 7,
+    };
+    let ref present = Args {
+        x: WeldVec::from(&x),
+        k: 2,
+    };
+
+    // The errno and message set on the VE are reported by the run.
+    let value = &WeldValue::new_from_data(missing as *const _ as Data);
+    let err = unsafe { module.run(context, value) }.unwrap_err();
+    assert_eq!(err.code(), WeldRuntimeErrno::KeyNotFoundError);
+    assert!(err.message().to_str().unwrap().contains("key not found"));
+
+    // The VE keeps running after an error, and the next run starts without it.
+    let value = &WeldValue::new_from_data(present as *const _ as Data);
+    let ret_value = unsafe { module.run(context, value).unwrap() };
+    assert_eq!(unsafe { *(ret_value.data() as *const i64) }, 20);
+
+    let code = "|x:i64| select(assert(x > 0L), x, 0L)";
+    let err_value = compile_and_run_error(code, conf, &-1i64);
+    assert_eq!(err_value.code(), WeldRuntimeErrno::AssertionError);
+}
+
+#[test]
+fn fake_ve_strings() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: WeldVec<u8>,
+        y: i64,
+    }
+
+    let ref conf = fake_ve_conf();
+    let code = "|x:vec[i8], y:i64|
+                {strconcat(toupper(substr(x, 0L, 4L)), tostring(y + strtoi64(substr(x, 5L, 2L)))),
+                 x < \"weld\"}";
+    let x = b"weld 42".to_vec();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: 1,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Pair<WeldVec<u8>, bool>;
+    let result = unsafe { (*data).clone() };
+    let string: Vec<u8> = (0..result.ele1.len as isize)
+        .map(|i| unsafe { *result.ele1.data.offset(i) })
+        .collect();
+    assert_eq!(string, b"WELD43");
+    assert!(!result.ele2);
+
+    let code = "|x:vec[i8]| strtof64(x)";
+    let x = b"4.2.1".to_vec();
+    let err_value = compile_and_run_error(code, conf, &WeldVec::from(&x));
+    assert_eq!(err_value.code(), WeldRuntimeErrno::ParseError);
+    assert!(err_value.message().to_str().unwrap().contains("invalid float"));
+}
+
+#[test]
+fn fake_ve_reuse_groupmerger() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        builder: GroupMerger<i64, i64>,
+        y: WeldVec<i64>,
+    }
+
+    #[allow(dead_code)]
+    struct Output {
+        size0: i64,
+        size1: i64,
+        elem: i64,
+    }
+
+    let ref conf = fake_ve_conf();
+    let ref mut context = WeldContext::new(conf).unwrap();
+
+    let code = "|x:vec[i64]| for(x, groupmerger[i64,i64], |b,i,e| merge(b, {e % 3L, e}))";
+    let first = WeldModule::compile(code, conf).unwrap();
+    // The groups grow beyond the capacity they had on the host.
+    let code = "|gm:groupmerger[i64,i64], y:vec[i64]|
+                let d = result(for(y, gm, |b,i,e| merge(b, {e % 3L, e})));
+                {len(lookup(d, 0L)), len(lookup(d, 1L)), lookup(lookup(d, 2L), 12L)}";
+    let second = WeldModule::compile(code, conf).unwrap();
+
+    let x: Vec<i64> = (0..30).collect();
+    let y: Vec<i64> = (30..60).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { first.run(context, input_value).unwrap() };
+    let builder = unsafe { (*(ret_value.data() as *const GroupMerger<i64, i64>)).clone() };
+
+    let ref input_data = Args {
+        builder,
+        y: WeldVec::from(&y),
+    };
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { second.run(context, input_value).unwrap() };
+    let result = unsafe { &*(ret_value.data() as *const Output) };
+    assert_eq!(result.size0, 20);
+    assert_eq!(result.size1, 20);
+    assert_eq!(result.elem, 38);
+}
+
+#[test]
+fn fake_ve_reuse_appender() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        builder: Appender<i64>,
+        y: WeldVec<i64>,
+    }
+
+    let ref conf = fake_ve_conf();
+    let ref mut context = WeldContext::new(conf).unwrap();
+
+    let first = WeldModule::compile(
+        "|x:vec[i64]| for(x, appender[i64], |b,i,e| merge(b, e))",
+        conf,
+    )
+    .unwrap();
+    let code = "|a:appender[i64], y:vec[i64]| result(for(y, a, |b,i,e| merge(b, e * 10L)))";
+    let second = WeldModule::compile(code, conf).unwrap();
+
+    let x: Vec<i64> = (0..100).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { first.run(context, input_value).unwrap() };
+    let builder = unsafe { (*(ret_value.data() as *const Appender<i64>)).clone() };
+
+    let ref input_data = Args {
+        builder,
+        y: WeldVec::from(&x),
+    };
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { second.run(context, input_value).unwrap() };
+    let result = unsafe { (*(ret_value.data() as *const WeldVec<i64>)).clone() };
+    assert_eq!(result.len, 200);
+    for i in 0..100 {
+        assert_eq!(unsafe { *result.data.offset(i) }, x[i as usize]);
+        assert_eq!(unsafe { *result.data.offset(100 + i) }, x[i as usize] * 10);
+    }
+}
+
+#[test]
+fn fake_ve_reuse_merger() {
+    // Besides its value, a merger holds a partial result for each of the 256 lanes of the VE.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        builder: [i64; 257],
+        y: WeldVec<i64>,
+    }
+
+    let ref conf = fake_ve_conf();
+    let ref mut context = WeldContext::new(conf).unwrap();
+
+    let code = "|x:vec[i64]| for(x, merger[i64,+], |b,i,e| merge(b, e))";
+    let first = WeldModule::compile(code, conf).unwrap();
+    let code = "|m:merger[i64,+], y:vec[i64]| result(for(y, m, |b,i,e| merge(b, e)))";
+    let second = WeldModule::compile(code, conf).unwrap();
+
+    let x: Vec<i64> = (0..1000).collect();
+    let ref input_data = WeldVec::from(&x);
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { first.run(context, input_value).unwrap() };
+    let builder = unsafe { *(ret_value.data() as *const [i64; 257]) };
+
+    let ref input_data = Args {
+        builder,
+        y: WeldVec::from(&x),
+    };
+    let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+    let ret_value = unsafe { second.run(context, input_value).unwrap() };
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, x.iter().sum::<i64>() * 2);
+}
+
+#[test]
+fn fake_ve_unserialized_small_vectors() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i32>,
+        y: WeldVec<i32>,
+    }
+
+    // A threshold of zero sends every vector with a transfer of its own.
+    let code = "|x:vec[i32], y:vec[i32]| result(for(zip(x, y), merger[i32,+], |b,i,e| merge(b, e.$0 * e.$1)))";
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.serializeThreshold", "0");
+
+    let x = vec![1, 2, 3, 4, 5];
+    let y = vec![5, 4, 3, 2, 1];
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i32) };
+    assert_eq!(result, 35);
+}
+
+#[test]
+fn fake_ve_adaptive_serialize_policy() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i64>,
+        y: WeldVec<i64>,
+    }
+
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.serializePolicy", "adaptive");
+
+    let ref mut context = WeldContext::new(conf).unwrap();
+    let code = "|x:vec[i64], y:vec[i64]| result(for(zip(x, y), merger[i64,+], |b,i,e| merge(b, e.$0 + e.$1)))";
+    let module = WeldModule::compile(code, conf).unwrap();
+
+    // The policy changes as the module measures transfers, which must not change the results.
+    for &size in [10, 100000, 10, 1000, 100000].iter() {
+        let x: Vec<i64> = (0..size).collect();
+        let y: Vec<i64> = (0..size).map(|e| e * 2).collect();
+        let ref input_data = Args {
+            x: WeldVec::from(&x),
+            y: WeldVec::from(&y),
+        };
+        let input_value = &WeldValue::new_from_data(input_data as *const _ as Data);
+        let ret_value = unsafe { module.run(context, input_value).unwrap() };
+        let result = unsafe { *(ret_value.data() as *const i64) };
+        assert_eq!(result, (0..size).map(|e| e * 3).sum::<i64>());
+    }
+}
+
+#[test]
+fn fake_ve_invalid_serialize_policy() {
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.serializePolicy", "sometimes");
+    let err_value = compile_and_run_error("|x:i64| x", conf, &0i64);
+    assert_eq!(err_value.code(), WeldRuntimeErrno::CompileError);
+}
+
+fn fake_ve_pool_conf() -> WeldConf {
+    let mut conf = fake_ve_conf();
+    conf.set("weld.ve.nodes", "1,2,3");
+    conf
+}
+
+#[test]
+fn fake_ve_partitioned_merger() {
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<i64>,
+        y: WeldVec<i64>,
+        a: i64,
+    }
+
+    let code = "|x:vec[i64], y:vec[i64], a:i64|
+                result(for(zip(x, y), merger[i64,+], |b,i,e| merge(b, e.$0 * e.$1 + a)))";
+    let ref conf = fake_ve_pool_conf();
+
+    // The vectors are split into chunks of different sizes.
+    let size = 1000;
+    let x: Vec<i64> = (0..size).collect();
+    let y: Vec<i64> = (0..size).map(|e| e % 7).collect();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+        a: 2,
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    let expected: i64 = x.iter().zip(y.iter()).map(|(a, b)| a * b + 2).sum();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn fake_ve_partitioned_appender() {
+    let code = "|x:vec[i32]| map(x, |e| e * 3)";
+    let ref conf = fake_ve_pool_conf();
+
+    // The chunks are concatenated in order.
+    let size = 1001;
+    let x: Vec<i32> = (0..size).collect();
+    let ref input_data = WeldVec::from(&x);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { (*(ret_value.data() as *const WeldVec<i32>)).clone() };
+    assert_eq!(result.len, size as i64);
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, x[i as usize] * 3);
+    }
+}
+
+#[test]
+fn fake_ve_partitioned_fallbacks() {
+    let ref conf = fake_ve_pool_conf();
+
+    // A loop that uses its index runs on a single node.
+    let code = "|x:vec[i64]| result(for(x, merger[i64,+], |b,i,e| merge(b, e * i)))";
+    let x: Vec<i64> = (0..100).collect();
+    let ref input_data = WeldVec::from(&x);
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, x.iter().map(|e| e * e).sum::<i64>());
+
+    // Vectors shorter than the number of nodes use fewer nodes.
+    let code = "|x:vec[i64]| result(for(x, merger[i64,max], |b,i,e| merge(b, e)))";
+    let x: Vec<i64> = vec![3, 9];
+    let ref input_data = WeldVec::from(&x);
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result = unsafe { *(ret_value.data() as *const i64) };
+    assert_eq!(result, 9);
+}
+
+#[test]
+fn fake_ve_invalid_nodes() {
+    let ref mut conf = fake_ve_conf();
+    conf.set("weld.ve.nodes", "0,x");
+    let err_value = compile_and_run_error("|x:i64| x", conf, &0i64);
+    assert_eq!(err_value.code(), WeldRuntimeErrno::CompileError);
+}
@@ -0,0 +1,139 @@
+//! Tests for the hash join macros.
+
+mod common;
+use crate::common::*;
+
+/// Returns the elements of a vector returned by a Weld program.
+fn vec_result<T: Clone>(value: &weld::WeldValue) -> Vec<T> {
+    let data = value.data() as *const WeldVec<T>;
+    let result = unsafe { (*data).clone() };
+    (0..result.len as isize)
+        .map(|i| unsafe { (*result.data.offset(i)).clone() })
+        .collect()
+}
+
+#[allow(dead_code)]
+struct Args {
+    x: WeldVec<i64>,
+    y: WeldVec<i64>,
+}
+
+/// Returns the arguments for a join of `x` and `y`.
+fn join_args(x: &[i64], y: &[i64]) -> Args {
+    Args {
+        x: WeldVec::from(x),
+        y: WeldVec::from(y),
+    }
+}
+
+#[test]
+fn inner_join() {
+    let code = "|x: vec[i64], y: vec[i64]| join(x, y, |a| a, |b| b % 10L)";
+    let ref conf = default_conf();
+
+    let x = vec![1, 2, 3];
+    let y = vec![11, 3, 21, 13, 4];
+    let ref input_data = join_args(&x, &y);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let mut result: Vec<Pair<i64, i64>> = vec_result(&ret_value);
+    result.sort_by_key(|p| (p.ele1, p.ele2));
+    assert_eq!(
+        result,
+        vec![
+            Pair::new(1, 11),
+            Pair::new(1, 21),
+            Pair::new(3, 3),
+            Pair::new(3, 13),
+        ]
+    );
+}
+
+#[test]
+fn left_outer_join() {
+    let code = "|x: vec[i64], y: vec[i64]| leftjoin(x, y, |a| a, |b| b % 10L)";
+    let ref conf = default_conf();
+
+    let x = vec![1, 2, 3];
+    let y = vec![11, 3, 21];
+    let ref input_data = join_args(&x, &y);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let mut result: Vec<Pair<i64, WeldOption<i64>>> = vec_result(&ret_value);
+    result.sort_by_key(|p| (p.ele1, p.ele2.value));
+    assert_eq!(
+        result,
+        vec![
+            Pair::new(1, WeldOption::new(11)),
+            Pair::new(1, WeldOption::new(21)),
+            Pair::new(2, WeldOption::null()),
+            Pair::new(3, WeldOption::new(3)),
+        ]
+    );
+}
+
+#[test]
+fn semi_and_anti_join() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        semi: WeldVec<i64>,
+        anti: WeldVec<i64>,
+    }
+
+    let code = "|x: vec[i64], y: vec[i64]|
+        {semijoin(x, y, |a| a, |b| b), antijoin(x, y, |a| a, |b| b)}";
+    let ref conf = default_conf();
+
+    // Duplicate keys on the right side do not duplicate rows on the left side.
+    let x = vec![5, 1, 4, 2, 3, 1];
+    let y = vec![1, 3, 3, 6];
+    let ref input_data = join_args(&x, &y);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { (*data).clone() };
+
+    let values = |v: &WeldVec<i64>| -> Vec<i64> {
+        (0..v.len)
+            .map(|i| unsafe { *v.data.offset(i as isize) })
+            .collect()
+    };
+    assert_eq!(values(&result.semi), vec![1, 3, 1]);
+    assert_eq!(values(&result.anti), vec![5, 4, 2]);
+}
+
+#[test]
+fn join_with_map_and_filter() {
+    // The probe loop is fused with the filter and map over the joined rows.
+    let code = "|x: vec[i64], y: vec[i64]|
+        map(filter(join(x, y, |a| a % 100L, |b| b), |r| r.$0 > 100L), |r| r.$0 + r.$1)";
+    let ref conf = default_conf();
+
+    let x = vec![1, 101, 2, 102, 203];
+    let y = vec![1, 2, 2];
+    let ref input_data = join_args(&x, &y);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let mut result: Vec<i64> = vec_result(&ret_value);
+    result.sort();
+    assert_eq!(result, vec![102, 104, 104]);
+}
+
+#[test]
+fn parallel_join() {
+    let code = "|x: vec[i64], y: vec[i64]|
+        result(for(join(x, y, |a| a, |b| b / 2L), merger[i64,+], |b, i, r| merge(b, r.$1)))";
+    let ref conf = many_threads_conf();
+
+    let x: Vec<i64> = (0..10000).collect();
+    let y: Vec<i64> = (0..10000).collect();
+    let ref input_data = join_args(&x, &y);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const i64;
+    let result = unsafe { (*data).clone() };
+
+    // Every key below 5000 matches two values on the right side.
+    assert_eq!(result, (0..10000).sum::<i64>());
+}
@@ -209,3 +209,167 @@ fn simple_float_min() {
     let result = unsafe { *data };
     assert!(result.close(3.1, 5));
 }
+
+#[test]
+fn simple_rounding() {
+    #[repr(C)]
+    struct Output {
+        floor: f64,
+        ceil: f64,
+        round: f64,
+        abs: f64,
+        cbrt: f64,
+    }
+
+    let code = "|x:f64| {floor(x), ceil(x), round(x), abs(x), cbrt(x * 10.0)}";
+    let ref conf = default_conf();
+    let ref input_data: f64 = -2.5;
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.floor, -3.0);
+    assert_eq!(result.ceil, -2.0);
+    assert_eq!(result.round, -3.0);
+    assert_eq!(result.abs, 2.5);
+    assert!(result.cbrt.close(-25.0f64.cbrt(), 5));
+}
+
+#[test]
+fn integer_unary_ops() {
+    #[repr(C)]
+    struct Output {
+        abs: i32,
+        popcount: i32,
+        clz: i32,
+        ctz: i32,
+        clz_zero: i64,
+        ctz_zero: i64,
+    }
+
+    let code = "|x:i32| {abs(x), popcount(x), clz(abs(x)), ctz(x), clz(0L), ctz(0L)}";
+    let ref conf = default_conf();
+    let ref input_data: i32 = -24;
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.abs, 24);
+    assert_eq!(result.popcount, (-24i32).count_ones() as i32);
+    assert_eq!(result.clz, 27);
+    assert_eq!(result.ctz, 3);
+    assert_eq!(result.clz_zero, 64);
+    assert_eq!(result.ctz_zero, 64);
+}
+
+#[test]
+fn bit_shifts() {
+    #[repr(C)]
+    struct Output {
+        left: i64,
+        right: i64,
+        unsigned_right: u8,
+    }
+
+    let code = "|x:i64| {x << 4L, x >> 2L, u8(x) >> u8(2)}";
+    let ref conf = default_conf();
+    let ref input_data: i64 = -12;
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.left, -192);
+    assert_eq!(result.right, -3);
+    assert_eq!(result.unsigned_right, (-12i64 as u8) >> 2);
+}
+
+#[test]
+fn bit_shifts_by_large_amounts() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: i64,
+        n: i64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        left: i64,
+        right: i64,
+        unsigned_right: u8,
+    }
+
+    let code = "|x:i64, n:i64| {x << n, x >> n, u8(x) >> u8(n)}";
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+
+    // Shift amounts are masked to the bit width of the type, like Rust's `wrapping_shl`.
+    for conf in [default_conf(), c_conf].iter() {
+        for &n in [64, 65, -1].iter() {
+            let x: i64 = -12;
+            let ref input_data = Args { x, n };
+
+            let ret_value = compile_and_run(code, conf, input_data);
+            let data = ret_value.data() as *const Output;
+            let result = unsafe { &*data };
+            assert_eq!(result.left, x.wrapping_shl(n as u32));
+            assert_eq!(result.right, x.wrapping_shr(n as u32));
+            assert_eq!(
+                result.unsigned_right,
+                (x as u8).wrapping_shr(u32::from(n as u8))
+            );
+        }
+    }
+}
+
+#[test]
+fn left_shifts_of_negative_values() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: i32,
+        n: i32,
+    }
+
+    #[repr(C)]
+    struct Output {
+        wide: i32,
+        narrow: i8,
+    }
+
+    let code = "|x:i32, n:i32| {x << n, i8(x) << i8(n)}";
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+
+    // Bits shifted out of a negative value are dropped, like with Rust's `wrapping_shl`.
+    for conf in [default_conf(), c_conf].iter() {
+        for &(x, n) in [(-12, 30), (-1, 31), (-128, 7), (-3, 6)].iter() {
+            let ref input_data = Args { x, n };
+
+            let ret_value = compile_and_run(code, conf, input_data);
+            let data = ret_value.data() as *const Output;
+            let result = unsafe { &*data };
+            assert_eq!(result.wide, x.wrapping_shl(n as u32));
+            assert_eq!(result.narrow, (x as i8).wrapping_shl(n as u32));
+        }
+    }
+}
+
+#[test]
+fn map_popcount() {
+    let code = "|x:vec[u64]| map(x, |a| popcount(a) + (a >> u64(60)))";
+    let ref conf = default_conf();
+
+    let input_vec: Vec<u64> = (0..100).map(|i| i * 0x1001 | (i % 16) << 60).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<u64>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(result.len as usize, input_vec.len());
+    for (i, expect) in input_vec.iter().enumerate() {
+        let expect = u64::from(expect.count_ones()) + (expect >> 60);
+        assert_eq!(unsafe { *result.data.offset(i as isize) }, expect);
+    }
+}
@@ -0,0 +1,139 @@
+//! Tests for optional values.
+
+mod common;
+use crate::common::*;
+
+/// Returns the elements of a vector returned by a Weld program.
+fn vec_result<T: Clone>(value: &weld::WeldValue) -> Vec<T> {
+    let data = value.data() as *const WeldVec<T>;
+    let result = unsafe { (*data).clone() };
+    (0..result.len as isize)
+        .map(|i| unsafe { (*result.data.offset(i)).clone() })
+        .collect()
+}
+
+#[test]
+fn null_aware_arithmetic() {
+    let code = "|x: vec[optional[i32]], y: vec[optional[i32]]|
+        result(for(zip(x, y), appender[optional[i32]], |b, i, e| merge(b, e.$0 * e.$1 + 1)))";
+    let ref conf = default_conf();
+
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<WeldOption<i32>>,
+        y: WeldVec<WeldOption<i32>>,
+    }
+    let x = vec![WeldOption::new(2), WeldOption::null(), WeldOption::new(4)];
+    let y = vec![WeldOption::new(3), WeldOption::new(5), WeldOption::null()];
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result: Vec<WeldOption<i32>> = vec_result(&ret_value);
+    assert_eq!(
+        result,
+        vec![WeldOption::new(7), WeldOption::null(), WeldOption::null()]
+    );
+}
+
+#[test]
+fn coalesce_and_isnull() {
+    let code = "|x: vec[optional[i64]]| {
+        result(for(x, merger[i64,+], |b, i, e| merge(b, coalesce(e, 100L)))),
+        result(for(x, merger[i64,+], |b, i, e| merge(b, if(isnull(e), 1L, 0L))))
+    }";
+    let ref conf = default_conf();
+
+    let x: Vec<WeldOption<i64>> = vec![Some(1), None, Some(2), None]
+        .into_iter()
+        .map(From::from)
+        .collect();
+    let ref input_data = WeldVec::from(&x);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Pair<i64, i64>;
+    let result = unsafe { (*data).clone() };
+    assert_eq!(result.ele1, 203);
+    assert_eq!(result.ele2, 2);
+}
+
+#[test]
+fn null_aware_comparison() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: WeldOption<f64>,
+        y: f64,
+    }
+
+    #[repr(C)]
+    struct Output {
+        less: WeldOption<bool>,
+        greater_or_equal: bool,
+        null: bool,
+    }
+
+    let code = "|x: optional[f64], y: f64| {x < y, coalesce(x >= y, false), isnull(x == null)}";
+    let ref conf = default_conf();
+
+    let ref input_data = Args {
+        x: WeldOption::new(1.5),
+        y: 2.0,
+    };
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.less, WeldOption::new(true));
+    assert!(!result.greater_or_equal);
+    assert!(result.null);
+
+    let ref input_data = Args {
+        x: WeldOption::null(),
+        y: 2.0,
+    };
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { &*data };
+    assert_eq!(result.less, WeldOption::null());
+    assert!(!result.greater_or_equal);
+    assert!(result.null);
+}
+
+#[test]
+fn divide_by_null() {
+    let code = "|x: i32, y: optional[i32]| {x / y, x % y}";
+    let ref conf = default_conf();
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: i32,
+        y: WeldOption<i32>,
+    }
+    let ref input_data = Args {
+        x: 10,
+        y: WeldOption::null(),
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Pair<WeldOption<i32>, WeldOption<i32>>;
+    let result = unsafe { (*data).clone() };
+    assert!(result.ele1.is_null());
+    assert!(result.ele2.is_null());
+}
+
+#[test]
+fn serialize_optionals() {
+    let code = "|x: vec[optional[i32]]|
+        deserialize[vec[optional[i32]]](serialize(x))";
+    let ref conf = default_conf();
+
+    let x = vec![WeldOption::new(1), WeldOption::null(), WeldOption::new(3)];
+    let ref input_data = WeldVec::from(&x);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let result: Vec<WeldOption<i32>> = vec_result(&ret_value);
+    assert_eq!(result, x);
+}
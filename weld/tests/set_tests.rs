@@ -0,0 +1,105 @@
+//! Tests for the set type and the setmerger builder.
+
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Returns configurations for the LLVM and the C backend with the given number of threads.
+fn backend_confs(threads: i32) -> Vec<WeldConf> {
+    let mut llvm_conf = default_conf();
+    llvm_conf.set("weld.threads", format!("{}", threads));
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+    c_conf.set("weld.threads", format!("{}", threads));
+    vec![llvm_conf, c_conf]
+}
+
+/// Returns the sorted values of a vector returned by Weld.
+fn sorted(v: &WeldVec<i32>) -> Vec<i32> {
+    let mut res: Vec<i32> = (0..v.len)
+        .map(|i| unsafe { *v.data.offset(i as isize) })
+        .collect();
+    res.sort();
+    res
+}
+
+#[test]
+fn simple_set() {
+    #[derive(Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Output {
+        len: i64,
+        has_three: WeldBool,
+        has_four: WeldBool,
+    }
+
+    let code = "|x:vec[i32]| let s = result(for(x, setmerger[i32], |b,i,e| merge(b, e)));
+        {len(s), contains(s, 3), contains(s, 4)}";
+
+    let input_vec = vec![1, 3, 3, 5, 1, 7];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { (*data).clone() };
+
+        let expected = Output {
+            len: 4,
+            has_three: 1,
+            has_four: 0,
+        };
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn set_tovec() {
+    let code = "|x:vec[i32]| tovec(result(for(x, setmerger[i32], |b,i,e| merge(b, e))))";
+
+    let input_vec = vec![7, 1, 3, 3, 5, 1, 7];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const WeldVec<i32>;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(sorted(&result), vec![1, 3, 5, 7]);
+    }
+}
+
+#[test]
+fn simple_parallel_set() {
+    let code = "|x:vec[i32]| len(result(@(grain_size: 100)for(x, setmerger[i32],
+        |b,i,e| merge(b, e % 100))))";
+
+    let input_vec: Vec<i32> = (0..10000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(4).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const i64;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(result, 100);
+    }
+}
+
+#[test]
+fn set_serialize() {
+    let code = "|x:vec[i32]| let s = result(for(x, setmerger[i32], |b,i,e| merge(b, e)));
+        tovec(deserialize[set[i32]](serialize(s)))";
+
+    let input_vec = vec![4, 2, 4, 8, 2, 6];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const WeldVec<i32>;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(sorted(&result), vec![2, 4, 6, 8]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Tests for the HyperLogLog and quantile sketch builders.
+
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Returns configurations for the LLVM and the C backend with the given number of threads.
+fn backend_confs(threads: i32) -> Vec<WeldConf> {
+    let mut llvm_conf = default_conf();
+    llvm_conf.set("weld.threads", format!("{}", threads));
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+    c_conf.set("weld.threads", format!("{}", threads));
+    vec![llvm_conf, c_conf]
+}
+
+/// Checks that `estimate` is within `error` of `expected`, relative to `expected`.
+fn assert_close(estimate: f64, expected: f64, error: f64) {
+    let relative = (estimate - expected).abs() / expected.abs();
+    assert!(
+        relative <= error,
+        "estimate {} is not within {} of {}",
+        estimate,
+        error,
+        expected
+    );
+}
+
+#[test]
+fn simple_distinct_count() {
+    let code = "|x:vec[i64]| distinctcount(result(for(x, hllmerger[i64], |b,i,e| merge(b, e))))";
+
+    let input_vec: Vec<i64> = (0..20000).map(|i| i % 10000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const i64;
+        let result = unsafe { (*data).clone() };
+
+        assert_close(result as f64, 10000.0, 0.05);
+    }
+}
+
+#[test]
+fn small_distinct_count() {
+    let code = "|x:vec[i32]| distinctcount(result(for(x, hllmerger[i32], |b,i,e| merge(b, e))))";
+
+    let input_vec = vec![1, 3, 3, 5, 1, 7];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const i64;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(result, 4);
+    }
+}
+
+#[test]
+fn parallel_distinct_count() {
+    let code = "|x:vec[i64]| distinctcount(result(@(grain_size: 100)for(x, hllmerger[i64],
+        |b,i,e| merge(b, e % 5000L))))";
+
+    let input_vec: Vec<i64> = (0..50000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(4).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const i64;
+        let result = unsafe { (*data).clone() };
+
+        assert_close(result as f64, 5000.0, 0.05);
+    }
+}
+
+#[test]
+fn simple_quantiles() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        median: f64,
+        p90: f64,
+        min: f64,
+    }
+
+    let code = "|x:vec[f64]| let q = result(for(x, quantilemerger[f64], |b,i,e| merge(b, e)));
+        {quantile(q, 0.5), quantile(q, 0.9), quantile(q, 0.0)}";
+
+    let input_vec: Vec<f64> = (1..1000).map(|i| i as f64).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { (*data).clone() };
+
+        assert_close(result.median, 500.0, 0.02);
+        assert_close(result.p90, 899.0, 0.02);
+        assert_close(result.min, 1.0, 0.02);
+    }
+}
+
+#[test]
+fn negative_quantiles() {
+    let code =
+        "|x:vec[f32]| quantile(result(for(x, quantilemerger[f32], |b,i,e| merge(b, e))), 0.25)";
+
+    let input_vec: Vec<f32> = (-100..100).map(|i| i as f32).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const f32;
+        let result = unsafe { (*data).clone() };
+
+        assert_close(result as f64, -51.0, 0.02);
+    }
+}
+
+#[test]
+fn empty_quantiles() {
+    let code =
+        "|x:vec[f64]| quantile(result(for(x, quantilemerger[f64], |b,i,e| merge(b, e))), 0.5)";
+
+    let input_vec: Vec<f64> = vec![];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    for conf in backend_confs(1).iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const f64;
+        let result = unsafe { (*data).clone() };
+
+        assert!(result.is_nan());
+    }
+}
+
+#[test]
+fn merge_serialized_sketches() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        distinct: i64,
+        median: f64,
+    }
+
+    // Sketches of each half of the input are serialized, and the partial sketches are merged.
+    let code = "|x:vec[f64]|
+        let n = len(x) / 2L;
+        let parts = map([slice(x, 0L, n), slice(x, n, n)], |p: vec[f64]|
+            let h = result(for(p, hllmerger[f64], |b,i,e| merge(b, e)));
+            let q = result(for(p, quantilemerger[f64], |b,i,e| merge(b, e)));
+            {serialize(h), serialize(q)});
+        let h = result(for(parts, hllmerger[f64],
+            |b,i,e| merge(b, deserialize[hll[f64]](e.$0))));
+        let q = result(for(parts, quantilemerger[f64],
+            |b,i,e| merge(b, deserialize[quantiles[f64]](e.$1))));
+        {distinctcount(h), quantile(q, 0.5)}";
+    // The C backend does not support vector literals, so this only runs on LLVM.
+    let ref conf = default_conf();
+
+    let input_vec: Vec<f64> = (0..2000).map(|i| (i % 1000) as f64 + 1.0).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { (*data).clone() };
+
+    assert_close(result.distinct as f64, 1000.0, 0.05);
+    assert_close(result.median, 500.0, 0.02);
+}
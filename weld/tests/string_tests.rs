@@ -0,0 +1,254 @@
+//! Tests for the built-in string operations.
+
+use weld::runtime::WeldRuntimeErrno;
+use weld::{WeldConf, WeldValue};
+
+mod common;
+use crate::common::*;
+
+/// Returns the bytes of a string returned by a Weld program.
+fn string_result(value: &WeldValue) -> Vec<u8> {
+    let data = value.data() as *const WeldVec<u8>;
+    let result = unsafe { (*data).clone() };
+    (0..result.len as isize)
+        .map(|i| unsafe { *result.data.offset(i) })
+        .collect()
+}
+
+/// Returns configurations for the LLVM and the C backend, which implement strings separately.
+fn backend_confs() -> Vec<WeldConf> {
+    let mut llvm_conf = default_conf();
+    llvm_conf.set("weld.backend", "llvm");
+    let mut host_conf = default_conf();
+    host_conf.set("weld.backend", "c-host");
+    vec![llvm_conf, host_conf]
+}
+
+#[test]
+fn concat_and_substr() {
+    let code = "|x: vec[i8], y: vec[i8]| substr(strconcat(x, y), 3L, 6L)";
+
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<u8>,
+        y: WeldVec<u8>,
+    }
+    let x = b"abcdef".to_vec();
+    let y = b"ghi".to_vec();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        assert_eq!(string_result(&ret_value), b"defghi");
+    }
+}
+
+#[test]
+fn substr_out_of_bounds() {
+    let code = "|x: vec[i8]| {substr(x, 4L, 10L), substr(x, 10L, 1L)}";
+
+    let x = b"abcdef".to_vec();
+    let ref input_data = WeldVec::from(&x);
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Pair<WeldVec<u8>, WeldVec<u8>>;
+        let result = unsafe { (*data).clone() };
+        assert_eq!(result.ele1.len, 2);
+        assert_eq!(unsafe { *result.ele1.data }, b'e');
+        assert_eq!(result.ele2.len, 0);
+    }
+}
+
+#[test]
+fn find_and_affixes() {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Output {
+        found: i64,
+        missing: i64,
+        starts: bool,
+        ends: bool,
+    }
+
+    let code = "|x: vec[i8]| {strfind(x, \"cd\"), strfind(x, \"dc\"),
+        startswith(x, \"abc\"), endswith(x, \"abc\")}";
+
+    let x = b"abcdef".to_vec();
+    let ref input_data = WeldVec::from(&x);
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { &*data };
+        assert_eq!(result.found, 2);
+        assert_eq!(result.missing, -1);
+        assert!(result.starts);
+        assert!(!result.ends);
+    }
+}
+
+#[test]
+fn change_case() {
+    let code = "|x: vec[i8]| strconcat(toupper(x), tolower(x))";
+
+    let x = b"Weld-1".to_vec();
+    let ref input_data = WeldVec::from(&x);
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        assert_eq!(string_result(&ret_value), b"WELD-1weld-1");
+    }
+}
+
+#[test]
+fn parse_numbers() {
+    let code = "|x: vec[i8], y: vec[i8]| {strtoi64(x), strtof64(y)}";
+
+    #[allow(dead_code)]
+    struct Args {
+        x: WeldVec<u8>,
+        y: WeldVec<u8>,
+    }
+    let x = b"-1234".to_vec();
+    let y = b"2.5e3".to_vec();
+    let ref input_data = Args {
+        x: WeldVec::from(&x),
+        y: WeldVec::from(&y),
+    };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Pair<i64, f64>;
+        let result = unsafe { (*data).clone() };
+        assert_eq!(result.ele1, -1234);
+        assert_eq!(result.ele2, 2500.0);
+    }
+}
+
+#[test]
+fn parse_invalid_number() {
+    let code = "|x: vec[i8]| strtoi64(x)";
+
+    let x = b"12a".to_vec();
+    let ref input_data = WeldVec::from(&x);
+
+    for conf in backend_confs().iter() {
+        let err_value = compile_and_run_error(code, conf, input_data);
+        assert_eq!(err_value.code(), WeldRuntimeErrno::ParseError);
+    }
+}
+
+#[test]
+fn format_numbers() {
+    let code = "|x: i32, y: f64| strconcat(strconcat(tostring(x), \",\"), tostring(y))";
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: i32,
+        y: f64,
+    }
+    let ref input_data = Args { x: -42, y: 0.25 };
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        assert_eq!(string_result(&ret_value), b"-42,0.25");
+    }
+}
+
+#[test]
+fn format_floats() {
+    let code = "|x: f64, y: f32| strconcat(strconcat(tostring(x), \",\"), tostring(y))";
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: f64,
+        y: f32,
+    }
+    let cases = [
+        (1e20, 1e20),
+        (1e-7, 1e-7),
+        (std::f64::NAN, std::f32::NAN),
+        (std::f64::NEG_INFINITY, std::f32::INFINITY),
+        (-0.0, 0.1),
+        (1.0, -1.5),
+        (0.1 + 0.2, 16777216.0),
+        (std::f64::MAX, std::f32::MIN_POSITIVE),
+        (5e-324, 1e-45),
+    ];
+
+    for conf in backend_confs().iter() {
+        for &(x, y) in cases.iter() {
+            let ref input_data = Args { x, y };
+            let ret_value = compile_and_run(code, conf, input_data);
+            // Floats are formatted like Rust formats them.
+            let expected = format!("{},{}", x, y);
+            assert_eq!(string_result(&ret_value), expected.as_bytes());
+        }
+    }
+
+    // The formatting does not use an exponent.
+    let ref input_data = Args { x: 1e20, y: std::f32::NAN };
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        assert_eq!(string_result(&ret_value), b"100000000000000000000,NaN");
+    }
+}
+
+#[test]
+fn format_bools() {
+    let code = "|x: bool| strconcat(strconcat(tostring(x), \",\"), tostring(!x))";
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, &true);
+        assert_eq!(string_result(&ret_value), b"true,false");
+    }
+}
+
+#[test]
+fn parse_non_decimal_floats() {
+    let code = "|x: vec[i8]| strtof64(x)";
+
+    for conf in backend_confs().iter() {
+        for x in [&b"0x10"[..], b"inf", b"nan", b" 1.5", b"1.5 "].iter() {
+            let x = x.to_vec();
+            let ref input_data = WeldVec::from(&x);
+            let err_value = compile_and_run_error(code, conf, input_data);
+            assert_eq!(err_value.code(), WeldRuntimeErrno::ParseError);
+        }
+        let x = b"-1.5e-1".to_vec();
+        let ref input_data = WeldVec::from(&x);
+        let ret_value = compile_and_run(code, conf, input_data);
+        assert_eq!(unsafe { *(ret_value.data() as *const f64) }, -0.15);
+    }
+}
+
+#[test]
+fn compare_strings() {
+    let code = "|x: vec[i8]| {x == \"abc\", x < \"abd\", toupper(x) > x}";
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Output {
+        equal: bool,
+        less: bool,
+        greater: bool,
+    }
+
+    let x = b"abc".to_vec();
+    let ref input_data = WeldVec::from(&x);
+
+    for conf in backend_confs().iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { &*data };
+        assert!(result.equal);
+        assert!(result.less);
+        assert!(!result.greater);
+    }
+}
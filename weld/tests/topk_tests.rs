@@ -0,0 +1,155 @@
+//! Tests for the topk builder.
+
+use weld::WeldConf;
+
+mod common;
+use crate::common::*;
+
+/// Returns a configuration for the C backend with the given number of threads.
+fn c_conf(threads: i32) -> WeldConf {
+    let mut conf = default_conf();
+    conf.set("weld.backend", "c-host");
+    conf.set("weld.threads", format!("{}", threads));
+    conf
+}
+
+/// Returns a permutation of `0..size`.
+fn shuffled(size: i64) -> Vec<i64> {
+    // 7919 is prime, so multiplying by it permutes the residues modulo `size` when `size` is not
+    // a multiple of it.
+    (0..size).map(|i| (i * 7919) % size).collect()
+}
+
+#[test]
+fn simple_topk() {
+    let code = "|x:vec[i32]| result(for(x, topk[i32,3], |b,i,e| merge(b, e)))";
+    let ref conf = default_conf();
+
+    let input_vec = vec![4, 9, -2, 9, 7, 1, 8];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i32>;
+    let result = unsafe { (*data).clone() };
+
+    let expected = [9, 9, 8];
+    assert_eq!(result.len, expected.len() as i64);
+    for i in 0..(expected.len() as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize])
+    }
+}
+
+#[test]
+fn topk_with_fewer_values_than_k() {
+    let code = "|x:vec[f64]| result(for(x, topk[f64,10], |b,i,e| merge(b, e)))";
+    let ref conf = default_conf();
+
+    let input_vec = vec![0.5, -1.5, 2.5];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<f64>;
+    let result = unsafe { (*data).clone() };
+
+    let expected = [2.5, 0.5, -1.5];
+    assert_eq!(result.len, expected.len() as i64);
+    for i in 0..(expected.len() as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize])
+    }
+}
+
+#[test]
+fn topk_by_score() {
+    #[allow(dead_code)]
+    struct Args {
+        scores: WeldVec<f64>,
+        ids: WeldVec<i64>,
+    }
+
+    // Structs are ordered by their first field, so this keeps the ids with the highest scores.
+    let code = "|scores:vec[f64], ids:vec[i64]|
+        map(result(for(zip(scores, ids), topk[{f64,i64},2], |b,i,e| merge(b, e))), |e| e.$1)";
+    let ref conf = default_conf();
+
+    let scores = vec![0.3, 0.9, 0.1, 0.7, 0.5];
+    let ids = vec![10, 11, 12, 13, 14];
+    let ref input_data = Args {
+        scores: WeldVec::from(&scores),
+        ids: WeldVec::from(&ids),
+    };
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+
+    let expected = [11, 13];
+    assert_eq!(result.len, expected.len() as i64);
+    for i in 0..(expected.len() as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize])
+    }
+}
+
+#[test]
+fn simple_parallel_topk() {
+    let code = "|x:vec[i64]| result(@(grain_size: 100)for(x, topk[i64,100], |b,i,e| merge(b, e)))";
+    let ref conf = many_threads_conf();
+
+    let size = 10000;
+    let input_vec = shuffled(size);
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(result.len, 100);
+    for i in 0..(result.len as isize) {
+        assert_eq!(unsafe { *result.data.offset(i) }, size - 1 - i as i64)
+    }
+}
+
+#[test]
+fn c_topk_with_ties() {
+    let code = "|x:vec[i64]| result(for(x, topk[i64,25], |b,i,e| merge(b, e)))";
+
+    // Each value appears 20 times, so the result ties across the values of several threads.
+    let input_vec: Vec<i64> = shuffled(20000).iter().map(|e| e % 1000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let mut expected = input_vec.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    expected.truncate(25);
+
+    for threads in [1, 4].iter() {
+        let ref conf = c_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const WeldVec<i64>;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(result.len, expected.len() as i64);
+        for i in 0..(expected.len() as isize) {
+            assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize])
+        }
+    }
+}
+
+#[test]
+fn c_topk_with_fewer_values_than_k() {
+    let code = "|x:vec[i64]| result(for(x, topk[i64,10000], |b,i,e| merge(b, e)))";
+
+    let input_vec: Vec<i64> = shuffled(5000).iter().map(|e| e / 2).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let mut expected = input_vec.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+
+    for threads in [1, 4].iter() {
+        let ref conf = c_conf(*threads);
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const WeldVec<i64>;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(result.len, expected.len() as i64);
+        for i in 0..(expected.len() as isize) {
+            assert_eq!(unsafe { *result.data.offset(i) }, expected[i as usize])
+        }
+    }
+}
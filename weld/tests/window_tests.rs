@@ -0,0 +1,229 @@
+//! Tests for window operators: scans, sliding windows, `lag` and `lead`.
+
+mod common;
+use crate::common::*;
+
+/// Returns the values of a vector returned by Weld.
+fn values<T: Copy>(v: &WeldVec<T>) -> Vec<T> {
+    (0..v.len)
+        .map(|i| unsafe { *v.data.offset(i as isize) })
+        .collect()
+}
+
+#[test]
+fn simple_scan() {
+    let code = "|x:vec[i32]| scan[i32,+](x)";
+    let ref conf = default_conf();
+
+    let input_vec = vec![1, 2, 3, 4, 5, 6, 7];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i32>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(values(&result), vec![1, 3, 6, 10, 15, 21, 28]);
+}
+
+#[test]
+fn scan_max() {
+    let code = "|x:vec[f64]| scan[?,max](x)";
+    let ref conf = default_conf();
+
+    let input_vec = vec![2.0, 1.0, 5.0, 3.0, 4.0, 7.0];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<f64>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(values(&result), vec![2.0, 2.0, 5.0, 5.0, 5.0, 7.0]);
+}
+
+#[test]
+fn empty_scan() {
+    let code = "|x:vec[i64]| scan[?,+](x)";
+    let ref conf = default_conf();
+
+    let input_vec: Vec<i64> = vec![];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(result.len, 0);
+}
+
+#[test]
+fn parallel_scan() {
+    let code = "|x:vec[i64]| scan[?,+](x)";
+    let ref conf = many_threads_conf();
+
+    let input_vec: Vec<i64> = (0..10000).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+
+    let expected: Vec<i64> = (0..10000).map(|i| i * (i + 1) / 2).collect();
+    assert_eq!(values(&result), expected);
+}
+
+#[test]
+fn moving_average() {
+    let code = "|x:vec[f64]| map(window[f64,+](x, 3L), |s| s / 3.0)";
+    let ref conf = default_conf();
+
+    let input_vec = vec![3.0, 6.0, 9.0, 12.0, 15.0];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<f64>;
+    let result = unsafe { (*data).clone() };
+
+    // The first frames are cut off at the start of the vector.
+    assert_eq!(values(&result), vec![1.0, 3.0, 6.0, 9.0, 12.0]);
+}
+
+#[test]
+fn sliding_min() {
+    let code = "|x:vec[i32]| window[?,min](x, 2L)";
+    let ref conf = default_conf();
+
+    let input_vec = vec![4, 2, 8, 6, 1, 9];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i32>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(values(&result), vec![4, 2, 2, 6, 1, 1]);
+}
+
+#[test]
+fn lag_and_lead() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        lag: WeldVec<i32>,
+        lead: WeldVec<i32>,
+    }
+
+    let code = "|x:vec[i32]| {lag(x, 1L, 0), lead(x, 2L, -1)}";
+    let ref conf = default_conf();
+
+    let input_vec = vec![10, 20, 30, 40, 50];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const Output;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(values(&result.lag), vec![0, 10, 20, 30, 40]);
+    assert_eq!(values(&result.lead), vec![30, 40, 50, -1, -1]);
+}
+
+#[test]
+fn differences_with_lag() {
+    let code = "|x:vec[i64]| let prev = lag(x, 1L, 0L);
+        result(for(zip(x, prev), appender[i64], |b,i,e| merge(b, e.$0 - e.$1)))";
+    let ref conf = default_conf();
+
+    let input_vec = vec![1, 4, 9, 16, 25];
+    let ref input_data = WeldVec::from(&input_vec);
+
+    let ret_value = compile_and_run(code, conf, input_data);
+    let data = ret_value.data() as *const WeldVec<i64>;
+    let result = unsafe { (*data).clone() };
+
+    assert_eq!(values(&result), vec![1, 3, 5, 7, 9]);
+}
+
+#[test]
+fn windows_of_every_size() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        sums: WeldVec<i64>,
+        float_sums: WeldVec<f64>,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Args {
+        x: WeldVec<i64>,
+        size: i64,
+    }
+
+    // Integer sums subtract prefix sums, and float sums add up each frame.
+    let code = "|x:vec[i64], size:i64|
+        {window[i64,+](x, size), window[f64,+](map(x, |e| f64(e)), size)}";
+    let mut llvm_conf = default_conf();
+    llvm_conf.set("weld.backend", "llvm");
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+
+    let input_vec: Vec<i64> = vec![5, -3, 8, 1, 4];
+    for &size in [-2, 0, 1, 3, 5, 10].iter() {
+        let ref input_data = Args {
+            x: WeldVec::from(&input_vec),
+            size,
+        };
+        // Frames are empty if the size is not positive.
+        let expected: Vec<i64> = (0..input_vec.len() as i64)
+            .map(|i| {
+                let start = (i + 1 - size).max(0).min(i + 1);
+                input_vec[start as usize..(i + 1) as usize].iter().sum()
+            })
+            .collect();
+
+        for conf in [llvm_conf.clone(), c_conf.clone()].iter() {
+            let ret_value = compile_and_run(code, conf, input_data);
+            let data = ret_value.data() as *const Output;
+            let result = unsafe { (*data).clone() };
+
+            assert_eq!(values(&result.sums), expected);
+            let expected: Vec<f64> = expected.iter().map(|&e| e as f64).collect();
+            assert_eq!(values(&result.float_sums), expected);
+        }
+    }
+}
+
+#[test]
+fn integer_windows_of_constant_size() {
+    #[derive(Clone, Debug)]
+    #[repr(C)]
+    struct Output {
+        small: WeldVec<i64>,
+        large: WeldVec<i64>,
+    }
+
+    // Small frames are summed frame by frame, and large frames subtract prefix sums.
+    let code = "|x:vec[i64]| {window[i64,+](x, 3L), window[i64,+](x, 100L)}";
+    let mut llvm_conf = default_conf();
+    llvm_conf.set("weld.backend", "llvm");
+    let mut c_conf = default_conf();
+    c_conf.set("weld.backend", "c-host");
+
+    let input_vec: Vec<i64> = (0..200).map(|i| (i * 7) % 11 - 5).collect();
+    let ref input_data = WeldVec::from(&input_vec);
+    let sums = |size: i64| -> Vec<i64> {
+        (0..input_vec.len() as i64)
+            .map(|i| {
+                let start = (i + 1 - size).max(0);
+                input_vec[start as usize..(i + 1) as usize].iter().sum()
+            })
+            .collect()
+    };
+
+    for conf in [llvm_conf, c_conf].iter() {
+        let ret_value = compile_and_run(code, conf, input_data);
+        let data = ret_value.data() as *const Output;
+        let result = unsafe { (*data).clone() };
+
+        assert_eq!(values(&result.small), sums(3));
+        assert_eq!(values(&result.large), sums(100));
+    }
+}